@@ -0,0 +1,490 @@
+//! Lectura de códigos escaneados: GS1-128 / GS1 DataBar y EAN-13 de peso o precio variable.
+//!
+//! - GS1: se reconocen por el identificador de simbología (`]C1`, `]e0`, `]d2`, `]Q3`), por
+//!   la forma legible con paréntesis (`(01)07501234567893(17)261231`) o por empezar con
+//!   `01` + GTIN de 14 dígitos. Los campos de largo variable terminan en GS (FNC1).
+//!   Se interpretan 01 GTIN, 10 lote, 17 caducidad, 310n peso neto en kg y 392n precio.
+//! - EAN-13 con prefijo 20–29: códigos de la tienda (báscula etiquetadora) con el artículo
+//!   y el precio o el peso dentro. El reparto de dígitos se configura en `VariableMeasureRules`.
+//! - Todo lo demás se busca tal cual.
+//!
+//! `lookup` es la clave con que se busca el producto; `alternates`, otras formas del mismo
+//! código por si el catálogo lo tiene guardado distinto (GTIN-14, EAN-13, UPC-A).
+
+use crate::db::{self, Db, Product};
+use crate::units;
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// FNC1 transmitido por el lector.
+const GS: char = '\u{1D}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeKind {
+    Plain,
+    Gs1,
+    VariableMeasure,
+}
+
+/// Elemento GS1 tal como vino (`ai` = identificador de aplicación).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Gs1Element {
+    pub ai: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScannedCode {
+    pub raw: String,
+    pub kind: CodeKind,
+    pub lookup: String,
+    pub alternates: Vec<String>,
+    pub gtin: Option<String>,
+    pub lot: Option<String>,
+    pub expiry: Option<NaiveDate>,
+    /// Peso en kg (310n o etiqueta de peso variable).
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub weight: Option<Decimal>,
+    /// Importe del renglón (392n o etiqueta de precio variable).
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub price: Option<Decimal>,
+    pub elements: Vec<Gs1Element>,
+}
+
+impl ScannedCode {
+    fn plain(raw: &str, code: &str) -> Self {
+        ScannedCode {
+            raw: raw.to_string(),
+            kind: CodeKind::Plain,
+            lookup: code.to_string(),
+            alternates: Vec::new(),
+            gtin: None,
+            lot: None,
+            expiry: None,
+            weight: None,
+            price: None,
+            elements: Vec::new(),
+        }
+    }
+
+    /// Cantidad a vender: el peso si viene en el código; si viene el importe, importe entre
+    /// precio unitario con los decimales del producto; si no, 1.
+    pub fn quantity_for(&self, unit_price: Decimal, precision: u32) -> Decimal {
+        if let Some(weight) = self.weight {
+            return units::normalize(weight);
+        }
+        match self.price {
+            Some(price) if unit_price > Decimal::ZERO => units::normalize((price / unit_price).round_dp(precision)),
+            _ => Decimal::ONE,
+        }
+    }
+}
+
+// ---------- Dígito verificador ----------
+
+/// Dígito verificador GS1 (módulo 10, pesos 3 y 1 desde la derecha) de `digits` sin él.
+pub fn check_digit(digits: &str) -> Option<u32> {
+    let mut sum = 0;
+    for (i, c) in digits.chars().rev().enumerate() {
+        let d = c.to_digit(10)?;
+        sum += if i % 2 == 0 { d * 3 } else { d };
+    }
+    Some((10 - sum % 10) % 10)
+}
+
+/// `true` si el último dígito es el verificador correcto.
+pub fn valid_check_digit(code: &str) -> bool {
+    code.len() > 1
+        && code.chars().all(|c| c.is_ascii_digit())
+        && check_digit(&code[..code.len() - 1]) == code[code.len() - 1..].parse().ok()
+}
+
+/// GTIN-14 en sus formas cortas: EAN-13 si empieza con 0, UPC-A si empieza con 00.
+fn gtin_alternates(gtin: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut short = gtin;
+    while short.len() > 12 && short.starts_with('0') {
+        short = &short[1..];
+        out.push(short.to_string());
+    }
+    out
+}
+
+// ---------- GS1 ----------
+
+/// Largo del AI y de su dato: `(dígitos del AI, largo fijo, largo máximo)`.
+fn ai_spec(data: &str) -> Option<(usize, Option<usize>, usize)> {
+    let spec = match data.get(..2)? {
+        "00" => (2, Some(18), 18),
+        "01" | "02" => (2, Some(14), 14),
+        "11" | "12" | "13" | "15" | "16" | "17" => (2, Some(6), 6),
+        "20" => (2, Some(2), 2),
+        "10" | "21" | "22" => (2, None, 20),
+        "30" | "37" => (2, None, 8),
+        "90" | "91" | "92" | "93" | "94" | "95" | "96" | "97" | "98" | "99" => (2, None, 90),
+        "24" | "25" | "40" | "42" => (3, None, 30),
+        "41" => (3, Some(13), 13),
+        "31" | "32" | "33" | "34" | "35" | "36" => (4, Some(6), 6),
+        "39" => (4, None, 18),
+        "70" | "71" | "72" | "80" | "81" | "82" => (4, None, 90),
+        _ => return None,
+    };
+    Some(spec)
+}
+
+/// Separa los elementos GS1 de la forma transmitida (AIs pegados, GS tras los variables).
+pub fn parse_gs1_elements(data: &str) -> Result<Vec<Gs1Element>, String> {
+    let mut rest = data.trim_start_matches(GS);
+    let mut elements = Vec::new();
+    while !rest.is_empty() {
+        let (ai_len, fixed, max) = ai_spec(rest).ok_or_else(|| format!("AI desconocido en {:?}", rest))?;
+        let ai = rest.get(..ai_len).filter(|ai| ai.chars().all(|c| c.is_ascii_digit()));
+        let ai = ai.ok_or_else(|| format!("AI incompleto en {:?}", rest))?.to_string();
+        let body = &rest[ai_len..];
+        let len = match fixed {
+            Some(n) => n,
+            None => body.find(GS).unwrap_or(body.len()),
+        };
+        if len > max {
+            return Err(format!("AI {} con más de {} caracteres", ai, max));
+        }
+        let value = body.get(..len).ok_or_else(|| format!("AI {} incompleto", ai))?;
+        elements.push(Gs1Element { ai, value: value.to_string() });
+        rest = body[len..].trim_start_matches(GS);
+    }
+    Ok(elements)
+}
+
+/// Forma legible: `(01)07501234567893(10)LOTE1`.
+fn parse_gs1_parenthesized(data: &str) -> Result<Vec<Gs1Element>, String> {
+    let mut elements = Vec::new();
+    for part in data.split('(').skip(1) {
+        let (ai, value) = part.split_once(')').ok_or("paréntesis sin cerrar")?;
+        if ai.len() < 2 || ai.len() > 4 || !ai.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("AI inválido ({})", ai));
+        }
+        elements.push(Gs1Element { ai: ai.to_string(), value: value.trim_end_matches(GS).to_string() });
+    }
+    Ok(elements)
+}
+
+/// `AAMMDD`; día 00 = último día del mes.
+fn parse_gs1_date(value: &str) -> Option<NaiveDate> {
+    if value.len() != 6 || !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let year = 2000 + value[0..2].parse::<i32>().ok()?;
+    let month = value[2..4].parse::<u32>().ok()?;
+    match value[4..6].parse::<u32>().ok()? {
+        0 => {
+            let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+            let next = if month == 12 { NaiveDate::from_ymd_opt(year + 1, 1, 1)? } else { first.with_month(month + 1)? };
+            next.pred_opt()
+        }
+        day => NaiveDate::from_ymd_opt(year, month, day),
+    }
+}
+
+/// Número con los decimales que indica el último dígito del AI (`3103` → 3).
+fn implied_decimal(ai: &str, value: &str) -> Result<Decimal, String> {
+    let decimals = ai[3..].parse::<u32>().map_err(|_| format!("AI {} inválido", ai))?;
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("AI {}: valor no numérico", ai));
+    }
+    let raw = value.parse::<i64>().map_err(|_| format!("AI {}: valor fuera de rango", ai))?;
+    Ok(Decimal::new(raw, decimals))
+}
+
+fn from_gs1(raw: &str, elements: Vec<Gs1Element>) -> Result<ScannedCode, String> {
+    let mut scan = ScannedCode { kind: CodeKind::Gs1, ..ScannedCode::plain(raw, "") };
+    for e in &elements {
+        match e.ai.as_str() {
+            "01" | "02" => {
+                if !valid_check_digit(&e.value) {
+                    return Err(format!("GTIN {} con dígito verificador incorrecto", e.value));
+                }
+                scan.gtin.get_or_insert_with(|| e.value.clone());
+            }
+            "10" => scan.lot = Some(e.value.clone()),
+            "17" => scan.expiry = Some(parse_gs1_date(&e.value).ok_or_else(|| format!("caducidad inválida {}", e.value))?),
+            ai if ai.len() == 4 && ai.starts_with("310") => scan.weight = Some(implied_decimal(ai, &e.value)?),
+            ai if ai.len() == 4 && ai.starts_with("392") => scan.price = Some(implied_decimal(ai, &e.value)?),
+            _ => {}
+        }
+    }
+    let gtin = scan.gtin.clone().ok_or("el código GS1 no trae GTIN (AI 01)")?;
+    scan.alternates = gtin_alternates(&gtin);
+    scan.alternates.push(gtin);
+    // La forma más corta es la que suele estar dada de alta (EAN-13 en México).
+    scan.lookup = scan.alternates.remove(0);
+    scan.elements = elements;
+    Ok(scan)
+}
+
+// ---------- Peso / precio variable ----------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddedValue {
+    #[default]
+    Price,
+    Weight,
+}
+
+/// Cómo reparte la báscula etiquetadora los 13 dígitos: prefijo (2), artículo
+/// (`item_digits`), valor (lo que sobra) y verificador.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariableMeasureRules {
+    #[serde(default = "default_item_digits")]
+    pub item_digits: usize,
+    /// Qué trae el valor en los prefijos que no están en `weight_prefixes`.
+    #[serde(default)]
+    pub value: EmbeddedValue,
+    /// Prefijos (20–29) que siempre llevan peso.
+    #[serde(default)]
+    pub weight_prefixes: Vec<u8>,
+    #[serde(default = "default_price_decimals")]
+    pub price_decimals: u32,
+    #[serde(default = "default_weight_decimals")]
+    pub weight_decimals: u32,
+}
+
+fn default_item_digits() -> usize {
+    5
+}
+
+fn default_price_decimals() -> u32 {
+    2
+}
+
+fn default_weight_decimals() -> u32 {
+    3
+}
+
+impl Default for VariableMeasureRules {
+    fn default() -> Self {
+        VariableMeasureRules {
+            item_digits: default_item_digits(),
+            value: EmbeddedValue::Price,
+            weight_prefixes: Vec::new(),
+            price_decimals: default_price_decimals(),
+            weight_decimals: default_weight_decimals(),
+        }
+    }
+}
+
+fn from_variable_measure(raw: &str, code: &str, rules: &VariableMeasureRules) -> Result<ScannedCode, String> {
+    if !(4..=6).contains(&rules.item_digits) {
+        return Err("El código de artículo de la etiqueta debe tener de 4 a 6 dígitos.".to_string());
+    }
+    if rules.price_decimals > 6 || rules.weight_decimals > 6 {
+        return Err("Los decimales del precio y del peso de la etiqueta deben ser de 0 a 6.".to_string());
+    }
+    if !valid_check_digit(code) {
+        return Err(format!("Etiqueta {} con dígito verificador incorrecto", code));
+    }
+    let prefix: u8 = code[..2].parse().map_err(|_| "prefijo inválido".to_string())?;
+    let item = &code[2..2 + rules.item_digits];
+    let value = &code[2 + rules.item_digits..12];
+    let value: i64 = value.parse().map_err(|_| format!("valor inválido {}", value))?;
+    let kind = if rules.weight_prefixes.contains(&prefix) { EmbeddedValue::Weight } else { rules.value };
+    let mut scan = ScannedCode { kind: CodeKind::VariableMeasure, ..ScannedCode::plain(raw, item) };
+    match kind {
+        EmbeddedValue::Price => scan.price = Some(Decimal::new(value, rules.price_decimals)),
+        EmbeddedValue::Weight => scan.weight = Some(Decimal::new(value, rules.weight_decimals)),
+    }
+    // El artículo sin ceros, con prefijo, o la etiqueta con el valor en ceros.
+    let zeroed = format!("{}{}", &code[..2 + rules.item_digits], "0".repeat(10 - rules.item_digits));
+    let zeroed = format!("{}{}", zeroed, check_digit(&zeroed).unwrap_or(0));
+    let trimmed = item.trim_start_matches('0');
+    let mut alternates = vec![format!("{}{}", &code[..2], item), zeroed];
+    if !trimmed.is_empty() && trimmed != item {
+        alternates.insert(0, trimmed.to_string());
+    }
+    scan.alternates = alternates;
+    Ok(scan)
+}
+
+// ---------- Entrada ----------
+
+/// Interpreta un código escaneado. Solo falla si el código dice ser GS1 o de peso
+/// variable y viene mal formado; lo que no se reconoce se devuelve como `Plain`.
+pub fn parse(raw: &str, rules: &VariableMeasureRules) -> Result<ScannedCode, String> {
+    let code = raw.trim_matches(|c: char| c.is_whitespace() || (c.is_control() && c != GS));
+    if code.is_empty() {
+        return Err("Código vacío.".to_string());
+    }
+    // Identificador de simbología AIM: `]` + letra + modificador.
+    let (symbology, data) = match code.strip_prefix(']').and_then(|rest| Some((rest.get(..2)?, rest.get(2..)?))) {
+        Some((id, rest)) => (Some(id), rest),
+        None => (None, code),
+    };
+    let gs1_symbology = matches!(symbology, Some("C1" | "e0" | "d2" | "Q3"));
+    if data.starts_with('(') {
+        return from_gs1(raw, parse_gs1_parenthesized(data)?);
+    }
+    let digits = data.chars().all(|c| c.is_ascii_digit());
+    // Sin identificador: `01` + GTIN-14; si son justo 16 dígitos, solo con verificador válido.
+    let looks_gs1 = data.starts_with("01")
+        && data.get(..16).is_some_and(|head| head.chars().all(|c| c.is_ascii_digit()))
+        && (data.len() > 16 || valid_check_digit(&data[2..]));
+    if gs1_symbology || looks_gs1 {
+        return from_gs1(raw, parse_gs1_elements(data)?);
+    }
+    if digits && data.len() == 13 && data.starts_with('2') {
+        return from_variable_measure(raw, data, rules);
+    }
+    Ok(ScannedCode::plain(raw, data))
+}
+
+// ---------- Comandos Tauri ----------
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanLookup {
+    pub scan: ScannedCode,
+    pub product: Option<Product>,
+    /// Cantidad a agregar al carrito (ver `ScannedCode::quantity_for`).
+    #[serde(with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+}
+
+#[tauri::command]
+pub fn barcode_parse(code: String, rules: Option<VariableMeasureRules>) -> Result<ScannedCode, String> {
+    parse(&code, &rules.unwrap_or_default())
+}
+
+/// Interpreta el código y busca el producto por `lookup` y luego por las alternativas.
+#[tauri::command]
+pub fn barcode_lookup(db: State<'_, Db>, code: String, rules: Option<VariableMeasureRules>) -> Result<ScanLookup, String> {
+    let scan = parse(&code, &rules.unwrap_or_default())?;
+    let product = db.with_conn(|conn| {
+        for key in std::iter::once(&scan.lookup).chain(&scan.alternates) {
+            if let Some(p) = db::find_product_by_code(conn, key)? {
+                return Ok(Some(p));
+            }
+        }
+        Ok(None)
+    })?;
+    let quantity = match &product {
+        Some(p) => scan.quantity_for(p.price, p.precision()),
+        None => scan.quantity_for(Decimal::ZERO, 0),
+    };
+    Ok(ScanLookup { scan, product, quantity })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    fn scan(raw: &str) -> ScannedCode {
+        parse(raw, &VariableMeasureRules::default()).unwrap()
+    }
+
+    /// EAN-13 de la báscula con su verificador.
+    fn label(body: &str) -> String {
+        format!("{}{}", body, check_digit(body).unwrap())
+    }
+
+    #[test]
+    fn check_digit_is_gs1_mod_10() {
+        assert_eq!(check_digit("750123456789"), Some(3));
+        assert_eq!(check_digit("0750123456789"), Some(3));
+        assert_eq!(check_digit("75012345678A"), None);
+        assert!(valid_check_digit("7501234567893"));
+        assert!(!valid_check_digit("7501234567890"));
+        assert!(!valid_check_digit("7"));
+    }
+
+    #[test]
+    fn variable_length_ais_end_at_gs() {
+        assert_eq!(scan("]C10107501234567893").kind, CodeKind::Gs1);
+        let code = scan(&format!("]C1010750123456789310LOTE-7{}17261231{}21SERIE", GS, GS));
+        assert_eq!(code.lot.as_deref(), Some("LOTE-7"));
+        assert_eq!(code.expiry, NaiveDate::from_ymd_opt(2026, 12, 31));
+        assert_eq!(code.elements.last(), Some(&Gs1Element { ai: "21".to_string(), value: "SERIE".to_string() }));
+        assert_eq!(code.lookup, "7501234567893");
+        assert_eq!(code.alternates, vec!["07501234567893".to_string()]);
+
+        assert!(parse_gs1_elements(&format!("10{}", "X".repeat(21))).is_err());
+        assert!(parse_gs1_elements("0107501234").is_err());
+    }
+
+    #[test]
+    fn parenthesized_form() {
+        let code = scan("(01)07501234567893(17)240200(10)A1");
+        assert_eq!(code.kind, CodeKind::Gs1);
+        assert_eq!(code.gtin.as_deref(), Some("07501234567893"));
+        assert_eq!(code.lot.as_deref(), Some("A1"));
+        assert!(parse("(01)07501234567890", &VariableMeasureRules::default()).is_err());
+        assert!(parse("(01)07501234567893(1X)5", &VariableMeasureRules::default()).is_err());
+    }
+
+    #[test]
+    fn expiry_day_00_is_the_last_day_of_the_month() {
+        assert_eq!(parse_gs1_date("240200"), NaiveDate::from_ymd_opt(2024, 2, 29));
+        assert_eq!(parse_gs1_date("250200"), NaiveDate::from_ymd_opt(2025, 2, 28));
+        assert_eq!(parse_gs1_date("261200"), NaiveDate::from_ymd_opt(2026, 12, 31));
+        assert_eq!(parse_gs1_date("260415"), NaiveDate::from_ymd_opt(2026, 4, 15));
+        assert_eq!(parse_gs1_date("261300"), None);
+        assert_eq!(parse_gs1_date("2612"), None);
+    }
+
+    #[test]
+    fn weight_and_price_ais_carry_their_decimals() {
+        let code = scan("]C101075012345678933103001250");
+        assert_eq!(code.weight, Some(d("1.250")));
+        assert_eq!(code.quantity_for(d("80"), 3), d("1.25"));
+
+        let code = scan(&format!("]C1010750123456789339220015{}10L1", GS));
+        assert_eq!(code.price, Some(d("0.15")));
+        let code = scan("]C10107501234567893392215990");
+        assert_eq!(code.price, Some(d("159.90")));
+        assert_eq!(code.quantity_for(d("39.975"), 3), d("4"));
+        assert!(parse("]C1010750123456789331030012X0", &VariableMeasureRules::default()).is_err());
+    }
+
+    #[test]
+    fn bare_01_gtin_is_gs1_only_with_a_valid_check_digit() {
+        assert_eq!(scan("0112345678901231").kind, CodeKind::Gs1);
+        assert_eq!(scan("0112345678901234").kind, CodeKind::Plain);
+        assert_eq!(scan("0112345678901234").lookup, "0112345678901234");
+        // Más de 16 dígitos: el resto son más AIs.
+        let code = scan("01123456789012313103000500");
+        assert_eq!((code.kind, code.weight), (CodeKind::Gs1, Some(d("0.500"))));
+        assert_eq!(scan("7501234567893").kind, CodeKind::Plain);
+    }
+
+    #[test]
+    fn store_labels_split_item_and_value_by_item_digits() {
+        let rules = |item_digits| VariableMeasureRules { item_digits, weight_prefixes: vec![22], ..Default::default() };
+
+        let code = parse(&label("201234012345"), &rules(4)).unwrap();
+        assert_eq!((code.kind, code.lookup.as_str(), code.price), (CodeKind::VariableMeasure, "1234", Some(d("123.45"))));
+        assert_eq!(code.alternates, vec!["201234".to_string(), label("201234000000")]);
+
+        let code = parse(&label("210012301599"), &rules(5)).unwrap();
+        assert_eq!((code.lookup.as_str(), code.price), ("00123", Some(d("15.99"))));
+        assert_eq!(code.alternates[0], "123");
+
+        let code = parse(&label("221234561250"), &rules(6)).unwrap();
+        assert_eq!((code.lookup.as_str(), code.weight, code.price), ("123456", Some(d("1.250")), None));
+
+        assert!(parse("2012340123450", &rules(4)).is_err());
+        assert!(parse(&label("201234012345"), &rules(7)).is_err());
+    }
+
+    #[test]
+    fn out_of_range_label_decimals_are_rejected() {
+        let rules = VariableMeasureRules { price_decimals: 29, ..Default::default() };
+        assert!(parse(&label("201234012345"), &rules).is_err());
+        let rules = VariableMeasureRules { weight_decimals: 40, weight_prefixes: vec![20], ..Default::default() };
+        assert!(parse(&label("201234012345"), &rules).is_err());
+    }
+}
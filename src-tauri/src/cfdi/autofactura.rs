@@ -0,0 +1,156 @@
+//! Autofactura: datos impresos en el ticket para que el cliente pida su factura después.
+//!
+//! El código de verificación es un HMAC-SHA256 del folio, la fecha y el total del ticket con
+//! una llave que se genera en esta caja y nunca sale de la base local. Al recibir la solicitud
+//! se recalcula contra la venta guardada: si alguien cambia el total, el código ya no cuadra.
+
+use super::{catalogos, get_settings, invoices};
+use crate::db::{self, Sale};
+use hmac::{Hmac, Mac};
+use rusqlite::{Connection, OptionalExtension};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Base32 de Crockford: sin I, L, O ni U para que el código se pueda dictar y capturar.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const CODE_LEN: usize = 10;
+
+/// Bloque de autofactura de un ticket.
+#[derive(Debug, Clone, Serialize)]
+pub struct TicketInvoiceData {
+    pub folio: String,
+    /// Fecha local de la venta (`AAAA-MM-DD`).
+    pub fecha: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total: Decimal,
+    /// Código de verificación, `XXXXX-XXXXX`.
+    pub codigo: String,
+    /// Contenido del QR: URL del portal con los datos o, sin portal, `RFC|folio|fecha|total|código`.
+    pub qr: String,
+    /// Último día para pedir la factura (`AAAA-MM-DD`), si hay plazo.
+    pub vence: Option<String>,
+}
+
+/// Datos que captura el cliente para pedir su factura.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutofacturaRequest {
+    pub folio: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total: Decimal,
+    pub codigo: String,
+}
+
+/// Llave de la caja para firmar tickets; se crea la primera vez que se usa.
+fn secret(conn: &Connection) -> Result<Vec<u8>, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let stored: Option<Vec<u8>> = conn
+        .query_row("SELECT autofactura_secret FROM cfdi_settings WHERE id = 1", [], |r| r.get(0))
+        .map_err(sql)?;
+    if let Some(key) = stored.filter(|k| !k.is_empty()) {
+        return Ok(key);
+    }
+    let mut key = vec![0u8; 32];
+    getrandom::getrandom(&mut key).map_err(|e| format!("No se pudo generar la llave de autofactura: {}", e))?;
+    conn.execute("UPDATE cfdi_settings SET autofactura_secret = ?1 WHERE id = 1", [&key])
+        .map_err(sql)?;
+    Ok(key)
+}
+
+/// Fecha local de la venta; es parte de lo firmado.
+fn sale_date(sale: &Sale) -> String {
+    let created = sale.created_at.as_deref().unwrap_or_default();
+    chrono::DateTime::parse_from_rfc3339(created)
+        .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|_| created.chars().take(10).collect())
+}
+
+fn code(key: &[u8], folio: &str, fecha: &str, total: Decimal) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC acepta llaves de cualquier tamaño");
+    mac.update(format!("{}|{}|{:.2}", folio, fecha, total.round_dp(2)).as_bytes());
+    let digest = mac.finalize().into_bytes();
+    // Los primeros 50 bits del HMAC, de 5 en 5.
+    let bits = u64::from_be_bytes(digest[..8].try_into().unwrap_or_default());
+    (0..CODE_LEN).map(|i| ALPHABET[((bits >> (59 - 5 * i)) & 0x1F) as usize] as char).collect()
+}
+
+/// Normaliza lo que captura el cliente: sin guiones ni espacios y con O→0, I/L→1.
+fn normalize_code(codigo: &str) -> String {
+    codigo
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+/// Comparación sin salir en el primer carácter distinto.
+fn same_code(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Datos de autofactura para imprimir en el ticket de una venta.
+pub fn for_sale(conn: &Connection, sale_id: i64) -> Result<TicketInvoiceData, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let settings = get_settings(conn).map_err(sql)?;
+    let sale = db::get_sale(conn, sale_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la venta {}.", sale_id))?;
+    let fecha = sale_date(&sale);
+    let raw = code(&secret(conn)?, &sale.sale_number, &fecha, sale.total);
+    let codigo = format!("{}-{}", &raw[..5], &raw[5..]);
+    let total = sale.total.round_dp(2);
+    let url = settings.autofactura_url.trim();
+    let qr = if url.is_empty() {
+        format!("{}|{}|{}|{:.2}|{}", settings.rfc, sale.sale_number, fecha, total, codigo)
+    } else {
+        let sep = if url.contains('?') { '&' } else { '?' };
+        format!("{}{}folio={}&fecha={}&total={:.2}&codigo={}", url, sep, sale.sale_number, fecha, total, codigo)
+    };
+    let vence = (settings.autofactura_days > 0)
+        .then(|| chrono::NaiveDate::parse_from_str(&fecha, "%Y-%m-%d").ok())
+        .flatten()
+        .map(|d| (d + chrono::Duration::days(settings.autofactura_days)).to_string());
+    Ok(TicketInvoiceData { folio: sale.sale_number, fecha, total, codigo, qr, vence })
+}
+
+/// Comprueba la solicitud contra la venta guardada y devuelve el id de la venta a facturar.
+pub fn verify(conn: &Connection, req: &AutofacturaRequest) -> Result<i64, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let not_found = || "Los datos del ticket no coinciden; revisa folio, total y código.".to_string();
+    let sale_id: i64 = conn
+        .query_row("SELECT id FROM sales WHERE sale_number = ?1", [req.folio.trim()], |r| r.get(0))
+        .optional()
+        .map_err(sql)?
+        .ok_or_else(not_found)?;
+    let sale = db::get_sale(conn, sale_id).map_err(sql)?.ok_or_else(not_found)?;
+    let fecha = sale_date(&sale);
+    let expected = code(&secret(conn)?, &sale.sale_number, &fecha, sale.total);
+    // Mismo mensaje para total y código: no revela cuál de los dos está mal.
+    if sale.total.round_dp(2) != req.total.round_dp(2) || !same_code(&expected, &normalize_code(&req.codigo)) {
+        log::warn!("autofactura: solicitud rechazada para el ticket {}", sale.sale_number);
+        return Err(not_found());
+    }
+    if sale.status != "completed" {
+        return Err(format!("El ticket {} no se puede facturar (está {}).", sale.sale_number, sale.status));
+    }
+    if let Some(invoice) = invoices::active_for_sale(conn, sale_id).map_err(sql)? {
+        return Err(if invoice.receptor_rfc == catalogos::RFC_PUBLICO_GENERAL {
+            format!("El ticket {} ya se incluyó en la factura global.", sale.sale_number)
+        } else {
+            format!("El ticket {} ya está facturado.", sale.sale_number)
+        });
+    }
+    let days = get_settings(conn).map_err(sql)?.autofactura_days;
+    if days > 0 {
+        if let Ok(date) = chrono::NaiveDate::parse_from_str(&fecha, "%Y-%m-%d") {
+            if chrono::Local::now().date_naive() > date + chrono::Duration::days(days) {
+                return Err(format!("El plazo para facturar el ticket {} ya venció.", sale.sale_number));
+            }
+        }
+    }
+    Ok(sale_id)
+}
@@ -0,0 +1,210 @@
+//! Cancelación de CFDI con motivo (`01`–`04`) y seguimiento del estatus en el SAT.
+//!
+//! La solicitud puede quedar `en_proceso` mientras el receptor la acepta; al consultar el
+//! estatus se pasa a `cancelado` o regresa a `vigente` si la rechazó. Cuando el CFDI queda
+//! cancelado se actualiza la venta:
+//! - motivo `03` (la operación no se realizó): las ventas se cancelan y el stock se restituye;
+//! - los demás: la venta sigue cobrada y vuelve a `ticket` si ya no tiene factura vigente; los
+//!   tickets de una factura global quedan libres para la siguiente.
+
+use super::invoices::{self, Invoice};
+use super::pac::{self, CancelRequest, CancelState, StatusQuery};
+use super::{catalogos, csd, get_settings, validate};
+use crate::db::Db;
+use rusqlite::{params, Connection, Transaction};
+
+/// Valida el motivo y, para el `01`, el UUID que sustituye al cancelado.
+fn check_motivo(conn: &Connection, invoice: &Invoice, motivo: &str, folio: Option<&str>) -> Result<(), String> {
+    if !catalogos::contains(catalogos::MOTIVOS_CANCELACION, motivo) {
+        return Err(format!("Motivo de cancelación {} inválido (01 a 04).", motivo));
+    }
+    match (motivo, folio) {
+        ("01", None) => Err("El motivo 01 requiere el UUID del CFDI que sustituye al cancelado.".to_string()),
+        ("01", Some(uuid)) => {
+            if !validate::is_uuid(uuid) {
+                return Err(format!("{} no es un UUID.", uuid));
+            }
+            if uuid.eq_ignore_ascii_case(&invoice.uuid) {
+                return Err("El CFDI no puede sustituirse a sí mismo.".to_string());
+            }
+            // Si lo timbramos aquí, tiene que estar vigente.
+            match invoices::get_by_uuid(conn, &uuid.to_uppercase()).map_err(|e| format!("base local: {}", e))? {
+                Some(replacement) if replacement.status != "vigente" => {
+                    Err(format!("El CFDI sustituto {} no está vigente.", replacement.uuid))
+                }
+                _ => Ok(()),
+            }
+        }
+        (_, Some(_)) => Err("Solo el motivo 01 lleva UUID de sustitución.".to_string()),
+        ("04", None) if invoice.receptor_rfc != catalogos::RFC_PUBLICO_GENERAL => {
+            Err("El motivo 04 es solo para facturas globales (público en general).".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Pasa la factura a cancelada y aplica el cambio en la venta.
+fn mark_cancelled(tx: &Transaction, invoice: &Invoice, detail: &str) -> Result<(), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    tx.execute(
+        "UPDATE invoices SET status = 'cancelado', cancel_detail = ?2,
+             cancelled_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+         WHERE id = ?1",
+        params![invoice.id, detail],
+    )
+    .map_err(sql)?;
+    for sale_id in invoices::sale_ids(tx, invoice.id).map_err(sql)? {
+        if invoice.cancel_motivo.as_deref() == Some("03") {
+            crate::sales::cancel_sale_tx(tx, sale_id)?;
+        } else if invoice.sale_id.is_some() && invoices::active_for_sale(tx, sale_id).map_err(sql)?.is_none() {
+            tx.execute(
+                "UPDATE sales SET receipt_type = 'ticket', updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+                [sale_id],
+            )
+            .map_err(sql)?;
+        }
+    }
+    Ok(())
+}
+
+/// Guarda el resultado de una solicitud o consulta de cancelación.
+fn apply(conn: &mut Connection, invoice: &Invoice, state: CancelState, detail: &str) -> Result<Invoice, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let tx = conn.transaction().map_err(sql)?;
+    match state {
+        CancelState::Cancelado => mark_cancelled(&tx, invoice, detail)?,
+        CancelState::EnProceso => {
+            tx.execute(
+                "UPDATE invoices SET status = 'en_proceso', cancel_detail = ?2 WHERE id = ?1",
+                params![invoice.id, detail],
+            )
+            .map_err(sql)?;
+        }
+        // El receptor rechazó: el CFDI sigue vigente y se puede volver a solicitar.
+        CancelState::Rechazado => {
+            tx.execute(
+                "UPDATE invoices SET status = 'vigente', cancel_detail = ?2 WHERE id = ?1",
+                params![invoice.id, detail],
+            )
+            .map_err(sql)?;
+        }
+    }
+    tx.commit().map_err(sql)?;
+    invoices::get(conn, invoice.id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la factura {}.", invoice.id))
+}
+
+/// Solicita al PAC la cancelación de una factura timbrada.
+pub fn cancel_invoice(db: &Db, invoice_id: i64, motivo: &str, folio_sustitucion: Option<&str>) -> Result<Invoice, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let folio_sustitucion = folio_sustitucion.map(str::trim).filter(|f| !f.is_empty());
+    let (invoice, rfc_emisor, csd, provider) = {
+        let conn = db.lock()?;
+        let invoice = invoices::get(&conn, invoice_id)
+            .map_err(sql)?
+            .ok_or_else(|| format!("No se encontró la factura {}.", invoice_id))?;
+        match invoice.status.as_str() {
+            "cancelado" => return Err(format!("La factura {} ya está cancelada.", invoice.uuid)),
+            "en_proceso" => {
+                return Err(format!(
+                    "La cancelación de {} está en proceso; consulta su estatus en el SAT.",
+                    invoice.uuid
+                ))
+            }
+            _ => {}
+        }
+        check_motivo(&conn, &invoice, motivo, folio_sustitucion)?;
+        let settings = get_settings(&conn).map_err(sql)?;
+        let rfc_emisor = settings.rfc.trim().to_uppercase();
+        let csd = csd::load(&conn, &rfc_emisor)?;
+        let provider = pac::provider(&conn)?;
+        conn.execute(
+            "UPDATE invoices SET cancel_motivo = ?2, folio_sustitucion = ?3,
+                 cancel_requested_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+             WHERE id = ?1",
+            params![invoice.id, motivo, folio_sustitucion.map(str::to_uppercase)],
+        )
+        .map_err(sql)?;
+        let invoice = invoices::get(&conn, invoice_id).map_err(sql)?.unwrap_or(invoice);
+        (invoice, rfc_emisor, csd, provider)
+    };
+
+    let result = provider.cancel(&CancelRequest {
+        rfc_emisor: &rfc_emisor,
+        rfc_receptor: &invoice.receptor_rfc,
+        uuid: &invoice.uuid,
+        total: invoice.total,
+        motivo,
+        folio_sustitucion,
+        csd: &csd,
+    })?;
+    log::info!("cfdi: cancelación de {} (motivo {}): {}", invoice.uuid, motivo, result.detail);
+
+    let mut conn = db.lock()?;
+    if let Some(acuse) = &result.acuse {
+        conn.execute("UPDATE invoices SET acuse = ?2 WHERE id = ?1", params![invoice.id, acuse])
+            .map_err(sql)?;
+    }
+    let updated = apply(&mut conn, &invoice, result.state, &result.detail)?;
+    if result.state == CancelState::Rechazado {
+        return Err(format!("El SAT rechazó la cancelación de {}: {}", invoice.uuid, result.detail));
+    }
+    Ok(updated)
+}
+
+/// Consulta en el SAT el estatus de una factura y actualiza la cancelación si cambió.
+pub fn refresh_status(db: &Db, invoice_id: i64) -> Result<Invoice, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let (invoice, rfc_emisor, provider) = {
+        let conn = db.lock()?;
+        let invoice = invoices::get(&conn, invoice_id)
+            .map_err(sql)?
+            .ok_or_else(|| format!("No se encontró la factura {}.", invoice_id))?;
+        let rfc_emisor = get_settings(&conn).map_err(sql)?.rfc.trim().to_uppercase();
+        (invoice, rfc_emisor, pac::provider(&conn)?)
+    };
+    let status = provider.status(&StatusQuery {
+        rfc_emisor: &rfc_emisor,
+        rfc_receptor: &invoice.receptor_rfc,
+        uuid: &invoice.uuid,
+        total: invoice.total,
+    })?;
+    let state = pac::cancel_state_from_sat(&status.estado, &status.estatus_cancelacion);
+    let mut conn = db.lock()?;
+    match state {
+        // Sin solicitud de por medio no hay nada que actualizar.
+        None => Ok(invoice),
+        Some(CancelState::Cancelado) if invoice.status == "cancelado" => Ok(invoice),
+        Some(state) => {
+            // Un rechazo viejo o una solicitud ajena no cambian una factura vigente.
+            if invoice.status != "en_proceso" && state != CancelState::Cancelado {
+                return Ok(invoice);
+            }
+            let detail = if status.estatus_cancelacion.is_empty() {
+                status.estado.clone()
+            } else {
+                status.estatus_cancelacion.clone()
+            };
+            log::info!("cfdi: {} en el SAT: {} / {}", invoice.uuid, status.estado, detail);
+            apply(&mut conn, &invoice, state, &detail)
+        }
+    }
+}
+
+/// Consulta todas las cancelaciones en proceso. Devuelve las que cambiaron de estatus.
+pub fn refresh_pending(db: &Db) -> Result<Vec<Invoice>, String> {
+    let pending = {
+        let conn = db.lock()?;
+        invoices::pending_cancellations(&conn).map_err(|e| format!("base local: {}", e))?
+    };
+    let mut changed = Vec::new();
+    for invoice in pending {
+        match refresh_status(db, invoice.id) {
+            Ok(updated) if updated.status != invoice.status => changed.push(updated),
+            Ok(_) => {}
+            Err(e) => log::warn!("cfdi: no se pudo consultar {}: {}", invoice.uuid, e),
+        }
+    }
+    Ok(changed)
+}
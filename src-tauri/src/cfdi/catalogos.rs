@@ -0,0 +1,169 @@
+//! Claves de los catálogos del SAT que usa el punto de venta (Anexo 20, CFDI 4.0).
+//! Solo las claves vigentes que aplican a un comercio; se validan antes de armar el XML.
+
+pub const REGIMENES_FISCALES: &[(&str, &str)] = &[
+    ("601", "General de Ley Personas Morales"),
+    ("603", "Personas Morales con Fines no Lucrativos"),
+    ("605", "Sueldos y Salarios e Ingresos Asimilados a Salarios"),
+    ("606", "Arrendamiento"),
+    ("607", "Régimen de Enajenación o Adquisición de Bienes"),
+    ("608", "Demás ingresos"),
+    ("610", "Residentes en el Extranjero sin Establecimiento Permanente en México"),
+    ("611", "Ingresos por Dividendos (socios y accionistas)"),
+    ("612", "Personas Físicas con Actividades Empresariales y Profesionales"),
+    ("614", "Ingresos por intereses"),
+    ("615", "Régimen de los ingresos por obtención de premios"),
+    ("616", "Sin obligaciones fiscales"),
+    ("620", "Sociedades Cooperativas de Producción que optan por diferir sus ingresos"),
+    ("621", "Incorporación Fiscal"),
+    ("622", "Actividades Agrícolas, Ganaderas, Silvícolas y Pesqueras"),
+    ("623", "Opcional para Grupos de Sociedades"),
+    ("624", "Coordinados"),
+    ("625", "Actividades Empresariales con ingresos a través de Plataformas Tecnológicas"),
+    ("626", "Régimen Simplificado de Confianza"),
+];
+
+pub const USOS_CFDI: &[(&str, &str)] = &[
+    ("G01", "Adquisición de mercancías"),
+    ("G02", "Devoluciones, descuentos o bonificaciones"),
+    ("G03", "Gastos en general"),
+    ("I01", "Construcciones"),
+    ("I02", "Mobiliario y equipo de oficina por inversiones"),
+    ("I03", "Equipo de transporte"),
+    ("I04", "Equipo de computo y accesorios"),
+    ("I05", "Dados, troqueles, moldes, matrices y herramental"),
+    ("I06", "Comunicaciones telefónicas"),
+    ("I07", "Comunicaciones satelitales"),
+    ("I08", "Otra maquinaria y equipo"),
+    ("D01", "Honorarios médicos, dentales y gastos hospitalarios"),
+    ("D02", "Gastos médicos por incapacidad o discapacidad"),
+    ("D03", "Gastos funerales"),
+    ("D04", "Donativos"),
+    ("D05", "Intereses reales pagados por créditos hipotecarios"),
+    ("D06", "Aportaciones voluntarias al SAR"),
+    ("D07", "Primas por seguros de gastos médicos"),
+    ("D08", "Gastos de transportación escolar obligatoria"),
+    ("D09", "Depósitos en cuentas para el ahorro, primas de pensiones"),
+    ("D10", "Pagos por servicios educativos (colegiaturas)"),
+    ("S01", "Sin efectos fiscales"),
+    ("CP01", "Pagos"),
+    ("CN01", "Nómina"),
+];
+
+pub const FORMAS_PAGO: &[(&str, &str)] = &[
+    ("01", "Efectivo"),
+    ("02", "Cheque nominativo"),
+    ("03", "Transferencia electrónica de fondos"),
+    ("04", "Tarjeta de crédito"),
+    ("05", "Monedero electrónico"),
+    ("06", "Dinero electrónico"),
+    ("08", "Vales de despensa"),
+    ("12", "Dación en pago"),
+    ("13", "Pago por subrogación"),
+    ("14", "Pago por consignación"),
+    ("15", "Condonación"),
+    ("17", "Compensación"),
+    ("23", "Novación"),
+    ("24", "Confusión"),
+    ("25", "Remisión de deuda"),
+    ("26", "Prescripción o caducidad"),
+    ("27", "A satisfacción del acreedor"),
+    ("28", "Tarjeta de débito"),
+    ("29", "Tarjeta de servicios"),
+    ("30", "Aplicación de anticipos"),
+    ("31", "Intermediario pagos"),
+    ("99", "Por definir"),
+];
+
+pub const METODOS_PAGO: &[(&str, &str)] = &[
+    ("PUE", "Pago en una sola exhibición"),
+    ("PPD", "Pago en parcialidades o diferido"),
+];
+
+/// c_Periodicidad de la factura global.
+pub const PERIODICIDADES: &[(&str, &str)] = &[
+    ("01", "Diario"),
+    ("02", "Semanal"),
+    ("03", "Quincenal"),
+    ("04", "Mensual"),
+    ("05", "Bimestral"),
+];
+
+/// c_MotivoCancelacion.
+pub const MOTIVOS_CANCELACION: &[(&str, &str)] = &[
+    ("01", "Comprobante emitido con errores con relación"),
+    ("02", "Comprobante emitido con errores sin relación"),
+    ("03", "No se llevó a cabo la operación"),
+    ("04", "Operación nominativa relacionada en una factura global"),
+];
+
+/// c_TipoRelacion que se usan desde el punto de venta.
+pub const TIPO_RELACION_SUSTITUCION: &str = "04";
+
+pub const MONEDAS: &[&str] = &["MXN", "USD", "XXX"];
+
+pub const TIPOS_COMPROBANTE: &[&str] = &["I", "E", "T", "N", "P"];
+
+pub const EXPORTACION: &[&str] = &["01", "02", "03", "04"];
+
+/// 01 no objeto de impuesto, 02 sí objeto, 03 sí objeto no obligado al desglose, 04 sí objeto sin causar.
+pub const OBJETOS_IMPUESTO: &[&str] = &["01", "02", "03", "04", "05"];
+
+pub const IMPUESTOS: &[&str] = &["001", "002", "003"];
+
+pub const TIPOS_FACTOR: &[&str] = &["Tasa", "Cuota", "Exento"];
+
+/// Claves de unidad más comunes en mostrador (c_ClaveUnidad).
+pub const CLAVES_UNIDAD: &[(&str, &str)] = &[
+    ("H87", "Pieza"),
+    ("KGM", "Kilogramo"),
+    ("GRM", "Gramo"),
+    ("LTR", "Litro"),
+    ("MLT", "Mililitro"),
+    ("MTR", "Metro"),
+    ("CMT", "Centímetro"),
+    ("XBX", "Caja"),
+    ("XPK", "Paquete"),
+    ("SET", "Conjunto"),
+    ("E48", "Unidad de servicio"),
+    ("ACT", "Actividad"),
+];
+
+/// Clave de producto "No existe en el catálogo".
+pub const CLAVE_PROD_SERV_GENERICA: &str = "01010101";
+pub const CLAVE_UNIDAD_PIEZA: &str = "H87";
+/// Clave de unidad "Actividad", la que pide el SAT para cada ticket de la factura global.
+pub const CLAVE_UNIDAD_ACTIVIDAD: &str = "ACT";
+
+/// RFC genérico para público en general y para extranjeros.
+pub const RFC_PUBLICO_GENERAL: &str = "XAXX010101000";
+pub const RFC_EXTRANJERO: &str = "XEXX010101000";
+pub const NOMBRE_PUBLICO_GENERAL: &str = "PUBLICO EN GENERAL";
+/// Régimen "Sin obligaciones fiscales" y uso "Sin efectos fiscales" del receptor genérico.
+pub const REGIMEN_SIN_OBLIGACIONES: &str = "616";
+pub const USO_SIN_EFECTOS: &str = "S01";
+
+pub fn contains(catalog: &[(&str, &str)], key: &str) -> bool {
+    catalog.iter().any(|(k, _)| *k == key)
+}
+
+pub fn description(catalog: &'static [(&'static str, &'static str)], key: &str) -> Option<&'static str> {
+    catalog.iter().find(|(k, _)| *k == key).map(|(_, d)| *d)
+}
+
+/// Forma de pago del SAT a partir del método de cobro del POS (`cash`, `card`, `transfer`).
+pub fn forma_pago_from_pos(payment_method: Option<&str>) -> &'static str {
+    match payment_method.unwrap_or("").trim().to_lowercase().as_str() {
+        "cash" | "efectivo" => "01",
+        "card" | "tarjeta" | "credit" | "credito" | "crédito" => "04",
+        "debit" | "debito" | "débito" => "28",
+        "transfer" | "transferencia" => "03",
+        "check" | "cheque" => "02",
+        _ => "99",
+    }
+}
+
+/// Nombre corto de la unidad para el atributo `Unidad`.
+pub fn unidad_name(clave: &str) -> Option<&'static str> {
+    description(CLAVES_UNIDAD, clave)
+}
@@ -0,0 +1,272 @@
+//! Modelo del `cfdi:Comprobante` 4.0 y su conversión a XML.
+
+use super::xml::Element;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+pub const NS_CFDI: &str = "http://www.sat.gob.mx/cfd/4";
+pub const NS_XSI: &str = "http://www.w3.org/2001/XMLSchema-instance";
+pub const SCHEMA_LOCATION: &str = "http://www.sat.gob.mx/cfd/4 http://www.sat.gob.mx/sitio_internet/cfd/4/cfdv40.xsd";
+
+/// Importes con dos decimales (`1234.50`).
+pub fn importe(d: Decimal) -> String {
+    format!("{:.2}", d)
+}
+
+/// Cantidades y valores unitarios: hasta 6 decimales, mínimo 2 (`3.5` -> `3.50`).
+pub fn decimal6(d: Decimal) -> String {
+    let d = d.round_dp(6).normalize();
+    if d.scale() < 2 {
+        format!("{:.2}", d)
+    } else {
+        d.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Traslado {
+    pub base: Decimal,
+    /// `002` IVA, `003` IEPS.
+    pub impuesto: String,
+    /// `Tasa` o `Exento`.
+    pub tipo_factor: String,
+    /// `0.160000`; vacío si es exento.
+    pub tasa_o_cuota: Option<String>,
+    pub importe: Option<Decimal>,
+}
+
+impl Traslado {
+    fn to_element(&self) -> Element {
+        Element::new("cfdi:Traslado")
+            .attr("Base", importe(self.base))
+            .attr("Impuesto", &self.impuesto)
+            .attr("TipoFactor", &self.tipo_factor)
+            .attr_opt("TasaOCuota", self.tasa_o_cuota.clone())
+            .attr_opt("Importe", self.importe.map(importe))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Concepto {
+    pub clave_prod_serv: String,
+    pub no_identificacion: Option<String>,
+    pub cantidad: Decimal,
+    pub clave_unidad: String,
+    pub unidad: Option<String>,
+    pub descripcion: String,
+    pub valor_unitario: Decimal,
+    pub importe: Decimal,
+    pub descuento: Option<Decimal>,
+    /// `01` no objeto de impuesto, `02` sí objeto.
+    pub objeto_imp: String,
+    pub traslados: Vec<Traslado>,
+}
+
+impl Concepto {
+    fn to_element(&self) -> Element {
+        let mut e = Element::new("cfdi:Concepto")
+            .attr("ClaveProdServ", &self.clave_prod_serv)
+            .attr_opt("NoIdentificacion", self.no_identificacion.clone())
+            .attr("Cantidad", decimal6(self.cantidad))
+            .attr("ClaveUnidad", &self.clave_unidad)
+            .attr_opt("Unidad", self.unidad.clone())
+            .attr("Descripcion", &self.descripcion)
+            .attr("ValorUnitario", decimal6(self.valor_unitario))
+            .attr("Importe", importe(self.importe))
+            .attr_opt("Descuento", self.descuento.map(importe))
+            .attr("ObjetoImp", &self.objeto_imp);
+        if !self.traslados.is_empty() {
+            let mut traslados = Element::new("cfdi:Traslados");
+            for t in &self.traslados {
+                traslados = traslados.child(t.to_element());
+            }
+            e = e.child(Element::new("cfdi:Impuestos").child(traslados));
+        }
+        e
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Emisor {
+    pub rfc: String,
+    pub nombre: String,
+    pub regimen_fiscal: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Receptor {
+    pub rfc: String,
+    pub nombre: String,
+    pub domicilio_fiscal: String,
+    pub regimen_fiscal: String,
+    pub uso_cfdi: String,
+}
+
+/// Nodo para la factura global a público en general.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InformacionGlobal {
+    /// `01` diaria, `02` semanal, `03` quincenal, `04` mensual, `05` bimestral.
+    pub periodicidad: String,
+    /// `01`–`12` (o `13`–`18` para bimestres).
+    pub meses: String,
+    pub anio: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CfdiRelacionados {
+    /// c_TipoRelacion, p. ej. `04` sustitución de CFDI previos.
+    pub tipo_relacion: String,
+    pub uuids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comprobante {
+    pub serie: Option<String>,
+    pub folio: Option<String>,
+    /// Hora local del lugar de expedición, `AAAA-MM-DDThh:mm:ss`.
+    pub fecha: String,
+    pub forma_pago: Option<String>,
+    pub subtotal: Decimal,
+    pub descuento: Option<Decimal>,
+    pub moneda: String,
+    pub total: Decimal,
+    /// `I` ingreso, `E` egreso, `P` pago.
+    pub tipo_de_comprobante: String,
+    pub exportacion: String,
+    pub metodo_pago: Option<String>,
+    pub lugar_expedicion: String,
+    pub no_certificado: Option<String>,
+    pub sello: Option<String>,
+    pub certificado: Option<String>,
+    pub informacion_global: Option<InformacionGlobal>,
+    pub relacionados: Option<CfdiRelacionados>,
+    pub emisor: Emisor,
+    pub receptor: Receptor,
+    pub conceptos: Vec<Concepto>,
+    /// Traslados agrupados por impuesto, tipo de factor y tasa.
+    pub traslados: Vec<Traslado>,
+    /// Nodos dentro de `cfdi:Complemento` (pagos, timbre fiscal digital).
+    #[serde(skip)]
+    pub complementos: Vec<Element>,
+    /// Prefijos de los complementos (`xmlns:pago20`, ...) y sus `schemaLocation`.
+    #[serde(skip)]
+    pub namespaces: Vec<(String, String, String)>,
+}
+
+impl Comprobante {
+    /// Suma de los traslados con tasa (los exentos no llevan importe).
+    pub fn total_traslados(&self) -> Option<Decimal> {
+        let rated: Vec<Decimal> = self.traslados.iter().filter_map(|t| t.importe).collect();
+        if rated.is_empty() {
+            None
+        } else {
+            Some(rated.into_iter().sum())
+        }
+    }
+
+    pub fn to_element(&self) -> Element {
+        let mut schema_location = SCHEMA_LOCATION.to_string();
+        let mut root = Element::new("cfdi:Comprobante")
+            .attr("xmlns:cfdi", NS_CFDI)
+            .attr("xmlns:xsi", NS_XSI);
+        for (prefix, uri, xsd) in &self.namespaces {
+            root = root.attr(&format!("xmlns:{}", prefix), uri);
+            schema_location.push_str(&format!(" {} {}", uri, xsd));
+        }
+        root = root
+            .attr("xsi:schemaLocation", schema_location)
+            .attr("Version", "4.0")
+            .attr_opt("Serie", self.serie.clone())
+            .attr_opt("Folio", self.folio.clone())
+            .attr("Fecha", &self.fecha)
+            .attr_opt("Sello", self.sello.clone())
+            .attr_opt("FormaPago", self.forma_pago.clone())
+            .attr_opt("NoCertificado", self.no_certificado.clone())
+            .attr_opt("Certificado", self.certificado.clone())
+            .attr("SubTotal", importe_o_cero(self.subtotal, &self.moneda))
+            .attr_opt("Descuento", self.descuento.map(importe))
+            .attr("Moneda", &self.moneda)
+            .attr("Total", importe_o_cero(self.total, &self.moneda))
+            .attr("TipoDeComprobante", &self.tipo_de_comprobante)
+            .attr("Exportacion", &self.exportacion)
+            .attr_opt("MetodoPago", self.metodo_pago.clone())
+            .attr("LugarExpedicion", &self.lugar_expedicion);
+
+        if let Some(g) = &self.informacion_global {
+            root = root.child(
+                Element::new("cfdi:InformacionGlobal")
+                    .attr("Periodicidad", &g.periodicidad)
+                    .attr("Meses", &g.meses)
+                    .attr("Año", g.anio.to_string()),
+            );
+        }
+        if let Some(rel) = &self.relacionados {
+            let mut e = Element::new("cfdi:CfdiRelacionados").attr("TipoRelacion", &rel.tipo_relacion);
+            for uuid in &rel.uuids {
+                e = e.child(Element::new("cfdi:CfdiRelacionado").attr("UUID", uuid));
+            }
+            root = root.child(e);
+        }
+        root = root
+            .child(
+                Element::new("cfdi:Emisor")
+                    .attr("Rfc", &self.emisor.rfc)
+                    .attr("Nombre", &self.emisor.nombre)
+                    .attr("RegimenFiscal", &self.emisor.regimen_fiscal),
+            )
+            .child(
+                Element::new("cfdi:Receptor")
+                    .attr("Rfc", &self.receptor.rfc)
+                    .attr("Nombre", &self.receptor.nombre)
+                    .attr("DomicilioFiscalReceptor", &self.receptor.domicilio_fiscal)
+                    .attr("RegimenFiscalReceptor", &self.receptor.regimen_fiscal)
+                    .attr("UsoCFDI", &self.receptor.uso_cfdi),
+            );
+
+        let mut conceptos = Element::new("cfdi:Conceptos");
+        for c in &self.conceptos {
+            let mut e = c.to_element();
+            // El concepto único del CFDI de pagos lleva Cantidad 1 y ValorUnitario/Importe en 0 literal.
+            if self.moneda == "XXX" {
+                e.set_attr("Cantidad", "1");
+                e.set_attr("ValorUnitario", "0");
+                e.set_attr("Importe", "0");
+            }
+            conceptos = conceptos.child(e);
+        }
+        root = root.child(conceptos);
+
+        if !self.traslados.is_empty() {
+            let mut traslados = Element::new("cfdi:Traslados");
+            for t in &self.traslados {
+                traslados = traslados.child(t.to_element());
+            }
+            root = root.child(
+                Element::new("cfdi:Impuestos")
+                    .attr_opt("TotalImpuestosTrasladados", self.total_traslados().map(importe))
+                    .child(traslados),
+            );
+        }
+        if !self.complementos.is_empty() {
+            let mut complemento = Element::new("cfdi:Complemento");
+            for c in &self.complementos {
+                complemento = complemento.child(c.clone());
+            }
+            root = root.child(complemento);
+        }
+        root
+    }
+
+    pub fn to_xml(&self) -> String {
+        self.to_element().to_document()
+    }
+}
+
+/// En el CFDI de pagos (moneda `XXX`) SubTotal y Total van en `0`.
+fn importe_o_cero(d: Decimal, moneda: &str) -> String {
+    if moneda == "XXX" {
+        "0".to_string()
+    } else {
+        importe(d)
+    }
+}
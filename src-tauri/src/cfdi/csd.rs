@@ -0,0 +1,198 @@
+//! Certificado de Sello Digital (CSD) del emisor.
+//!
+//! El `.cer` y el `.key` se guardan en la base local tal como los entrega el SAT: la llave
+//! privada sigue cifrada con su contraseña (PKCS#8). La contraseña va al llavero del sistema
+//! (Keychain, Credential Manager o Secret Service), nunca a la base ni a archivos.
+
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use x509_parser::prelude::*;
+
+const KEYRING_SERVICE: &str = "moneymachine-csd";
+
+/// OID `x500UniqueIdentifier`: el SAT pone ahí el RFC del titular (y el del representante legal).
+const OID_UNIQUE_IDENTIFIER: &str = "2.5.4.45";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CertificateInfo {
+    /// Número de certificado de 20 dígitos (`NoCertificado`).
+    pub numero: String,
+    pub rfc: String,
+    pub nombre: String,
+    /// Vigencia en UTC (ISO 8601).
+    pub valid_from: String,
+    pub valid_to: String,
+}
+
+impl CertificateInfo {
+    pub fn is_valid_at(&self, when: chrono::DateTime<chrono::Utc>) -> bool {
+        let parse = |s: &str| chrono::DateTime::parse_from_rfc3339(s).map(|d| d.with_timezone(&chrono::Utc));
+        match (parse(&self.valid_from), parse(&self.valid_to)) {
+            (Ok(from), Ok(to)) => from <= when && when <= to,
+            _ => false,
+        }
+    }
+}
+
+/// CSD listo para sellar.
+pub struct Csd {
+    pub info: CertificateInfo,
+    pub cer_der: Vec<u8>,
+    pub key: RsaPrivateKey,
+}
+
+fn timestamp(t: &ASN1Time) -> String {
+    chrono::DateTime::from_timestamp(t.timestamp(), 0)
+        .map(|d| d.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// Lee número, RFC, nombre y vigencia de un `.cer` (DER).
+pub fn read_certificate(cer_der: &[u8]) -> Result<(CertificateInfo, RsaPublicKey), String> {
+    let (_, cert) = X509Certificate::from_der(cer_der)
+        .map_err(|e| format!("El archivo .cer no es un certificado válido: {}", e))?;
+
+    // El número de serie del SAT son dígitos ASCII codificados en bytes.
+    let serial = cert.raw_serial();
+    let numero = if serial.iter().all(|b| b.is_ascii_digit()) {
+        String::from_utf8_lossy(serial).to_string()
+    } else {
+        cert.raw_serial_as_string().replace(':', "")
+    };
+
+    let subject = cert.subject();
+    let unique = subject
+        .iter_attributes()
+        .find(|a| a.attr_type().to_id_string() == OID_UNIQUE_IDENTIFIER)
+        .and_then(|a| a.as_str().ok())
+        .unwrap_or_default();
+    let rfc = unique.split('/').next().unwrap_or_default().trim().to_uppercase();
+    if rfc.is_empty() {
+        return Err("El certificado no trae RFC; verifica que sea un CSD emitido por el SAT.".to_string());
+    }
+    let nombre = subject
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let public = RsaPublicKey::from_public_key_der(cert.public_key().raw)
+        .map_err(|e| format!("El certificado no tiene una llave RSA: {}", e))?;
+    let validity = cert.validity();
+    Ok((
+        CertificateInfo {
+            numero,
+            rfc,
+            nombre,
+            valid_from: timestamp(&validity.not_before),
+            valid_to: timestamp(&validity.not_after),
+        },
+        public,
+    ))
+}
+
+/// Descifra el `.key` (PKCS#8 cifrado, DER) con su contraseña.
+pub fn read_private_key(key_der: &[u8], password: &str) -> Result<RsaPrivateKey, String> {
+    RsaPrivateKey::from_pkcs8_encrypted_der(key_der, password.as_bytes()).map_err(|e| match e {
+        pkcs8::Error::EncryptedPrivateKey(_) => "La contraseña de la llave privada (.key) es incorrecta.".to_string(),
+        other => format!("No se pudo leer la llave privada (.key): {}", other),
+    })
+}
+
+/// Valida el par `.cer`/`.key` y devuelve el CSD. `expected_rfc` es el RFC del emisor configurado.
+pub fn open_pair(cer_der: &[u8], key_der: &[u8], password: &str, expected_rfc: Option<&str>) -> Result<Csd, String> {
+    let (info, public) = read_certificate(cer_der)?;
+    let key = read_private_key(key_der, password)?;
+    if key.to_public_key() != public {
+        return Err("La llave privada (.key) no corresponde al certificado (.cer).".to_string());
+    }
+    let now = chrono::Utc::now();
+    if !info.is_valid_at(now) {
+        return Err(format!(
+            "El certificado {} no está vigente (válido del {} al {}). Tramita uno nuevo en el SAT.",
+            info.numero, info.valid_from, info.valid_to
+        ));
+    }
+    if let Some(expected) = expected_rfc.map(|r| r.trim().to_uppercase()).filter(|r| !r.is_empty()) {
+        if expected != info.rfc {
+            return Err(format!(
+                "El certificado es del RFC {} pero el emisor configurado es {}.",
+                info.rfc, expected
+            ));
+        }
+    }
+    Ok(Csd { info, cer_der: cer_der.to_vec(), key })
+}
+
+fn keyring_entry(numero: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, numero).map_err(|e| format!("llavero del sistema: {}", e))
+}
+
+/// Guarda el CSD (reemplaza el anterior) y su contraseña en el llavero.
+pub(crate) fn store(conn: &Connection, cer_der: &[u8], key_der: &[u8], password: &str, csd: &Csd) -> Result<(), String> {
+    keyring_entry(&csd.info.numero)?
+        .set_password(password)
+        .map_err(|e| format!("No se pudo guardar la contraseña en el llavero del sistema: {}", e))?;
+    let previous: Option<String> = conn
+        .query_row("SELECT numero FROM cfdi_csd WHERE id = 1", [], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("base local: {}", e))?;
+    conn.execute(
+        "INSERT INTO cfdi_csd (id, cer, key, numero, rfc, valid_from, valid_to)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(id) DO UPDATE SET cer = excluded.cer, key = excluded.key, numero = excluded.numero,
+             rfc = excluded.rfc, valid_from = excluded.valid_from, valid_to = excluded.valid_to,
+             imported_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')",
+        params![cer_der, key_der, csd.info.numero, csd.info.rfc, csd.info.valid_from, csd.info.valid_to],
+    )
+    .map_err(|e| format!("base local: {}", e))?;
+    if let Some(old) = previous.filter(|n| *n != csd.info.numero) {
+        // La contraseña del certificado anterior ya no se necesita.
+        if let Ok(entry) = keyring_entry(&old) {
+            let _ = entry.delete_credential();
+        }
+    }
+    Ok(())
+}
+
+/// Datos del CSD guardado, sin abrir la llave.
+pub(crate) fn stored_info(conn: &Connection) -> Result<Option<CertificateInfo>, String> {
+    let cer: Option<Vec<u8>> = conn
+        .query_row("SELECT cer FROM cfdi_csd WHERE id = 1", [], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("base local: {}", e))?;
+    match cer {
+        Some(der) => read_certificate(&der).map(|(info, _)| Some(info)),
+        None => Ok(None),
+    }
+}
+
+/// Abre el CSD guardado (llave descifrada con la contraseña del llavero) para sellar.
+pub(crate) fn load(conn: &Connection, expected_rfc: &str) -> Result<Csd, String> {
+    let stored: Option<(Vec<u8>, Vec<u8>, String)> = conn
+        .query_row("SELECT cer, key, numero FROM cfdi_csd WHERE id = 1", [], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+        })
+        .optional()
+        .map_err(|e| format!("base local: {}", e))?;
+    let (cer, key, numero) =
+        stored.ok_or("No hay certificado de sello digital (CSD); impórtalo en Configuración → Facturación.")?;
+    let password = keyring_entry(&numero)?
+        .get_password()
+        .map_err(|e| format!("No se encontró la contraseña del CSD en el llavero del sistema; vuelve a importarlo ({}).", e))?;
+    open_pair(&cer, &key, &password, Some(expected_rfc))
+}
+
+pub(crate) fn remove(conn: &Connection) -> Result<(), String> {
+    if let Some(info) = stored_info(conn)? {
+        if let Ok(entry) = keyring_entry(&info.numero) {
+            let _ = entry.delete_credential();
+        }
+    }
+    conn.execute("DELETE FROM cfdi_csd WHERE id = 1", [])
+        .map(|_| ())
+        .map_err(|e| format!("base local: {}", e))
+}
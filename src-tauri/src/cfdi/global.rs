@@ -0,0 +1,169 @@
+//! Factura global a público en general (`XAXX010101000`).
+//!
+//! Junta los tickets cobrados que nadie facturó en el periodo: un concepto por ticket
+//! (ClaveProdServ 01010101, unidad ACT, NoIdentificacion = folio del ticket) con sus impuestos
+//! y `InformacionGlobal` con periodicidad, meses y año.
+
+use super::comprobante::{Comprobante, Concepto, InformacionGlobal, Receptor};
+use super::{catalogos, emisor, fecha_emision, fill_totals, get_settings, group_traslados, sale_item_taxes};
+use crate::db::{self, Sale};
+use crate::money::{Currency, Money};
+use chrono::{Datelike, NaiveDate};
+use rusqlite::Connection;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GlobalInvoiceRequest {
+    /// c_Periodicidad: `01` diaria, `02` semanal, `03` quincenal, `04` mensual, `05` bimestral.
+    pub periodicidad: String,
+    /// Primer y último día del periodo (`AAAA-MM-DD`, hora local).
+    pub desde: String,
+    pub hasta: String,
+    /// Forma de pago; por omisión la de mayor monto entre los tickets.
+    #[serde(default)]
+    pub forma_pago: Option<String>,
+}
+
+/// Tickets cobrados en el periodo que no están en ninguna factura vigente.
+pub(crate) fn pending_sales(conn: &Connection, desde: &str, hasta: &str) -> rusqlite::Result<Vec<Sale>> {
+    let mut stmt = conn.prepare(
+        "SELECT * FROM sales
+         WHERE status = 'completed'
+           AND date(created_at, 'localtime') BETWEEN ?1 AND ?2
+           AND NOT EXISTS (
+               SELECT 1 FROM invoice_sales s JOIN invoices i ON i.id = s.invoice_id
+               WHERE s.sale_id = sales.id AND i.status != 'cancelado')
+         ORDER BY created_at",
+    )?;
+    let mut sales = stmt.query_map([desde, hasta], Sale::from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+    for sale in sales.iter_mut() {
+        if let Some(id) = sale.id {
+            sale.sale_items = db::sale_items(conn, id)?;
+        }
+    }
+    Ok(sales)
+}
+
+/// `InformacionGlobal` del periodo. El periodo no puede pasar de un mes (o bimestre).
+fn informacion_global(periodicidad: &str, desde: NaiveDate, hasta: NaiveDate) -> Result<InformacionGlobal, String> {
+    if !catalogos::contains(catalogos::PERIODICIDADES, periodicidad) {
+        return Err(format!("Periodicidad {} inválida (01 a 05).", periodicidad));
+    }
+    if hasta < desde {
+        return Err("La fecha final del periodo es anterior a la inicial.".to_string());
+    }
+    let bimestral = periodicidad == "05";
+    let bimestre = |d: NaiveDate| (d.month() - 1) / 2;
+    let same_period = desde.year() == hasta.year()
+        && if bimestral { bimestre(desde) == bimestre(hasta) } else { desde.month() == hasta.month() };
+    if !same_period {
+        return Err(if bimestral {
+            "El periodo debe caer dentro de un solo bimestre.".to_string()
+        } else {
+            "El periodo debe caer dentro de un solo mes.".to_string()
+        });
+    }
+    let meses = if bimestral { 13 + bimestre(desde) } else { desde.month() };
+    Ok(InformacionGlobal { periodicidad: periodicidad.to_string(), meses: format!("{:02}", meses), anio: desde.year() })
+}
+
+/// Concepto de un ticket: importes sin impuestos y traslados agrupados por tasa. El importe de
+/// cada traslado se recalcula sobre la base del ticket, que es lo que valida el SAT por concepto.
+fn ticket_concepto(conn: &Connection, sale: &Sale) -> Result<Concepto, String> {
+    let taxes = sale_item_taxes(conn, sale)?;
+    let importe = Money::sum(taxes.iter().map(|t| t.gross.base), Currency::MXN);
+    let neto = Money::sum(taxes.iter().map(|t| t.net.base), Currency::MXN);
+    let item_traslados: Vec<_> = taxes.iter().flat_map(|t| super::concepto_traslados(&t.profile, &t.net)).collect();
+    let mut traslados = group_traslados(&item_traslados);
+    for t in traslados.iter_mut() {
+        let tasa = t.tasa_o_cuota.as_deref().and_then(|r| r.parse::<Decimal>().ok());
+        if let (Some(tasa), Some(importe)) = (tasa, t.importe.as_mut()) {
+            *importe = (t.base * tasa).round_dp(2);
+        }
+    }
+    let importe = importe.to_decimal();
+    let descuento = (importe - neto.to_decimal()).max(Decimal::ZERO);
+    Ok(Concepto {
+        clave_prod_serv: catalogos::CLAVE_PROD_SERV_GENERICA.to_string(),
+        no_identificacion: Some(sale.sale_number.clone()),
+        cantidad: Decimal::ONE,
+        clave_unidad: catalogos::CLAVE_UNIDAD_ACTIVIDAD.to_string(),
+        unidad: None,
+        descripcion: "Venta".to_string(),
+        valor_unitario: importe,
+        importe,
+        descuento: if descuento.is_zero() { None } else { Some(descuento) },
+        objeto_imp: "02".to_string(),
+        traslados,
+    })
+}
+
+/// Forma de pago con el mayor monto cobrado entre los tickets.
+fn forma_pago_predominante(sales: &[Sale]) -> Option<&'static str> {
+    let mut totals: BTreeMap<&'static str, Decimal> = BTreeMap::new();
+    for sale in sales {
+        *totals.entry(catalogos::forma_pago_from_pos(sale.payment_method.as_deref())).or_default() += sale.total;
+    }
+    totals.into_iter().filter(|(fp, _)| *fp != "99").max_by_key(|(_, total)| *total).map(|(fp, _)| fp)
+}
+
+/// Arma (sin sellar) la factura global del periodo y devuelve los tickets que incluye.
+pub fn build_global_invoice(conn: &Connection, req: &GlobalInvoiceRequest) -> Result<(Comprobante, Vec<i64>), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let settings = get_settings(conn).map_err(sql)?;
+    settings.check().map_err(|e| format!("Configura los datos fiscales del emisor:\n{}", e))?;
+    let parse = |d: &str| {
+        NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").map_err(|_| format!("Fecha inválida: {} (AAAA-MM-DD).", d))
+    };
+    let (desde, hasta) = (parse(&req.desde)?, parse(&req.hasta)?);
+    let global = informacion_global(&req.periodicidad, desde, hasta)?;
+
+    let sales = pending_sales(conn, &desde.to_string(), &hasta.to_string()).map_err(sql)?;
+    if sales.is_empty() {
+        return Err(format!("No hay tickets sin facturar del {} al {}.", desde, hasta));
+    }
+    let conceptos = sales.iter().map(|s| ticket_concepto(conn, s)).collect::<Result<Vec<_>, _>>()?;
+    let forma_pago = match &req.forma_pago {
+        Some(fp) => fp.clone(),
+        None => forma_pago_predominante(&sales)
+            .ok_or("No se pudo deducir la forma de pago de los tickets; indícala.")?
+            .to_string(),
+    };
+
+    let mut cfdi = Comprobante {
+        serie: Some(settings.serie.trim().to_string()).filter(|s| !s.is_empty()),
+        folio: Some(settings.next_folio.to_string()),
+        fecha: fecha_emision(),
+        forma_pago: Some(forma_pago),
+        subtotal: Decimal::ZERO,
+        descuento: None,
+        moneda: "MXN".to_string(),
+        total: Decimal::ZERO,
+        tipo_de_comprobante: "I".to_string(),
+        exportacion: "01".to_string(),
+        metodo_pago: Some("PUE".to_string()),
+        lugar_expedicion: settings.codigo_postal.clone(),
+        no_certificado: None,
+        sello: None,
+        certificado: None,
+        informacion_global: Some(global),
+        relacionados: None,
+        emisor: emisor(&settings),
+        receptor: Receptor {
+            rfc: catalogos::RFC_PUBLICO_GENERAL.to_string(),
+            nombre: catalogos::NOMBRE_PUBLICO_GENERAL.to_string(),
+            domicilio_fiscal: settings.codigo_postal.clone(),
+            regimen_fiscal: catalogos::REGIMEN_SIN_OBLIGACIONES.to_string(),
+            uso_cfdi: catalogos::USO_SIN_EFECTOS.to_string(),
+        },
+        conceptos,
+        traslados: Vec::new(),
+        complementos: Vec::new(),
+        namespaces: Vec::new(),
+    };
+    fill_totals(&mut cfdi);
+    super::check(&cfdi)?;
+    Ok((cfdi, sales.iter().filter_map(|s| s.id).collect()))
+}
@@ -0,0 +1,197 @@
+//! CFDI timbrados guardados en la base local (tabla `invoices`).
+
+use super::comprobante::Comprobante;
+use super::pac::StampResult;
+use crate::db::get_decimal;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Invoice {
+    pub id: i64,
+    pub sale_id: Option<i64>,
+    pub tipo_de_comprobante: String,
+    pub serie: Option<String>,
+    pub folio: Option<String>,
+    pub uuid: String,
+    pub receptor_rfc: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total: Decimal,
+    /// `vigente`, `en_proceso` (cancelación pendiente de aceptar) o `cancelado`.
+    pub status: String,
+    pub pac: String,
+    pub stamped_at: String,
+    pub created_at: String,
+    /// c_MotivoCancelacion de la solicitud de cancelación.
+    pub cancel_motivo: Option<String>,
+    pub folio_sustitucion: Option<String>,
+    /// Último estatus de cancelación que reportó el SAT.
+    pub cancel_detail: Option<String>,
+    pub cancel_requested_at: Option<String>,
+    pub cancelled_at: Option<String>,
+}
+
+impl Invoice {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Invoice {
+            id: row.get("id")?,
+            sale_id: row.get("sale_id")?,
+            tipo_de_comprobante: row.get("tipo_de_comprobante")?,
+            serie: row.get("serie")?,
+            folio: row.get("folio")?,
+            uuid: row.get("uuid")?,
+            receptor_rfc: row.get("receptor_rfc")?,
+            total: get_decimal(row, "total")?,
+            status: row.get("status")?,
+            pac: row.get("pac")?,
+            stamped_at: row.get("stamped_at")?,
+            created_at: row.get("created_at")?,
+            cancel_motivo: row.get("cancel_motivo")?,
+            folio_sustitucion: row.get("folio_sustitucion")?,
+            cancel_detail: row.get("cancel_detail")?,
+            cancel_requested_at: row.get("cancel_requested_at")?,
+            cancelled_at: row.get("cancelled_at")?,
+        })
+    }
+}
+
+const COLUMNS: &str = "id, sale_id, tipo_de_comprobante, serie, folio, uuid, receptor_rfc, total, status, pac,
+     stamped_at, created_at, cancel_motivo, folio_sustitucion, cancel_detail, cancel_requested_at, cancelled_at";
+
+/// Aparta las ventas mientras se timbra su CFDI. Falla si otra solicitud ya las tiene apartadas.
+pub(crate) fn reserve(conn: &Connection, sale_ids: &[i64]) -> Result<(), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    for sale_id in sale_ids {
+        let reserved = conn
+            .execute("INSERT INTO invoice_reservations (sale_id) VALUES (?1) ON CONFLICT DO NOTHING", [sale_id])
+            .map_err(sql)?;
+        if reserved == 0 {
+            return Err(format!("La venta {} ya se está facturando en otra solicitud.", sale_id));
+        }
+    }
+    Ok(())
+}
+
+/// Libera las ventas apartadas cuando el timbrado no llega a guardarse.
+pub(crate) fn release(conn: &Connection, sale_ids: &[i64]) -> rusqlite::Result<()> {
+    for sale_id in sale_ids {
+        conn.execute("DELETE FROM invoice_reservations WHERE sale_id = ?1", [sale_id])?;
+    }
+    Ok(())
+}
+
+/// Guarda el CFDI timbrado, lo liga a sus ventas y las libera. Una factura individual deja la
+/// venta como `invoice`; los tickets de una factura global conservan su tipo de comprobante.
+///
+/// Vuelve a revisar que ninguna venta tenga ya otro CFDI vigente (salvo el que este sustituye).
+pub(crate) fn insert(
+    conn: &mut Connection,
+    sale_ids: &[i64],
+    cfdi: &Comprobante,
+    stamp: &StampResult,
+    pac: &str,
+) -> Result<Invoice, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let tx = conn.transaction().map_err(sql)?;
+    let replaced: Vec<&String> = cfdi.relacionados.iter().flat_map(|r| r.uuids.iter()).collect();
+    for sale_id in sale_ids {
+        if let Some(existing) = active_for_sale(&tx, *sale_id).map_err(sql)? {
+            if !replaced.iter().any(|uuid| uuid.eq_ignore_ascii_case(&existing.uuid)) {
+                return Err(format!(
+                    "El CFDI {} se timbró pero la venta {} ya tiene la factura {}; cancélalo ante el SAT.",
+                    stamp.uuid, sale_id, existing.uuid
+                ));
+            }
+        }
+    }
+    let individual = cfdi.informacion_global.is_none() && sale_ids.len() == 1;
+    tx.execute(
+        "INSERT INTO invoices (sale_id, tipo_de_comprobante, serie, folio, uuid, receptor_rfc, total, xml, pac, stamped_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            if individual { sale_ids.first() } else { None },
+            cfdi.tipo_de_comprobante,
+            cfdi.serie,
+            cfdi.folio,
+            stamp.uuid,
+            cfdi.receptor.rfc,
+            cfdi.total.to_string(),
+            stamp.xml,
+            pac,
+            stamp.fecha_timbrado
+        ],
+    )
+    .map_err(sql)?;
+    let id = tx.last_insert_rowid();
+    for sale_id in sale_ids {
+        tx.execute("INSERT INTO invoice_sales (invoice_id, sale_id) VALUES (?1, ?2)", params![id, sale_id])
+            .map_err(sql)?;
+        if individual {
+            tx.execute(
+                "UPDATE sales SET receipt_type = 'invoice', updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+                [sale_id],
+            )
+            .map_err(sql)?;
+        }
+    }
+    release(&tx, sale_ids).map_err(sql)?;
+    tx.commit().map_err(sql)?;
+    get(conn, id).map_err(sql)?.ok_or_else(|| "No se encontró la factura guardada.".to_string())
+}
+
+pub(crate) fn get(conn: &Connection, id: i64) -> rusqlite::Result<Option<Invoice>> {
+    conn.query_row(&format!("SELECT {} FROM invoices WHERE id = ?1", COLUMNS), [id], Invoice::from_row)
+        .optional()
+}
+
+/// Factura vigente (o con cancelación en proceso) que incluye la venta, individual o global.
+pub(crate) fn active_for_sale(conn: &Connection, sale_id: i64) -> rusqlite::Result<Option<Invoice>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM invoices
+             WHERE status != 'cancelado' AND id IN (SELECT invoice_id FROM invoice_sales WHERE sale_id = ?1)
+             ORDER BY id DESC LIMIT 1",
+            COLUMNS
+        ),
+        [sale_id],
+        Invoice::from_row,
+    )
+    .optional()
+}
+
+/// Ventas incluidas en una factura.
+pub(crate) fn sale_ids(conn: &Connection, invoice_id: i64) -> rusqlite::Result<Vec<i64>> {
+    let mut stmt = conn.prepare("SELECT sale_id FROM invoice_sales WHERE invoice_id = ?1 ORDER BY sale_id")?;
+    let rows = stmt.query_map([invoice_id], |r| r.get(0))?;
+    rows.collect()
+}
+
+pub(crate) fn get_by_uuid(conn: &Connection, uuid: &str) -> rusqlite::Result<Option<Invoice>> {
+    conn.query_row(&format!("SELECT {} FROM invoices WHERE uuid = ?1", COLUMNS), [uuid], Invoice::from_row)
+        .optional()
+}
+
+/// Facturas con la cancelación pendiente de que el receptor la acepte.
+pub(crate) fn pending_cancellations(conn: &Connection) -> rusqlite::Result<Vec<Invoice>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM invoices WHERE status = 'en_proceso' ORDER BY id", COLUMNS))?;
+    let rows = stmt.query_map([], Invoice::from_row)?;
+    rows.collect()
+}
+
+pub(crate) fn list(conn: &Connection, sale_id: Option<i64>, limit: i64) -> rusqlite::Result<Vec<Invoice>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM invoices WHERE ?1 IS NULL OR sale_id = ?1 ORDER BY id DESC LIMIT ?2",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![sale_id, limit], Invoice::from_row)?;
+    rows.collect()
+}
+
+pub(crate) fn xml(conn: &Connection, id: i64) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT xml FROM invoices WHERE id = ?1", [id], |r| r.get(0)).optional()
+}
+
+pub(crate) fn acuse(conn: &Connection, id: i64) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT acuse FROM invoices WHERE id = ?1", [id], |r| r.get(0)).optional().map(Option::flatten)
+}
@@ -0,0 +1,714 @@
+//! Facturación electrónica CFDI 4.0.
+//!
+//! - `comprobante`: modelo del `cfdi:Comprobante` y su XML.
+//! - `catalogos`: claves del SAT (régimen, uso CFDI, forma/método de pago, unidades).
+//! - `validate`: reglas del XSD y de cálculo antes de mandar a timbrar.
+//! - `csd` / `sello`: certificado del emisor, cadena original y sello RSA-SHA256.
+//! - `pac` / `invoices`: timbrado con el PAC y CFDI timbrados guardados.
+//! - `cancel`: cancelación con motivo y seguimiento del estatus en el SAT.
+//! - `global`: factura global a público en general con los tickets no facturados.
+//! - `autofactura`: código de verificación y QR del ticket para que el cliente se facture.
+//! - `reader`: lectura de CFDI 3.3/4.0 de proveedores para importar compras.
+//! - `pagos`: pagos a facturas PPD y su CFDI de pagos (complemento Pagos 2.0).
+//!
+//! Los importes del CFDI van sin impuestos: de cada partida se toma la base que guardó
+//! el motor de impuestos (`tax.rs`) al cobrar la venta.
+
+pub mod autofactura;
+pub mod cancel;
+pub mod catalogos;
+pub mod comprobante;
+pub mod csd;
+pub mod global;
+pub mod invoices;
+pub mod pac;
+pub mod pagos;
+pub mod reader;
+pub mod sello;
+pub mod validate;
+pub mod xml;
+
+use crate::db::{self, Db, Sale};
+use crate::money::{Currency, Money};
+use crate::tax::{self, IvaRate, LineTax, TaxProfile};
+use comprobante::{CfdiRelacionados, Comprobante, Concepto, Emisor, Receptor, Traslado};
+use rusqlite::{params, Connection, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::State;
+
+/// Datos del emisor y valores por omisión (Configuración → Facturación MX).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CfdiSettings {
+    pub rfc: String,
+    pub razon_social: String,
+    pub regimen_fiscal: String,
+    /// Código postal del lugar de expedición.
+    pub codigo_postal: String,
+    #[serde(default)]
+    pub serie: String,
+    /// Siguiente folio a usar; se incrementa al timbrar.
+    #[serde(default = "default_folio")]
+    pub next_folio: i64,
+    #[serde(default = "default_metodo_pago")]
+    pub metodo_pago: String,
+    #[serde(default = "default_uso_cfdi")]
+    pub uso_cfdi: String,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub ask_customer: bool,
+    /// Portal de autofactura que se pone en el QR del ticket (vacío: solo los datos).
+    #[serde(default)]
+    pub autofactura_url: String,
+    /// Días después de la venta para pedir la factura desde el ticket (0: sin plazo).
+    #[serde(default = "default_autofactura_days")]
+    pub autofactura_days: i64,
+}
+
+fn default_folio() -> i64 {
+    1
+}
+
+fn default_metodo_pago() -> String {
+    "PUE".to_string()
+}
+
+fn default_uso_cfdi() -> String {
+    "G03".to_string()
+}
+
+fn default_autofactura_days() -> i64 {
+    30
+}
+
+impl CfdiSettings {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(CfdiSettings {
+            rfc: row.get("rfc")?,
+            razon_social: row.get("razon_social")?,
+            regimen_fiscal: row.get("regimen_fiscal")?,
+            codigo_postal: row.get("codigo_postal")?,
+            serie: row.get("serie")?,
+            next_folio: row.get("next_folio")?,
+            metodo_pago: row.get("metodo_pago")?,
+            uso_cfdi: row.get("uso_cfdi")?,
+            enabled: row.get("enabled")?,
+            ask_customer: row.get("ask_customer")?,
+            autofactura_url: row.get("autofactura_url")?,
+            autofactura_days: row.get("autofactura_days")?,
+        })
+    }
+
+    /// Errores de configuración que impiden facturar.
+    pub fn check(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if !validate::is_rfc(&self.rfc) {
+            errors.push("RFC del emisor inválido.");
+        }
+        if self.razon_social.trim().is_empty() {
+            errors.push("Falta el nombre o razón social del emisor.");
+        }
+        if !catalogos::contains(catalogos::REGIMENES_FISCALES, &self.regimen_fiscal) {
+            errors.push("Selecciona el régimen fiscal del emisor.");
+        }
+        if !validate::is_codigo_postal(&self.codigo_postal) {
+            errors.push("El código postal del emisor debe tener 5 dígitos.");
+        }
+        if !catalogos::contains(catalogos::METODOS_PAGO, &self.metodo_pago) {
+            errors.push("Método de pago default inválido (PUE o PPD).");
+        }
+        if !catalogos::contains(catalogos::USOS_CFDI, &self.uso_cfdi) {
+            errors.push("Uso CFDI default inválido.");
+        }
+        if self.next_folio < 1 {
+            errors.push("El folio inicial debe ser mayor a cero.");
+        }
+        if self.autofactura_days < 0 {
+            errors.push("El plazo de autofactura no puede ser negativo.");
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+pub(crate) fn get_settings(conn: &Connection) -> rusqlite::Result<CfdiSettings> {
+    conn.query_row("SELECT * FROM cfdi_settings WHERE id = 1", [], CfdiSettings::from_row)
+}
+
+/// Opciones al facturar una venta; lo que no venga se toma del cliente o de la configuración.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct InvoiceOptions {
+    #[serde(default)]
+    pub customer_id: Option<i64>,
+    #[serde(default)]
+    pub uso_cfdi: Option<String>,
+    #[serde(default)]
+    pub forma_pago: Option<String>,
+    #[serde(default)]
+    pub metodo_pago: Option<String>,
+    /// UUID del CFDI que esta factura sustituye (antes de cancelarlo con motivo 01).
+    #[serde(default)]
+    pub sustituye: Option<String>,
+}
+
+/// Impuestos de una partida: antes del descuento (para `Importe`) y después (para los traslados).
+pub(crate) struct ItemTaxes {
+    pub profile: TaxProfile,
+    pub gross: LineTax,
+    pub net: LineTax,
+}
+
+/// Impuestos de cada partida de la venta. Las ventas anteriores a los perfiles de impuesto no
+/// los guardaron: se recalculan con el perfil actual del producto prorrateando el descuento.
+pub(crate) fn sale_item_taxes(conn: &Connection, sale: &Sale) -> Result<Vec<ItemTaxes>, String> {
+    let currency = Currency::MXN;
+    let gross_amounts: Vec<Money> = sale.sale_items.iter().map(|i| Money::from_decimal(i.subtotal, currency)).collect();
+    let shares = Money::from_decimal(sale.discount, currency).allocate(&gross_amounts);
+    let mut out = Vec::with_capacity(sale.sale_items.len());
+    for ((item, gross_amount), share) in sale.sale_items.iter().zip(&gross_amounts).zip(shares) {
+        let profile = match &item.tax_profile {
+            Some(p) => p.clone(),
+            None => tax::profile_for_product(conn, item.product_id, Decimal::from(16))?,
+        };
+        let gross = tax::compute_line(*gross_amount, &profile);
+        let net = match item.tax_base {
+            Some(base) => LineTax {
+                base: Money::from_decimal(base, currency),
+                ieps: Money::from_decimal(item.tax_ieps.unwrap_or_default(), currency),
+                iva: Money::from_decimal(item.tax_iva.unwrap_or_default(), currency),
+                total: *gross_amount - share,
+            },
+            None => tax::compute_line(*gross_amount - share, &profile),
+        };
+        out.push(ItemTaxes { profile, gross, net });
+    }
+    Ok(out)
+}
+
+/// Traslados de un concepto: IEPS sobre la base y IVA sobre base + IEPS.
+pub(crate) fn concepto_traslados(profile: &TaxProfile, net: &LineTax) -> Vec<Traslado> {
+    let mut traslados = Vec::new();
+    if let Some(rate) = profile.ieps_rate.filter(|r| !r.is_zero()) {
+        traslados.push(Traslado {
+            base: net.base.to_decimal(),
+            impuesto: tax::SAT_IEPS.to_string(),
+            tipo_factor: "Tasa".to_string(),
+            tasa_o_cuota: Some(format!("{:.6}", rate / Decimal::ONE_HUNDRED)),
+            importe: Some(net.ieps.to_decimal()),
+        });
+    }
+    let iva_base = (net.base + net.ieps).to_decimal();
+    traslados.push(match profile.iva {
+        IvaRate::Exento => Traslado {
+            base: iva_base,
+            impuesto: tax::SAT_IVA.to_string(),
+            tipo_factor: "Exento".to_string(),
+            tasa_o_cuota: None,
+            importe: None,
+        },
+        iva => Traslado {
+            base: iva_base,
+            impuesto: tax::SAT_IVA.to_string(),
+            tipo_factor: "Tasa".to_string(),
+            tasa_o_cuota: Some(format!("{:.6}", iva.percent().unwrap_or_default() / Decimal::ONE_HUNDRED)),
+            importe: Some(net.iva.to_decimal()),
+        },
+    });
+    traslados
+}
+
+/// (impuesto, tipo de factor, tasa) -> (base, importe)
+type TrasladoGroups = BTreeMap<(String, String, Option<String>), (Decimal, Option<Decimal>)>;
+
+/// Agrupa traslados por impuesto, tipo de factor y tasa.
+pub(crate) fn group_traslados<'a>(traslados: impl IntoIterator<Item = &'a Traslado>) -> Vec<Traslado> {
+    let mut groups = TrasladoGroups::new();
+    for t in traslados {
+        let entry = groups
+            .entry((t.impuesto.clone(), t.tipo_factor.clone(), t.tasa_o_cuota.clone()))
+            .or_insert((Decimal::ZERO, None));
+        entry.0 += t.base;
+        if let Some(importe) = t.importe {
+            entry.1 = Some(entry.1.unwrap_or_default() + importe);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|((impuesto, tipo_factor, tasa_o_cuota), (base, importe))| Traslado {
+            base,
+            impuesto,
+            tipo_factor,
+            tasa_o_cuota,
+            importe,
+        })
+        .collect()
+}
+
+/// Cierra el comprobante: SubTotal, Descuento y Total a partir de los conceptos.
+pub(crate) fn fill_totals(cfdi: &mut Comprobante) {
+    cfdi.traslados = group_traslados(cfdi.conceptos.iter().flat_map(|c| c.traslados.iter()));
+    cfdi.subtotal = cfdi.conceptos.iter().map(|c| c.importe).sum();
+    let descuento: Decimal = cfdi.conceptos.iter().filter_map(|c| c.descuento).sum();
+    cfdi.descuento = if descuento.is_zero() { None } else { Some(descuento) };
+    cfdi.total = cfdi.subtotal - descuento + cfdi.total_traslados().unwrap_or_default();
+}
+
+pub(crate) fn emisor(settings: &CfdiSettings) -> Emisor {
+    Emisor {
+        rfc: settings.rfc.trim().to_uppercase(),
+        nombre: settings.razon_social.trim().to_uppercase(),
+        regimen_fiscal: settings.regimen_fiscal.clone(),
+    }
+}
+
+/// Fecha de emisión en hora local, como la pide el Anexo 20.
+pub(crate) fn fecha_emision() -> String {
+    chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Arma (sin sellar) el CFDI de ingreso de una venta para el cliente indicado.
+pub fn build_invoice(conn: &Connection, sale_id: i64, opts: &InvoiceOptions) -> Result<Comprobante, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let settings = get_settings(conn).map_err(sql)?;
+    settings.check().map_err(|e| format!("Configura los datos fiscales del emisor:\n{}", e))?;
+
+    let sale = db::get_sale(conn, sale_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la venta {}.", sale_id))?;
+    if sale.status != "completed" {
+        return Err(format!("Solo se facturan ventas cobradas (la venta está {}).", sale.status));
+    }
+    let customer_id = opts
+        .customer_id
+        .or(sale.customer_id)
+        .ok_or("La venta no tiene cliente; selecciona uno o usa la factura global.")?;
+    let customer = db::get_customer(conn, customer_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró el cliente {}.", customer_id))?;
+    let rfc = customer.rfc.as_deref().unwrap_or("").trim().to_uppercase();
+    if rfc.is_empty() {
+        return Err("El cliente no tiene RFC.".to_string());
+    }
+    let nombre = format!(
+        "{} {}",
+        customer.name.as_deref().unwrap_or("").trim(),
+        customer.last_name.as_deref().unwrap_or("").trim()
+    )
+    .trim()
+    .to_uppercase();
+    let regimen = customer
+        .tax_regime
+        .clone()
+        .filter(|r| !r.trim().is_empty())
+        .ok_or("El cliente no tiene régimen fiscal; captúralo de su constancia de situación fiscal.")?;
+    let receptor = Receptor {
+        rfc,
+        nombre,
+        domicilio_fiscal: customer.postal_code.clone().unwrap_or_default().trim().to_string(),
+        regimen_fiscal: regimen,
+        uso_cfdi: opts
+            .uso_cfdi
+            .clone()
+            .or(customer.cfdi_use.clone())
+            .unwrap_or_else(|| settings.uso_cfdi.clone()),
+    };
+
+    let taxes = sale_item_taxes(conn, &sale)?;
+    let mut conceptos = Vec::with_capacity(taxes.len());
+    for (item, t) in sale.sale_items.iter().zip(&taxes) {
+        let product = db::get_product(conn, item.product_id).map_err(sql)?;
+        // ClaveUnidad: la capturada en el producto o la de la unidad con que se vendió (kg → KGM).
+        let (name, code, prod_serv, clave_unidad) = match &product {
+            Some(p) => (
+                p.name.clone(),
+                Some(p.code.clone()),
+                p.sat_product_key.clone().filter(|k| !k.trim().is_empty()),
+                p.sat_unit_key
+                    .clone()
+                    .filter(|k| !k.trim().is_empty())
+                    .unwrap_or_else(|| item.unit.sat_key().to_string()),
+            ),
+            None => (format!("Producto {}", item.product_id), None, None, item.unit.sat_key().to_string()),
+        };
+        let cantidad = item.quantity;
+        let importe = t.gross.base.to_decimal();
+        let descuento = (t.gross.base - t.net.base).to_decimal().max(Decimal::ZERO);
+        conceptos.push(Concepto {
+            clave_prod_serv: prod_serv.unwrap_or_else(|| catalogos::CLAVE_PROD_SERV_GENERICA.to_string()),
+            no_identificacion: code,
+            cantidad,
+            unidad: catalogos::unidad_name(&clave_unidad).map(str::to_string),
+            clave_unidad,
+            descripcion: name,
+            valor_unitario: (importe / cantidad).round_dp(6),
+            importe,
+            descuento: if descuento.is_zero() { None } else { Some(descuento) },
+            objeto_imp: "02".to_string(),
+            traslados: concepto_traslados(&t.profile, &t.net),
+        });
+    }
+
+    let metodo_pago = opts.metodo_pago.clone().unwrap_or_else(|| settings.metodo_pago.clone());
+    let forma_pago = if metodo_pago == "PPD" {
+        "99".to_string()
+    } else {
+        opts.forma_pago
+            .clone()
+            .unwrap_or_else(|| catalogos::forma_pago_from_pos(sale.payment_method.as_deref()).to_string())
+    };
+
+    let mut cfdi = Comprobante {
+        serie: Some(settings.serie.trim().to_string()).filter(|s| !s.is_empty()),
+        folio: Some(settings.next_folio.to_string()),
+        fecha: fecha_emision(),
+        forma_pago: Some(forma_pago),
+        subtotal: Decimal::ZERO,
+        descuento: None,
+        moneda: "MXN".to_string(),
+        total: Decimal::ZERO,
+        tipo_de_comprobante: "I".to_string(),
+        exportacion: "01".to_string(),
+        metodo_pago: Some(metodo_pago),
+        lugar_expedicion: settings.codigo_postal.clone(),
+        no_certificado: None,
+        sello: None,
+        certificado: None,
+        informacion_global: None,
+        relacionados: opts.sustituye.as_ref().map(|uuid| CfdiRelacionados {
+            tipo_relacion: catalogos::TIPO_RELACION_SUSTITUCION.to_string(),
+            uuids: vec![uuid.trim().to_uppercase()],
+        }),
+        emisor: emisor(&settings),
+        receptor,
+        conceptos,
+        traslados: Vec::new(),
+        complementos: Vec::new(),
+        namespaces: Vec::new(),
+    };
+    fill_totals(&mut cfdi);
+    check(&cfdi)?;
+    Ok(cfdi)
+}
+
+/// Valida y junta los errores en un solo mensaje.
+pub fn check(cfdi: &Comprobante) -> Result<(), String> {
+    let errors = validate::validate(cfdi);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("El CFDI no es válido:\n{}", errors.join("\n")))
+    }
+}
+
+// ---------- Comandos Tauri ----------
+
+#[tauri::command]
+pub fn cfdi_get_settings(db: State<'_, Db>) -> Result<CfdiSettings, String> {
+    db.with_conn(|conn| get_settings(conn))
+}
+
+#[tauri::command]
+pub fn cfdi_save_settings(db: State<'_, Db>, settings: CfdiSettings) -> Result<CfdiSettings, String> {
+    let mut s = settings;
+    s.rfc = s.rfc.trim().to_uppercase();
+    s.serie = s.serie.trim().to_uppercase();
+    // Se puede guardar a medias mientras la facturación esté apagada.
+    if s.enabled {
+        s.check()?;
+    }
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE cfdi_settings SET rfc = ?1, razon_social = ?2, regimen_fiscal = ?3, codigo_postal = ?4,
+                 serie = ?5, next_folio = ?6, metodo_pago = ?7, uso_cfdi = ?8, enabled = ?9, ask_customer = ?10,
+                 autofactura_url = ?11, autofactura_days = ?12, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+             WHERE id = 1",
+            params![
+                s.rfc, s.razon_social.trim(), s.regimen_fiscal, s.codigo_postal.trim(), s.serie, s.next_folio,
+                s.metodo_pago, s.uso_cfdi, s.enabled, s.ask_customer, s.autofactura_url.trim(),
+                s.autofactura_days.max(0)
+            ],
+        )?;
+        get_settings(conn)
+    })
+}
+
+/// Arma, valida y sella con el CSD guardado el CFDI de una venta.
+pub fn build_sealed_invoice(conn: &Connection, sale_id: i64, opts: &InvoiceOptions) -> Result<Comprobante, String> {
+    let mut cfdi = build_invoice(conn, sale_id, opts)?;
+    let csd = csd::load(conn, &cfdi.emisor.rfc)?;
+    sello::seal(&mut cfdi, &csd)?;
+    Ok(cfdi)
+}
+
+/// Importa el `.cer` y `.key` del CSD. Valida contraseña, que la llave sea del certificado,
+/// vigencia y que el RFC coincida con el del emisor configurado.
+#[tauri::command]
+pub fn cfdi_import_csd(
+    db: State<'_, Db>,
+    cer_path: String,
+    key_path: String,
+    password: String,
+) -> Result<csd::CertificateInfo, String> {
+    let cer = std::fs::read(&cer_path).map_err(|e| format!("No se pudo leer {}: {}", cer_path, e))?;
+    let key = std::fs::read(&key_path).map_err(|e| format!("No se pudo leer {}: {}", key_path, e))?;
+    let conn = db.lock()?;
+    let settings = get_settings(&conn).map_err(|e| format!("base local: {}", e))?;
+    let pair = csd::open_pair(&cer, &key, &password, Some(&settings.rfc))?;
+    csd::store(&conn, &cer, &key, &password, &pair)?;
+    log::info!("cfdi: CSD {} de {} importado (vence {})", pair.info.numero, pair.info.rfc, pair.info.valid_to);
+    Ok(pair.info)
+}
+
+/// Datos del CSD guardado (`None` si no se ha importado).
+#[tauri::command]
+pub fn cfdi_csd_info(db: State<'_, Db>) -> Result<Option<csd::CertificateInfo>, String> {
+    let conn = db.lock()?;
+    csd::stored_info(&conn)
+}
+
+#[tauri::command]
+pub fn cfdi_remove_csd(db: State<'_, Db>) -> Result<(), String> {
+    let conn = db.lock()?;
+    csd::remove(&conn)
+}
+
+/// XML sellado (listo para timbrar) de una venta local.
+#[tauri::command]
+pub fn cfdi_seal_invoice(db: State<'_, Db>, sale_id: i64, options: Option<InvoiceOptions>) -> Result<String, String> {
+    let conn = db.lock()?;
+    let cfdi = build_sealed_invoice(&conn, sale_id, &options.unwrap_or_default())?;
+    Ok(cfdi.to_xml())
+}
+
+/// XML del CFDI 4.0 (sin sello) de una venta local, ya validado.
+#[tauri::command]
+pub fn cfdi_build_invoice(db: State<'_, Db>, sale_id: i64, options: Option<InvoiceOptions>) -> Result<String, String> {
+    let conn = db.lock()?;
+    let cfdi = build_invoice(&conn, sale_id, &options.unwrap_or_default())?;
+    Ok(cfdi.to_xml())
+}
+
+/// Sella con `build`, timbra con el PAC configurado y guarda el CFDI ligado a las ventas que
+/// devuelve `build`.
+///
+/// El folio y las ventas se apartan antes de llamar al PAC (sin tener la base bloqueada durante
+/// la llamada), así otra solicitud no timbra las mismas ventas; si el timbrado falla, las ventas
+/// se liberan y queda un hueco en la numeración, que el SAT permite.
+pub(crate) fn stamp_and_store(
+    db: &Db,
+    build: impl FnOnce(&Connection) -> Result<(Comprobante, Vec<i64>), String>,
+) -> Result<invoices::Invoice, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let (cfdi, sale_ids, provider) = {
+        let mut conn = db.lock()?;
+        let (mut cfdi, sale_ids) = build(&conn)?;
+        let csd = csd::load(&conn, &cfdi.emisor.rfc)?;
+        sello::seal(&mut cfdi, &csd)?;
+        let provider = pac::provider(&conn)?;
+        let tx = conn.transaction().map_err(sql)?;
+        invoices::reserve(&tx, &sale_ids)?;
+        tx.execute("UPDATE cfdi_settings SET next_folio = next_folio + 1 WHERE id = 1", [])
+            .map_err(sql)?;
+        tx.commit().map_err(sql)?;
+        (cfdi, sale_ids, provider)
+    };
+    let stamp = match provider.stamp(&cfdi.to_xml()) {
+        Ok(stamp) => stamp,
+        Err(e) => {
+            db.with_conn(|conn| invoices::release(conn, &sale_ids))?;
+            return Err(e.into());
+        }
+    };
+    log::info!(
+        "cfdi: {} folio {} timbrado con {} (UUID {}, {} venta(s))",
+        cfdi.tipo_de_comprobante,
+        cfdi.folio.as_deref().unwrap_or(""),
+        provider.name(),
+        stamp.uuid,
+        sale_ids.len()
+    );
+    let mut conn = db.lock()?;
+    let stored = invoices::insert(&mut conn, &sale_ids, &cfdi, &stamp, provider.name());
+    if stored.is_err() {
+        invoices::release(&conn, &sale_ids).map_err(sql)?;
+    }
+    stored
+}
+
+/// Sella, timbra y guarda el CFDI de una venta.
+pub fn stamp_sale(db: &Db, sale_id: i64, opts: &InvoiceOptions) -> Result<invoices::Invoice, String> {
+    stamp_and_store(db, |conn| {
+        // Solo se vuelve a facturar una venta para sustituir su CFDI vigente.
+        if let Some(existing) = invoices::active_for_sale(conn, sale_id).map_err(|e| format!("base local: {}", e))? {
+            let replaces = opts.sustituye.as_deref().is_some_and(|u| u.trim().eq_ignore_ascii_case(&existing.uuid));
+            if !replaces {
+                return Err(format!("La venta ya está facturada (UUID {}).", existing.uuid));
+            }
+        }
+        Ok((build_invoice(conn, sale_id, opts)?, vec![sale_id]))
+    })
+}
+
+/// Timbra la factura de una venta y devuelve el registro guardado.
+#[tauri::command]
+pub fn cfdi_stamp_invoice(
+    db: State<'_, Db>,
+    sale_id: i64,
+    options: Option<InvoiceOptions>,
+) -> Result<invoices::Invoice, String> {
+    stamp_sale(&db, sale_id, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn cfdi_list_invoices(
+    db: State<'_, Db>,
+    sale_id: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<invoices::Invoice>, String> {
+    db.with_conn(|conn| invoices::list(conn, sale_id, limit.unwrap_or(50)))
+}
+
+/// XML timbrado de una factura guardada.
+#[tauri::command]
+pub fn cfdi_get_invoice_xml(db: State<'_, Db>, id: i64) -> Result<String, String> {
+    db.with_conn(|conn| invoices::xml(conn, id))?
+        .ok_or_else(|| format!("No se encontró la factura {}.", id))
+}
+
+#[tauri::command]
+pub fn cfdi_get_pac_settings(db: State<'_, Db>) -> Result<pac::PacSettings, String> {
+    db.with_conn(|conn| pac::get_settings(conn))
+}
+
+/// Guarda el PAC a usar; `password` solo si se cambia.
+#[tauri::command]
+pub fn cfdi_save_pac_settings(
+    db: State<'_, Db>,
+    settings: pac::PacSettings,
+    password: Option<String>,
+) -> Result<pac::PacSettings, String> {
+    let conn = db.lock()?;
+    pac::save_settings(&conn, &settings, password.as_deref())?;
+    pac::get_settings(&conn).map_err(|e| format!("base local: {}", e))
+}
+
+/// Solicita la cancelación de una factura. `folio_sustitucion` solo con motivo 01.
+#[tauri::command]
+pub fn cfdi_cancel_invoice(
+    db: State<'_, Db>,
+    invoice_id: i64,
+    motivo: String,
+    folio_sustitucion: Option<String>,
+) -> Result<invoices::Invoice, String> {
+    cancel::cancel_invoice(&db, invoice_id, &motivo, folio_sustitucion.as_deref())
+}
+
+/// Consulta el estatus en el SAT de una factura (o de todas las cancelaciones en proceso).
+#[tauri::command]
+pub fn cfdi_refresh_invoice_status(
+    db: State<'_, Db>,
+    invoice_id: Option<i64>,
+) -> Result<Vec<invoices::Invoice>, String> {
+    match invoice_id {
+        Some(id) => cancel::refresh_status(&db, id).map(|i| vec![i]),
+        None => cancel::refresh_pending(&db),
+    }
+}
+
+/// Acuse de cancelación del SAT (XML), si ya se recibió.
+#[tauri::command]
+pub fn cfdi_get_cancel_acuse(db: State<'_, Db>, id: i64) -> Result<Option<String>, String> {
+    db.with_conn(|conn| invoices::acuse(conn, id))
+}
+
+/// Tickets cobrados y sin factura entre dos fechas (`AAAA-MM-DD`, hora local).
+#[tauri::command]
+pub fn cfdi_global_pending_sales(db: State<'_, Db>, desde: String, hasta: String) -> Result<Vec<Sale>, String> {
+    db.with_conn(|conn| global::pending_sales(conn, &desde, &hasta))
+}
+
+/// Timbra la factura global del periodo y liga los tickets incluidos.
+#[tauri::command]
+pub fn cfdi_stamp_global_invoice(
+    db: State<'_, Db>,
+    request: global::GlobalInvoiceRequest,
+) -> Result<invoices::Invoice, String> {
+    stamp_and_store(&db, |conn| global::build_global_invoice(conn, &request))
+}
+
+/// Registra un pago recibido de una factura PPD.
+#[tauri::command]
+pub fn cfdi_register_payment(
+    db: State<'_, Db>,
+    invoice_id: i64,
+    payment: pagos::PaymentInput,
+) -> Result<pagos::Payment, String> {
+    let conn = db.lock()?;
+    pagos::register(&conn, invoice_id, &payment)
+}
+
+#[tauri::command]
+pub fn cfdi_list_payments(db: State<'_, Db>, invoice_id: i64) -> Result<Vec<pagos::Payment>, String> {
+    db.with_conn(|conn| pagos::list(conn, invoice_id))
+}
+
+/// Timbra el CFDI de pagos (REP) de un pago registrado.
+#[tauri::command]
+pub fn cfdi_stamp_payment(db: State<'_, Db>, payment_id: i64) -> Result<pagos::Payment, String> {
+    let rep = stamp_and_store(&db, |conn| Ok((pagos::build_payment_cfdi(conn, payment_id)?, Vec::new())))?;
+    let conn = db.lock()?;
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    pagos::set_rep(&conn, payment_id, rep.id).map_err(sql)?;
+    pagos::get(&conn, payment_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró el pago {}.", payment_id))
+}
+
+/// Borra la última parcialidad de una factura si no tiene REP vigente.
+#[tauri::command]
+pub fn cfdi_delete_payment(db: State<'_, Db>, payment_id: i64) -> Result<(), String> {
+    let conn = db.lock()?;
+    pagos::delete(&conn, payment_id)
+}
+
+/// Bloque de autofactura (folio, código y QR) para el ticket de una venta.
+#[tauri::command]
+pub fn cfdi_ticket_autofactura(db: State<'_, Db>, sale_id: i64) -> Result<autofactura::TicketInvoiceData, String> {
+    let conn = db.lock()?;
+    autofactura::for_sale(&conn, sale_id)
+}
+
+/// Comprueba los datos de un ticket que el cliente quiere facturar y devuelve la venta.
+#[tauri::command]
+pub fn cfdi_verify_autofactura(db: State<'_, Db>, request: autofactura::AutofacturaRequest) -> Result<Sale, String> {
+    let conn = db.lock()?;
+    let sale_id = autofactura::verify(&conn, &request)?;
+    db::get_sale(&conn, sale_id)
+        .map_err(|e| format!("base local: {}", e))?
+        .ok_or_else(|| format!("No se encontró la venta {}.", sale_id))
+}
+
+/// Timbra la factura que pide el cliente con los datos de su ticket.
+#[tauri::command]
+pub fn cfdi_stamp_autofactura(
+    db: State<'_, Db>,
+    request: autofactura::AutofacturaRequest,
+    options: InvoiceOptions,
+) -> Result<invoices::Invoice, String> {
+    let opts = InvoiceOptions { sustituye: None, ..options };
+    stamp_and_store(&db, |conn| {
+        let sale_id = autofactura::verify(conn, &request)?;
+        Ok((build_invoice(conn, sale_id, &opts)?, vec![sale_id]))
+    })
+}
+
+/// Lee el XML de un CFDI de proveedor (3.3 o 4.0) para la revisión de la compra.
+#[tauri::command]
+pub fn cfdi_parse_xml(xml: String) -> Result<reader::CfdiDocument, String> {
+    reader::read_cfdi(&xml)
+}
@@ -0,0 +1,676 @@
+//! Proveedores de certificación (PAC): timbrado, cancelación y consulta de estado.
+//!
+//! `PacProvider` es lo único que ve el resto del módulo. Hay dos implementaciones:
+//! - `FinkokPac`: servicios SOAP de Finkok (demo o producción).
+//! - `MockPac`: PAC local para pruebas; pone un `TimbreFiscalDigital` falso y simula la
+//!   cancelación (con aceptación del receptor para montos mayores a $1,000).
+
+use super::csd::Csd;
+use super::xml::{self, Element};
+use base64::Engine;
+use rusqlite::{params, Connection, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+const KEYRING_SERVICE: &str = "moneymachine-pac";
+
+pub const NS_TFD: &str = "http://www.sat.gob.mx/TimbreFiscalDigital";
+const TFD_SCHEMA: &str =
+    "http://www.sat.gob.mx/TimbreFiscalDigital http://www.sat.gob.mx/sitio_internet/cfd/TimbreFiscalDigital/TimbreFiscalDigitalv11.xsd";
+
+#[derive(Debug)]
+pub enum PacError {
+    /// Sin conexión o el PAC no respondió.
+    Network(String),
+    /// El PAC o el SAT rechazaron la solicitud.
+    Rejected { code: String, message: String },
+    Other(String),
+}
+
+impl fmt::Display for PacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacError::Network(e) => write!(f, "No se pudo conectar con el PAC: {}", e),
+            PacError::Rejected { code, message } => write!(f, "El PAC rechazó la solicitud ({}): {}", code, message),
+            PacError::Other(e) => write!(f, "PAC: {}", e),
+        }
+    }
+}
+
+impl From<PacError> for String {
+    fn from(e: PacError) -> String {
+        e.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StampResult {
+    pub uuid: String,
+    pub fecha_timbrado: String,
+    pub no_certificado_sat: String,
+    /// Comprobante completo con el `TimbreFiscalDigital` en el complemento.
+    pub xml: String,
+}
+
+/// Estado de una solicitud de cancelación.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelState {
+    /// El receptor debe aceptar o rechazar (hasta 72 horas).
+    EnProceso,
+    Cancelado,
+    /// El receptor la rechazó o el SAT no la permite.
+    Rechazado,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CancelResult {
+    pub state: CancelState,
+    /// Texto del PAC/SAT (`Cancelado sin aceptación`, `En proceso`, ...).
+    pub detail: String,
+    /// Acuse de cancelación firmado por el SAT (XML), si el PAC lo entrega.
+    pub acuse: Option<String>,
+}
+
+pub struct CancelRequest<'a> {
+    pub rfc_emisor: &'a str,
+    pub rfc_receptor: &'a str,
+    pub uuid: &'a str,
+    pub total: Decimal,
+    /// c_MotivoCancelacion `01`–`04`.
+    pub motivo: &'a str,
+    /// UUID que sustituye al cancelado (solo motivo `01`).
+    pub folio_sustitucion: Option<&'a str>,
+    pub csd: &'a Csd,
+}
+
+pub struct StatusQuery<'a> {
+    pub rfc_emisor: &'a str,
+    pub rfc_receptor: &'a str,
+    pub uuid: &'a str,
+    pub total: Decimal,
+}
+
+/// Respuesta del servicio de consulta del SAT.
+#[derive(Debug, Clone, Serialize)]
+pub struct SatStatus {
+    /// `Vigente`, `Cancelado` o `No Encontrado`.
+    pub estado: String,
+    pub es_cancelable: String,
+    /// `En proceso`, `Cancelado sin aceptación`, `Solicitud rechazada`, ... (vacío si no hay solicitud).
+    pub estatus_cancelacion: String,
+}
+
+pub trait PacProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn stamp(&self, xml: &str) -> Result<StampResult, PacError>;
+    fn cancel(&self, req: &CancelRequest) -> Result<CancelResult, PacError>;
+    fn status(&self, query: &StatusQuery) -> Result<SatStatus, PacError>;
+}
+
+/// Interpreta el texto de estatus de cancelación del SAT.
+pub fn cancel_state_from_sat(estado: &str, estatus_cancelacion: &str) -> Option<CancelState> {
+    let e = estatus_cancelacion.to_lowercase();
+    if estado.eq_ignore_ascii_case("Cancelado") || e.starts_with("cancelado") || e.contains("plazo vencido") {
+        Some(CancelState::Cancelado)
+    } else if e.contains("en proceso") {
+        Some(CancelState::EnProceso)
+    } else if e.contains("rechaz") {
+        Some(CancelState::Rechazado)
+    } else {
+        None
+    }
+}
+
+// ---------- Configuración ----------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacSettings {
+    /// `mock` o `finkok`.
+    pub provider: String,
+    #[serde(default)]
+    pub username: String,
+    /// Ambiente de pruebas del PAC.
+    #[serde(default)]
+    pub sandbox: bool,
+}
+
+impl PacSettings {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(PacSettings { provider: row.get("provider")?, username: row.get("username")?, sandbox: row.get("sandbox")? })
+    }
+}
+
+pub(crate) fn get_settings(conn: &Connection) -> rusqlite::Result<PacSettings> {
+    conn.query_row("SELECT * FROM pac_settings WHERE id = 1", [], PacSettings::from_row)
+}
+
+fn keyring_entry(username: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, username).map_err(|e| format!("llavero del sistema: {}", e))
+}
+
+/// Guarda la configuración; la contraseña del PAC va al llavero del sistema.
+pub(crate) fn save_settings(conn: &Connection, settings: &PacSettings, password: Option<&str>) -> Result<(), String> {
+    if !matches!(settings.provider.as_str(), "mock" | "finkok") {
+        return Err(format!("PAC no soportado: {}", settings.provider));
+    }
+    if settings.provider != "mock" && settings.username.trim().is_empty() {
+        return Err("Captura el usuario del PAC.".to_string());
+    }
+    if let Some(password) = password.filter(|p| !p.is_empty()) {
+        keyring_entry(settings.username.trim())?
+            .set_password(password)
+            .map_err(|e| format!("No se pudo guardar la contraseña en el llavero del sistema: {}", e))?;
+    }
+    conn.execute(
+        "UPDATE pac_settings SET provider = ?1, username = ?2, sandbox = ?3 WHERE id = 1",
+        params![settings.provider, settings.username.trim(), settings.sandbox],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("base local: {}", e))
+}
+
+/// PAC configurado en la base local.
+pub(crate) fn provider(conn: &Connection) -> Result<Box<dyn PacProvider>, String> {
+    let settings = get_settings(conn).map_err(|e| format!("base local: {}", e))?;
+    match settings.provider.as_str() {
+        "mock" => Ok(Box::new(MockPac)),
+        "finkok" => {
+            let password = keyring_entry(&settings.username)?
+                .get_password()
+                .map_err(|e| format!("No se encontró la contraseña del PAC en el llavero del sistema ({}).", e))?;
+            Ok(Box::new(FinkokPac::new(&settings.username, &password, settings.sandbox)))
+        }
+        other => Err(format!("PAC no soportado: {}", other)),
+    }
+}
+
+// ---------- Finkok (SOAP) ----------
+
+const FINKOK_DEMO: &str = "https://demo-facturacion.finkok.com/servicios/soap";
+const FINKOK_PROD: &str = "https://facturacion.finkok.com/servicios/soap";
+const NS_STAMP: &str = "http://facturacion.finkok.com/stamp";
+const NS_CANCEL: &str = "http://facturacion.finkok.com/cancel";
+const NS_CANCEL_APPS: &str = "apps.services.soap.core.views";
+
+pub struct FinkokPac {
+    agent: ureq::Agent,
+    base_url: &'static str,
+    username: String,
+    password: String,
+}
+
+impl FinkokPac {
+    pub fn new(username: &str, password: &str, sandbox: bool) -> FinkokPac {
+        FinkokPac {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(60)).build(),
+            base_url: if sandbox { FINKOK_DEMO } else { FINKOK_PROD },
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    fn credentials(&self, prefix: &str) -> String {
+        format!(
+            "<{p}:username>{}</{p}:username><{p}:password>{}</{p}:password>",
+            xml::escape(&self.username),
+            xml::escape(&self.password),
+            p = prefix
+        )
+    }
+
+    /// Manda el sobre SOAP y devuelve el `Body` de la respuesta.
+    fn call(&self, service: &str, action: &str, namespaces: &str, body: &str) -> Result<Element, PacError> {
+        let envelope = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><soapenv:Envelope xmlns:soapenv=\"http://schemas.xmlsoap.org/soap/envelope/\" {}><soapenv:Header/><soapenv:Body>{}</soapenv:Body></soapenv:Envelope>",
+            namespaces, body
+        );
+        let response = self
+            .agent
+            .post(&format!("{}/{}", self.base_url, service))
+            .set("Content-Type", "text/xml; charset=utf-8")
+            .set("SOAPAction", action)
+            .send_string(&envelope);
+        let text = match response {
+            Ok(r) => r.into_string().map_err(|e| PacError::Network(e.to_string()))?,
+            // Los SOAP Fault llegan con HTTP 500 pero traen el detalle en el cuerpo.
+            Err(ureq::Error::Status(_, r)) => r.into_string().map_err(|e| PacError::Network(e.to_string()))?,
+            Err(e) => return Err(PacError::Network(e.to_string())),
+        };
+        let root = xml::parse(&text).map_err(PacError::Other)?;
+        if let Some(fault) = root.descendant("Fault") {
+            let message = fault.descendant("faultstring").map(|f| f.text.clone()).unwrap_or_default();
+            return Err(PacError::Rejected { code: "SOAP".to_string(), message });
+        }
+        root.descendant("Body").cloned().ok_or_else(|| PacError::Other("respuesta SOAP sin Body".to_string()))
+    }
+}
+
+fn text_of(root: &Element, local: &str) -> String {
+    root.descendant(local).map(|e| e.text.trim().to_string()).unwrap_or_default()
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let b64 = base64::engine::general_purpose::STANDARD.encode(der);
+    let lines: Vec<&str> = b64.as_bytes().chunks(64).map(|c| std::str::from_utf8(c).unwrap_or_default()).collect();
+    format!("-----BEGIN {}-----\n{}\n-----END {}-----\n", label, lines.join("\n"), label)
+}
+
+/// Finkok pide la llave en PEM cifrada con la contraseña de la cuenta de Finkok.
+fn finkok_key_pem(csd: &Csd, password: &str) -> Result<String, PacError> {
+    use rsa::pkcs8::EncodePrivateKey;
+    let plain = csd.key.to_pkcs8_der().map_err(|e| PacError::Other(e.to_string()))?;
+    let info = pkcs8::PrivateKeyInfo::try_from(plain.as_bytes()).map_err(|e| PacError::Other(e.to_string()))?;
+    // Sal e IV nuevos en cada solicitud.
+    let mut salt = [0u8; 16];
+    let mut iv = [0u8; 16];
+    getrandom::getrandom(&mut salt).map_err(|e| PacError::Other(e.to_string()))?;
+    getrandom::getrandom(&mut iv).map_err(|e| PacError::Other(e.to_string()))?;
+    let params = pkcs8::pkcs5::pbes2::Parameters::pbkdf2_sha256_aes256cbc(2048, &salt, &iv)
+        .map_err(|e| PacError::Other(e.to_string()))?;
+    let encrypted = info
+        .encrypt_with_params(params, password.as_bytes())
+        .map_err(|e| PacError::Other(e.to_string()))?;
+    Ok(pem("ENCRYPTED PRIVATE KEY", encrypted.as_bytes()))
+}
+
+impl PacProvider for FinkokPac {
+    fn name(&self) -> &str {
+        "finkok"
+    }
+
+    fn stamp(&self, cfdi_xml: &str) -> Result<StampResult, PacError> {
+        let body = format!(
+            "<stam:stamp><stam:xml>{}</stam:xml>{}</stam:stamp>",
+            base64::engine::general_purpose::STANDARD.encode(cfdi_xml.as_bytes()),
+            self.credentials("stam")
+        );
+        let response = self.call("stamp", "stamp", &format!("xmlns:stam=\"{}\"", NS_STAMP), &body)?;
+        let stamped = text_of(&response, "xml");
+        if stamped.is_empty() {
+            let incidencia = response.descendant("Incidencia");
+            let code = incidencia.map(|i| text_of(i, "CodigoError")).unwrap_or_default();
+            let message = incidencia
+                .map(|i| text_of(i, "MensajeIncidencia"))
+                .filter(|m| !m.is_empty())
+                .unwrap_or_else(|| text_of(&response, "CodEstatus"));
+            return Err(PacError::Rejected { code, message });
+        }
+        Ok(StampResult {
+            uuid: text_of(&response, "UUID").to_uppercase(),
+            fecha_timbrado: text_of(&response, "Fecha"),
+            no_certificado_sat: text_of(&response, "NoCertificadoSAT"),
+            xml: stamped,
+        })
+    }
+
+    fn cancel(&self, req: &CancelRequest) -> Result<CancelResult, PacError> {
+        let body = format!(
+            "<can:cancel><can:UUIDS><apps:UUID UUID=\"{}\" Motivo=\"{}\" FolioSustitucion=\"{}\"/></can:UUIDS>{}<can:taxpayer_id>{}</can:taxpayer_id><can:cer>{}</can:cer><can:key>{}</can:key><can:store_pending>false</can:store_pending></can:cancel>",
+            xml::escape(req.uuid),
+            xml::escape(req.motivo),
+            xml::escape(req.folio_sustitucion.unwrap_or("")),
+            self.credentials("can"),
+            xml::escape(req.rfc_emisor),
+            base64::engine::general_purpose::STANDARD.encode(pem("CERTIFICATE", &req.csd.cer_der)),
+            base64::engine::general_purpose::STANDARD.encode(finkok_key_pem(req.csd, &self.password)?),
+        );
+        let namespaces = format!("xmlns:can=\"{}\" xmlns:apps=\"{}\"", NS_CANCEL, NS_CANCEL_APPS);
+        let response = self.call("cancel", "cancel", &namespaces, &body)?;
+        let Some(folio) = response.descendant("Folio") else {
+            return Err(PacError::Rejected {
+                code: text_of(&response, "CodEstatus"),
+                message: "el PAC no devolvió el estatus del folio".to_string(),
+            });
+        };
+        let estatus_uuid = text_of(folio, "EstatusUUID");
+        let detail = text_of(folio, "EstatusCancelacion");
+        // 201: solicitud recibida, 202: ya estaba cancelado. Otros códigos son rechazo.
+        let state = match estatus_uuid.as_str() {
+            "202" => CancelState::Cancelado,
+            "201" => cancel_state_from_sat("", &detail).unwrap_or(CancelState::EnProceso),
+            code => {
+                return Err(PacError::Rejected {
+                    code: code.to_string(),
+                    message: if detail.is_empty() { text_of(&response, "CodEstatus") } else { detail },
+                })
+            }
+        };
+        let acuse = Some(text_of(&response, "Acuse")).filter(|a| !a.is_empty());
+        Ok(CancelResult { state, detail, acuse })
+    }
+
+    fn status(&self, query: &StatusQuery) -> Result<SatStatus, PacError> {
+        let body = format!(
+            "<can:get_sat_status>{}<can:taxpayer_id>{}</can:taxpayer_id><can:rtaxpayer_id>{}</can:rtaxpayer_id><can:uuid>{}</can:uuid><can:total>{}</can:total></can:get_sat_status>",
+            self.credentials("can"),
+            xml::escape(query.rfc_emisor),
+            xml::escape(query.rfc_receptor),
+            xml::escape(query.uuid),
+            query.total
+        );
+        let response =
+            self.call("cancel", "get_sat_status", &format!("xmlns:can=\"{}\"", NS_CANCEL), &body)?;
+        let estado = text_of(&response, "Estado");
+        if estado.is_empty() {
+            return Err(PacError::Rejected {
+                code: text_of(&response, "CodigoEstatus"),
+                message: "el SAT no devolvió el estado del comprobante".to_string(),
+            });
+        }
+        Ok(SatStatus {
+            estado,
+            es_cancelable: text_of(&response, "EsCancelable"),
+            estatus_cancelacion: text_of(&response, "EstatusCancelacion"),
+        })
+    }
+}
+
+// ---------- PAC simulado ----------
+
+/// Estado de los UUID timbrados por el PAC simulado (vive mientras corre la app).
+fn mock_registry() -> &'static Mutex<HashMap<String, SatStatus>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, SatStatus>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub struct MockPac;
+
+impl MockPac {
+    fn fake_uuid(seed: &str) -> String {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let hash = Sha256::new().chain_update(seed.as_bytes()).chain_update(nanos.to_le_bytes()).finalize();
+        let hex: String = hash.iter().take(16).map(|b| format!("{:02X}", b)).collect();
+        // Formato 8-4-4-4-12 con versión 4.
+        format!("{}-{}-4{}-A{}-{}", &hex[0..8], &hex[8..12], &hex[13..16], &hex[17..20], &hex[20..32])
+    }
+}
+
+impl PacProvider for MockPac {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn stamp(&self, cfdi_xml: &str) -> Result<StampResult, PacError> {
+        let mut root = xml::parse(cfdi_xml).map_err(PacError::Other)?;
+        let sello = root.attr_value("Sello").unwrap_or_default().to_string();
+        if sello.is_empty() {
+            return Err(PacError::Rejected { code: "302".to_string(), message: "El comprobante no está sellado.".to_string() });
+        }
+        if root.descendant("TimbreFiscalDigital").is_some() {
+            return Err(PacError::Rejected { code: "307".to_string(), message: "El comprobante ya tiene timbre.".to_string() });
+        }
+        let uuid = Self::fake_uuid(cfdi_xml);
+        let fecha = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+        let no_certificado_sat = "00001000000000000000".to_string();
+        let cadena_tfd = format!("||1.1|{}|{}|SPR190613I52|{}|{}||", uuid, fecha, sello, no_certificado_sat);
+        let sello_sat = base64::engine::general_purpose::STANDARD.encode(Sha256::digest(cadena_tfd.as_bytes()));
+        let tfd = Element::new("tfd:TimbreFiscalDigital")
+            .attr("xmlns:tfd", NS_TFD)
+            .attr("xsi:schemaLocation", TFD_SCHEMA)
+            .attr("Version", "1.1")
+            .attr("UUID", &uuid)
+            .attr("FechaTimbrado", &fecha)
+            .attr("RfcProvCertif", "SPR190613I52")
+            .attr("SelloCFD", sello)
+            .attr("NoCertificadoSAT", &no_certificado_sat)
+            .attr("SelloSAT", sello_sat);
+        match root.find_mut("cfdi:Complemento") {
+            Some(complemento) => complemento.children.push(tfd),
+            None => root.children.push(Element::new("cfdi:Complemento").child(tfd)),
+        }
+        if let Ok(mut registry) = mock_registry().lock() {
+            registry.insert(
+                uuid.clone(),
+                SatStatus {
+                    estado: "Vigente".to_string(),
+                    es_cancelable: "Cancelable con aceptación".to_string(),
+                    estatus_cancelacion: String::new(),
+                },
+            );
+        }
+        Ok(StampResult { uuid, fecha_timbrado: fecha, no_certificado_sat, xml: root.to_document() })
+    }
+
+    fn cancel(&self, req: &CancelRequest) -> Result<CancelResult, PacError> {
+        let mut registry = mock_registry().lock().map_err(|_| PacError::Other("PAC simulado bloqueado".to_string()))?;
+        let Some(status) = registry.get_mut(req.uuid) else {
+            return Err(PacError::Rejected { code: "205".to_string(), message: "UUID no encontrado.".to_string() });
+        };
+        if status.estado == "Cancelado" {
+            return Ok(CancelResult { state: CancelState::Cancelado, detail: status.estatus_cancelacion.clone(), acuse: None });
+        }
+        // Como el SAT: arriba de $1,000 el receptor tiene que aceptar la cancelación.
+        let (state, detail) = if req.total > Decimal::from(1000) {
+            (CancelState::EnProceso, "En proceso")
+        } else {
+            (CancelState::Cancelado, "Cancelado sin aceptación")
+        };
+        status.estatus_cancelacion = detail.to_string();
+        if state == CancelState::Cancelado {
+            status.estado = "Cancelado".to_string();
+        }
+        let acuse = Element::new("Acuse")
+            .attr("Fecha", chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string())
+            .attr("RfcEmisor", req.rfc_emisor)
+            .child(
+                Element::new("Folios")
+                    .child(Element { text: req.uuid.to_string(), ..Element::new("UUID") })
+                    .child(Element { text: "201".to_string(), ..Element::new("EstatusUUID") })
+                    .child(Element { text: req.motivo.to_string(), ..Element::new("Motivo") }),
+            );
+        Ok(CancelResult { state, detail: detail.to_string(), acuse: Some(acuse.to_document()) })
+    }
+
+    fn status(&self, query: &StatusQuery) -> Result<SatStatus, PacError> {
+        let mut registry = mock_registry().lock().map_err(|_| PacError::Other("PAC simulado bloqueado".to_string()))?;
+        match registry.get_mut(query.uuid) {
+            Some(status) => {
+                // El receptor "acepta" la cancelación en cuanto se consulta.
+                if status.estatus_cancelacion == "En proceso" {
+                    status.estado = "Cancelado".to_string();
+                    status.estatus_cancelacion = "Cancelado con aceptación".to_string();
+                }
+                Ok(status.clone())
+            }
+            None => Ok(SatStatus {
+                estado: "No Encontrado".to_string(),
+                es_cancelable: String::new(),
+                estatus_cancelacion: String::new(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfdi::comprobante::{Comprobante, Concepto, Emisor, Receptor, Traslado};
+    use crate::cfdi::csd::CertificateInfo;
+    use crate::cfdi::{fill_totals, invoices, validate};
+    use crate::db::Db;
+    use rsa::{BigUint, RsaPrivateKey};
+
+    fn comprobante(precio: i64) -> Comprobante {
+        let importe = Decimal::from(precio);
+        let mut cfdi = Comprobante {
+            serie: Some("A".into()),
+            folio: Some("1".into()),
+            fecha: "2026-01-15T10:00:00".into(),
+            forma_pago: Some("01".into()),
+            subtotal: Decimal::ZERO,
+            descuento: None,
+            moneda: "MXN".into(),
+            total: Decimal::ZERO,
+            tipo_de_comprobante: "I".into(),
+            exportacion: "01".into(),
+            metodo_pago: Some("PUE".into()),
+            lugar_expedicion: "64000".into(),
+            no_certificado: Some("30001000000500003416".into()),
+            // El PAC simulado solo revisa que el comprobante venga sellado.
+            sello: Some("c2VsbG8gZGUgcHJ1ZWJh".into()),
+            certificado: None,
+            informacion_global: None,
+            relacionados: None,
+            emisor: Emisor { rfc: "EKU9003173C9".into(), nombre: "ESCUELA KEMPER URGATE".into(), regimen_fiscal: "601".into() },
+            receptor: Receptor {
+                rfc: "XAXX010101000".into(),
+                nombre: "PUBLICO EN GENERAL".into(),
+                domicilio_fiscal: "64000".into(),
+                regimen_fiscal: "616".into(),
+                uso_cfdi: "S01".into(),
+            },
+            conceptos: vec![Concepto {
+                clave_prod_serv: "01010101".into(),
+                no_identificacion: None,
+                cantidad: Decimal::ONE,
+                clave_unidad: "H87".into(),
+                unidad: None,
+                descripcion: "Producto de prueba".into(),
+                valor_unitario: importe,
+                importe,
+                descuento: None,
+                objeto_imp: "02".into(),
+                traslados: vec![Traslado {
+                    base: importe,
+                    impuesto: "002".into(),
+                    tipo_factor: "Tasa".into(),
+                    tasa_o_cuota: Some("0.160000".into()),
+                    importe: Some(importe * Decimal::new(16, 2)),
+                }],
+            }],
+            traslados: Vec::new(),
+            complementos: Vec::new(),
+            namespaces: Vec::new(),
+        };
+        fill_totals(&mut cfdi);
+        cfdi
+    }
+
+    /// El PAC simulado no usa el CSD al cancelar; basta una llave mínima válida.
+    fn csd() -> Csd {
+        let key = RsaPrivateKey::from_p_q(BigUint::from(61u32), BigUint::from(53u32), BigUint::from(17u32)).unwrap();
+        let info = CertificateInfo {
+            numero: "30001000000500003416".into(),
+            rfc: "EKU9003173C9".into(),
+            nombre: "ESCUELA KEMPER URGATE".into(),
+            valid_from: "2023-01-01T00:00:00Z".into(),
+            valid_to: "2027-01-01T00:00:00Z".into(),
+        };
+        Csd { info, cer_der: Vec::new(), key }
+    }
+
+    fn cancel(uuid: &str, total: Decimal, csd: &Csd) -> Result<CancelResult, PacError> {
+        MockPac.cancel(&CancelRequest {
+            rfc_emisor: "EKU9003173C9",
+            rfc_receptor: "XAXX010101000",
+            uuid,
+            total,
+            motivo: "02",
+            folio_sustitucion: None,
+            csd,
+        })
+    }
+
+    fn status(uuid: &str, total: Decimal) -> SatStatus {
+        MockPac
+            .status(&StatusQuery { rfc_emisor: "EKU9003173C9", rfc_receptor: "XAXX010101000", uuid, total })
+            .unwrap()
+    }
+
+    #[test]
+    fn stamp_adds_timbre_and_stores_uuid() {
+        let cfdi = comprobante(100);
+        let stamp = MockPac.stamp(&cfdi.to_xml()).unwrap();
+        assert!(validate::is_uuid(&stamp.uuid));
+
+        let root = xml::parse(&stamp.xml).unwrap();
+        let tfd = root.descendant("TimbreFiscalDigital").expect("TimbreFiscalDigital");
+        assert_eq!(tfd.attr_value("UUID"), Some(stamp.uuid.as_str()));
+        assert_eq!(tfd.attr_value("SelloCFD"), cfdi.sello.as_deref());
+
+        let db = Db::open_in_memory().unwrap();
+        let mut conn = db.lock().unwrap();
+        let invoice = invoices::insert(&mut conn, &[], &cfdi, &stamp, MockPac.name()).unwrap();
+        assert_eq!(invoice.status, "vigente");
+        assert_eq!(invoice.total, Decimal::from(116));
+        assert_eq!(invoices::get_by_uuid(&conn, &stamp.uuid).unwrap().map(|i| i.id), Some(invoice.id));
+        assert_eq!(invoices::xml(&conn, invoice.id).unwrap(), Some(stamp.xml.clone()));
+
+        // Un comprobante ya timbrado o sin sello se rechaza.
+        assert!(matches!(MockPac.stamp(&stamp.xml), Err(PacError::Rejected { code, .. }) if code == "307"));
+        let unsealed = Comprobante { sello: None, ..comprobante(100) };
+        assert!(matches!(MockPac.stamp(&unsealed.to_xml()), Err(PacError::Rejected { code, .. }) if code == "302"));
+    }
+
+    #[test]
+    fn reserved_sales_are_invoiced_only_once() {
+        let db = Db::open_in_memory().unwrap();
+        let mut conn = db.lock().unwrap();
+        conn.execute("INSERT INTO sales (sale_number, subtotal, total) VALUES ('SALE-1', '100', '116')", []).unwrap();
+        let sale_id = conn.last_insert_rowid();
+
+        invoices::reserve(&conn, &[sale_id]).unwrap();
+        assert!(invoices::reserve(&conn, &[sale_id]).is_err());
+
+        let cfdi = comprobante(100);
+        let stamp = MockPac.stamp(&cfdi.to_xml()).unwrap();
+        invoices::insert(&mut conn, &[sale_id], &cfdi, &stamp, MockPac.name()).unwrap();
+        let reserved: i64 = conn.query_row("SELECT COUNT(*) FROM invoice_reservations", [], |r| r.get(0)).unwrap();
+        assert_eq!(reserved, 0);
+
+        // Aunque se salte la reserva, el guardado no liga un segundo CFDI vigente a la venta.
+        let again = MockPac.stamp(&comprobante(100).to_xml()).unwrap();
+        let err = invoices::insert(&mut conn, &[sale_id], &cfdi, &again, MockPac.name()).unwrap_err();
+        assert!(err.contains(&stamp.uuid), "{}", err);
+        assert!(invoices::get_by_uuid(&conn, &again.uuid).unwrap().is_none());
+    }
+
+    #[test]
+    fn small_cancellation_is_immediate() {
+        let csd = csd();
+        let cfdi = comprobante(100);
+        let stamp = MockPac.stamp(&cfdi.to_xml()).unwrap();
+        assert_eq!(status(&stamp.uuid, cfdi.total).estado, "Vigente");
+
+        let result = cancel(&stamp.uuid, cfdi.total, &csd).unwrap();
+        assert_eq!(result.state, CancelState::Cancelado);
+        assert!(result.acuse.is_some_and(|a| a.contains(&stamp.uuid)));
+
+        let sat = status(&stamp.uuid, cfdi.total);
+        assert_eq!(cancel_state_from_sat(&sat.estado, &sat.estatus_cancelacion), Some(CancelState::Cancelado));
+        // Volver a cancelar no falla.
+        assert_eq!(cancel(&stamp.uuid, cfdi.total, &csd).unwrap().state, CancelState::Cancelado);
+    }
+
+    #[test]
+    fn large_cancellation_waits_for_receptor() {
+        let csd = csd();
+        let cfdi = comprobante(5000);
+        let stamp = MockPac.stamp(&cfdi.to_xml()).unwrap();
+
+        let result = cancel(&stamp.uuid, cfdi.total, &csd).unwrap();
+        assert_eq!(result.state, CancelState::EnProceso);
+        assert_eq!(cancel_state_from_sat("Vigente", &result.detail), Some(CancelState::EnProceso));
+
+        let sat = status(&stamp.uuid, cfdi.total);
+        assert_eq!(sat.estado, "Cancelado");
+        assert_eq!(sat.estatus_cancelacion, "Cancelado con aceptación");
+        assert_eq!(cancel_state_from_sat(&sat.estado, &sat.estatus_cancelacion), Some(CancelState::Cancelado));
+    }
+
+    #[test]
+    fn unknown_uuid_is_rejected() {
+        let uuid = "00000000-0000-4000-A000-000000000000";
+        assert!(matches!(cancel(uuid, Decimal::ONE, &csd()), Err(PacError::Rejected { code, .. }) if code == "205"));
+        assert_eq!(status(uuid, Decimal::ONE).estado, "No Encontrado");
+    }
+}
@@ -0,0 +1,388 @@
+//! Pagos a facturas PPD y su CFDI de pagos (tipo `P`, complemento Pagos 2.0).
+//!
+//! Cada pago registrado guarda su número de parcialidad y los saldos anterior e insoluto.
+//! El REP se arma con los datos del CFDI timbrado (UUID, receptor e impuestos): los impuestos
+//! del documento se prorratean por lo pagado y el último pago se lleva el remanente para que
+//! la suma de las parcialidades cuadre con la factura.
+
+use super::comprobante::{self, Comprobante, Concepto, Receptor, Traslado};
+use super::invoices::{self, Invoice};
+use super::xml::{self, Element};
+use super::{catalogos, emisor, fecha_emision, get_settings};
+use crate::db::get_decimal;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+pub const NS_PAGOS: &str = "http://www.sat.gob.mx/Pagos20";
+const XSD_PAGOS: &str = "http://www.sat.gob.mx/sitio_internet/cfd/Pagos/Pagos20.xsd";
+
+/// Clave "Pago" del concepto único del REP.
+const CLAVE_PROD_SERV_PAGO: &str = "84111506";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Payment {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub num_parcialidad: i64,
+    pub fecha_pago: String,
+    pub forma_pago: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub monto: Decimal,
+    pub num_operacion: Option<String>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub saldo_anterior: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub saldo_insoluto: Decimal,
+    /// CFDI de pagos timbrado para este pago.
+    pub rep_invoice_id: Option<i64>,
+    pub rep_uuid: Option<String>,
+    pub rep_status: Option<String>,
+    pub created_at: String,
+}
+
+impl Payment {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Payment {
+            id: row.get("id")?,
+            invoice_id: row.get("invoice_id")?,
+            num_parcialidad: row.get("num_parcialidad")?,
+            fecha_pago: row.get("fecha_pago")?,
+            forma_pago: row.get("forma_pago")?,
+            monto: get_decimal(row, "monto")?,
+            num_operacion: row.get("num_operacion")?,
+            saldo_anterior: get_decimal(row, "saldo_anterior")?,
+            saldo_insoluto: get_decimal(row, "saldo_insoluto")?,
+            rep_invoice_id: row.get("rep_invoice_id")?,
+            rep_uuid: row.get("rep_uuid")?,
+            rep_status: row.get("rep_status")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+
+    /// Ya tiene un REP que no se ha cancelado.
+    fn is_stamped(&self) -> bool {
+        self.rep_invoice_id.is_some() && self.rep_status.as_deref() != Some("cancelado")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentInput {
+    /// `AAAA-MM-DDThh:mm:ss`; por omisión, ahora.
+    #[serde(default)]
+    pub fecha_pago: Option<String>,
+    /// c_FormaPago (no puede ser `99`).
+    pub forma_pago: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub monto: Decimal,
+    /// Referencia bancaria o número de cheque.
+    #[serde(default)]
+    pub num_operacion: Option<String>,
+}
+
+const SELECT: &str = "SELECT p.*, r.uuid AS rep_uuid, r.status AS rep_status
+     FROM invoice_payments p LEFT JOIN invoices r ON r.id = p.rep_invoice_id";
+
+pub(crate) fn get(conn: &Connection, id: i64) -> rusqlite::Result<Option<Payment>> {
+    conn.query_row(&format!("{} WHERE p.id = ?1", SELECT), [id], Payment::from_row).optional()
+}
+
+pub(crate) fn list(conn: &Connection, invoice_id: i64) -> rusqlite::Result<Vec<Payment>> {
+    let mut stmt = conn.prepare(&format!("{} WHERE p.invoice_id = ?1 ORDER BY p.num_parcialidad", SELECT))?;
+    let rows = stmt.query_map([invoice_id], Payment::from_row)?;
+    rows.collect()
+}
+
+/// Datos de la factura PPD que hacen falta para el REP, leídos de su XML timbrado.
+struct Documento {
+    uuid: String,
+    serie: Option<String>,
+    folio: Option<String>,
+    total: Decimal,
+    receptor: Receptor,
+    traslados: Vec<Traslado>,
+}
+
+fn decimal_attr(e: &Element, key: &str) -> Result<Decimal, String> {
+    e.attr_value(key)
+        .unwrap_or_default()
+        .parse()
+        .map_err(|_| format!("El CFDI trae {} inválido.", key))
+}
+
+fn documento(conn: &Connection, invoice: &Invoice) -> Result<Documento, String> {
+    let xml_text = invoices::xml(conn, invoice.id)
+        .map_err(|e| format!("base local: {}", e))?
+        .ok_or_else(|| format!("No se encontró el XML de la factura {}.", invoice.uuid))?;
+    let root = xml::parse(&xml_text)?;
+    if root.attr_value("TipoDeComprobante") != Some("I") || root.attr_value("MetodoPago") != Some("PPD") {
+        return Err(format!("La factura {} no es de ingreso con método de pago PPD.", invoice.uuid));
+    }
+    if root.attr_value("Moneda") != Some("MXN") {
+        return Err("Solo se registran pagos de facturas en pesos (MXN).".to_string());
+    }
+    let receptor = root.find("cfdi:Receptor").ok_or("El CFDI no trae receptor.")?;
+    let attr = |key: &str| receptor.attr_value(key).unwrap_or_default().to_string();
+    let mut traslados = Vec::new();
+    if let Some(list) = root.find("cfdi:Impuestos").and_then(|i| i.find("cfdi:Traslados")) {
+        for t in &list.children {
+            traslados.push(Traslado {
+                base: decimal_attr(t, "Base")?,
+                impuesto: t.attr_value("Impuesto").unwrap_or_default().to_string(),
+                tipo_factor: t.attr_value("TipoFactor").unwrap_or_default().to_string(),
+                tasa_o_cuota: t.attr_value("TasaOCuota").map(str::to_string),
+                importe: t.attr_value("Importe").map(|_| decimal_attr(t, "Importe")).transpose()?,
+            });
+        }
+    }
+    Ok(Documento {
+        uuid: invoice.uuid.clone(),
+        serie: root.attr_value("Serie").map(str::to_string),
+        folio: root.attr_value("Folio").map(str::to_string),
+        total: decimal_attr(&root, "Total")?,
+        receptor: Receptor {
+            rfc: attr("Rfc"),
+            nombre: attr("Nombre"),
+            domicilio_fiscal: attr("DomicilioFiscalReceptor"),
+            regimen_fiscal: attr("RegimenFiscalReceptor"),
+            uso_cfdi: "CP01".to_string(),
+        },
+        traslados,
+    })
+}
+
+/// Registra un pago parcial (o total) a una factura PPD vigente.
+pub(crate) fn register(conn: &Connection, invoice_id: i64, input: &PaymentInput) -> Result<Payment, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let invoice = invoices::get(conn, invoice_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la factura {}.", invoice_id))?;
+    if invoice.status != "vigente" {
+        return Err(format!("La factura {} no está vigente.", invoice.uuid));
+    }
+    let doc = documento(conn, &invoice)?;
+    if !catalogos::contains(catalogos::FORMAS_PAGO, &input.forma_pago) || input.forma_pago == "99" {
+        return Err(format!("Forma de pago {} inválida para un pago recibido.", input.forma_pago));
+    }
+    let monto = input.monto.round_dp(2);
+    if monto <= Decimal::ZERO {
+        return Err("El monto del pago debe ser mayor a cero.".to_string());
+    }
+    let fecha_pago = match input.fecha_pago.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+        Some(f) => chrono::NaiveDateTime::parse_from_str(f, "%Y-%m-%dT%H:%M:%S")
+            .map(|d| d.format("%Y-%m-%dT%H:%M:%S").to_string())
+            .map_err(|_| format!("Fecha de pago inválida: {} (AAAA-MM-DDThh:mm:ss).", f))?,
+        None => fecha_emision(),
+    };
+
+    let previous = list(conn, invoice_id).map_err(sql)?;
+    let saldo_anterior = doc.total - previous.iter().map(|p| p.monto).sum::<Decimal>();
+    if monto > saldo_anterior {
+        return Err(format!("El pago ({}) excede el saldo de la factura ({}).", monto, saldo_anterior));
+    }
+    let num_parcialidad = previous.last().map(|p| p.num_parcialidad).unwrap_or(0) + 1;
+    conn.execute(
+        "INSERT INTO invoice_payments (invoice_id, num_parcialidad, fecha_pago, forma_pago, monto, num_operacion,
+             saldo_anterior, saldo_insoluto)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            invoice_id,
+            num_parcialidad,
+            fecha_pago,
+            input.forma_pago,
+            monto.to_string(),
+            input.num_operacion.as_deref().map(str::trim).filter(|n| !n.is_empty()),
+            saldo_anterior.to_string(),
+            (saldo_anterior - monto).to_string()
+        ],
+    )
+    .map_err(sql)?;
+    get(conn, conn.last_insert_rowid())
+        .map_err(sql)?
+        .ok_or_else(|| "No se encontró el pago guardado.".to_string())
+}
+
+/// Borra el último pago de una factura si no tiene un REP vigente.
+pub(crate) fn delete(conn: &Connection, payment_id: i64) -> Result<(), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let payment = get(conn, payment_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró el pago {}.", payment_id))?;
+    if payment.is_stamped() {
+        return Err("El pago ya tiene CFDI de pagos; cancélalo antes de borrar el pago.".to_string());
+    }
+    let last: i64 = conn
+        .query_row("SELECT MAX(num_parcialidad) FROM invoice_payments WHERE invoice_id = ?1", [payment.invoice_id], |r| {
+            r.get(0)
+        })
+        .map_err(sql)?;
+    if payment.num_parcialidad != last {
+        return Err("Solo se puede borrar la última parcialidad.".to_string());
+    }
+    conn.execute("DELETE FROM invoice_payments WHERE id = ?1", [payment_id]).map(|_| ()).map_err(sql)
+}
+
+/// Parte de cada impuesto del documento que corresponde a `pagado`.
+fn prorrateo(t: &Traslado, pagado: Decimal, total: Decimal) -> Decimal {
+    (t.base * pagado / total).round_dp(2)
+}
+
+/// TrasladosDR del pago. La última parcialidad toma lo que falta de cada base.
+fn traslados_dr(doc: &Documento, payment: &Payment, previous: &[Payment]) -> Vec<Traslado> {
+    let last = payment.saldo_insoluto.is_zero();
+    doc.traslados
+        .iter()
+        .map(|t| {
+            let base = if last {
+                t.base - previous.iter().map(|p| prorrateo(t, p.monto, doc.total)).sum::<Decimal>()
+            } else {
+                prorrateo(t, payment.monto, doc.total)
+            };
+            let tasa = t.tasa_o_cuota.as_deref().and_then(|r| r.parse::<Decimal>().ok());
+            Traslado {
+                base,
+                impuesto: t.impuesto.clone(),
+                tipo_factor: t.tipo_factor.clone(),
+                tasa_o_cuota: t.tasa_o_cuota.clone(),
+                importe: t.importe.and(tasa).map(|tasa| (base * tasa).round_dp(2)),
+            }
+        })
+        .collect()
+}
+
+fn traslado_element(name: &str, suffix: &str, t: &Traslado) -> Element {
+    Element::new(name)
+        .attr(&format!("Base{}", suffix), comprobante::importe(t.base))
+        .attr(&format!("Impuesto{}", suffix), &t.impuesto)
+        .attr(&format!("TipoFactor{}", suffix), &t.tipo_factor)
+        .attr_opt(&format!("TasaOCuota{}", suffix), t.tasa_o_cuota.clone())
+        .attr_opt(&format!("Importe{}", suffix), t.importe.map(comprobante::importe))
+}
+
+/// `pago20:Totales`: bases e impuestos de IVA por tasa y monto total.
+fn totales(traslados: &[Traslado], monto: Decimal) -> Element {
+    let iva = |tasa: &str| {
+        traslados
+            .iter()
+            .find(|t| t.impuesto == crate::tax::SAT_IVA && t.tasa_o_cuota.as_deref() == Some(tasa))
+    };
+    let mut e = Element::new("pago20:Totales");
+    for (tasa, suffix) in [("0.160000", "16"), ("0.080000", "8"), ("0.000000", "0")] {
+        if let Some(t) = iva(tasa) {
+            e = e
+                .attr(&format!("TotalTrasladosBaseIVA{}", suffix), comprobante::importe(t.base))
+                .attr(&format!("TotalTrasladosImpuestoIVA{}", suffix), comprobante::importe(t.importe.unwrap_or_default()));
+        }
+    }
+    if let Some(t) = traslados.iter().find(|t| t.impuesto == crate::tax::SAT_IVA && t.tipo_factor == "Exento") {
+        e = e.attr("TotalTrasladosBaseIVAExento", comprobante::importe(t.base));
+    }
+    e.attr("MontoTotalPagos", comprobante::importe(monto))
+}
+
+/// Arma (sin sellar) el CFDI de pagos de un pago registrado.
+pub fn build_payment_cfdi(conn: &Connection, payment_id: i64) -> Result<Comprobante, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let settings = get_settings(conn).map_err(sql)?;
+    settings.check().map_err(|e| format!("Configura los datos fiscales del emisor:\n{}", e))?;
+    let payment = get(conn, payment_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró el pago {}.", payment_id))?;
+    if payment.is_stamped() {
+        return Err(format!("El pago ya tiene CFDI de pagos ({}).", payment.rep_uuid.unwrap_or_default()));
+    }
+    let invoice = invoices::get(conn, payment.invoice_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la factura {}.", payment.invoice_id))?;
+    let doc = documento(conn, &invoice)?;
+    let previous: Vec<Payment> = list(conn, invoice.id)
+        .map_err(sql)?
+        .into_iter()
+        .filter(|p| p.num_parcialidad < payment.num_parcialidad)
+        .collect();
+    let traslados = traslados_dr(&doc, &payment, &previous);
+
+    let mut docto = Element::new("pago20:DoctoRelacionado")
+        .attr("IdDocumento", &doc.uuid)
+        .attr_opt("Serie", doc.serie.clone())
+        .attr_opt("Folio", doc.folio.clone())
+        .attr("MonedaDR", "MXN")
+        .attr("EquivalenciaDR", "1")
+        .attr("NumParcialidad", payment.num_parcialidad.to_string())
+        .attr("ImpSaldoAnt", comprobante::importe(payment.saldo_anterior))
+        .attr("ImpPagado", comprobante::importe(payment.monto))
+        .attr("ImpSaldoInsoluto", comprobante::importe(payment.saldo_insoluto))
+        .attr("ObjetoImpDR", if traslados.is_empty() { "01" } else { "02" });
+    let mut pago = Element::new("pago20:Pago")
+        .attr("FechaPago", &payment.fecha_pago)
+        .attr("FormaDePagoP", &payment.forma_pago)
+        .attr("MonedaP", "MXN")
+        .attr("TipoCambioP", "1")
+        .attr("Monto", comprobante::importe(payment.monto))
+        .attr_opt("NumOperacion", payment.num_operacion.clone());
+    if !traslados.is_empty() {
+        let mut dr = Element::new("pago20:TrasladosDR");
+        let mut p = Element::new("pago20:TrasladosP");
+        for t in &traslados {
+            dr = dr.child(traslado_element("pago20:TrasladoDR", "DR", t));
+            p = p.child(traslado_element("pago20:TrasladoP", "P", t));
+        }
+        docto = docto.child(Element::new("pago20:ImpuestosDR").child(dr));
+        pago = pago.child(docto).child(Element::new("pago20:ImpuestosP").child(p));
+    } else {
+        pago = pago.child(docto);
+    }
+    let pagos = Element::new("pago20:Pagos")
+        .attr("Version", "2.0")
+        .child(totales(&traslados, payment.monto))
+        .child(pago);
+
+    let cfdi = Comprobante {
+        serie: Some(settings.serie.trim().to_string()).filter(|s| !s.is_empty()),
+        folio: Some(settings.next_folio.to_string()),
+        fecha: fecha_emision(),
+        forma_pago: None,
+        subtotal: Decimal::ZERO,
+        descuento: None,
+        moneda: "XXX".to_string(),
+        total: Decimal::ZERO,
+        tipo_de_comprobante: "P".to_string(),
+        exportacion: "01".to_string(),
+        metodo_pago: None,
+        lugar_expedicion: settings.codigo_postal.clone(),
+        no_certificado: None,
+        sello: None,
+        certificado: None,
+        informacion_global: None,
+        relacionados: None,
+        emisor: emisor(&settings),
+        receptor: doc.receptor,
+        conceptos: vec![Concepto {
+            clave_prod_serv: CLAVE_PROD_SERV_PAGO.to_string(),
+            no_identificacion: None,
+            cantidad: Decimal::ONE,
+            clave_unidad: catalogos::CLAVE_UNIDAD_ACTIVIDAD.to_string(),
+            unidad: None,
+            descripcion: "Pago".to_string(),
+            valor_unitario: Decimal::ZERO,
+            importe: Decimal::ZERO,
+            descuento: None,
+            objeto_imp: "01".to_string(),
+            traslados: Vec::new(),
+        }],
+        traslados: Vec::new(),
+        complementos: vec![pagos],
+        namespaces: vec![("pago20".to_string(), NS_PAGOS.to_string(), XSD_PAGOS.to_string())],
+    };
+    super::check(&cfdi)?;
+    Ok(cfdi)
+}
+
+/// Liga el REP timbrado a su pago.
+pub(crate) fn set_rep(conn: &Connection, payment_id: i64, rep_invoice_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE invoice_payments SET rep_invoice_id = ?2 WHERE id = ?1",
+        params![payment_id, rep_invoice_id],
+    )
+    .map(|_| ())
+}
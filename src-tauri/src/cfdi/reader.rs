@@ -0,0 +1,237 @@
+//! Lectura de CFDI 3.3 y 4.0 de proveedores (importar compras).
+//!
+//! Se buscan los nodos por nombre local, así que no importa el prefijo (`cfdi:`, `tfd:` o
+//! ninguno). Los importes se leen como `Decimal`; un número mal formado es error, no cero.
+//! Las diferencias de cuadre (conceptos contra SubTotal, impuestos contra Total) van en
+//! `warnings` para que la revisión las muestre sin rechazar el archivo.
+
+use super::xml::{self, Element};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CfdiParty {
+    pub rfc: String,
+    pub nombre: Option<String>,
+    pub regimen_fiscal: Option<String>,
+    /// Solo receptor.
+    pub uso_cfdi: Option<String>,
+    /// Solo receptor en 4.0.
+    pub domicilio_fiscal: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CfdiTax {
+    /// `001` ISR, `002` IVA, `003` IEPS.
+    pub impuesto: String,
+    /// `Tasa`, `Cuota` o `Exento` (las retenciones no lo traen a nivel documento).
+    pub tipo_factor: Option<String>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub tasa_o_cuota: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub base: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub importe: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CfdiLine {
+    pub clave_prod_serv: String,
+    pub no_identificacion: Option<String>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub cantidad: Decimal,
+    pub clave_unidad: Option<String>,
+    pub unidad: Option<String>,
+    pub descripcion: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub valor_unitario: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub importe: Decimal,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub descuento: Option<Decimal>,
+    pub traslados: Vec<CfdiTax>,
+    pub retenciones: Vec<CfdiTax>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CfdiDocument {
+    /// `3.3` o `4.0`.
+    pub version: String,
+    pub tipo_de_comprobante: String,
+    pub serie: Option<String>,
+    pub folio: Option<String>,
+    pub fecha: String,
+    /// Del timbre fiscal; `None` si el XML no está timbrado.
+    pub uuid: Option<String>,
+    pub fecha_timbrado: Option<String>,
+    pub forma_pago: Option<String>,
+    pub metodo_pago: Option<String>,
+    pub moneda: String,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub tipo_cambio: Option<Decimal>,
+    pub emisor: CfdiParty,
+    pub receptor: CfdiParty,
+    pub conceptos: Vec<CfdiLine>,
+    /// Impuestos a nivel documento.
+    pub traslados: Vec<CfdiTax>,
+    pub retenciones: Vec<CfdiTax>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub total_traslados: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub total_retenciones: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub subtotal: Decimal,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub descuento: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total: Decimal,
+    pub warnings: Vec<String>,
+}
+
+/// Atributo opcional sin espacios; vacío cuenta como ausente.
+fn text(e: &Element, key: &str) -> Option<String> {
+    e.attr_value(key).map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+fn decimal_opt(e: &Element, key: &str) -> Result<Option<Decimal>, String> {
+    match e.attr_value(key).map(str::trim).filter(|v| !v.is_empty()) {
+        Some(v) => v
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} trae {}=\"{}\", que no es un número.", e.local_name(), key, v)),
+        None => Ok(None),
+    }
+}
+
+fn decimal(e: &Element, key: &str) -> Result<Decimal, String> {
+    decimal_opt(e, key)?.ok_or_else(|| format!("A {} le falta el atributo {}.", e.local_name(), key))
+}
+
+/// Hijo directo con ese nombre local.
+fn child<'a>(e: &'a Element, local: &str) -> Option<&'a Element> {
+    e.children.iter().find(|c| c.local_name() == local)
+}
+
+fn taxes(impuestos: Option<&Element>, group: &str, item: &str) -> Result<Vec<CfdiTax>, String> {
+    let Some(list) = impuestos.and_then(|i| child(i, group)) else {
+        return Ok(Vec::new());
+    };
+    list.children
+        .iter()
+        .filter(|t| t.local_name() == item)
+        .map(|t| {
+            Ok(CfdiTax {
+                impuesto: text(t, "Impuesto").unwrap_or_default(),
+                tipo_factor: text(t, "TipoFactor"),
+                tasa_o_cuota: decimal_opt(t, "TasaOCuota")?,
+                base: decimal_opt(t, "Base")?,
+                importe: decimal_opt(t, "Importe")?,
+            })
+        })
+        .collect()
+}
+
+fn party(e: &Element) -> CfdiParty {
+    CfdiParty {
+        rfc: text(e, "Rfc").unwrap_or_default().to_uppercase(),
+        nombre: text(e, "Nombre"),
+        regimen_fiscal: text(e, "RegimenFiscal").or_else(|| text(e, "RegimenFiscalReceptor")),
+        uso_cfdi: text(e, "UsoCFDI"),
+        domicilio_fiscal: text(e, "DomicilioFiscalReceptor"),
+    }
+}
+
+fn line(c: &Element) -> Result<CfdiLine, String> {
+    let impuestos = child(c, "Impuestos");
+    Ok(CfdiLine {
+        clave_prod_serv: text(c, "ClaveProdServ").unwrap_or_default(),
+        no_identificacion: text(c, "NoIdentificacion"),
+        cantidad: decimal(c, "Cantidad")?,
+        clave_unidad: text(c, "ClaveUnidad"),
+        unidad: text(c, "Unidad"),
+        descripcion: text(c, "Descripcion").unwrap_or_default(),
+        valor_unitario: decimal(c, "ValorUnitario")?,
+        importe: decimal(c, "Importe")?,
+        descuento: decimal_opt(c, "Descuento")?,
+        traslados: taxes(impuestos, "Traslados", "Traslado")?,
+        retenciones: taxes(impuestos, "Retenciones", "Retencion")?,
+    })
+}
+
+/// Diferencias de cuadre que vale la pena revisar antes de dar entrada a la compra.
+fn check_totals(doc: &CfdiDocument) -> Vec<String> {
+    let mut warnings = Vec::new();
+    // Un centavo por concepto, como en la validación del SAT.
+    let tolerance = Decimal::new(1, 2) * Decimal::from(doc.conceptos.len().max(1));
+    let conceptos: Decimal = doc.conceptos.iter().map(|c| c.importe).sum();
+    if (conceptos - doc.subtotal).abs() > tolerance {
+        warnings.push(format!("Los conceptos suman {} y el SubTotal es {}.", conceptos, doc.subtotal));
+    }
+    for c in &doc.conceptos {
+        if (c.cantidad * c.valor_unitario - c.importe).abs() > Decimal::new(1, 2) {
+            warnings.push(format!(
+                "{}: {} x {} no da el importe {}.",
+                c.descripcion, c.cantidad, c.valor_unitario, c.importe
+            ));
+        }
+    }
+    let expected = doc.subtotal - doc.descuento.unwrap_or_default() + doc.total_traslados.unwrap_or_default()
+        - doc.total_retenciones.unwrap_or_default();
+    if (expected - doc.total).abs() > tolerance {
+        warnings.push(format!("SubTotal, descuento e impuestos dan {} y el Total es {}.", expected, doc.total));
+    }
+    warnings
+}
+
+/// Lee un CFDI 3.3 o 4.0 (de ingreso, egreso o traslado) a datos tipados.
+pub fn read_cfdi(xml_text: &str) -> Result<CfdiDocument, String> {
+    let root = xml::parse(xml_text)?;
+    if root.local_name() != "Comprobante" {
+        return Err("El XML no es un CFDI (falta el nodo Comprobante).".to_string());
+    }
+    let version = text(&root, "Version").or_else(|| text(&root, "version")).unwrap_or_default();
+    if version != "3.3" && version != "4.0" {
+        return Err(format!("Versión de CFDI no soportada: {} (se leen 3.3 y 4.0).", version));
+    }
+    let emisor = child(&root, "Emisor").ok_or("El CFDI no trae Emisor.")?;
+    let receptor = child(&root, "Receptor").ok_or("El CFDI no trae Receptor.")?;
+    let conceptos = child(&root, "Conceptos")
+        .map(|list| list.children.iter().filter(|c| c.local_name() == "Concepto").map(line).collect())
+        .transpose()?
+        .unwrap_or_default();
+    let impuestos = child(&root, "Impuestos");
+    let timbre = child(&root, "Complemento").and_then(|c| child(c, "TimbreFiscalDigital"));
+
+    let mut doc = CfdiDocument {
+        version,
+        tipo_de_comprobante: text(&root, "TipoDeComprobante").unwrap_or_default(),
+        serie: text(&root, "Serie"),
+        folio: text(&root, "Folio"),
+        fecha: text(&root, "Fecha").unwrap_or_default(),
+        uuid: timbre.and_then(|t| text(t, "UUID")).map(|u| u.to_uppercase()),
+        fecha_timbrado: timbre.and_then(|t| text(t, "FechaTimbrado")),
+        forma_pago: text(&root, "FormaPago"),
+        metodo_pago: text(&root, "MetodoPago"),
+        moneda: text(&root, "Moneda").unwrap_or_else(|| "MXN".to_string()),
+        tipo_cambio: decimal_opt(&root, "TipoCambio")?,
+        emisor: party(emisor),
+        receptor: party(receptor),
+        conceptos,
+        traslados: taxes(impuestos, "Traslados", "Traslado")?,
+        retenciones: taxes(impuestos, "Retenciones", "Retencion")?,
+        total_traslados: impuestos.map(|i| decimal_opt(i, "TotalImpuestosTrasladados")).transpose()?.flatten(),
+        total_retenciones: impuestos.map(|i| decimal_opt(i, "TotalImpuestosRetenidos")).transpose()?.flatten(),
+        subtotal: decimal(&root, "SubTotal")?,
+        descuento: decimal_opt(&root, "Descuento")?,
+        total: decimal(&root, "Total")?,
+        warnings: Vec::new(),
+    };
+    if doc.uuid.is_none() {
+        doc.warnings.push("El XML no está timbrado (no trae TimbreFiscalDigital).".to_string());
+    }
+    if doc.tipo_de_comprobante != "P" {
+        let warnings = check_totals(&doc);
+        doc.warnings.extend(warnings);
+    }
+    Ok(doc)
+}
@@ -0,0 +1,109 @@
+//! Cadena original y sello digital (RSA-SHA256) del comprobante.
+//!
+//! La cadena sigue `cadenaoriginal_4_0.xslt`: los valores de los atributos en el orden del
+//! Anexo 20, separados por `|`, entre `||` y con los espacios normalizados. Como el modelo
+//! escribe los atributos en ese mismo orden, basta recorrer el árbol.
+
+use super::comprobante::Comprobante;
+use super::csd::Csd;
+use super::xml::Element;
+use base64::Engine;
+use rsa::pkcs1v15::SigningKey;
+use rsa::signature::{SignatureEncoding, Signer};
+use sha2::Sha256;
+
+/// Nodos que no forman parte de la cadena original del comprobante.
+const SKIPPED_ELEMENTS: &[&str] = &["cfdi:Addenda", "tfd:TimbreFiscalDigital"];
+
+/// Colapsa espacios consecutivos y recorta, como `normalize-space()` del XSLT.
+fn normalize(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn is_skipped_attr(element: &Element, key: &str) -> bool {
+    key.starts_with("xmlns")
+        || key.starts_with("xsi:")
+        || (element.name == "cfdi:Comprobante" && (key == "Sello" || key == "Certificado"))
+}
+
+fn push_attrs(element: &Element, keys: Option<&[&str]>, out: &mut Vec<String>) {
+    for (k, v) in &element.attrs {
+        if is_skipped_attr(element, k) || keys.is_some_and(|keys| !keys.contains(&k.as_str())) {
+            continue;
+        }
+        let v = normalize(v);
+        if !v.is_empty() {
+            out.push(v);
+        }
+    }
+}
+
+fn walk(element: &Element, parent: Option<&str>, out: &mut Vec<String>) {
+    if SKIPPED_ELEMENTS.contains(&element.name.as_str()) {
+        return;
+    }
+    // En los impuestos del comprobante el XSLT pone cada total después de su lista.
+    if element.name == "cfdi:Impuestos" && parent == Some("cfdi:Comprobante") {
+        if let Some(retenciones) = element.find("cfdi:Retenciones") {
+            walk(retenciones, Some(&element.name), out);
+        }
+        push_attrs(element, Some(&["TotalImpuestosRetenidos"]), out);
+        if let Some(traslados) = element.find("cfdi:Traslados") {
+            walk(traslados, Some(&element.name), out);
+        }
+        push_attrs(element, Some(&["TotalImpuestosTrasladados"]), out);
+        return;
+    }
+    push_attrs(element, None, out);
+    for child in &element.children {
+        walk(child, Some(&element.name), out);
+    }
+}
+
+/// Cadena original de un árbol ya armado (`||a|b|c||`).
+pub fn cadena_original_of(root: &Element) -> String {
+    let mut values = Vec::new();
+    walk(root, None, &mut values);
+    format!("||{}||", values.join("|"))
+}
+
+pub fn cadena_original(cfdi: &Comprobante) -> String {
+    cadena_original_of(&cfdi.to_element())
+}
+
+/// Firma RSA-SHA256 (PKCS#1 v1.5) en base64.
+pub fn sign(csd: &Csd, cadena: &str) -> String {
+    let signer = SigningKey::<Sha256>::new(csd.key.clone());
+    let signature = signer.sign(cadena.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(signature.to_bytes())
+}
+
+/// Pone `NoCertificado`, `Certificado` y `Sello`. Revisa que el CSD sea del emisor y esté
+/// vigente en la fecha del comprobante.
+pub fn seal(cfdi: &mut Comprobante, csd: &Csd) -> Result<(), String> {
+    if csd.info.rfc != cfdi.emisor.rfc {
+        return Err(format!(
+            "El CSD es del RFC {} y el comprobante lo emite {}.",
+            csd.info.rfc, cfdi.emisor.rfc
+        ));
+    }
+    let fecha = chrono::NaiveDateTime::parse_from_str(&cfdi.fecha, "%Y-%m-%dT%H:%M:%S")
+        .map_err(|_| format!("Fecha del comprobante inválida: {}", cfdi.fecha))?;
+    let fecha_utc = fecha
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|d| d.with_timezone(&chrono::Utc))
+        .unwrap_or_else(|| fecha.and_utc());
+    if !csd.info.is_valid_at(fecha_utc) {
+        return Err(format!(
+            "El CSD {} no está vigente en la fecha del comprobante (vence {}).",
+            csd.info.numero, csd.info.valid_to
+        ));
+    }
+    cfdi.no_certificado = Some(csd.info.numero.clone());
+    cfdi.certificado = Some(base64::engine::general_purpose::STANDARD.encode(&csd.cer_der));
+    cfdi.sello = None;
+    let cadena = cadena_original(cfdi);
+    cfdi.sello = Some(sign(csd, &cadena));
+    Ok(())
+}
@@ -0,0 +1,298 @@
+//! Validación del comprobante contra las reglas del esquema `cfdv40.xsd` (obligatorios,
+//! patrones, longitudes, catálogos) y las de cálculo del Anexo 20 que revisa el PAC.
+//! No sustituye la validación del PAC, pero evita timbrados rechazados por errores comunes.
+
+use super::catalogos::{self, RFC_EXTRANJERO, RFC_PUBLICO_GENERAL};
+use super::comprobante::{Comprobante, Concepto, Traslado};
+use rust_decimal::Decimal;
+
+/// Diferencia máxima permitida por redondeo entre importes calculados.
+fn tolerance() -> Decimal {
+    Decimal::new(1, 2)
+}
+
+/// RFC de persona moral (12) o física (13): letras, fecha AAMMDD y homoclave.
+pub fn is_rfc(rfc: &str) -> bool {
+    let chars: Vec<char> = rfc.chars().collect();
+    let letters = match chars.len() {
+        12 => 3,
+        13 => 4,
+        _ => return false,
+    };
+    let (prefix, rest) = chars.split_at(letters);
+    if !prefix.iter().all(|c| c.is_ascii_uppercase() || *c == '&' || *c == 'Ñ') {
+        return false;
+    }
+    let date: String = rest[..6].iter().collect();
+    if !date.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let month: u32 = date[2..4].parse().unwrap_or(0);
+    let day: u32 = date[4..6].parse().unwrap_or(0);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return false;
+    }
+    let homoclave = &rest[6..];
+    homoclave[..2].iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && (homoclave[2].is_ascii_digit() || homoclave[2] == 'A')
+}
+
+pub fn is_codigo_postal(cp: &str) -> bool {
+    cp.len() == 5 && cp.chars().all(|c| c.is_ascii_digit())
+}
+
+/// UUID del timbre fiscal (8-4-4-4-12 hexadecimal).
+pub fn is_uuid(uuid: &str) -> bool {
+    let groups: Vec<&str> = uuid.split('-').collect();
+    groups.len() == 5
+        && groups.iter().zip([8, 4, 4, 4, 12]).all(|(g, n)| g.len() == n && g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_fecha(fecha: &str) -> bool {
+    chrono::NaiveDateTime::parse_from_str(fecha, "%Y-%m-%dT%H:%M:%S").is_ok()
+}
+
+fn check_len(errors: &mut Vec<String>, field: &str, value: &str, min: usize, max: usize) {
+    let n = value.chars().count();
+    if n < min || n > max {
+        errors.push(format!("{} debe tener entre {} y {} caracteres.", field, min, max));
+    }
+}
+
+/// `max_diff`: diferencia permitida entre `Importe` y base × tasa.
+fn check_traslado(errors: &mut Vec<String>, ctx: &str, t: &Traslado, max_diff: Decimal) {
+    if !catalogos::IMPUESTOS.contains(&t.impuesto.as_str()) {
+        errors.push(format!("{}: impuesto {} no existe en c_Impuesto.", ctx, t.impuesto));
+    }
+    if !catalogos::TIPOS_FACTOR.contains(&t.tipo_factor.as_str()) {
+        errors.push(format!("{}: TipoFactor {} inválido.", ctx, t.tipo_factor));
+    }
+    if t.base < Decimal::ZERO {
+        errors.push(format!("{}: la base del impuesto no puede ser negativa.", ctx));
+    }
+    if t.tipo_factor == "Exento" {
+        if t.tasa_o_cuota.is_some() || t.importe.is_some() {
+            errors.push(format!("{}: un traslado exento no lleva TasaOCuota ni Importe.", ctx));
+        }
+        return;
+    }
+    let (Some(tasa), Some(importe)) = (&t.tasa_o_cuota, t.importe) else {
+        errors.push(format!("{}: falta TasaOCuota o Importe del traslado.", ctx));
+        return;
+    };
+    match tasa.parse::<Decimal>() {
+        Ok(rate) => {
+            if (t.base * rate - importe).abs() > max_diff {
+                errors.push(format!(
+                    "{}: el importe del impuesto {} no corresponde a base {} × {}.",
+                    ctx, importe, t.base, tasa
+                ));
+            }
+        }
+        Err(_) => errors.push(format!("{}: TasaOCuota {} no es numérica.", ctx, tasa)),
+    }
+}
+
+fn check_concepto(errors: &mut Vec<String>, i: usize, c: &Concepto) {
+    let ctx = format!("Concepto {}", i + 1);
+    if c.clave_prod_serv.len() != 8 || !c.clave_prod_serv.chars().all(|ch| ch.is_ascii_digit()) {
+        errors.push(format!("{}: ClaveProdServ {} debe ser de 8 dígitos.", ctx, c.clave_prod_serv));
+    }
+    if c.cantidad <= Decimal::ZERO {
+        errors.push(format!("{}: la cantidad debe ser mayor a cero.", ctx));
+    }
+    check_len(errors, &format!("{}: ClaveUnidad", ctx), &c.clave_unidad, 1, 3);
+    check_len(errors, &format!("{}: Descripcion", ctx), &c.descripcion, 1, 1000);
+    if c.valor_unitario < Decimal::ZERO {
+        errors.push(format!("{}: el valor unitario no puede ser negativo.", ctx));
+    }
+    if (c.cantidad * c.valor_unitario - c.importe).abs() > tolerance() {
+        errors.push(format!("{}: Importe {} no es Cantidad × ValorUnitario.", ctx, c.importe));
+    }
+    if let Some(d) = c.descuento {
+        if d < Decimal::ZERO || d > c.importe {
+            errors.push(format!("{}: el descuento debe estar entre 0 y el importe.", ctx));
+        }
+    }
+    if !catalogos::OBJETOS_IMPUESTO.contains(&c.objeto_imp.as_str()) {
+        errors.push(format!("{}: ObjetoImp {} inválido.", ctx, c.objeto_imp));
+    }
+    if c.objeto_imp == "02" && c.traslados.is_empty() {
+        errors.push(format!("{}: ObjetoImp 02 requiere desglose de impuestos.", ctx));
+    }
+    if c.objeto_imp == "01" && !c.traslados.is_empty() {
+        errors.push(format!("{}: ObjetoImp 01 no debe llevar impuestos.", ctx));
+    }
+    for t in &c.traslados {
+        check_traslado(errors, &ctx, t, tolerance());
+    }
+}
+
+/// Devuelve todos los errores encontrados (vacío si el comprobante es válido).
+pub fn validate(cfdi: &Comprobante) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if let Some(serie) = &cfdi.serie {
+        check_len(&mut errors, "Serie", serie, 1, 25);
+    }
+    if let Some(folio) = &cfdi.folio {
+        check_len(&mut errors, "Folio", folio, 1, 40);
+    }
+    if !is_fecha(&cfdi.fecha) {
+        errors.push(format!("Fecha {} no tiene el formato AAAA-MM-DDThh:mm:ss.", cfdi.fecha));
+    }
+    if !catalogos::TIPOS_COMPROBANTE.contains(&cfdi.tipo_de_comprobante.as_str()) {
+        errors.push(format!("TipoDeComprobante {} inválido.", cfdi.tipo_de_comprobante));
+    }
+    if !catalogos::MONEDAS.contains(&cfdi.moneda.as_str()) {
+        errors.push(format!("Moneda {} no soportada.", cfdi.moneda));
+    }
+    if !catalogos::EXPORTACION.contains(&cfdi.exportacion.as_str()) {
+        errors.push(format!("Exportacion {} inválida.", cfdi.exportacion));
+    }
+    if let Some(g) = &cfdi.informacion_global {
+        if !catalogos::contains(catalogos::PERIODICIDADES, &g.periodicidad) {
+            errors.push(format!("Periodicidad {} no existe en c_Periodicidad.", g.periodicidad));
+        }
+        let bimestral = g.periodicidad == "05";
+        let meses_ok = match g.meses.parse::<u32>() {
+            Ok(m) if g.meses.len() == 2 => (bimestral && (13..=18).contains(&m)) || (!bimestral && (1..=12).contains(&m)),
+            _ => false,
+        };
+        if !meses_ok {
+            errors.push(format!("Meses {} no corresponde a la periodicidad {}.", g.meses, g.periodicidad));
+        }
+        if cfdi.receptor.rfc != RFC_PUBLICO_GENERAL {
+            errors.push("InformacionGlobal solo aplica con el RFC de público en general.".to_string());
+        }
+    }
+    if let Some(rel) = &cfdi.relacionados {
+        if rel.uuids.is_empty() {
+            errors.push("CfdiRelacionados debe tener al menos un UUID.".to_string());
+        }
+        for uuid in rel.uuids.iter().filter(|u| !is_uuid(u)) {
+            errors.push(format!("CfdiRelacionado: {} no es un UUID.", uuid));
+        }
+    }
+    if !is_codigo_postal(&cfdi.lugar_expedicion) {
+        errors.push("LugarExpedicion debe ser un código postal de 5 dígitos.".to_string());
+    }
+    if let Some(fp) = &cfdi.forma_pago {
+        if !catalogos::contains(catalogos::FORMAS_PAGO, fp) {
+            errors.push(format!("FormaPago {} no existe en c_FormaPago.", fp));
+        }
+    }
+    if let Some(mp) = &cfdi.metodo_pago {
+        if !catalogos::contains(catalogos::METODOS_PAGO, mp) {
+            errors.push(format!("MetodoPago {} no existe en c_MetodoPago.", mp));
+        }
+    }
+    if cfdi.tipo_de_comprobante == "I" {
+        match (cfdi.metodo_pago.as_deref(), cfdi.forma_pago.as_deref()) {
+            (None, _) | (_, None) => errors.push("Un CFDI de ingreso requiere MetodoPago y FormaPago.".to_string()),
+            (Some("PPD"), Some(fp)) if fp != "99" => {
+                errors.push("Con MetodoPago PPD la FormaPago debe ser 99 (por definir).".to_string())
+            }
+            (Some("PUE"), Some("99")) => {
+                errors.push("Con MetodoPago PUE la FormaPago no puede ser 99.".to_string())
+            }
+            _ => {}
+        }
+    }
+    if cfdi.tipo_de_comprobante == "P" {
+        if cfdi.moneda != "XXX" {
+            errors.push("El CFDI de pagos usa Moneda XXX.".to_string());
+        }
+        if cfdi.forma_pago.is_some() || cfdi.metodo_pago.is_some() {
+            errors.push("El CFDI de pagos no lleva FormaPago ni MetodoPago.".to_string());
+        }
+        if cfdi.receptor.uso_cfdi != "CP01" {
+            errors.push("El CFDI de pagos usa UsoCFDI CP01.".to_string());
+        }
+        if cfdi.complementos.is_empty() {
+            errors.push("El CFDI de pagos requiere el complemento de pagos.".to_string());
+        }
+    }
+
+    // Emisor
+    if !is_rfc(&cfdi.emisor.rfc) {
+        errors.push(format!("RFC del emisor {} no es válido.", cfdi.emisor.rfc));
+    }
+    check_len(&mut errors, "Nombre del emisor", &cfdi.emisor.nombre, 1, 254);
+    if !catalogos::contains(catalogos::REGIMENES_FISCALES, &cfdi.emisor.regimen_fiscal) {
+        errors.push(format!("Régimen fiscal del emisor {} inválido.", cfdi.emisor.regimen_fiscal));
+    }
+
+    // Receptor
+    let r = &cfdi.receptor;
+    if !is_rfc(&r.rfc) {
+        errors.push(format!("RFC del receptor {} no es válido.", r.rfc));
+    }
+    check_len(&mut errors, "Nombre del receptor", &r.nombre, 1, 254);
+    if !is_codigo_postal(&r.domicilio_fiscal) {
+        errors.push("El domicilio fiscal del receptor debe ser un código postal de 5 dígitos.".to_string());
+    }
+    if !catalogos::contains(catalogos::REGIMENES_FISCALES, &r.regimen_fiscal) {
+        errors.push(format!("Régimen fiscal del receptor {} inválido.", r.regimen_fiscal));
+    }
+    if !catalogos::contains(catalogos::USOS_CFDI, &r.uso_cfdi) {
+        errors.push(format!("Uso CFDI {} no existe en c_UsoCFDI.", r.uso_cfdi));
+    }
+    if r.rfc == RFC_PUBLICO_GENERAL || r.rfc == RFC_EXTRANJERO {
+        if r.uso_cfdi != "S01" && r.uso_cfdi != "CP01" {
+            errors.push("Para RFC genérico el Uso CFDI debe ser S01.".to_string());
+        }
+        if r.regimen_fiscal != "616" {
+            errors.push("Para RFC genérico el régimen del receptor debe ser 616.".to_string());
+        }
+        if r.rfc == RFC_PUBLICO_GENERAL && r.domicilio_fiscal != cfdi.lugar_expedicion {
+            errors.push("Para público en general el domicilio del receptor es el LugarExpedicion.".to_string());
+        }
+    }
+    if r.rfc == RFC_PUBLICO_GENERAL && r.nombre == catalogos::NOMBRE_PUBLICO_GENERAL && cfdi.informacion_global.is_none() {
+        errors.push("La factura a PUBLICO EN GENERAL requiere InformacionGlobal.".to_string());
+    }
+
+    // Conceptos e importes
+    if cfdi.conceptos.is_empty() {
+        errors.push("El comprobante necesita al menos un concepto.".to_string());
+    }
+    for (i, c) in cfdi.conceptos.iter().enumerate() {
+        check_concepto(&mut errors, i, c);
+    }
+    // En el comprobante cada concepto ya se redondeó por separado: la diferencia crece con ellos.
+    let max_diff = tolerance() * Decimal::from(cfdi.conceptos.len().max(1));
+    for t in &cfdi.traslados {
+        check_traslado(&mut errors, "Impuestos", t, max_diff);
+    }
+
+    if cfdi.moneda != "XXX" {
+        let subtotal: Decimal = cfdi.conceptos.iter().map(|c| c.importe).sum();
+        if (subtotal - cfdi.subtotal).abs() > tolerance() {
+            errors.push(format!("SubTotal {} no es la suma de los conceptos ({}).", cfdi.subtotal, subtotal));
+        }
+        let descuento: Decimal = cfdi.conceptos.iter().filter_map(|c| c.descuento).sum();
+        if (descuento - cfdi.descuento.unwrap_or_default()).abs() > tolerance() {
+            errors.push(format!("Descuento {} no es la suma de los conceptos ({}).", cfdi.descuento.unwrap_or_default(), descuento));
+        }
+        // Cada traslado del comprobante debe ser la suma de los mismos traslados en los conceptos.
+        for t in &cfdi.traslados {
+            let same = |c: &&Traslado| c.impuesto == t.impuesto && c.tipo_factor == t.tipo_factor && c.tasa_o_cuota == t.tasa_o_cuota;
+            let importe: Decimal = cfdi
+                .conceptos
+                .iter()
+                .flat_map(|c| c.traslados.iter())
+                .filter(same)
+                .filter_map(|c| c.importe)
+                .sum();
+            if (importe - t.importe.unwrap_or_default()).abs() > tolerance() {
+                errors.push(format!("El traslado {} {:?} no cuadra con los conceptos.", t.impuesto, t.tasa_o_cuota));
+            }
+        }
+        let expected = cfdi.subtotal - cfdi.descuento.unwrap_or_default() + cfdi.total_traslados().unwrap_or_default();
+        if (expected - cfdi.total).abs() > tolerance() {
+            errors.push(format!("Total {} no es SubTotal − Descuento + impuestos ({}).", cfdi.total, expected));
+        }
+    }
+    errors
+}
@@ -0,0 +1,185 @@
+//! Árbol XML mínimo para armar y leer comprobantes. Los atributos se guardan en el orden del
+//! Anexo 20, que es el mismo que usa la cadena original.
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// Texto directo del nodo (respuestas SOAP); en los comprobantes va vacío.
+    pub text: String,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Element {
+        Element { name: name.to_string(), attrs: Vec::new(), children: Vec::new(), text: String::new() }
+    }
+
+    pub fn attr(mut self, key: &str, value: impl Into<String>) -> Element {
+        self.attrs.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// Agrega el atributo solo si trae valor (los opcionales vacíos no se escriben).
+    pub fn attr_opt(self, key: &str, value: Option<impl Into<String>>) -> Element {
+        match value {
+            Some(v) => self.attr(key, v),
+            None => self,
+        }
+    }
+
+    pub fn child(mut self, child: Element) -> Element {
+        self.children.push(child);
+        self
+    }
+
+    pub fn attr_value(&self, key: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Cambia (o agrega al final) un atributo.
+    pub fn set_attr(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.attrs.iter_mut().find(|(k, _)| k == key) {
+            Some(slot) => slot.1 = value,
+            None => self.attrs.push((key.to_string(), value)),
+        }
+    }
+
+    /// Nombre sin prefijo de espacio de nombres (`cfdi:Emisor` -> `Emisor`).
+    pub fn local_name(&self) -> &str {
+        self.name.rsplit(':').next().unwrap_or(&self.name)
+    }
+
+    /// Primer descendiente (en profundidad) con ese nombre local, sin importar el prefijo.
+    pub fn descendant(&self, local: &str) -> Option<&Element> {
+        for child in &self.children {
+            if child.local_name() == local {
+                return Some(child);
+            }
+            if let Some(found) = child.descendant(local) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Todos los descendientes con ese nombre local.
+    pub fn descendants<'a>(&'a self, local: &str, out: &mut Vec<&'a Element>) {
+        for child in &self.children {
+            if child.local_name() == local {
+                out.push(child);
+            }
+            child.descendants(local, out);
+        }
+    }
+
+    /// Primer hijo directo con ese nombre.
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.children.iter_mut().find(|c| c.name == name)
+    }
+
+    fn write(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(&self.name);
+        for (k, v) in &self.attrs {
+            out.push(' ');
+            out.push_str(k);
+            out.push_str("=\"");
+            out.push_str(&escape(v));
+            out.push('"');
+        }
+        if self.children.is_empty() && self.text.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        out.push_str(&escape(&self.text));
+        for child in &self.children {
+            child.write(out);
+        }
+        out.push_str("</");
+        out.push_str(&self.name);
+        out.push('>');
+    }
+
+    /// Documento completo con declaración XML, sin saltos de línea (así lo esperan los PAC).
+    pub fn to_document(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+        self.write(&mut out);
+        out
+    }
+}
+
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn start_element(start: &BytesStart) -> Result<Element, String> {
+    let mut element = Element::new(&String::from_utf8_lossy(start.name().as_ref()));
+    for attr in start.attributes() {
+        let attr = attr.map_err(|e| format!("XML inválido: {}", e))?;
+        let value = attr.unescape_value().map_err(|e| format!("XML inválido: {}", e))?;
+        element.attrs.push((String::from_utf8_lossy(attr.key.as_ref()).to_string(), value.to_string()));
+    }
+    Ok(element)
+}
+
+/// Lee un documento XML completo a un árbol `Element` (el nodo raíz).
+pub fn parse(xml: &str) -> Result<Element, String> {
+    // Algunos archivos traen BOM al inicio.
+    let mut reader = Reader::from_str(xml.trim_start_matches('\u{feff}'));
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => stack.push(start_element(&start)?),
+            Ok(Event::Empty(start)) => {
+                let element = start_element(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Ok(Event::Text(text)) => {
+                // Se ignora la indentación entre nodos.
+                let text = text.unescape().map_err(|e| format!("XML inválido: {}", e))?;
+                if let (Some(current), false) = (stack.last_mut(), text.trim().is_empty()) {
+                    current.text.push_str(&text);
+                }
+            }
+            Ok(Event::CData(data)) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Ok(Event::End(_)) => {
+                let done = stack.pop().ok_or("XML inválido: cierre sin apertura")?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(done),
+                    None => return Ok(done),
+                }
+            }
+            Ok(Event::Eof) => return Err("XML incompleto.".to_string()),
+            Ok(_) => {}
+            Err(e) => return Err(format!("XML inválido en la posición {}: {}", reader.error_position(), e)),
+        }
+    }
+}
@@ -0,0 +1,1279 @@
+//! Base de datos local (SQLite) para operar sin internet.
+//! Replica las tablas de `supabase_schema.sql` (productos, clientes, ventas, partidas,
+//! cotizaciones). Los importes se guardan como TEXT para no perder centavos.
+
+use crate::inventory::{self, MovementType, NewMovement};
+use crate::money::TaxRounding;
+use crate::tax::TaxProfile;
+use crate::units::{self, Unit};
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Row, RowIndex};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+use tauri::State;
+
+/// Migraciones en orden. La posición + 1 es la versión guardada en `PRAGMA user_version`.
+/// Nunca editar una migración publicada: agregar una nueva al final.
+const MIGRATIONS: &[&str] = &[
+    // 1: esquema inicial (supabase_schema.sql + pending sales + cotizaciones)
+    r#"
+    CREATE TABLE products (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        code TEXT UNIQUE NOT NULL,
+        barcode TEXT,
+        name TEXT NOT NULL,
+        description TEXT,
+        price TEXT NOT NULL DEFAULT '0',
+        cost TEXT NOT NULL DEFAULT '0',
+        stock INTEGER NOT NULL DEFAULT 0,
+        category TEXT,
+        supplier TEXT,
+        minimum_stock INTEGER DEFAULT 10,
+        image_url TEXT,
+        last_sale_date TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_products_barcode ON products(barcode);
+    CREATE INDEX idx_products_name ON products(name);
+    CREATE INDEX idx_products_category ON products(category);
+
+    CREATE TABLE customers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        phone TEXT UNIQUE,
+        name TEXT,
+        last_name TEXT,
+        rfc TEXT,
+        email TEXT,
+        address TEXT,
+        city TEXT,
+        state TEXT,
+        postal_code TEXT,
+        pending_csf INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_customers_rfc ON customers(rfc);
+
+    CREATE TABLE sales (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        sale_number TEXT UNIQUE NOT NULL,
+        subtotal TEXT NOT NULL,
+        discount TEXT NOT NULL DEFAULT '0',
+        tax TEXT NOT NULL DEFAULT '0',
+        total TEXT NOT NULL,
+        payment_method TEXT,
+        receipt_type TEXT NOT NULL DEFAULT 'ticket',
+        customer_id INTEGER REFERENCES customers(id) ON DELETE SET NULL,
+        user_id TEXT,
+        notes TEXT,
+        status TEXT NOT NULL DEFAULT 'completed'
+            CHECK (status IN ('pending', 'completed', 'cancelled')),
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_sales_created_at ON sales(created_at DESC);
+    CREATE INDEX idx_sales_status_created_at ON sales(status, created_at DESC);
+
+    CREATE TABLE sale_items (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        sale_id INTEGER NOT NULL REFERENCES sales(id) ON DELETE CASCADE,
+        product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE RESTRICT,
+        quantity INTEGER NOT NULL,
+        unit_price TEXT NOT NULL,
+        subtotal TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_sale_items_sale_id ON sale_items(sale_id);
+    CREATE INDEX idx_sale_items_product_id ON sale_items(product_id);
+
+    CREATE TABLE quotations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        quotation_code TEXT UNIQUE NOT NULL,
+        items TEXT NOT NULL,
+        subtotal TEXT NOT NULL,
+        discount TEXT NOT NULL DEFAULT '0',
+        subtotal_after_discount TEXT NOT NULL,
+        tax_rate TEXT NOT NULL DEFAULT '0',
+        tax_amount TEXT NOT NULL DEFAULT '0',
+        total TEXT NOT NULL,
+        user_id TEXT,
+        status TEXT NOT NULL DEFAULT 'active',
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        expires_at TEXT,
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_quotations_status ON quotations(status);
+
+    CREATE VIEW pending_sales AS
+        SELECT * FROM sales WHERE status = 'pending' ORDER BY created_at DESC;
+    "#,
+    // 2: sincronización con Supabase (ids remotos, cola de salida y marcas de agua)
+    r#"
+    ALTER TABLE products ADD COLUMN remote_id INTEGER;
+    CREATE UNIQUE INDEX idx_products_remote_id ON products(remote_id);
+    ALTER TABLE customers ADD COLUMN remote_id INTEGER;
+    CREATE UNIQUE INDEX idx_customers_remote_id ON customers(remote_id);
+    ALTER TABLE sales ADD COLUMN remote_id INTEGER;
+    ALTER TABLE quotations ADD COLUMN remote_id INTEGER;
+
+    CREATE TABLE sync_outbox (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        entity TEXT NOT NULL,
+        local_id INTEGER NOT NULL,
+        delta INTEGER,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE UNIQUE INDEX idx_sync_outbox_entity ON sync_outbox(entity, local_id) WHERE entity <> 'stock';
+
+    CREATE TABLE sync_state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    "#,
+    // 3: perfiles de impuestos (IVA/IEPS) por producto e impuestos guardados por partida
+    r#"
+    CREATE TABLE tax_profiles (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE,
+        iva TEXT NOT NULL DEFAULT 'general' CHECK (iva IN ('general', 'frontera', 'cero', 'exento')),
+        ieps_rate TEXT,
+        price_includes_tax INTEGER NOT NULL DEFAULT 1,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    INSERT INTO tax_profiles (name, iva, ieps_rate) VALUES
+        ('IVA 16%', 'general', NULL),
+        ('IVA 8% frontera', 'frontera', NULL),
+        ('IVA tasa 0%', 'cero', NULL),
+        ('Exento de IVA', 'exento', NULL),
+        ('IEPS 8% + IVA 16%', 'general', '8');
+
+    ALTER TABLE products ADD COLUMN tax_profile_id INTEGER REFERENCES tax_profiles(id) ON DELETE SET NULL;
+
+    ALTER TABLE sales ADD COLUMN tax_rounding TEXT NOT NULL DEFAULT 'per_document';
+    ALTER TABLE sale_items ADD COLUMN tax_profile TEXT;
+    ALTER TABLE sale_items ADD COLUMN tax_base TEXT;
+    ALTER TABLE sale_items ADD COLUMN tax_ieps TEXT;
+    ALTER TABLE sale_items ADD COLUMN tax_iva TEXT;
+    "#,
+    // 4: facturación CFDI 4.0 (datos del emisor, claves SAT de productos y datos fiscales de clientes)
+    r#"
+    CREATE TABLE cfdi_settings (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        rfc TEXT NOT NULL DEFAULT '',
+        razon_social TEXT NOT NULL DEFAULT '',
+        regimen_fiscal TEXT NOT NULL DEFAULT '',
+        codigo_postal TEXT NOT NULL DEFAULT '',
+        serie TEXT NOT NULL DEFAULT '',
+        next_folio INTEGER NOT NULL DEFAULT 1,
+        metodo_pago TEXT NOT NULL DEFAULT 'PUE',
+        uso_cfdi TEXT NOT NULL DEFAULT 'G03',
+        enabled INTEGER NOT NULL DEFAULT 0,
+        ask_customer INTEGER NOT NULL DEFAULT 1,
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    INSERT INTO cfdi_settings (id) VALUES (1);
+
+    ALTER TABLE products ADD COLUMN sat_product_key TEXT;
+    ALTER TABLE products ADD COLUMN sat_unit_key TEXT;
+    ALTER TABLE customers ADD COLUMN tax_regime TEXT;
+    ALTER TABLE customers ADD COLUMN cfdi_use TEXT;
+    "#,
+    // 5: certificado de sello digital (la contraseña vive en el llavero del sistema)
+    r#"
+    CREATE TABLE cfdi_csd (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        cer BLOB NOT NULL,
+        key BLOB NOT NULL,
+        numero TEXT NOT NULL,
+        rfc TEXT NOT NULL,
+        valid_from TEXT NOT NULL,
+        valid_to TEXT NOT NULL,
+        imported_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    "#,
+    // 6: CFDI timbrados y PAC configurado (la contraseña del PAC vive en el llavero del sistema)
+    r#"
+    CREATE TABLE invoices (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        sale_id INTEGER REFERENCES sales(id) ON DELETE SET NULL,
+        tipo_de_comprobante TEXT NOT NULL DEFAULT 'I',
+        serie TEXT,
+        folio TEXT,
+        uuid TEXT UNIQUE NOT NULL,
+        receptor_rfc TEXT NOT NULL,
+        total TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'vigente'
+            CHECK (status IN ('vigente', 'en_proceso', 'cancelado')),
+        xml TEXT NOT NULL,
+        pac TEXT NOT NULL,
+        stamped_at TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_invoices_sale_id ON invoices(sale_id);
+
+    CREATE TABLE pac_settings (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        provider TEXT NOT NULL DEFAULT 'mock',
+        username TEXT NOT NULL DEFAULT '',
+        sandbox INTEGER NOT NULL DEFAULT 1
+    );
+    INSERT INTO pac_settings (id) VALUES (1);
+    "#,
+    // 7: cancelación de CFDI (motivo, UUID que sustituye y acuse del SAT)
+    r#"
+    ALTER TABLE invoices ADD COLUMN cancel_motivo TEXT;
+    ALTER TABLE invoices ADD COLUMN folio_sustitucion TEXT;
+    ALTER TABLE invoices ADD COLUMN cancel_detail TEXT;
+    ALTER TABLE invoices ADD COLUMN cancel_requested_at TEXT;
+    ALTER TABLE invoices ADD COLUMN cancelled_at TEXT;
+    ALTER TABLE invoices ADD COLUMN acuse TEXT;
+    CREATE INDEX idx_invoices_status ON invoices(status);
+    "#,
+    // 8: ventas incluidas en cada CFDI (la factura global agrupa muchos tickets)
+    r#"
+    CREATE TABLE invoice_sales (
+        invoice_id INTEGER NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+        sale_id INTEGER NOT NULL REFERENCES sales(id) ON DELETE CASCADE,
+        PRIMARY KEY (invoice_id, sale_id)
+    );
+    CREATE INDEX idx_invoice_sales_sale_id ON invoice_sales(sale_id);
+    INSERT INTO invoice_sales (invoice_id, sale_id) SELECT id, sale_id FROM invoices WHERE sale_id IS NOT NULL;
+    "#,
+    // 9: pagos a facturas PPD y su CFDI de pagos (REP)
+    r#"
+    CREATE TABLE invoice_payments (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        invoice_id INTEGER NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+        num_parcialidad INTEGER NOT NULL,
+        fecha_pago TEXT NOT NULL,
+        forma_pago TEXT NOT NULL,
+        monto TEXT NOT NULL,
+        num_operacion TEXT,
+        saldo_anterior TEXT NOT NULL,
+        saldo_insoluto TEXT NOT NULL,
+        rep_invoice_id INTEGER REFERENCES invoices(id) ON DELETE SET NULL,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        UNIQUE (invoice_id, num_parcialidad)
+    );
+    "#,
+    // 10: autofactura desde el ticket (llave local para el código de verificación)
+    r#"
+    ALTER TABLE cfdi_settings ADD COLUMN autofactura_secret BLOB;
+    ALTER TABLE cfdi_settings ADD COLUMN autofactura_url TEXT NOT NULL DEFAULT '';
+    ALTER TABLE cfdi_settings ADD COLUMN autofactura_days INTEGER NOT NULL DEFAULT 30;
+    "#,
+    // 11: mapeos de columnas guardados para importar catálogos (CSV / Excel)
+    r#"
+    CREATE TABLE import_mappings (
+        name TEXT PRIMARY KEY,
+        mapping TEXT NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    "#,
+    // 12: códigos del proveedor confirmados como alias de productos propios
+    r#"
+    CREATE TABLE supplier_aliases (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        supplier_rfc TEXT NOT NULL,
+        supplier_code TEXT NOT NULL,
+        product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+        description TEXT,
+        confirmations INTEGER NOT NULL DEFAULT 1,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        UNIQUE (supplier_rfc, supplier_code)
+    );
+    CREATE INDEX idx_supplier_aliases_product_id ON supplier_aliases(product_id);
+    "#,
+    // 13: compras recibidas (entrada de mercancía) y costo promedio por producto
+    r#"
+    ALTER TABLE products ADD COLUMN average_cost TEXT NOT NULL DEFAULT '0';
+    UPDATE products SET average_cost = cost;
+
+    CREATE TABLE purchases (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        supplier_rfc TEXT,
+        supplier_name TEXT,
+        invoice_uuid TEXT UNIQUE,
+        folio TEXT,
+        invoice_date TEXT,
+        total TEXT NOT NULL,
+        user_id TEXT,
+        notes TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_purchases_supplier_rfc ON purchases(supplier_rfc);
+
+    CREATE TABLE purchase_items (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        purchase_id INTEGER NOT NULL REFERENCES purchases(id) ON DELETE CASCADE,
+        product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE RESTRICT,
+        supplier_code TEXT,
+        description TEXT,
+        quantity INTEGER NOT NULL,
+        unit_cost TEXT NOT NULL,
+        subtotal TEXT NOT NULL,
+        average_cost TEXT NOT NULL
+    );
+    CREATE INDEX idx_purchase_items_purchase_id ON purchase_items(purchase_id);
+    CREATE INDEX idx_purchase_items_product_id ON purchase_items(product_id);
+    "#,
+    // 14: kardex de inventario (solo se agregan renglones); arranca con la existencia actual
+    r#"
+    CREATE TABLE inventory_movements (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        product_id INTEGER NOT NULL,
+        movement_type TEXT NOT NULL
+            CHECK (movement_type IN ('sale', 'return', 'purchase', 'adjustment', 'transfer', 'cancellation')),
+        quantity INTEGER NOT NULL,
+        balance INTEGER NOT NULL,
+        reference_type TEXT,
+        reference_id INTEGER,
+        reference TEXT,
+        user_id TEXT,
+        notes TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    CREATE INDEX idx_inventory_movements_product_id ON inventory_movements(product_id, id);
+    CREATE INDEX idx_inventory_movements_reference ON inventory_movements(reference_type, reference_id);
+    CREATE TRIGGER inventory_movements_no_update BEFORE UPDATE ON inventory_movements
+    BEGIN
+        SELECT RAISE(ABORT, 'el kardex no se modifica; registra un ajuste');
+    END;
+    CREATE TRIGGER inventory_movements_no_delete BEFORE DELETE ON inventory_movements
+    BEGIN
+        SELECT RAISE(ABORT, 'el kardex no se borra; registra un ajuste');
+    END;
+    INSERT INTO inventory_movements (product_id, movement_type, quantity, balance, reference_type, notes)
+    SELECT id, 'adjustment', stock, stock, 'opening', 'Existencia inicial' FROM products WHERE stock <> 0;
+    "#,
+    // 15: conteo físico por sesiones; `expected` es la existencia al abrir
+    r#"
+    CREATE TABLE stocktakes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        category TEXT,
+        status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'closed', 'cancelled')),
+        opened_by TEXT,
+        closed_by TEXT,
+        notes TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        closed_at TEXT
+    );
+    CREATE TABLE stocktake_items (
+        stocktake_id INTEGER NOT NULL REFERENCES stocktakes(id) ON DELETE CASCADE,
+        product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+        expected INTEGER NOT NULL,
+        PRIMARY KEY (stocktake_id, product_id)
+    );
+    CREATE TABLE stocktake_counts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        stocktake_id INTEGER NOT NULL,
+        product_id INTEGER NOT NULL,
+        quantity INTEGER NOT NULL,
+        counter TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        FOREIGN KEY (stocktake_id, product_id) REFERENCES stocktake_items(stocktake_id, product_id) ON DELETE CASCADE
+    );
+    CREATE INDEX idx_stocktake_counts_item ON stocktake_counts(stocktake_id, product_id);
+    "#,
+    // 16: unidades de medida y cantidades con decimales. SQLite no cambia el tipo de una columna:
+    // existencias y partidas siguen en sus columnas INTEGER, que guardan REAL si hay decimales.
+    r#"
+    ALTER TABLE products ADD COLUMN unit TEXT NOT NULL DEFAULT 'pieza'
+        CHECK (unit IN ('pieza', 'kg', 'g', 'm', 'l'));
+    ALTER TABLE products ADD COLUMN quantity_precision INTEGER CHECK (quantity_precision BETWEEN 0 AND 3);
+    ALTER TABLE sale_items ADD COLUMN unit TEXT;
+    "#,
+    // 17: ventas apartadas mientras se timbra su CFDI (dos solicitudes no facturan la misma venta)
+    r#"
+    CREATE TABLE invoice_reservations (
+        sale_id INTEGER PRIMARY KEY REFERENCES sales(id) ON DELETE CASCADE,
+        reserved_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+    "#,
+    // 18: último movimiento del kardex al registrar cada lectura del conteo físico; lo que se
+    // mueve después se suma a lo contado. Las lecturas anteriores se ubican por su `created_at`.
+    r#"
+    ALTER TABLE stocktake_counts ADD COLUMN ledger_id INTEGER;
+    "#,
+];
+
+/// Conexión compartida a la base local. Se registra como estado de Tauri en `run()`.
+pub struct Db {
+    conn: Mutex<Connection>,
+}
+
+impl Db {
+    /// Abre (o crea) la base en `path` y aplica las migraciones pendientes.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("No se pudo crear {}: {}", dir.display(), e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("abrir base local: {}", e))?;
+        Self::init(conn)
+    }
+
+    /// Base en memoria, útil para pruebas y para el modo demo.
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| format!("abrir base local: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self, String> {
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")
+            .map_err(|e| format!("configurar base local: {}", e))?;
+        migrate(&mut conn)?;
+        // Al arrancar no hay timbrado en curso: lo apartado por una sesión que se cerró se libera.
+        conn.execute("DELETE FROM invoice_reservations", [])
+            .map_err(|e| format!("configurar base local: {}", e))?;
+        Ok(Db { conn: Mutex::new(conn) })
+    }
+
+    /// Ejecuta `f` con la conexión bloqueada. Los errores de SQLite se convierten a texto.
+    pub fn with_conn<T>(&self, f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+        let mut conn = self.lock()?;
+        f(&mut conn).map_err(|e| format!("base local: {}", e))
+    }
+
+    /// Bloquea la conexión para operaciones que mezclan errores de SQLite y de validación.
+    pub(crate) fn lock(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|_| "base local bloqueada".to_string())
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let current: usize = conn
+        .query_row("PRAGMA user_version", [], |r| r.get::<_, i64>(0))
+        .map_err(|e| format!("leer versión de esquema: {}", e))? as usize;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = i + 1;
+        let tx = conn.transaction().map_err(|e| format!("migración {}: {}", version, e))?;
+        tx.execute_batch(sql).map_err(|e| format!("migración {}: {}", version, e))?;
+        tx.pragma_update(None, "user_version", version as i64)
+            .map_err(|e| format!("migración {}: {}", version, e))?;
+        tx.commit().map_err(|e| format!("migración {}: {}", version, e))?;
+        log::info!("db: migración {} aplicada", version);
+    }
+    Ok(())
+}
+
+/// Lee una columna TEXT con un importe decimal.
+pub(crate) fn get_decimal(row: &Row, col: &str) -> rusqlite::Result<Decimal> {
+    let raw: String = row.get(col)?;
+    Decimal::from_str(raw.trim()).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// Lee una columna TEXT opcional con un importe decimal.
+pub(crate) fn get_decimal_opt(row: &Row, col: &str) -> rusqlite::Result<Option<Decimal>> {
+    let raw: Option<String> = row.get(col)?;
+    Ok(raw.and_then(|s| Decimal::from_str(s.trim()).ok()))
+}
+
+/// Lee una cantidad (existencia, partida, movimiento): INTEGER en piezas, REAL con decimales.
+pub(crate) fn get_quantity<I: RowIndex>(row: &Row, col: I) -> rusqlite::Result<Decimal> {
+    let conversion = |ty, e: Box<dyn std::error::Error + Send + Sync>| rusqlite::Error::FromSqlConversionFailure(0, ty, e);
+    let value = match row.get_ref(col)? {
+        ValueRef::Null => Decimal::ZERO,
+        ValueRef::Integer(i) => Decimal::from(i),
+        ValueRef::Real(f) => Decimal::try_from(f).map_err(|e| conversion(rusqlite::types::Type::Real, Box::new(e)))?,
+        ValueRef::Text(t) => String::from_utf8_lossy(t)
+            .trim()
+            .parse()
+            .map_err(|e: rust_decimal::Error| conversion(rusqlite::types::Type::Text, Box::new(e)))?,
+        ValueRef::Blob(_) => return Err(conversion(rusqlite::types::Type::Blob, "cantidad inválida".into())),
+    };
+    Ok(units::normalize(value))
+}
+
+/// Cantidad opcional (NULL = sin valor, p. ej. un producto que nadie ha contado).
+pub(crate) fn get_quantity_opt<I: RowIndex + Copy>(row: &Row, col: I) -> rusqlite::Result<Option<Decimal>> {
+    match row.get_ref(col)? {
+        ValueRef::Null => Ok(None),
+        _ => get_quantity(row, col).map(Some),
+    }
+}
+
+/// Valor para guardar una cantidad: entero si no lleva decimales (así quedan las piezas de siempre).
+pub(crate) fn quantity_value(quantity: Decimal) -> Value {
+    let quantity = units::normalize(quantity);
+    match quantity.fract().is_zero().then(|| quantity.to_i64()).flatten() {
+        Some(whole) => Value::Integer(whole),
+        None => Value::Real(quantity.to_f64().unwrap_or_default()),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Product {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub code: String,
+    pub barcode: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub price: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub cost: Decimal,
+    /// Costo promedio ponderado; lo actualizan las compras recibidas (`purchases`).
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub average_cost: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub stock: Decimal,
+    /// Unidad en que se vende y se cuenta (pieza, kg, g, m, l).
+    #[serde(default)]
+    pub unit: Unit,
+    /// Decimales que admite la cantidad; sin valor, los de la unidad.
+    #[serde(default)]
+    pub quantity_precision: Option<u32>,
+    pub category: Option<String>,
+    pub supplier: Option<String>,
+    pub minimum_stock: Option<i64>,
+    pub image_url: Option<String>,
+    /// Perfil de impuestos; sin perfil se usa la tasa global de Configuración.
+    #[serde(default)]
+    pub tax_profile_id: Option<i64>,
+    /// Clave de producto o servicio del SAT (c_ClaveProdServ); sin clave se factura como 01010101.
+    #[serde(default)]
+    pub sat_product_key: Option<String>,
+    /// Clave de unidad del SAT (c_ClaveUnidad); sin clave se toma la de `unit` (H87 para pieza).
+    #[serde(default)]
+    pub sat_unit_key: Option<String>,
+    #[serde(default)]
+    pub last_sale_date: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Product {
+    /// Decimales que admite la cantidad de este producto.
+    pub fn precision(&self) -> u32 {
+        self.quantity_precision.unwrap_or_else(|| self.unit.default_precision()).min(units::QUANTITY_DP)
+    }
+
+    pub(crate) fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Product {
+            id: row.get("id")?,
+            code: row.get("code")?,
+            barcode: row.get("barcode")?,
+            name: row.get("name")?,
+            description: row.get("description")?,
+            price: get_decimal(row, "price")?,
+            cost: get_decimal(row, "cost")?,
+            average_cost: get_decimal(row, "average_cost")?,
+            stock: get_quantity(row, "stock")?,
+            unit: Unit::from_db(row.get::<_, Option<String>>("unit")?.as_deref()),
+            quantity_precision: row.get("quantity_precision")?,
+            category: row.get("category")?,
+            supplier: row.get("supplier")?,
+            minimum_stock: row.get("minimum_stock")?,
+            image_url: row.get("image_url")?,
+            tax_profile_id: row.get("tax_profile_id")?,
+            sat_product_key: row.get("sat_product_key")?,
+            sat_unit_key: row.get("sat_unit_key")?,
+            last_sale_date: row.get("last_sale_date")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Customer {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub phone: Option<String>,
+    pub name: Option<String>,
+    pub last_name: Option<String>,
+    pub rfc: Option<String>,
+    pub email: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub postal_code: Option<String>,
+    #[serde(default)]
+    pub pending_csf: bool,
+    /// Régimen fiscal del cliente (c_RegimenFiscal), como aparece en su constancia.
+    #[serde(default)]
+    pub tax_regime: Option<String>,
+    /// Uso CFDI habitual del cliente (c_UsoCFDI).
+    #[serde(default)]
+    pub cfdi_use: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Customer {
+    pub(crate) fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Customer {
+            id: row.get("id")?,
+            phone: row.get("phone")?,
+            name: row.get("name")?,
+            last_name: row.get("last_name")?,
+            rfc: row.get("rfc")?,
+            email: row.get("email")?,
+            address: row.get("address")?,
+            city: row.get("city")?,
+            state: row.get("state")?,
+            postal_code: row.get("postal_code")?,
+            pending_csf: row.get("pending_csf")?,
+            tax_regime: row.get("tax_regime")?,
+            cfdi_use: row.get("cfdi_use")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaleItem {
+    #[serde(default)]
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub sale_id: Option<i64>,
+    pub product_id: i64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+    /// Unidad del producto al momento de la venta.
+    #[serde(default)]
+    pub unit: Unit,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unit_price: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub subtotal: Decimal,
+    /// Perfil de impuestos con el que se vendió (copia, por si después cambia el del producto).
+    #[serde(default)]
+    pub tax_profile: Option<TaxProfile>,
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub tax_base: Option<Decimal>,
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub tax_ieps: Option<Decimal>,
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub tax_iva: Option<Decimal>,
+}
+
+impl SaleItem {
+    pub(crate) fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(SaleItem {
+            id: row.get("id")?,
+            sale_id: row.get("sale_id")?,
+            product_id: row.get("product_id")?,
+            quantity: get_quantity(row, "quantity")?,
+            unit: Unit::from_db(row.get::<_, Option<String>>("unit")?.as_deref()),
+            unit_price: get_decimal(row, "unit_price")?,
+            subtotal: get_decimal(row, "subtotal")?,
+            tax_profile: row
+                .get::<_, Option<String>>("tax_profile")?
+                .and_then(|p| serde_json::from_str(&p).ok()),
+            tax_base: get_decimal_opt(row, "tax_base")?,
+            tax_ieps: get_decimal_opt(row, "tax_ieps")?,
+            tax_iva: get_decimal_opt(row, "tax_iva")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sale {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub sale_number: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub subtotal: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub discount: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub tax: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total: Decimal,
+    pub payment_method: Option<String>,
+    #[serde(default = "default_receipt_type")]
+    pub receipt_type: String,
+    pub customer_id: Option<i64>,
+    pub user_id: Option<String>,
+    pub notes: Option<String>,
+    #[serde(default = "default_sale_status")]
+    pub status: String,
+    /// Regla de redondeo con la que se calcularon los impuestos (para reimprimir el desglose igual).
+    #[serde(default)]
+    pub tax_rounding: TaxRounding,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub sale_items: Vec<SaleItem>,
+}
+
+fn default_receipt_type() -> String {
+    "ticket".to_string()
+}
+
+fn default_sale_status() -> String {
+    "completed".to_string()
+}
+
+impl Sale {
+    pub(crate) fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Sale {
+            id: row.get("id")?,
+            sale_number: row.get("sale_number")?,
+            subtotal: get_decimal(row, "subtotal")?,
+            discount: get_decimal(row, "discount")?,
+            tax: get_decimal(row, "tax")?,
+            total: get_decimal(row, "total")?,
+            payment_method: row.get("payment_method")?,
+            receipt_type: row.get("receipt_type")?,
+            customer_id: row.get("customer_id")?,
+            user_id: row.get("user_id")?,
+            notes: row.get("notes")?,
+            status: row.get("status")?,
+            tax_rounding: if row.get::<_, String>("tax_rounding")? == "per_line" {
+                TaxRounding::PerLine
+            } else {
+                TaxRounding::PerDocument
+            },
+            created_at: row.get("created_at")?,
+            sale_items: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quotation {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub quotation_code: String,
+    pub items: serde_json::Value,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub subtotal: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub discount: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub subtotal_after_discount: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub tax_rate: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub tax_amount: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total: Decimal,
+    pub user_id: Option<String>,
+    #[serde(default = "default_quotation_status")]
+    pub status: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+fn default_quotation_status() -> String {
+    "active".to_string()
+}
+
+impl Quotation {
+    pub(crate) fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let items: String = row.get("items")?;
+        Ok(Quotation {
+            id: row.get("id")?,
+            quotation_code: row.get("quotation_code")?,
+            items: serde_json::from_str(&items).unwrap_or(serde_json::Value::Array(Vec::new())),
+            subtotal: get_decimal(row, "subtotal")?,
+            discount: get_decimal(row, "discount")?,
+            subtotal_after_discount: get_decimal(row, "subtotal_after_discount")?,
+            tax_rate: get_decimal(row, "tax_rate")?,
+            tax_amount: get_decimal(row, "tax_amount")?,
+            total: get_decimal(row, "total")?,
+            user_id: row.get("user_id")?,
+            status: row.get("status")?,
+            created_at: row.get("created_at")?,
+            expires_at: row.get("expires_at")?,
+        })
+    }
+}
+
+// ---------- Productos ----------
+
+pub(crate) fn list_products(conn: &Connection, search: Option<&str>, limit: i64) -> rusqlite::Result<Vec<Product>> {
+    let pattern = format!("%{}%", search.unwrap_or("").trim());
+    let mut stmt = conn.prepare(
+        "SELECT * FROM products
+         WHERE name LIKE ?1 OR code LIKE ?1 OR IFNULL(barcode, '') LIKE ?1
+         ORDER BY name LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![pattern, limit], Product::from_row)?;
+    rows.collect()
+}
+
+pub(crate) fn get_product(conn: &Connection, id: i64) -> rusqlite::Result<Option<Product>> {
+    conn.query_row("SELECT * FROM products WHERE id = ?1", [id], Product::from_row)
+        .optional()
+}
+
+/// Busca por código interno o código de barras (lo que manda el escáner).
+pub(crate) fn find_product_by_code(conn: &Connection, code: &str) -> rusqlite::Result<Option<Product>> {
+    conn.query_row(
+        "SELECT * FROM products WHERE code = ?1 OR barcode = ?1 ORDER BY code = ?1 DESC LIMIT 1",
+        [code.trim()],
+        Product::from_row,
+    )
+    .optional()
+}
+
+/// Inserta o actualiza (si trae `id`) un producto y devuelve la fila guardada. Escribe también
+/// el kardex y la cola de sincronización: llamarla dentro de una transacción.
+pub(crate) fn save_product(conn: &Connection, p: &Product) -> rusqlite::Result<Product> {
+    let id = match p.id {
+        Some(id) => {
+            // Sin la fila no se escribe nada: ni movimiento en el kardex ni delta para el servidor.
+            let old_stock = conn.query_row("SELECT stock FROM products WHERE id = ?1", [id], |r| get_quantity(r, 0))?;
+            let stock_change = p.stock - old_stock;
+            conn.execute(
+                "UPDATE products SET code = ?2, barcode = ?3, name = ?4, description = ?5, price = ?6,
+                     cost = ?7, stock = ?8, category = ?9, supplier = ?10, minimum_stock = ?11,
+                     image_url = ?12, tax_profile_id = ?13, sat_product_key = ?14, sat_unit_key = ?15,
+                     unit = ?16, quantity_precision = ?17, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                 WHERE id = ?1",
+                params![
+                    id, p.code, p.barcode, p.name, p.description, p.price.to_string(), p.cost.to_string(),
+                    quantity_value(p.stock), p.category, p.supplier, p.minimum_stock, p.image_url, p.tax_profile_id,
+                    p.sat_product_key, p.sat_unit_key, p.unit.as_str(), p.quantity_precision
+                ],
+            )?;
+            let edit = NewMovement {
+                reference_type: Some("product"),
+                ..NewMovement::new(id, MovementType::Adjustment, stock_change)
+            };
+            inventory::log_movement(conn, &edit.notes(Some("Existencia editada en el producto")))?;
+            // El cambio de stock se sube como delta (al ligarse el producto, si aún no existe allá).
+            crate::sync::enqueue_stock_delta(conn, id, stock_change)?;
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO products (code, barcode, name, description, price, cost, average_cost, stock,
+                     category, supplier, minimum_stock, image_url, tax_profile_id, sat_product_key, sat_unit_key,
+                     unit, quantity_precision)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                params![
+                    p.code, p.barcode, p.name, p.description, p.price.to_string(), p.cost.to_string(),
+                    quantity_value(p.stock), p.category, p.supplier, p.minimum_stock, p.image_url, p.tax_profile_id,
+                    p.sat_product_key, p.sat_unit_key, p.unit.as_str(), p.quantity_precision
+                ],
+            )?;
+            let id = conn.last_insert_rowid();
+            let opening = NewMovement {
+                reference_type: Some("opening"),
+                ..NewMovement::new(id, MovementType::Adjustment, p.stock)
+            };
+            inventory::log_movement(conn, &opening.notes(Some("Existencia inicial")))?;
+            // Se da de alta en el servidor antes que las ventas que lo usen (la cola va en orden).
+            crate::sync::enqueue(conn, "product", id)?;
+            id
+        }
+    };
+    conn.query_row("SELECT * FROM products WHERE id = ?1", [id], Product::from_row)
+}
+
+// ---------- Clientes ----------
+
+pub(crate) fn save_customer(conn: &Connection, c: &Customer) -> rusqlite::Result<Customer> {
+    let id = match c.id {
+        Some(id) => {
+            conn.execute(
+                "UPDATE customers SET phone = ?2, name = ?3, last_name = ?4, rfc = ?5, email = ?6,
+                     address = ?7, city = ?8, state = ?9, postal_code = ?10, pending_csf = ?11,
+                     tax_regime = ?12, cfdi_use = ?13, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                 WHERE id = ?1",
+                params![
+                    id, c.phone, c.name, c.last_name, c.rfc, c.email, c.address, c.city, c.state,
+                    c.postal_code, c.pending_csf, c.tax_regime, c.cfdi_use
+                ],
+            )?;
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO customers (phone, name, last_name, rfc, email, address, city, state,
+                     postal_code, pending_csf, tax_regime, cfdi_use)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    c.phone, c.name, c.last_name, c.rfc, c.email, c.address, c.city, c.state,
+                    c.postal_code, c.pending_csf, c.tax_regime, c.cfdi_use
+                ],
+            )?;
+            conn.last_insert_rowid()
+        }
+    };
+    conn.query_row("SELECT * FROM customers WHERE id = ?1", [id], Customer::from_row)
+}
+
+pub(crate) fn get_customer(conn: &Connection, id: i64) -> rusqlite::Result<Option<Customer>> {
+    conn.query_row("SELECT * FROM customers WHERE id = ?1", [id], Customer::from_row)
+        .optional()
+}
+
+// ---------- Ventas ----------
+
+pub(crate) fn get_sale(conn: &Connection, id: i64) -> rusqlite::Result<Option<Sale>> {
+    let sale = conn
+        .query_row("SELECT * FROM sales WHERE id = ?1", [id], Sale::from_row)
+        .optional()?;
+    match sale {
+        Some(mut sale) => {
+            sale.sale_items = sale_items(conn, id)?;
+            Ok(Some(sale))
+        }
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn sale_items(conn: &Connection, sale_id: i64) -> rusqlite::Result<Vec<SaleItem>> {
+    let mut stmt = conn.prepare("SELECT * FROM sale_items WHERE sale_id = ?1 ORDER BY id")?;
+    let rows = stmt.query_map([sale_id], SaleItem::from_row)?;
+    rows.collect()
+}
+
+/// Inserta la venta y sus partidas tal cual vienen (no toca inventario).
+pub(crate) fn insert_sale(conn: &Connection, s: &Sale) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO sales (sale_number, subtotal, discount, tax, total, payment_method, receipt_type,
+             customer_id, user_id, notes, status, tax_rounding)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            s.sale_number, s.subtotal.to_string(), s.discount.to_string(), s.tax.to_string(),
+            s.total.to_string(), s.payment_method, s.receipt_type, s.customer_id, s.user_id, s.notes,
+            s.status,
+            match s.tax_rounding {
+                TaxRounding::PerLine => "per_line",
+                TaxRounding::PerDocument => "per_document",
+            }
+        ],
+    )?;
+    let sale_id = conn.last_insert_rowid();
+    for item in &s.sale_items {
+        conn.execute(
+            "INSERT INTO sale_items (sale_id, product_id, quantity, unit_price, subtotal, tax_profile,
+                 tax_base, tax_ieps, tax_iva, unit)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                sale_id, item.product_id, quantity_value(item.quantity), item.unit_price.to_string(),
+                item.subtotal.to_string(), item.tax_profile.as_ref().and_then(|p| serde_json::to_string(p).ok()),
+                item.tax_base.map(|d| d.to_string()), item.tax_ieps.map(|d| d.to_string()),
+                item.tax_iva.map(|d| d.to_string()), item.unit.as_str()
+            ],
+        )?;
+    }
+    Ok(sale_id)
+}
+
+// ---------- Comandos Tauri ----------
+
+#[tauri::command]
+pub fn db_list_products(db: State<'_, Db>, search: Option<String>, limit: Option<i64>) -> Result<Vec<Product>, String> {
+    db.with_conn(|conn| list_products(conn, search.as_deref(), limit.unwrap_or(500)))
+}
+
+#[tauri::command]
+pub fn db_get_product(db: State<'_, Db>, id: i64) -> Result<Option<Product>, String> {
+    db.with_conn(|conn| get_product(conn, id))
+}
+
+#[tauri::command]
+pub fn db_find_product_by_code(db: State<'_, Db>, code: String) -> Result<Option<Product>, String> {
+    db.with_conn(|conn| find_product_by_code(conn, &code))
+}
+
+#[tauri::command]
+pub fn db_save_product(db: State<'_, Db>, product: Product) -> Result<Product, String> {
+    if product.code.trim().is_empty() || product.name.trim().is_empty() {
+        return Err("El producto necesita código y nombre.".to_string());
+    }
+    units::check_precision(product.stock, product.unit, product.precision(), &product.name)?;
+    let sql = |e: rusqlite::Error| match e {
+        rusqlite::Error::QueryReturnedNoRows => format!("No se encontró el producto {}.", product.id.unwrap_or_default()),
+        e => format!("base local: {}", e),
+    };
+    let mut conn = db.lock()?;
+    let tx = conn.transaction().map_err(sql)?;
+    let saved = save_product(&tx, &product).map_err(sql)?;
+    tx.commit().map_err(sql)?;
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn db_delete_product(db: State<'_, Db>, id: i64) -> Result<(), String> {
+    db.with_conn(|conn| conn.execute("DELETE FROM products WHERE id = ?1", [id]).map(|_| ()))
+}
+
+#[tauri::command]
+pub fn db_list_customers(db: State<'_, Db>, search: Option<String>) -> Result<Vec<Customer>, String> {
+    let pattern = format!("%{}%", search.unwrap_or_default().trim());
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT * FROM customers
+             WHERE IFNULL(name, '') || ' ' || IFNULL(last_name, '') LIKE ?1
+                OR IFNULL(phone, '') LIKE ?1 OR IFNULL(rfc, '') LIKE ?1
+             ORDER BY name LIMIT 200",
+        )?;
+        let rows = stmt.query_map([pattern], Customer::from_row)?;
+        rows.collect()
+    })
+}
+
+#[tauri::command]
+pub fn db_get_customer(db: State<'_, Db>, id: i64) -> Result<Option<Customer>, String> {
+    db.with_conn(|conn| get_customer(conn, id))
+}
+
+#[tauri::command]
+pub fn db_save_customer(db: State<'_, Db>, customer: Customer) -> Result<Customer, String> {
+    db.with_conn(|conn| save_customer(conn, &customer))
+}
+
+#[tauri::command]
+pub fn db_delete_customer(db: State<'_, Db>, id: i64) -> Result<(), String> {
+    db.with_conn(|conn| conn.execute("DELETE FROM customers WHERE id = ?1", [id]).map(|_| ()))
+}
+
+/// Lista ventas recientes, opcionalmente por estado (`pending`, `completed`, `cancelled`).
+#[tauri::command]
+pub fn db_list_sales(db: State<'_, Db>, status: Option<String>, limit: Option<i64>) -> Result<Vec<Sale>, String> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT * FROM sales WHERE ?1 IS NULL OR status = ?1 ORDER BY created_at DESC LIMIT ?2",
+        )?;
+        let mut sales = stmt
+            .query_map(params![status, limit.unwrap_or(50)], Sale::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for sale in sales.iter_mut() {
+            if let Some(id) = sale.id {
+                sale.sale_items = sale_items(conn, id)?;
+            }
+        }
+        Ok(sales)
+    })
+}
+
+#[tauri::command]
+pub fn db_list_pending_sales(db: State<'_, Db>) -> Result<Vec<Sale>, String> {
+    db_list_sales(db, Some("pending".to_string()), Some(500))
+}
+
+#[tauri::command]
+pub fn db_get_sale(db: State<'_, Db>, id: i64) -> Result<Option<Sale>, String> {
+    db.with_conn(|conn| get_sale(conn, id))
+}
+
+/// Guarda una venta con sus partidas en una sola transacción.
+#[tauri::command]
+pub fn db_save_sale(db: State<'_, Db>, sale: Sale) -> Result<Sale, String> {
+    if sale.sale_items.is_empty() {
+        return Err("La venta no tiene productos.".to_string());
+    }
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let id = insert_sale(&tx, &sale)?;
+        crate::sync::enqueue(&tx, "sale", id)?;
+        tx.commit()?;
+        get_sale(conn, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+    })
+}
+
+#[tauri::command]
+pub fn db_update_sale_status(db: State<'_, Db>, id: i64, status: String) -> Result<(), String> {
+    if !matches!(status.as_str(), "pending" | "completed" | "cancelled") {
+        return Err(format!("Estado de venta inválido: {}", status));
+    }
+    if status == "cancelled" {
+        // Una venta facturada solo se cancela cancelando su CFDI (motivo 03).
+        if let Some(uuid) = db.with_conn(|conn| active_invoice_uuid(conn, id))? {
+            return Err(format!(
+                "La venta tiene la factura {} vigente; cancela el CFDI con motivo 03 para cancelar la venta.",
+                uuid
+            ));
+        }
+    }
+    crate::sales::update_sale_status(&db, id, &status)
+}
+
+/// Factura vigente (o con cancelación en proceso) que ampara la venta.
+fn active_invoice_uuid(conn: &Connection, sale_id: i64) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT i.uuid FROM invoices i JOIN invoice_sales s ON s.invoice_id = i.id
+         WHERE s.sale_id = ?1 AND i.status != 'cancelado' LIMIT 1",
+        [sale_id],
+        |r| r.get(0),
+    )
+    .optional()
+}
+
+/// Borra una venta pendiente o cancelada junto con su fila en la cola de sincronización. Una
+/// venta cobrada ya movió inventario: primero se cancela (`db_update_sale_status`).
+pub(crate) fn delete_sale(conn: &mut Connection, id: i64) -> Result<(), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let tx = conn.transaction().map_err(sql)?;
+    if let Some(uuid) = active_invoice_uuid(&tx, id).map_err(sql)? {
+        return Err(format!("La venta tiene la factura {} vigente; no se puede eliminar.", uuid));
+    }
+    let status: Option<String> = tx
+        .query_row("SELECT status FROM sales WHERE id = ?1", [id], |r| r.get(0))
+        .optional()
+        .map_err(sql)?;
+    if status.as_deref() == Some("completed") {
+        return Err("La venta ya está cobrada; cancélala para regresar el inventario en lugar de eliminarla.".to_string());
+    }
+    tx.execute("DELETE FROM sync_outbox WHERE entity = 'sale' AND local_id = ?1", [id])
+        .map_err(sql)?;
+    tx.execute("DELETE FROM sales WHERE id = ?1", [id]).map_err(sql)?;
+    tx.commit().map_err(sql)
+}
+
+#[tauri::command]
+pub fn db_delete_sale(db: State<'_, Db>, id: i64) -> Result<(), String> {
+    let mut conn = db.lock()?;
+    delete_sale(&mut conn, id)
+}
+
+#[tauri::command]
+pub fn db_list_quotations(db: State<'_, Db>, status: Option<String>) -> Result<Vec<Quotation>, String> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT * FROM quotations WHERE ?1 IS NULL OR status = ?1 ORDER BY created_at DESC LIMIT 200",
+        )?;
+        let rows = stmt.query_map([status], Quotation::from_row)?;
+        rows.collect()
+    })
+}
+
+#[tauri::command]
+pub fn db_get_quotation_by_code(db: State<'_, Db>, code: String) -> Result<Option<Quotation>, String> {
+    db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT * FROM quotations WHERE quotation_code = ?1",
+            [code.trim()],
+            Quotation::from_row,
+        )
+        .optional()
+    })
+}
+
+#[tauri::command]
+pub fn db_save_quotation(db: State<'_, Db>, quotation: Quotation) -> Result<Quotation, String> {
+    let q = quotation;
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO quotations (quotation_code, items, subtotal, discount, subtotal_after_discount,
+                 tax_rate, tax_amount, total, user_id, status, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(quotation_code) DO UPDATE SET
+                 items = excluded.items, subtotal = excluded.subtotal, discount = excluded.discount,
+                 subtotal_after_discount = excluded.subtotal_after_discount, tax_rate = excluded.tax_rate,
+                 tax_amount = excluded.tax_amount, total = excluded.total, status = excluded.status,
+                 expires_at = excluded.expires_at,
+                 updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')",
+            params![
+                q.quotation_code, q.items.to_string(), q.subtotal.to_string(), q.discount.to_string(),
+                q.subtotal_after_discount.to_string(), q.tax_rate.to_string(), q.tax_amount.to_string(),
+                q.total.to_string(), q.user_id, q.status, q.expires_at
+            ],
+        )?;
+        let saved = conn.query_row(
+            "SELECT * FROM quotations WHERE quotation_code = ?1",
+            [&q.quotation_code],
+            Quotation::from_row,
+        )?;
+        if let Some(id) = saved.id {
+            crate::sync::enqueue(conn, "quotation", id)?;
+        }
+        Ok(saved)
+    })
+}
+
+#[tauri::command]
+pub fn db_update_quotation_status(db: State<'_, Db>, code: String, status: String) -> Result<(), String> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE quotations SET status = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+             WHERE quotation_code = ?1",
+            params![code, status],
+        )?;
+        let id: Option<i64> = conn
+            .query_row("SELECT id FROM quotations WHERE quotation_code = ?1", [&code], |r| r.get(0))
+            .optional()?;
+        match id {
+            Some(id) => crate::sync::enqueue(conn, "quotation", id),
+            None => Ok(()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(id: Option<i64>, stock: i64) -> Product {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "code": "C1", "barcode": null, "name": "Cuerda", "description": null, "price": 12,
+            "stock": stock, "category": null, "supplier": null, "minimum_stock": null, "image_url": null,
+        }))
+        .unwrap()
+    }
+
+    fn counts(conn: &Connection) -> (i64, i64) {
+        let movements = conn.query_row("SELECT COUNT(*) FROM inventory_movements", [], |r| r.get(0)).unwrap();
+        let outbox = conn.query_row("SELECT COUNT(*) FROM sync_outbox", [], |r| r.get(0)).unwrap();
+        (movements, outbox)
+    }
+
+    #[test]
+    fn only_uncharged_sales_are_deleted_with_their_outbox_row() {
+        let db = Db::open_in_memory().unwrap();
+        let mut conn = db.lock().unwrap();
+        for (number, status) in [("S-1", "completed"), ("S-2", "pending")] {
+            conn.execute(
+                "INSERT INTO sales (sale_number, subtotal, total, status) VALUES (?1, '10', '10', ?2)",
+                [number, status],
+            )
+            .unwrap();
+            crate::sync::enqueue(&conn, "sale", conn.last_insert_rowid()).unwrap();
+        }
+        let sales = |conn: &Connection| conn.query_row("SELECT COUNT(*) FROM sales", [], |r| r.get::<_, i64>(0)).unwrap();
+
+        assert!(delete_sale(&mut conn, 1).unwrap_err().contains("cancélala"));
+        assert_eq!((sales(&conn), counts(&conn).1), (2, 2));
+
+        delete_sale(&mut conn, 2).unwrap();
+        assert_eq!((sales(&conn), counts(&conn).1), (1, 1));
+    }
+
+    #[test]
+    fn product_edits_log_stock_only_for_existing_rows() {
+        let db = Db::open_in_memory().unwrap();
+        let mut conn = db.lock().unwrap();
+        // El alta queda en cola para subir el producto.
+        let id = save_product(&conn, &product(None, 5)).unwrap().id.unwrap();
+        assert_eq!(counts(&conn), (1, 1));
+
+        let tx = conn.transaction().unwrap();
+        assert!(matches!(save_product(&tx, &product(Some(id + 1), 9)), Err(rusqlite::Error::QueryReturnedNoRows)));
+        drop(tx);
+        assert_eq!(counts(&conn), (1, 1));
+
+        // Aún sin ligar al servidor, el cambio se encola como delta y espera al alta.
+        assert_eq!(save_product(&conn, &product(Some(id), 8)).unwrap().stock, Decimal::from(8));
+        assert_eq!(counts(&conn), (2, 2));
+
+        conn.execute("UPDATE products SET remote_id = 40 WHERE id = ?1", [id]).unwrap();
+        save_product(&conn, &product(Some(id), 6)).unwrap();
+        assert_eq!(counts(&conn), (3, 3));
+    }
+}
@@ -0,0 +1,448 @@
+//! Display de cliente (VFD de 2x20, protocolo Epson DM-D / ESC/POS) por puerto serie o USB-serie.
+//!
+//! El frontend manda qué mostrar (artículo escaneado, total, cambio, mensaje de espera) y aquí
+//! se arman los dos renglones. Cada pantalla se escribe completa: posición del cursor y el
+//! renglón rellenado con espacios, así no quedan restos de la anterior.
+
+use super::transport::{SerialSettings, SerialTransport, Transport};
+use crate::money::Money;
+use crate::units::{self, Unit};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+pub const DISPLAY_COLUMNS: usize = 20;
+
+const ESC: u8 = 0x1B;
+const US: u8 = 0x1F;
+const CLR: u8 = 0x0C;
+const HOME: u8 = 0x0B;
+
+const DEFAULT_IDLE: &str = "Bienvenido";
+
+/// Los dos renglones que ve el cliente.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DisplayLines {
+    pub top: String,
+    pub bottom: String,
+}
+
+// ---------- Contenido ----------
+
+/// Quita acentos y cambia lo que no sea ASCII por `?`; los VFD traen solo la tabla básica.
+pub fn to_display_ascii(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'Á' | 'À' | 'Ä' | 'Â' => 'A',
+            'É' | 'È' | 'Ë' | 'Ê' => 'E',
+            'Í' | 'Ì' | 'Ï' | 'Î' => 'I',
+            'Ó' | 'Ò' | 'Ö' | 'Ô' => 'O',
+            'Ú' | 'Ù' | 'Ü' | 'Û' => 'U',
+            'ñ' => 'n',
+            'Ñ' => 'N',
+            '¿' | '¡' => ' ',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '?',
+        })
+        .collect()
+}
+
+fn fit(text: &str, columns: usize) -> String {
+    let cut: String = to_display_ascii(text.trim()).chars().take(columns).collect();
+    format!("{:<columns$}", cut, columns = columns)
+}
+
+fn center(text: &str, columns: usize) -> String {
+    let text: String = to_display_ascii(text.trim()).chars().take(columns).collect();
+    let pad = (columns - text.len()) / 2;
+    fit(&format!("{}{}", " ".repeat(pad), text), columns)
+}
+
+/// Etiqueta a la izquierda e importe a la derecha; si no caben, gana el importe.
+fn split_line(label: &str, value: &str, columns: usize) -> String {
+    let value: String = value.chars().take(columns).collect();
+    let room = columns.saturating_sub(value.len() + 1);
+    let label: String = to_display_ascii(label.trim()).chars().take(room).collect();
+    format!("{:<w$}{}", label, value, w = columns - value.len())
+}
+
+/// Artículo recién escaneado: nombre arriba; cantidad, precio e importe abajo.
+pub fn item_lines(name: &str, quantity: Decimal, unit: Unit, precision: u32, unit_price: Money, columns: usize) -> DisplayLines {
+    let subtotal = unit_price.times(quantity).to_string();
+    let quantity = units::format(quantity, unit, precision);
+    let detail = format!("{} x {}", quantity, unit_price);
+    // Si el detalle no cabe junto al importe se deja solo la cantidad.
+    let left = if detail.len() + 1 + subtotal.len() <= columns { detail } else { quantity };
+    DisplayLines { top: fit(name, columns), bottom: split_line(&left, &subtotal, columns) }
+}
+
+pub fn total_lines(total: Money, items: Option<usize>, columns: usize) -> DisplayLines {
+    let top = match items {
+        Some(n) => split_line("TOTAL", &format!("{} art.", n), columns),
+        None => fit("TOTAL", columns),
+    };
+    DisplayLines { top, bottom: split_line("", &total.to_string(), columns) }
+}
+
+pub fn change_lines(paid: Money, change: Money, columns: usize) -> DisplayLines {
+    DisplayLines {
+        top: split_line("Pago", &paid.to_string(), columns),
+        bottom: split_line("Cambio", &change.to_string(), columns),
+    }
+}
+
+/// Mensaje de espera centrado; un `\n` o `|` separa los renglones, si no se parte por palabras.
+pub fn idle_lines(message: &str, columns: usize) -> DisplayLines {
+    let message = message.trim();
+    let (top, bottom) = match message.split_once(['\n', '|']) {
+        Some((a, b)) => (a.to_string(), b.to_string()),
+        None if message.chars().count() <= columns => (message.to_string(), String::new()),
+        None => {
+            let mut top = String::new();
+            let mut rest = Vec::new();
+            for word in message.split_whitespace() {
+                if rest.is_empty() && top.chars().count() + word.chars().count() + usize::from(!top.is_empty()) <= columns {
+                    if !top.is_empty() {
+                        top.push(' ');
+                    }
+                    top.push_str(word);
+                } else {
+                    rest.push(word);
+                }
+            }
+            (top, rest.join(" "))
+        }
+    };
+    DisplayLines { top: center(&top, columns), bottom: center(&bottom, columns) }
+}
+
+// ---------- Protocolo DM-D ----------
+
+/// Inicializa, modo sobrescritura (sin desplazamiento) y cursor apagado.
+pub fn init_bytes() -> Vec<u8> {
+    vec![ESC, b'@', US, 0x01, US, b'C', 0, CLR]
+}
+
+/// Escribe los dos renglones completos (`US $ x y` + texto).
+pub fn lines_bytes(lines: &DisplayLines, columns: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(2 * (columns + 4));
+    for (row, text) in [(1u8, &lines.top), (2u8, &lines.bottom)] {
+        out.extend_from_slice(&[US, b'$', 1, row]);
+        out.extend(fit(text, columns).bytes());
+    }
+    out
+}
+
+pub struct PoleDisplay {
+    transport: Box<dyn Transport>,
+    columns: usize,
+}
+
+impl PoleDisplay {
+    pub fn new(transport: Box<dyn Transport>, columns: usize) -> Self {
+        PoleDisplay { transport, columns }
+    }
+
+    pub fn name(&self) -> String {
+        self.transport.name()
+    }
+
+    pub fn init(&mut self) -> Result<(), String> {
+        self.send(&init_bytes())
+    }
+
+    pub fn show(&mut self, lines: &DisplayLines) -> Result<(), String> {
+        self.send(&lines_bytes(lines, self.columns))
+    }
+
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.send(&[CLR])
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<(), String> {
+        self.transport
+            .write_all(data)
+            .map_err(|e| format!("Error de comunicación con el display: {}", e))
+    }
+}
+
+// ---------- Display simulado ----------
+
+/// Pantalla que interpreta los comandos DM-D; sirve para pruebas y para ver en el
+/// frontend lo mismo que vería el cliente.
+#[derive(Clone)]
+pub struct SimulatedDisplay {
+    screen: Arc<Mutex<Vec<Vec<u8>>>>,
+    columns: usize,
+    cursor: (usize, usize),
+    /// Bytes de parámetros que faltan por consumir del último comando.
+    pending: Vec<u8>,
+}
+
+impl SimulatedDisplay {
+    pub fn new(columns: usize) -> Self {
+        SimulatedDisplay {
+            screen: Arc::new(Mutex::new(vec![vec![b' '; columns]; 2])),
+            columns,
+            cursor: (0, 0),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn lines(&self) -> DisplayLines {
+        let screen = self.screen.lock().map(|s| s.clone()).unwrap_or_default();
+        let row = |i: usize| screen.get(i).map(|r| String::from_utf8_lossy(r).to_string()).unwrap_or_default();
+        DisplayLines { top: row(0), bottom: row(1) }
+    }
+
+    fn clear(&mut self) {
+        if let Ok(mut s) = self.screen.lock() {
+            s.iter_mut().for_each(|r| r.fill(b' '));
+        }
+        self.cursor = (0, 0);
+    }
+
+    /// Cuántos bytes lleva el comando que empieza en `cmd` (incluido el prefijo).
+    fn command_len(cmd: &[u8]) -> Option<usize> {
+        match cmd {
+            [ESC] | [US] => None,
+            [ESC, b'@'] | [US, 0x01..=0x03] => Some(2),
+            [ESC, _] | [US, b'C'] | [US, b'X'] => Some(3),
+            [US, b'$'] | [US, b'$', _] => Some(4),
+            [US, ..] => Some(2),
+            _ => Some(cmd.len()),
+        }
+    }
+
+    fn apply(&mut self, cmd: &[u8]) {
+        match cmd {
+            [ESC, b'@'] => self.clear(),
+            [US, b'$', x, y] => {
+                let x = (*x as usize).clamp(1, self.columns) - 1;
+                let y = (*y as usize).clamp(1, 2) - 1;
+                self.cursor = (x, y);
+            }
+            _ => {}
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        match byte {
+            CLR => self.clear(),
+            HOME => self.cursor = (0, 0),
+            b'\r' => self.cursor.0 = 0,
+            b'\n' => self.cursor.1 = 1,
+            b if b >= 0x20 => {
+                let (x, y) = self.cursor;
+                if let Ok(mut s) = self.screen.lock() {
+                    s[y][x] = b;
+                }
+                // Modo sobrescritura: al final del renglón pasa al siguiente.
+                self.cursor = if x + 1 < self.columns { (x + 1, y) } else { (0, (y + 1) % 2) };
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Transport for SimulatedDisplay {
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        for &byte in data {
+            if self.pending.is_empty() && byte != ESC && byte != US {
+                self.put(byte);
+                continue;
+            }
+            self.pending.push(byte);
+            if let Some(n) = Self::command_len(&self.pending) {
+                if self.pending.len() >= n {
+                    let cmd = std::mem::take(&mut self.pending);
+                    self.apply(&cmd);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+
+    fn name(&self) -> String {
+        "simulado".to_string()
+    }
+}
+
+// ---------- Servicio ----------
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DisplayConfig {
+    /// Puerto serie; sin puerto y con `simulated` se usa el display simulado.
+    #[serde(default)]
+    pub serial: Option<SerialSettings>,
+    #[serde(default)]
+    pub simulated: bool,
+    /// Columnas por renglón (20 en la mayoría).
+    #[serde(default)]
+    pub columns: Option<usize>,
+    /// Mensaje de espera; `|` separa los dos renglones.
+    #[serde(default)]
+    pub idle_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DisplayStatus {
+    pub connected: bool,
+    pub port: Option<String>,
+    pub simulated: bool,
+    /// Lo último que se mandó a la pantalla.
+    pub lines: DisplayLines,
+    pub last_error: Option<String>,
+}
+
+struct Connected {
+    display: PoleDisplay,
+    columns: usize,
+    idle_message: String,
+}
+
+/// Estado de Tauri del display de cliente.
+#[derive(Default)]
+pub struct DisplayService {
+    inner: Mutex<Option<Connected>>,
+    status: Mutex<DisplayStatus>,
+}
+
+impl DisplayService {
+    pub fn status(&self) -> DisplayStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    fn set_status(&self, f: impl FnOnce(&mut DisplayStatus)) -> DisplayStatus {
+        match self.status.lock() {
+            Ok(mut s) => {
+                f(&mut s);
+                s.clone()
+            }
+            Err(_) => DisplayStatus::default(),
+        }
+    }
+
+    pub fn connect(&self, config: &DisplayConfig) -> Result<DisplayStatus, String> {
+        let columns = config.columns.unwrap_or(DISPLAY_COLUMNS).clamp(8, 40);
+        let transport: Box<dyn Transport> = match &config.serial {
+            Some(serial) if !serial.port.trim().is_empty() => Box::new(SerialTransport::open(serial)?),
+            _ if config.simulated => Box::new(SimulatedDisplay::new(columns)),
+            _ => return Err("Indique el puerto del display.".to_string()),
+        };
+        let mut display = PoleDisplay::new(transport, columns);
+        display.init()?;
+        let port = display.name();
+        let idle_message = config.idle_message.clone().filter(|m| !m.trim().is_empty()).unwrap_or_else(|| DEFAULT_IDLE.to_string());
+        *self.inner.lock().map_err(|_| "display bloqueado".to_string())? = Some(Connected { display, columns, idle_message });
+        log::info!("display: conectado en {}", port);
+        self.set_status(|s| {
+            *s = DisplayStatus { connected: true, simulated: port == "simulado", port: Some(port), ..Default::default() }
+        });
+        self.idle(None)
+    }
+
+    pub fn disconnect(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            if let Some(c) = inner.as_mut() {
+                let _ = c.display.clear();
+            }
+            *inner = None;
+        }
+        self.set_status(|s| *s = DisplayStatus::default());
+    }
+
+    /// Arma los renglones con las columnas del display conectado y los manda.
+    /// Si el puerto falla el display se da por desconectado.
+    fn show(&self, build: impl FnOnce(usize, &str) -> DisplayLines) -> Result<DisplayStatus, String> {
+        let mut inner = self.inner.lock().map_err(|_| "display bloqueado".to_string())?;
+        let Some(c) = inner.as_mut() else {
+            return Err("No hay display de cliente conectado.".to_string());
+        };
+        let lines = build(c.columns, &c.idle_message);
+        match c.display.show(&lines) {
+            Ok(()) => Ok(self.set_status(|s| s.lines = lines)),
+            Err(e) => {
+                log::warn!("display: {}", e);
+                *inner = None;
+                self.set_status(|s| {
+                    s.connected = false;
+                    s.last_error = Some(e.clone());
+                });
+                Err(e)
+            }
+        }
+    }
+
+    pub fn idle(&self, message: Option<&str>) -> Result<DisplayStatus, String> {
+        self.show(|columns, idle| idle_lines(message.unwrap_or(idle), columns))
+    }
+}
+
+/// Artículo que se acaba de escanear o pesar.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DisplayItem {
+    pub name: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: Decimal,
+    #[serde(default)]
+    pub unit: Unit,
+    /// Decimales de la cantidad; sin valor se usan los de la unidad.
+    #[serde(default)]
+    pub precision: Option<u32>,
+    pub unit_price: Decimal,
+}
+
+fn default_quantity() -> Decimal {
+    Decimal::ONE
+}
+
+// ---------- Comandos Tauri ----------
+
+#[tauri::command]
+pub fn display_connect(service: State<'_, DisplayService>, config: DisplayConfig) -> Result<DisplayStatus, String> {
+    service.connect(&config)
+}
+
+#[tauri::command]
+pub fn display_disconnect(service: State<'_, DisplayService>) {
+    service.disconnect()
+}
+
+#[tauri::command]
+pub fn display_status(service: State<'_, DisplayService>) -> DisplayStatus {
+    service.status()
+}
+
+#[tauri::command]
+pub fn display_item(service: State<'_, DisplayService>, item: DisplayItem) -> Result<DisplayStatus, String> {
+    let precision = item.precision.unwrap_or_else(|| item.unit.default_precision());
+    service.show(|columns, _| item_lines(&item.name, item.quantity, item.unit, precision, Money::mxn(item.unit_price), columns))
+}
+
+#[tauri::command]
+pub fn display_total(service: State<'_, DisplayService>, total: Decimal, items: Option<usize>) -> Result<DisplayStatus, String> {
+    service.show(|columns, _| total_lines(Money::mxn(total), items, columns))
+}
+
+#[tauri::command]
+pub fn display_change(service: State<'_, DisplayService>, paid: Decimal, change: Decimal) -> Result<DisplayStatus, String> {
+    service.show(|columns, _| change_lines(Money::mxn(paid), Money::mxn(change), columns))
+}
+
+/// Mensaje de espera; sin `message` se usa el configurado al conectar.
+#[tauri::command]
+pub fn display_idle(service: State<'_, DisplayService>, message: Option<String>) -> Result<DisplayStatus, String> {
+    service.idle(message.as_deref())
+}
@@ -0,0 +1,14 @@
+//! Periféricos del punto de venta: impresoras por el spooler del sistema y el resto por puerto
+//! serie o USB-serie. Todos escriben a través de `transport::Transport`.
+//!
+//! - `transport`: canal de bytes (puerto serie real o dispositivo simulado) y lista de puertos.
+//! - `printer`: impresoras térmicas de tickets y etiquetas (trabajos crudos ESC/POS).
+//! - `scale`: básculas de mostrador, protocolos Toledo 8217 y CAS continuo.
+//! - `display`: display de cliente VFD de 2x20 (Epson DM-D).
+//! - `scanner`: lectores de código de barras en modo serie o HID (Linux), fuera del webview.
+
+pub mod display;
+pub mod printer;
+pub mod scale;
+pub mod scanner;
+pub mod transport;
@@ -0,0 +1,142 @@
+//! Impresoras térmicas (tickets y etiquetas) a través del spooler del sistema.
+//!
+//! Los bytes ESC/POS ya armados se mandan como trabajo crudo: `lp -o raw` en macOS y Linux
+//! (CUPS) y `raw-printer` en Windows. `SpoolerTransport` expone eso como un `Transport` de
+//! solo escritura, igual que el puerto serie de la báscula o del display.
+
+use super::transport::Transport;
+use std::io;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+/// En Windows: devuelve el nombre de impresora a usar (el dado o la predeterminada). Para enviar bytes crudos sin que PowerShell corrompa el contenido.
+#[cfg(target_os = "windows")]
+pub(crate) fn windows_printer_name(printer_name: &str) -> Result<String, String> {
+    let name = printer_name.trim();
+    if !name.is_empty() {
+        return Ok(name.to_string());
+    }
+    let output = std::process::Command::new("powershell")
+        .creation_flags(0x08000000)
+        .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_Printer -Filter \"Default=$true\").Name"])
+        .output()
+        .map_err(|e| format!("No se pudo obtener impresora: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let default = stdout.trim();
+    if default.is_empty() {
+        return Err("Selecciona una impresora en Configuración o define una predeterminada en Windows.".to_string());
+    }
+    Ok(default.to_string())
+}
+
+/// Cada `write_all` es un trabajo de impresión completo en la cola de la impresora.
+/// Nombre vacío = impresora predeterminada del sistema.
+pub struct SpoolerTransport {
+    printer_name: String,
+    /// Nombre del trabajo en la cola (`Ticket`, `Etiquetas`, ...).
+    job: &'static str,
+}
+
+impl SpoolerTransport {
+    pub fn new(printer_name: &str, job: &'static str) -> Self {
+        SpoolerTransport { printer_name: printer_name.trim().to_string(), job }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn submit(&self, data: &[u8]) -> Result<(), String> {
+        use std::io::Write;
+        use std::process::Command;
+        // En macOS el trabajo va como archivo .bin con formato octet-stream.
+        let file_name = format!(
+            "pos_{}_{}.bin",
+            self.job.to_lowercase(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        );
+        let path = std::env::temp_dir().join(&file_name);
+        let mut file = std::fs::File::create(&path).map_err(|e| format!("Failed to create temp file: {}", e))?;
+        file.write_all(data).map_err(|e| format!("Failed to write print job: {}", e))?;
+        file.sync_all().map_err(|e| format!("Failed to sync file: {}", e))?;
+        drop(file);
+        let path_str = path.to_string_lossy();
+        let mut cmd = Command::new("lp");
+        if !self.printer_name.is_empty() {
+            cmd.arg("-d").arg(&self.printer_name);
+        }
+        let status = cmd
+            .args(["-o", "raw", "-o", "document-format=application/octet-stream", path_str.as_ref()])
+            .status();
+        std::fs::remove_file(&path).ok();
+        let exit_status = status.map_err(|e| format!("lp failed: {}", e))?;
+        if !exit_status.success() {
+            return Err("No se pudo imprimir. En macOS agregue la impresora como Raw en http://localhost:631 (Administration > Add Printer > USB > Make: Raw).".to_string());
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn submit(&self, data: &[u8]) -> Result<(), String> {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+        let mut cmd = Command::new("lp");
+        if !self.printer_name.is_empty() {
+            cmd.arg("-d").arg(&self.printer_name);
+        }
+        let mut child = cmd
+            .args(["-o", "raw"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("lp spawn: {}", e))?;
+        if let Some(ref mut stdin) = child.stdin {
+            stdin.write_all(data).map_err(|e| format!("lp stdin: {}", e))?;
+        }
+        drop(child.stdin.take());
+        let exit_status = child.wait().map_err(|e| format!("lp wait: {}", e))?;
+        if !exit_status.success() {
+            return Err("No se pudo imprimir. En Ajustes del sistema → Impresoras, revisa que la impresora esté “Aceptando trabajos” y que uses controlador Genérico o Raw si está disponible.".to_string());
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn submit(&self, data: &[u8]) -> Result<(), String> {
+        let name = windows_printer_name(&self.printer_name)?;
+        raw_printer::write_to_device(&name, data, Some(self.job))
+            .map(|_| ())
+            .map_err(|e| format!("Error al imprimir en '{}': {}", name, e))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    fn submit(&self, _data: &[u8]) -> Result<(), String> {
+        Err("Impresión no soportada en esta plataforma.".to_string())
+    }
+}
+
+impl Transport for SpoolerTransport {
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.submit(data).map_err(io::Error::other)?;
+        log::info!("impresora: trabajo {} enviado a {} ({} bytes)", self.job, self.name(), data.len());
+        Ok(())
+    }
+
+    /// El spooler no devuelve nada de la impresora.
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+
+    fn name(&self) -> String {
+        if self.printer_name.is_empty() {
+            "predeterminada".to_string()
+        } else {
+            self.printer_name.clone()
+        }
+    }
+}
+
+/// Manda un trabajo ESC/POS a la impresora por el spooler.
+pub fn print_raw(printer_name: &str, job: &'static str, data: &[u8]) -> Result<(), String> {
+    SpoolerTransport::new(printer_name, job).write_all(data).map_err(|e| e.to_string())
+}
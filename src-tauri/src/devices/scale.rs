@@ -0,0 +1,702 @@
+//! Básculas de mostrador (Torrey, CAS, Mettler Toledo) por puerto serie o USB-serie.
+//!
+//! Dos protocolos cubren a la mayoría:
+//! - `toledo`: Mettler Toledo 8217, bajo demanda. Se manda `W` y la báscula responde
+//!   `STX 01.234 CR`; en movimiento o fuera de rango responde `STX ? <estado> CR`.
+//!   Torrey y CAS lo traen como modo "Toledo" o "ECR". Suele ir a 9600 7E1.
+//! - `cas`: transmisión continua de renglones `ST,GS,+  1.234kg` (`US` = inestable,
+//!   `OL` = sobrecarga). También se aceptan renglones sin encabezado (`  1.234 kg`).
+//!
+//! Las lecturas siempre van en kg. Un hilo lee la báscula y emite `scale-weight` cada vez
+//! que cambia el peso o su estado; `scale_read` espera una lectura estable para cobrar.
+
+use super::transport::{self, SerialSettings, SerialTransport, Transport};
+use crate::units;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Evento con cada lectura nueva de la báscula (`WeightReading`).
+pub const SCALE_WEIGHT_EVENT: &str = "scale-weight";
+/// Evento al conectar, desconectar o perder la báscula (`ScaleStatus`).
+pub const SCALE_STATUS_EVENT: &str = "scale-status";
+
+const STX: u8 = 0x02;
+const CR: u8 = 0x0D;
+const LF: u8 = 0x0A;
+/// Tramas más largas que esto son basura (báscula a otra velocidad, ruido en la línea).
+const MAX_FRAME: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleProtocol {
+    #[default]
+    Toledo,
+    Cas,
+}
+
+impl ScaleProtocol {
+    /// Bajo demanda: hay que pedir cada lectura.
+    pub fn on_demand(&self) -> bool {
+        matches!(self, ScaleProtocol::Toledo)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeightStatus {
+    #[default]
+    Stable,
+    Motion,
+    Overload,
+    UnderZero,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeightReading {
+    /// Peso en kg con 3 decimales; 0 si la báscula está en sobrecarga o bajo cero.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub weight: Decimal,
+    pub stable: bool,
+    pub status: WeightStatus,
+}
+
+impl WeightReading {
+    pub fn new(weight: Decimal, status: WeightStatus) -> Self {
+        WeightReading { weight: units::normalize(weight), stable: status == WeightStatus::Stable, status }
+    }
+}
+
+impl Default for WeightReading {
+    fn default() -> Self {
+        WeightReading::new(Decimal::ZERO, WeightStatus::Stable)
+    }
+}
+
+// ---------- Tramas ----------
+
+/// Separa el flujo de bytes en tramas y las convierte en lecturas.
+pub struct FrameParser {
+    protocol: ScaleProtocol,
+    buf: Vec<u8>,
+}
+
+impl FrameParser {
+    pub fn new(protocol: ScaleProtocol) -> Self {
+        FrameParser { protocol, buf: Vec::new() }
+    }
+
+    /// Agrega bytes recibidos y devuelve las lecturas de las tramas completas.
+    /// Las tramas que no se entienden se descartan con aviso en la bitácora.
+    pub fn push(&mut self, data: &[u8]) -> Vec<WeightReading> {
+        self.buf.extend_from_slice(data);
+        let mut readings = Vec::new();
+        loop {
+            let frame = match self.protocol {
+                ScaleProtocol::Toledo => self.next_toledo(),
+                ScaleProtocol::Cas => self.next_line(),
+            };
+            let Some(frame) = frame else { break };
+            let text = String::from_utf8_lossy(&frame).to_string();
+            let parsed = match self.protocol {
+                ScaleProtocol::Toledo => parse_toledo(&frame),
+                ScaleProtocol::Cas => parse_cas(&text),
+            };
+            match parsed {
+                Ok(Some(r)) => readings.push(r),
+                Ok(None) => {}
+                Err(e) => log::warn!("báscula: trama descartada {:?}: {}", text, e),
+            }
+        }
+        if self.buf.len() > MAX_FRAME {
+            log::warn!("báscula: {} bytes sin fin de trama, se descartan", self.buf.len());
+            self.buf.clear();
+        }
+        readings
+    }
+
+    /// Contenido entre STX y CR; lo que venga antes del STX se ignora.
+    fn next_toledo(&mut self) -> Option<Vec<u8>> {
+        let start = self.buf.iter().position(|&b| b == STX)?;
+        let end = start + self.buf[start..].iter().position(|&b| b == CR)?;
+        let frame = self.buf[start + 1..end].to_vec();
+        self.buf.drain(..=end);
+        Some(frame)
+    }
+
+    fn next_line(&mut self) -> Option<Vec<u8>> {
+        let end = self.buf.iter().position(|&b| b == LF || b == CR)?;
+        let frame = self.buf[..end].to_vec();
+        self.buf.drain(..=end);
+        Some(frame)
+    }
+}
+
+/// `01.234` o `01234` (sin punto, 3 decimales implícitos); `?` + byte de estado si no hay peso.
+fn parse_toledo(frame: &[u8]) -> Result<Option<WeightReading>, String> {
+    if frame.first() == Some(&b'?') {
+        let status = frame.get(1).copied().unwrap_or(0);
+        let status = if status & 0x02 != 0 {
+            WeightStatus::Overload
+        } else if status & 0x04 != 0 {
+            WeightStatus::UnderZero
+        } else {
+            WeightStatus::Motion
+        };
+        return Ok(Some(WeightReading::new(Decimal::ZERO, status)));
+    }
+    let text = String::from_utf8_lossy(frame);
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    if !text.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Err("peso con caracteres no numéricos".to_string());
+    }
+    let weight = if text.contains('.') {
+        Decimal::from_str(text).map_err(|e| e.to_string())?
+    } else {
+        let raw = i64::from_str(text).map_err(|e| e.to_string())?;
+        Decimal::new(raw, 3)
+    };
+    Ok(Some(WeightReading::new(weight, WeightStatus::Stable)))
+}
+
+/// `ST,GS,+  1.234kg`; sin encabezado se toma como estable.
+fn parse_cas(line: &str) -> Result<Option<WeightReading>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+    let (header, value) = match parts.as_slice() {
+        [value] => (None, *value),
+        [header, value] => (Some(*header), *value),
+        [header, _, value, ..] => (Some(*header), *value),
+        _ => return Err("trama vacía".to_string()),
+    };
+    let status = match header.map(str::to_uppercase).as_deref() {
+        None | Some("ST") => WeightStatus::Stable,
+        Some("US") => WeightStatus::Motion,
+        Some("OL") => return Ok(Some(WeightReading::new(Decimal::ZERO, WeightStatus::Overload))),
+        Some(other) => return Err(format!("encabezado desconocido {}", other)),
+    };
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let number: String = value[..split].chars().filter(|c| !c.is_whitespace()).collect();
+    let weight = Decimal::from_str(number.trim_start_matches('+')).map_err(|_| format!("peso inválido {}", value))?;
+    let weight = match value[split..].trim().to_lowercase().as_str() {
+        "" | "kg" => weight,
+        "g" => weight / Decimal::from(1000),
+        "lb" => weight * Decimal::new(45359237, 8),
+        other => return Err(format!("unidad desconocida {}", other)),
+    };
+    if weight.is_sign_negative() && !weight.is_zero() {
+        return Ok(Some(WeightReading::new(Decimal::ZERO, WeightStatus::UnderZero)));
+    }
+    Ok(Some(WeightReading::new(weight, status)))
+}
+
+/// Trama que mandaría una báscula real con esta lectura (la usa el simulador).
+pub fn encode_frame(protocol: ScaleProtocol, reading: &WeightReading) -> Vec<u8> {
+    match protocol {
+        ScaleProtocol::Toledo => {
+            let body = match reading.status {
+                WeightStatus::Stable => format!("{:06.3}", reading.weight.round_dp(3)),
+                WeightStatus::Motion => "?\x01".to_string(),
+                WeightStatus::Overload => "?\x02".to_string(),
+                WeightStatus::UnderZero => "?\x04".to_string(),
+            };
+            let mut frame = vec![STX];
+            frame.extend_from_slice(body.as_bytes());
+            frame.push(CR);
+            frame
+        }
+        ScaleProtocol::Cas => {
+            let header = match reading.status {
+                WeightStatus::Stable => "ST",
+                WeightStatus::Motion => "US",
+                WeightStatus::Overload => "OL",
+                WeightStatus::UnderZero => "ST",
+            };
+            let weight = if reading.status == WeightStatus::UnderZero { Decimal::new(-1, 3) } else { reading.weight };
+            let sign = if weight.is_sign_negative() { '-' } else { '+' };
+            format!("{},GS,{}{:>8.3}kg\r\n", header, sign, weight.abs().round_dp(3)).into_bytes()
+        }
+    }
+}
+
+// ---------- Báscula simulada ----------
+
+/// Peso que reporta la báscula simulada; se comparte con el comando que lo cambia.
+#[derive(Clone, Default)]
+pub struct SimulatorHandle(Arc<Mutex<WeightReading>>);
+
+impl SimulatorHandle {
+    pub fn set(&self, weight: Decimal, status: WeightStatus) {
+        if let Ok(mut r) = self.0.lock() {
+            *r = WeightReading::new(weight, status);
+        }
+    }
+
+    fn current(&self) -> WeightReading {
+        self.0.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+}
+
+/// Transporte que se comporta como una báscula: responde a `W` (toledo) o
+/// manda una trama cada `interval` (cas).
+pub struct SimulatedScale {
+    protocol: ScaleProtocol,
+    handle: SimulatorHandle,
+    pending: VecDeque<u8>,
+    interval: Duration,
+}
+
+impl SimulatedScale {
+    pub fn new(protocol: ScaleProtocol, handle: SimulatorHandle) -> Self {
+        SimulatedScale { protocol, handle, pending: VecDeque::new(), interval: Duration::from_millis(100) }
+    }
+}
+
+impl Transport for SimulatedScale {
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.protocol.on_demand() {
+            for _ in data.iter().filter(|&&b| b == b'W') {
+                self.pending.extend(encode_frame(self.protocol, &self.handle.current()));
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            if self.protocol.on_demand() {
+                std::thread::sleep(Duration::from_millis(20));
+                return Ok(0);
+            }
+            std::thread::sleep(self.interval);
+            self.pending.extend(encode_frame(self.protocol, &self.handle.current()));
+        }
+        let n = buf.len().min(self.pending.len());
+        for (slot, byte) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+
+    fn name(&self) -> String {
+        "simulada".to_string()
+    }
+}
+
+// ---------- Lectura ----------
+
+pub struct Scale {
+    transport: Box<dyn Transport>,
+    protocol: ScaleProtocol,
+    parser: FrameParser,
+}
+
+impl Scale {
+    pub fn new(transport: Box<dyn Transport>, protocol: ScaleProtocol) -> Self {
+        Scale { transport, protocol, parser: FrameParser::new(protocol) }
+    }
+
+    pub fn name(&self) -> String {
+        self.transport.name()
+    }
+
+    /// Pide el peso si el protocolo es bajo demanda y devuelve lo que llegó.
+    /// Un error aquí significa que se perdió el puerto (cable desconectado).
+    pub fn poll(&mut self) -> Result<Vec<WeightReading>, String> {
+        let io_err = |e: io::Error| format!("Error de comunicación con la báscula: {}", e);
+        if self.protocol.on_demand() {
+            self.transport.write_all(b"W").map_err(io_err)?;
+        }
+        let mut buf = [0u8; 128];
+        let mut readings = Vec::new();
+        // Bajo demanda se sigue leyendo hasta tener la respuesta completa.
+        for _ in 0..5 {
+            let n = self.transport.read(&mut buf).map_err(io_err)?;
+            if n == 0 {
+                break;
+            }
+            readings.extend(self.parser.push(&buf[..n]));
+            if !readings.is_empty() {
+                break;
+            }
+        }
+        Ok(readings)
+    }
+
+    /// Lee hasta obtener un peso estable o agotar `timeout`.
+    pub fn read_stable(&mut self, timeout: Duration) -> Result<WeightReading, String> {
+        let deadline = Instant::now() + timeout;
+        let mut last = None;
+        while Instant::now() < deadline {
+            for r in self.poll()? {
+                if r.stable {
+                    return Ok(r);
+                }
+                last = Some(r);
+            }
+        }
+        Err(unstable_message(last.as_ref()))
+    }
+}
+
+fn unstable_message(last: Option<&WeightReading>) -> String {
+    match last.map(|r| r.status) {
+        Some(WeightStatus::Overload) => "La báscula está en sobrecarga.".to_string(),
+        Some(WeightStatus::UnderZero) => "La báscula marca bajo cero; vuelva a ponerla en cero.".to_string(),
+        Some(_) => "La báscula no se estabilizó; intente de nuevo.".to_string(),
+        None => "La báscula no respondió.".to_string(),
+    }
+}
+
+// ---------- Servicio ----------
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScaleConfig {
+    #[serde(default)]
+    pub protocol: ScaleProtocol,
+    /// Puerto serie; sin puerto y con `simulated` se usa la báscula simulada.
+    #[serde(default)]
+    pub serial: Option<SerialSettings>,
+    #[serde(default)]
+    pub simulated: bool,
+    /// Cada cuánto se pide el peso en protocolos bajo demanda (mínimo 100 ms).
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScaleStatus {
+    pub connected: bool,
+    pub port: Option<String>,
+    pub protocol: Option<ScaleProtocol>,
+    pub simulated: bool,
+    pub last_error: Option<String>,
+    pub last_reading: Option<WeightReading>,
+}
+
+/// Estado de Tauri de la báscula. Cada conexión tiene su número de sesión; el hilo
+/// de lectura termina en cuanto el número cambia (desconexión o nueva conexión).
+#[derive(Default)]
+pub struct ScaleService {
+    session: AtomicU64,
+    status: Mutex<ScaleStatus>,
+    simulator: Mutex<Option<SimulatorHandle>>,
+}
+
+impl ScaleService {
+    pub fn status(&self) -> ScaleStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    fn set_status(&self, f: impl FnOnce(&mut ScaleStatus)) -> ScaleStatus {
+        match self.status.lock() {
+            Ok(mut s) => {
+                f(&mut s);
+                s.clone()
+            }
+            Err(_) => ScaleStatus::default(),
+        }
+    }
+
+    /// Guarda la lectura; devuelve `true` si cambió respecto a la anterior.
+    fn record(&self, reading: &WeightReading) -> bool {
+        let mut changed = false;
+        self.set_status(|s| {
+            changed = s.last_reading.as_ref() != Some(reading);
+            s.last_reading = Some(reading.clone());
+        });
+        changed
+    }
+
+    /// Cambia el peso de la báscula simulada conectada.
+    pub fn simulate(&self, weight: Decimal, status: WeightStatus) -> Result<(), String> {
+        let sim = self.simulator.lock().map_err(|_| "báscula bloqueada".to_string())?;
+        match sim.as_ref() {
+            Some(handle) => {
+                handle.set(weight, status);
+                Ok(())
+            }
+            None => Err("No hay una báscula simulada conectada.".to_string()),
+        }
+    }
+
+    /// Última lectura estable que llegue antes de `timeout`.
+    pub fn wait_stable(&self, timeout: Duration) -> Result<WeightReading, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let status = self.status();
+            if !status.connected {
+                return Err("No hay báscula conectada.".to_string());
+            }
+            if let Some(r) = status.last_reading.as_ref().filter(|r| r.stable) {
+                return Ok(r.clone());
+            }
+            if Instant::now() >= deadline {
+                return Err(unstable_message(status.last_reading.as_ref()));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+fn open_scale(service: &ScaleService, config: &ScaleConfig) -> Result<Scale, String> {
+    let mut sim = service.simulator.lock().map_err(|_| "báscula bloqueada".to_string())?;
+    *sim = None;
+    let transport: Box<dyn Transport> = match &config.serial {
+        Some(serial) if !serial.port.trim().is_empty() => Box::new(SerialTransport::open(serial)?),
+        _ if config.simulated => {
+            let handle = SimulatorHandle::default();
+            *sim = Some(handle.clone());
+            Box::new(SimulatedScale::new(config.protocol, handle))
+        }
+        _ => return Err("Indique el puerto de la báscula.".to_string()),
+    };
+    Ok(Scale::new(transport, config.protocol))
+}
+
+fn emit_status(app: &AppHandle, status: &ScaleStatus) {
+    if let Err(e) = app.emit(SCALE_STATUS_EVENT, status.clone()) {
+        log::warn!("báscula: no se pudo emitir estado: {}", e);
+    }
+}
+
+// ---------- Comandos Tauri ----------
+
+#[tauri::command]
+pub fn scale_list_ports() -> Result<Vec<transport::PortInfo>, String> {
+    transport::list_ports()
+}
+
+/// Abre la báscula y arranca el hilo que emite `scale-weight`. Si ya había una conectada se cierra.
+#[tauri::command]
+pub fn scale_connect(app: AppHandle, service: State<'_, ScaleService>, config: ScaleConfig) -> Result<ScaleStatus, String> {
+    let session = service.session.fetch_add(1, Ordering::SeqCst) + 1;
+    let mut scale = match open_scale(&service, &config) {
+        Ok(scale) => scale,
+        Err(e) => {
+            let status = service.set_status(|s| {
+                *s = ScaleStatus { last_error: Some(e.clone()), ..Default::default() };
+            });
+            emit_status(&app, &status);
+            return Err(e);
+        }
+    };
+    let port = scale.name();
+    let status = service.set_status(|s| {
+        *s = ScaleStatus {
+            connected: true,
+            port: Some(port.clone()),
+            protocol: Some(config.protocol),
+            simulated: port == "simulada",
+            ..Default::default()
+        }
+    });
+    log::info!("báscula: conectada en {} ({:?})", port, config.protocol);
+    emit_status(&app, &status);
+
+    let interval = Duration::from_millis(config.poll_interval_ms.unwrap_or(200).max(100));
+    std::thread::spawn(move || {
+        let service = app.state::<ScaleService>();
+        let active = || service.session.load(Ordering::SeqCst) == session;
+        while active() {
+            match scale.poll() {
+                Ok(readings) => {
+                    for r in readings {
+                        if active() && service.record(&r) {
+                            if let Err(e) = app.emit(SCALE_WEIGHT_EVENT, r) {
+                                log::warn!("báscula: no se pudo emitir peso: {}", e);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("báscula: {}", e);
+                    if active() {
+                        let status = service.set_status(|s| {
+                            s.connected = false;
+                            s.last_error = Some(e);
+                        });
+                        emit_status(&app, &status);
+                    }
+                    break;
+                }
+            }
+            if config.protocol.on_demand() {
+                std::thread::sleep(interval);
+            }
+        }
+        log::info!("báscula: lectura detenida en {}", port);
+    });
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn scale_disconnect(app: AppHandle, service: State<'_, ScaleService>) {
+    service.session.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut sim) = service.simulator.lock() {
+        *sim = None;
+    }
+    let status = service.set_status(|s| *s = ScaleStatus::default());
+    emit_status(&app, &status);
+}
+
+#[tauri::command]
+pub fn scale_status(service: State<'_, ScaleService>) -> ScaleStatus {
+    service.status()
+}
+
+/// Peso estable para agregar al carrito; espera hasta `timeout_ms` (3 s por omisión).
+#[tauri::command]
+pub fn scale_read(service: State<'_, ScaleService>, timeout_ms: Option<u64>) -> Result<WeightReading, String> {
+    service.wait_stable(Duration::from_millis(timeout_ms.unwrap_or(3000)))
+}
+
+/// Pone un peso en la báscula simulada (pruebas y demostraciones).
+#[tauri::command]
+pub fn scale_simulate(
+    service: State<'_, ScaleService>,
+    weight: Decimal,
+    status: Option<WeightStatus>,
+) -> Result<(), String> {
+    service.simulate(weight, status.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kg(milli: i64) -> Decimal {
+        Decimal::new(milli, 3)
+    }
+
+    fn simulated(protocol: ScaleProtocol) -> (Scale, SimulatorHandle) {
+        let handle = SimulatorHandle::default();
+        let scale = Scale::new(Box::new(SimulatedScale::new(protocol, handle.clone())), protocol);
+        (scale, handle)
+    }
+
+    /// Transporte que entrega los bytes tal como llegarían por el puerto, en pedazos.
+    struct Script(VecDeque<Vec<u8>>);
+
+    impl Transport for Script {
+        fn write_all(&mut self, _data: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some(chunk) = self.0.pop_front() else { return Ok(0) };
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+
+        fn name(&self) -> String {
+            "script".to_string()
+        }
+    }
+
+    #[test]
+    fn toledo_stable_and_motion() {
+        let (mut scale, handle) = simulated(ScaleProtocol::Toledo);
+        handle.set(kg(1234), WeightStatus::Stable);
+        assert_eq!(scale.poll().unwrap(), vec![WeightReading::new(kg(1234), WeightStatus::Stable)]);
+        assert_eq!(scale.read_stable(Duration::from_millis(200)).unwrap().weight, kg(1234));
+
+        handle.set(kg(500), WeightStatus::Motion);
+        let readings = scale.poll().unwrap();
+        assert_eq!(readings.len(), 1);
+        assert!(!readings[0].stable);
+        assert_eq!(readings[0].status, WeightStatus::Motion);
+        let err = scale.read_stable(Duration::from_millis(100)).unwrap_err();
+        assert!(err.contains("no se estabilizó"), "{}", err);
+    }
+
+    #[test]
+    fn cas_stable_and_motion() {
+        let (mut scale, handle) = simulated(ScaleProtocol::Cas);
+        handle.set(kg(2750), WeightStatus::Stable);
+        assert_eq!(scale.poll().unwrap(), vec![WeightReading::new(kg(2750), WeightStatus::Stable)]);
+
+        handle.set(kg(300), WeightStatus::Motion);
+        assert_eq!(scale.poll().unwrap(), vec![WeightReading::new(kg(300), WeightStatus::Motion)]);
+    }
+
+    #[test]
+    fn overload_and_under_zero() {
+        for protocol in [ScaleProtocol::Toledo, ScaleProtocol::Cas] {
+            let (mut scale, handle) = simulated(protocol);
+            handle.set(kg(99999), WeightStatus::Overload);
+            let readings = scale.poll().unwrap();
+            assert_eq!(readings, vec![WeightReading::new(Decimal::ZERO, WeightStatus::Overload)], "{:?}", protocol);
+            let err = scale.read_stable(Duration::from_millis(150)).unwrap_err();
+            assert!(err.contains("sobrecarga"), "{:?}: {}", protocol, err);
+
+            handle.set(Decimal::ZERO, WeightStatus::UnderZero);
+            assert_eq!(scale.poll().unwrap()[0].status, WeightStatus::UnderZero, "{:?}", protocol);
+        }
+    }
+
+    #[test]
+    fn toledo_frames_split_or_with_noise() {
+        let mut parser = FrameParser::new(ScaleProtocol::Toledo);
+        assert!(parser.push(b"\x00\xff\x0201.").is_empty());
+        assert_eq!(parser.push(b"234\r"), vec![WeightReading::new(kg(1234), WeightStatus::Stable)]);
+        // Sin punto decimal: tres decimales implícitos.
+        assert_eq!(parser.push(b"\x0200750\r"), vec![WeightReading::new(kg(750), WeightStatus::Stable)]);
+    }
+
+    #[test]
+    fn malformed_frames_are_dropped() {
+        let mut toledo = FrameParser::new(ScaleProtocol::Toledo);
+        assert!(toledo.push(b"\x0201.2x4\r").is_empty());
+        assert!(toledo.push(b"\x02\r").is_empty());
+        // Basura sin fin de trama no se acumula para siempre.
+        assert!(toledo.push(&[STX; MAX_FRAME + 1]).is_empty());
+        assert_eq!(toledo.push(b"\r\x0202.000\r"), vec![WeightReading::new(kg(2000), WeightStatus::Stable)]);
+
+        let mut cas = FrameParser::new(ScaleProtocol::Cas);
+        assert!(cas.push(b"XX,GS,+  1.000kg\r\n").is_empty());
+        assert!(cas.push(b"ST,GS,+  1.0z0kg\r\n").is_empty());
+        assert!(cas.push(b"ST,GS,+  1.000oz\r\n").is_empty());
+        assert_eq!(cas.push(b"ST,GS,+  250g\r\n"), vec![WeightReading::new(kg(250), WeightStatus::Stable)]);
+        assert_eq!(cas.push(b"  1.500 kg\r\n"), vec![WeightReading::new(kg(1500), WeightStatus::Stable)]);
+    }
+
+    #[test]
+    fn poll_skips_malformed_frame_and_keeps_reading() {
+        let chunks = vec![b"\x02?".to_vec(), b"\x04\r\x02ab\r".to_vec(), b"\x0201.100\r".to_vec()];
+        let mut scale = Scale::new(Box::new(Script(chunks.into())), ScaleProtocol::Toledo);
+        assert_eq!(scale.poll().unwrap(), vec![WeightReading::new(Decimal::ZERO, WeightStatus::UnderZero)]);
+        assert_eq!(scale.poll().unwrap(), vec![WeightReading::new(kg(1100), WeightStatus::Stable)]);
+        assert!(scale.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn encoded_frames_parse_back() {
+        for protocol in [ScaleProtocol::Toledo, ScaleProtocol::Cas] {
+            for status in [WeightStatus::Stable, WeightStatus::Motion] {
+                let reading = WeightReading::new(kg(12345), status);
+                let mut parser = FrameParser::new(protocol);
+                let parsed = parser.push(&encode_frame(protocol, &reading));
+                let expected = if protocol == ScaleProtocol::Toledo && status == WeightStatus::Motion {
+                    // Toledo no manda el peso mientras la báscula se mueve.
+                    WeightReading::new(Decimal::ZERO, WeightStatus::Motion)
+                } else {
+                    reading
+                };
+                assert_eq!(parsed, vec![expected], "{:?} {:?}", protocol, status);
+            }
+        }
+    }
+}
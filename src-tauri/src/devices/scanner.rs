@@ -0,0 +1,659 @@
+//! Lectores de código de barras leídos desde el backend, sin depender del foco del webview.
+//!
+//! - Serie (RS-232 o USB-serie, "modo COM" del lector): el lector manda el código como texto.
+//! - HID en Linux: `/dev/input/eventN` (evdev) o `/dev/hidrawN`. El lector se presenta como
+//!   teclado; aquí se traducen las teclas a texto con la distribución US, que es la que
+//!   traen de fábrica. Con evdev el dispositivo se toma en exclusiva para que las teclas
+//!   no lleguen también a la ventana.
+//!
+//! Cada lectura se separa por el prefijo y sufijo configurados (por omisión, sin prefijo y
+//! terminada en CR o LF) y se emite como `barcode-scanned` a toda la aplicación.
+
+use super::transport::{self, SerialSettings, SerialTransport, Transport};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Evento con cada código leído (`ScanEvent`).
+pub const BARCODE_SCANNED_EVENT: &str = "barcode-scanned";
+/// Evento al conectar, desconectar o perder el lector (`ScannerStatus`).
+pub const SCANNER_STATUS_EVENT: &str = "scanner-status";
+
+const CR: u8 = 0x0D;
+const LF: u8 = 0x0A;
+/// FNC1 de GS1 (separador de campos de longitud variable); se conserva dentro del código.
+const GS: u8 = 0x1D;
+/// Ningún código de barras lineal o 2D de mostrador pasa de esto; más es ruido o un lector sin sufijo.
+const MAX_FRAME: usize = 512;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanEvent {
+    pub code: String,
+    /// Puerto o dispositivo que lo leyó.
+    pub source: String,
+}
+
+// ---------- Tramas ----------
+
+/// Separa el texto que manda el lector en códigos según prefijo y sufijo.
+pub struct BarcodeFramer {
+    prefix: Vec<u8>,
+    /// Vacío = termina en CR o LF.
+    suffix: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl BarcodeFramer {
+    pub fn new(prefix: &str, suffix: &str) -> Self {
+        BarcodeFramer { prefix: prefix.as_bytes().to_vec(), suffix: suffix.as_bytes().to_vec(), buf: Vec::new() }
+    }
+
+    /// Agrega bytes recibidos y devuelve los códigos completos.
+    pub fn push(&mut self, data: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(data);
+        let mut codes = Vec::new();
+        while let Some((end, len)) = self.terminator() {
+            let frame: Vec<u8> = self.buf.drain(..end + len).take(end).collect();
+            if let Some(code) = self.strip(&frame) {
+                codes.push(code);
+            }
+        }
+        if self.buf.len() > MAX_FRAME {
+            log::warn!("lector: {} bytes sin sufijo, se descartan", self.buf.len());
+            self.buf.clear();
+        }
+        codes
+    }
+
+    /// Posición y largo del primer sufijo completo.
+    fn terminator(&self) -> Option<(usize, usize)> {
+        if self.suffix.is_empty() {
+            let end = self.buf.iter().position(|&b| b == CR || b == LF)?;
+            return Some((end, 1));
+        }
+        let end = self.buf.windows(self.suffix.len()).position(|w| w == self.suffix.as_slice())?;
+        Some((end, self.suffix.len()))
+    }
+
+    /// Quita el prefijo y los espacios y controles de las orillas (menos GS).
+    fn strip(&self, frame: &[u8]) -> Option<String> {
+        let body = if self.prefix.is_empty() {
+            frame
+        } else {
+            match frame.windows(self.prefix.len()).rposition(|w| w == self.prefix.as_slice()) {
+                Some(at) => &frame[at + self.prefix.len()..],
+                None => {
+                    log::warn!("lector: lectura sin prefijo descartada {:?}", String::from_utf8_lossy(frame));
+                    return None;
+                }
+            }
+        };
+        let noise = |b: &u8| *b != GS && (b.is_ascii_whitespace() || b.is_ascii_control());
+        let start = body.iter().position(|b| !noise(b))?;
+        let end = body.iter().rposition(|b| !noise(b))?;
+        Some(String::from_utf8_lossy(&body[start..=end]).to_string())
+    }
+}
+
+// ---------- Teclado (HID) ----------
+
+/// Carácter con Shift en distribución US.
+fn shifted(c: u8) -> u8 {
+    match c {
+        b'a'..=b'z' => c.to_ascii_uppercase(),
+        b'1' => b'!',
+        b'2' => b'@',
+        b'3' => b'#',
+        b'4' => b'$',
+        b'5' => b'%',
+        b'6' => b'^',
+        b'7' => b'&',
+        b'8' => b'*',
+        b'9' => b'(',
+        b'0' => b')',
+        b'-' => b'_',
+        b'=' => b'+',
+        b'[' => b'{',
+        b']' => b'}',
+        b'\\' => b'|',
+        b';' => b':',
+        b'\'' => b'"',
+        b'`' => b'~',
+        b',' => b'<',
+        b'.' => b'>',
+        b'/' => b'?',
+        other => other,
+    }
+}
+
+/// Aplica Shift o Ctrl a la tecla. Ctrl+letra da el control ASCII; así llega el FNC1
+/// (Ctrl+] = GS) de los lectores configurados para GS1.
+fn apply_modifiers(base: u8, shift: bool, ctrl: bool) -> u8 {
+    if ctrl && (b'@'..=b'~').contains(&base) {
+        return base & 0x1F;
+    }
+    if shift {
+        shifted(base)
+    } else {
+        base
+    }
+}
+
+/// Tecla de evdev (`KEY_*`) sin modificadores; Enter y Tab como CR y TAB.
+pub fn evdev_key_char(code: u16) -> Option<u8> {
+    const ROW_Q: &[u8] = b"qwertyuiop[]";
+    const ROW_A: &[u8] = b"asdfghjkl;'`";
+    const ROW_Z: &[u8] = b"\\zxcvbnm,./";
+    let c = match code {
+        2..=10 => b'1' + (code - 2) as u8,
+        11 => b'0',
+        12 => b'-',
+        13 => b'=',
+        15 => b'\t',
+        16..=27 => ROW_Q[(code - 16) as usize],
+        28 | 96 => CR,
+        30..=41 => ROW_A[(code - 30) as usize],
+        43..=53 => ROW_Z[(code - 43) as usize],
+        55 => b'*',
+        57 => b' ',
+        71..=73 => b'7' + (code - 71) as u8,
+        74 => b'-',
+        75..=77 => b'4' + (code - 75) as u8,
+        78 => b'+',
+        79..=81 => b'1' + (code - 79) as u8,
+        82 => b'0',
+        83 => b'.',
+        98 => b'/',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Uso de la página de teclado HID (reporte de arranque) sin modificadores.
+pub fn hid_usage_char(usage: u8) -> Option<u8> {
+    const SYMBOLS: &[u8] = b"-=[]\\\0;'`,./";
+    let c = match usage {
+        0x04..=0x1D => b'a' + (usage - 0x04),
+        0x1E..=0x26 => b'1' + (usage - 0x1E),
+        0x27 => b'0',
+        0x28 | 0x58 => CR,
+        0x2B => b'\t',
+        0x2C => b' ',
+        0x2D..=0x38 => match SYMBOLS[(usage - 0x2D) as usize] {
+            0 => return None,
+            c => c,
+        },
+        0x54 => b'/',
+        0x55 => b'*',
+        0x56 => b'-',
+        0x57 => b'+',
+        0x59..=0x61 => b'1' + (usage - 0x59),
+        0x62 => b'0',
+        0x63 => b'.',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Traduce eventos de evdev a texto llevando la cuenta de Shift y Ctrl.
+#[derive(Default)]
+pub struct EvdevDecoder {
+    shift: bool,
+    ctrl: bool,
+}
+
+impl EvdevDecoder {
+    const EV_KEY: u16 = 1;
+    const LEFT_CTRL: u16 = 29;
+    const RIGHT_CTRL: u16 = 97;
+    const LEFT_SHIFT: u16 = 42;
+    const RIGHT_SHIFT: u16 = 54;
+
+    /// `value`: 1 = presionada, 0 = soltada, 2 = repetición (se ignora).
+    pub fn key(&mut self, kind: u16, code: u16, value: i32) -> Option<u8> {
+        if kind != Self::EV_KEY {
+            return None;
+        }
+        match code {
+            Self::LEFT_SHIFT | Self::RIGHT_SHIFT => self.shift = value != 0,
+            Self::LEFT_CTRL | Self::RIGHT_CTRL => self.ctrl = value != 0,
+            _ if value == 1 => return evdev_key_char(code).map(|c| apply_modifiers(c, self.shift, self.ctrl)),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Traduce reportes de teclado HID (`modificadores, 0, k1..k6`) a texto. Solo cuentan
+/// las teclas que no venían en el reporte anterior.
+#[derive(Default)]
+pub struct HidReportDecoder {
+    pressed: [u8; 6],
+}
+
+impl HidReportDecoder {
+    pub fn report(&mut self, report: &[u8]) -> Vec<u8> {
+        // Algunos lectores anteponen el número de reporte.
+        let report = if report.len() == 9 { &report[1..] } else { report };
+        if report.len() < 8 {
+            return Vec::new();
+        }
+        let modifiers = report[0];
+        let shift = modifiers & 0x22 != 0;
+        let ctrl = modifiers & 0x11 != 0;
+        let mut keys = [0u8; 6];
+        keys.copy_from_slice(&report[2..8]);
+        let out = keys
+            .iter()
+            .filter(|&&k| k > 0x03 && !self.pressed.contains(&k))
+            .filter_map(|&k| hid_usage_char(k))
+            .map(|c| apply_modifiers(c, shift, ctrl))
+            .collect();
+        self.pressed = keys;
+        out
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use hid::HidTransport;
+
+#[cfg(target_os = "linux")]
+mod hid {
+    use super::{EvdevDecoder, HidReportDecoder, Transport};
+    use std::collections::VecDeque;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Read};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+
+    /// `EVIOCGRAB`: `_IOW('E', 0x90, int)`.
+    const EVIOCGRAB: libc::c_ulong = 0x4004_4590;
+
+    enum Decoder {
+        Evdev(EvdevDecoder),
+        Hidraw(HidReportDecoder),
+    }
+
+    /// Lector HID en modo teclado. Se abre sin bloqueo para que el hilo pueda terminar
+    /// aunque no se escanee nada.
+    pub struct HidTransport {
+        file: File,
+        path: String,
+        decoder: Decoder,
+        text: VecDeque<u8>,
+    }
+
+    impl HidTransport {
+        pub fn open(path: &str, grab: bool) -> Result<Self, String> {
+            let file = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::PermissionDenied => format!(
+                        "Sin permiso para leer {}: agregue el usuario al grupo input o una regla udev para el lector.",
+                        path
+                    ),
+                    _ => format!("No se pudo abrir el lector {}: {}", path, e),
+                })?;
+            let is_hidraw = path.contains("hidraw");
+            if grab && !is_hidraw {
+                // SAFETY: ioctl sobre un descriptor abierto con un entero como argumento.
+                if unsafe { libc::ioctl(file.as_raw_fd(), EVIOCGRAB as _, 1 as libc::c_int) } != 0 {
+                    log::warn!("lector: no se pudo tomar {} en exclusiva: {}", path, io::Error::last_os_error());
+                }
+            }
+            let decoder = if is_hidraw { Decoder::Hidraw(HidReportDecoder::default()) } else { Decoder::Evdev(EvdevDecoder::default()) };
+            Ok(HidTransport { file, path: path.to_string(), decoder, text: VecDeque::new() })
+        }
+
+        fn fill(&mut self) -> io::Result<()> {
+            let event_size = std::mem::size_of::<libc::input_event>();
+            let mut raw = [0u8; 64 * 24];
+            let n = match self.file.read(&mut raw) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "el lector se desconectó")),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(20));
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+            match &mut self.decoder {
+                Decoder::Hidraw(d) => self.text.extend(d.report(&raw[..n])),
+                Decoder::Evdev(d) => {
+                    // `input_event` = timeval + tipo (u16) + código (u16) + valor (i32).
+                    let offset = event_size - 8;
+                    for event in raw[..n].chunks_exact(event_size) {
+                        let kind = u16::from_ne_bytes([event[offset], event[offset + 1]]);
+                        let code = u16::from_ne_bytes([event[offset + 2], event[offset + 3]]);
+                        let value = i32::from_ne_bytes([event[offset + 4], event[offset + 5], event[offset + 6], event[offset + 7]]);
+                        self.text.extend(d.key(kind, code, value));
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Transport for HidTransport {
+        fn write_all(&mut self, _data: &[u8]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.text.is_empty() {
+                self.fill()?;
+            }
+            let n = buf.len().min(self.text.len());
+            for (slot, byte) in buf.iter_mut().zip(self.text.drain(..n)) {
+                *slot = byte;
+            }
+            Ok(n)
+        }
+
+        fn name(&self) -> String {
+            self.path.clone()
+        }
+    }
+}
+
+// ---------- Lector simulado ----------
+
+/// Texto pendiente del lector simulado; se comparte con el comando que "escanea".
+#[derive(Clone, Default)]
+pub struct SimulatorHandle {
+    queue: Arc<Mutex<VecDeque<u8>>>,
+    prefix: String,
+    suffix: String,
+}
+
+impl SimulatorHandle {
+    pub fn new(prefix: &str, suffix: &str) -> Self {
+        let suffix = if suffix.is_empty() { "\r" } else { suffix };
+        SimulatorHandle { queue: Arc::default(), prefix: prefix.to_string(), suffix: suffix.to_string() }
+    }
+
+    /// Encola el código con el prefijo y sufijo configurados, como lo mandaría el lector.
+    pub fn scan(&self, code: &str) {
+        if let Ok(mut q) = self.queue.lock() {
+            q.extend(self.prefix.bytes().chain(code.bytes()).chain(self.suffix.bytes()));
+        }
+    }
+}
+
+pub struct SimulatedScanner {
+    handle: SimulatorHandle,
+}
+
+impl Transport for SimulatedScanner {
+    fn write_all(&mut self, _data: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match self.handle.queue.lock() {
+            Ok(mut q) => {
+                let n = buf.len().min(q.len());
+                for (slot, byte) in buf.iter_mut().zip(q.drain(..n)) {
+                    *slot = byte;
+                }
+                n
+            }
+            Err(_) => 0,
+        };
+        if n == 0 {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Ok(n)
+    }
+
+    fn name(&self) -> String {
+        "simulado".to_string()
+    }
+}
+
+// ---------- Dispositivos ----------
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScannerDevice {
+    /// Puerto serie o ruta del dispositivo HID.
+    pub path: String,
+    /// `serie`, `evdev` o `hidraw`.
+    pub kind: String,
+    pub description: Option<String>,
+}
+
+/// Puertos serie y, en Linux, teclados de `/dev/input/by-id` y dispositivos hidraw.
+pub fn list_devices() -> Result<Vec<ScannerDevice>, String> {
+    let mut devices: Vec<ScannerDevice> = transport::list_ports()?
+        .into_iter()
+        .map(|p| ScannerDevice { path: p.name, kind: "serie".to_string(), description: p.description })
+        .collect();
+    #[cfg(target_os = "linux")]
+    devices.extend(linux_hid_devices());
+    Ok(devices)
+}
+
+#[cfg(target_os = "linux")]
+fn linux_hid_devices() -> Vec<ScannerDevice> {
+    let mut devices = Vec::new();
+    if let Ok(entries) = std::fs::read_dir("/dev/input/by-id") {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with("-event-kbd") {
+                devices.push(ScannerDevice {
+                    path: entry.path().to_string_lossy().to_string(),
+                    kind: "evdev".to_string(),
+                    description: Some(name.trim_end_matches("-event-kbd").trim_start_matches("usb-").replace('_', " ")),
+                });
+            }
+        }
+    }
+    if let Ok(entries) = std::fs::read_dir("/sys/class/hidraw") {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let description = std::fs::read_to_string(entry.path().join("device/uevent"))
+                .ok()
+                .and_then(|u| u.lines().find_map(|l| l.strip_prefix("HID_NAME=").map(str::to_string)));
+            devices.push(ScannerDevice { path: format!("/dev/{}", name), kind: "hidraw".to_string(), description });
+        }
+    }
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    devices
+}
+
+// ---------- Servicio ----------
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScannerConfig {
+    /// Lector en modo serie.
+    #[serde(default)]
+    pub serial: Option<SerialSettings>,
+    /// Lector HID (Linux): `/dev/input/eventN`, `/dev/input/by-id/...-event-kbd` o `/dev/hidrawN`.
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Sin puerto ni dispositivo y con `simulated` se usa el lector simulado.
+    #[serde(default)]
+    pub simulated: bool,
+    #[serde(default)]
+    pub prefix: String,
+    /// Sin valor, cada lectura termina en CR o LF.
+    #[serde(default)]
+    pub suffix: String,
+    /// Con evdev, toma el lector en exclusiva para que no escriba en la ventana.
+    #[serde(default = "default_grab")]
+    pub grab: bool,
+}
+
+fn default_grab() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScannerStatus {
+    pub connected: bool,
+    pub device: Option<String>,
+    pub simulated: bool,
+    pub last_error: Option<String>,
+    pub last_code: Option<String>,
+    pub scans: u64,
+}
+
+/// Estado de Tauri del lector. Igual que la báscula, cada conexión tiene su número de
+/// sesión y el hilo de lectura termina en cuanto cambia.
+#[derive(Default)]
+pub struct ScannerService {
+    session: AtomicU64,
+    status: Mutex<ScannerStatus>,
+    simulator: Mutex<Option<SimulatorHandle>>,
+}
+
+impl ScannerService {
+    pub fn status(&self) -> ScannerStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    fn set_status(&self, f: impl FnOnce(&mut ScannerStatus)) -> ScannerStatus {
+        match self.status.lock() {
+            Ok(mut s) => {
+                f(&mut s);
+                s.clone()
+            }
+            Err(_) => ScannerStatus::default(),
+        }
+    }
+
+    /// Manda un código al lector simulado conectado, con su prefijo y sufijo.
+    pub fn simulate(&self, code: &str) -> Result<(), String> {
+        let sim = self.simulator.lock().map_err(|_| "lector bloqueado".to_string())?;
+        match sim.as_ref() {
+            Some(handle) => {
+                handle.scan(code);
+                Ok(())
+            }
+            None => Err("No hay un lector simulado conectado.".to_string()),
+        }
+    }
+}
+
+fn open_scanner(service: &ScannerService, config: &ScannerConfig) -> Result<Box<dyn Transport>, String> {
+    let mut sim = service.simulator.lock().map_err(|_| "lector bloqueado".to_string())?;
+    *sim = None;
+    let device = config.device.as_deref().map(str::trim).filter(|d| !d.is_empty());
+    let transport: Box<dyn Transport> = match (&config.serial, device) {
+        (Some(serial), _) if !serial.port.trim().is_empty() => Box::new(SerialTransport::open(serial)?),
+        #[cfg(target_os = "linux")]
+        (_, Some(path)) => Box::new(HidTransport::open(path, config.grab)?),
+        #[cfg(not(target_os = "linux"))]
+        (_, Some(_)) => return Err("Los lectores HID solo se leen directamente en Linux; use el modo serie.".to_string()),
+        _ if config.simulated => {
+            let handle = SimulatorHandle::new(&config.prefix, &config.suffix);
+            *sim = Some(handle.clone());
+            Box::new(SimulatedScanner { handle })
+        }
+        _ => return Err("Indique el puerto o dispositivo del lector.".to_string()),
+    };
+    Ok(transport)
+}
+
+fn emit_status(app: &AppHandle, status: &ScannerStatus) {
+    if let Err(e) = app.emit(SCANNER_STATUS_EVENT, status.clone()) {
+        log::warn!("lector: no se pudo emitir estado: {}", e);
+    }
+}
+
+// ---------- Comandos Tauri ----------
+
+#[tauri::command]
+pub fn scanner_list_devices() -> Result<Vec<ScannerDevice>, String> {
+    list_devices()
+}
+
+/// Abre el lector y arranca el hilo que emite `barcode-scanned`. Si ya había uno conectado se cierra.
+#[tauri::command]
+pub fn scanner_connect(app: AppHandle, service: State<'_, ScannerService>, config: ScannerConfig) -> Result<ScannerStatus, String> {
+    let session = service.session.fetch_add(1, Ordering::SeqCst) + 1;
+    let mut transport = match open_scanner(&service, &config) {
+        Ok(transport) => transport,
+        Err(e) => {
+            let status = service.set_status(|s| {
+                *s = ScannerStatus { last_error: Some(e.clone()), ..Default::default() };
+            });
+            emit_status(&app, &status);
+            return Err(e);
+        }
+    };
+    let device = transport.name();
+    let status = service.set_status(|s| {
+        *s = ScannerStatus { connected: true, simulated: device == "simulado", device: Some(device.clone()), ..Default::default() }
+    });
+    log::info!("lector: conectado en {}", device);
+    emit_status(&app, &status);
+
+    let mut framer = BarcodeFramer::new(&config.prefix, &config.suffix);
+    std::thread::spawn(move || {
+        let service = app.state::<ScannerService>();
+        let active = || service.session.load(Ordering::SeqCst) == session;
+        let mut buf = [0u8; 256];
+        while active() {
+            match transport.read(&mut buf) {
+                Ok(0) => {}
+                Ok(n) => {
+                    for code in framer.push(&buf[..n]) {
+                        if !active() {
+                            break;
+                        }
+                        service.set_status(|s| {
+                            s.last_code = Some(code.clone());
+                            s.scans += 1;
+                        });
+                        let event = ScanEvent { code, source: device.clone() };
+                        if let Err(e) = app.emit(BARCODE_SCANNED_EVENT, event) {
+                            log::warn!("lector: no se pudo emitir código: {}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("lector: {}", e);
+                    if active() {
+                        let status = service.set_status(|s| {
+                            s.connected = false;
+                            s.last_error = Some(format!("Error de comunicación con el lector: {}", e));
+                        });
+                        emit_status(&app, &status);
+                    }
+                    break;
+                }
+            }
+        }
+        log::info!("lector: lectura detenida en {}", device);
+    });
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn scanner_disconnect(app: AppHandle, service: State<'_, ScannerService>) {
+    service.session.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut sim) = service.simulator.lock() {
+        *sim = None;
+    }
+    let status = service.set_status(|s| *s = ScannerStatus::default());
+    emit_status(&app, &status);
+}
+
+#[tauri::command]
+pub fn scanner_status(service: State<'_, ScannerService>) -> ScannerStatus {
+    service.status()
+}
+
+/// "Escanea" un código en el lector simulado (pruebas y demostraciones).
+#[tauri::command]
+pub fn scanner_simulate(service: State<'_, ScannerService>, code: String) -> Result<(), String> {
+    service.simulate(&code)
+}
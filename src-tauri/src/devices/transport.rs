@@ -0,0 +1,164 @@
+//! Transporte de bytes hacia los periféricos: puerto serie (RS-232 o USB-serie), el spooler
+//! de impresión (`printer::SpoolerTransport`) o un dispositivo simulado. Los protocolos de
+//! cada periférico solo ven `Transport`.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// Canal de bytes con un periférico. `read` espera a lo más el tiempo configurado y
+/// devuelve `Ok(0)` si no llegó nada.
+pub trait Transport: Send {
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()>;
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    /// Nombre para bitácora y estado (`/dev/ttyUSB0`, `COM3`, `simulada`).
+    fn name(&self) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Parity {
+    #[default]
+    None,
+    Even,
+    Odd,
+}
+
+/// Parámetros del puerto serie. Los valores por omisión son 9600 8N1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerialSettings {
+    pub port: String,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+    #[serde(default = "default_data_bits")]
+    pub data_bits: u8,
+    #[serde(default)]
+    pub parity: Parity,
+    #[serde(default = "default_stop_bits")]
+    pub stop_bits: u8,
+    /// Espera máxima de cada lectura.
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+
+fn default_data_bits() -> u8 {
+    8
+}
+
+fn default_stop_bits() -> u8 {
+    1
+}
+
+fn default_timeout_ms() -> u64 {
+    100
+}
+
+impl SerialSettings {
+    pub fn new(port: &str) -> Self {
+        SerialSettings {
+            port: port.to_string(),
+            baud_rate: default_baud_rate(),
+            data_bits: default_data_bits(),
+            parity: Parity::None,
+            stop_bits: default_stop_bits(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+pub struct SerialTransport {
+    port: Box<dyn serialport::SerialPort>,
+}
+
+impl SerialTransport {
+    pub fn open(settings: &SerialSettings) -> Result<Self, String> {
+        let data_bits = match settings.data_bits {
+            7 => serialport::DataBits::Seven,
+            8 => serialport::DataBits::Eight,
+            n => return Err(format!("Bits de datos no soportados: {} (use 7 u 8).", n)),
+        };
+        let stop_bits = match settings.stop_bits {
+            1 => serialport::StopBits::One,
+            2 => serialport::StopBits::Two,
+            n => return Err(format!("Bits de parada no soportados: {} (use 1 o 2).", n)),
+        };
+        let parity = match settings.parity {
+            Parity::None => serialport::Parity::None,
+            Parity::Even => serialport::Parity::Even,
+            Parity::Odd => serialport::Parity::Odd,
+        };
+        let port = serialport::new(settings.port.trim(), settings.baud_rate)
+            .data_bits(data_bits)
+            .stop_bits(stop_bits)
+            .parity(parity)
+            .timeout(Duration::from_millis(settings.timeout_ms.max(10)))
+            .open()
+            .map_err(|e| format!("No se pudo abrir el puerto {}: {}", settings.port, e))?;
+        Ok(SerialTransport { port })
+    }
+}
+
+impl Transport for SerialTransport {
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.port.write_all(data)?;
+        self.port.flush()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.port.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Ok(0),
+            other => other,
+        }
+    }
+
+    fn name(&self) -> String {
+        self.port.name().unwrap_or_else(|| "serie".to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortInfo {
+    pub name: String,
+    /// `usb`, `pci`, `bluetooth` o `desconocido`.
+    pub kind: String,
+    /// Fabricante y modelo que reporta el adaptador USB, si los hay.
+    pub description: Option<String>,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+}
+
+/// Puertos serie del equipo, para que el usuario elija dónde está conectado el periférico.
+pub fn list_ports() -> Result<Vec<PortInfo>, String> {
+    let ports = serialport::available_ports().map_err(|e| format!("No se pudieron listar los puertos: {}", e))?;
+    Ok(ports
+        .into_iter()
+        .map(|p| match p.port_type {
+            serialport::SerialPortType::UsbPort(usb) => {
+                let parts: Vec<String> = [usb.manufacturer, usb.product].into_iter().flatten().collect();
+                PortInfo {
+                    name: p.port_name,
+                    kind: "usb".to_string(),
+                    description: (!parts.is_empty()).then(|| parts.join(" ")),
+                    vid: Some(usb.vid),
+                    pid: Some(usb.pid),
+                }
+            }
+            other => PortInfo {
+                name: p.port_name,
+                kind: match other {
+                    serialport::SerialPortType::PciPort => "pci",
+                    serialport::SerialPortType::BluetoothPort => "bluetooth",
+                    _ => "desconocido",
+                }
+                .to_string(),
+                description: None,
+                vid: None,
+                pid: None,
+            },
+        })
+        .collect())
+}
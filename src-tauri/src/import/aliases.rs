@@ -0,0 +1,201 @@
+//! Códigos de producto del proveedor ya confirmados ("su NoIdentificacion es nuestro producto").
+//!
+//! Cada vez que el cajero confirma una partida se guarda el alias por RFC del proveedor y
+//! código del proveedor; en la siguiente factura `matching` lo toma como coincidencia exacta.
+//! El código se guarda sin espacios a los lados y en mayúsculas.
+
+use crate::db::Db;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::State;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SupplierAlias {
+    pub id: i64,
+    pub supplier_rfc: String,
+    pub supplier_code: String,
+    pub product_id: i64,
+    pub product_code: String,
+    pub product_name: String,
+    /// Descripción con que lo factura el proveedor.
+    pub description: Option<String>,
+    /// Veces que se ha confirmado la misma relación.
+    pub confirmations: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl SupplierAlias {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(SupplierAlias {
+            id: row.get("id")?,
+            supplier_rfc: row.get("supplier_rfc")?,
+            supplier_code: row.get("supplier_code")?,
+            product_id: row.get("product_id")?,
+            product_code: row.get("product_code")?,
+            product_name: row.get("product_name")?,
+            description: row.get("description")?,
+            confirmations: row.get("confirmations")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+/// Partida confirmada en la revisión de una factura.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfirmedMatch {
+    pub supplier_code: String,
+    pub product_id: i64,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+const SELECT: &str = "SELECT a.*, p.code AS product_code, p.name AS product_name
+     FROM supplier_aliases a JOIN products p ON p.id = a.product_id";
+
+fn clean_rfc(rfc: &str) -> Result<String, String> {
+    let rfc = rfc.trim().to_uppercase();
+    if rfc.is_empty() {
+        return Err("Falta el RFC del proveedor.".to_string());
+    }
+    Ok(rfc)
+}
+
+fn clean_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+fn product_exists(conn: &Connection, id: i64) -> Result<bool, String> {
+    conn.query_row("SELECT 1 FROM products WHERE id = ?1", [id], |_| Ok(()))
+        .optional()
+        .map(|r| r.is_some())
+        .map_err(|e| format!("base local: {}", e))
+}
+
+/// Guarda (o refuerza) los alias de las partidas confirmadas; devuelve cuántos se guardaron.
+pub fn learn(conn: &mut Connection, supplier_rfc: &str, matches: &[ConfirmedMatch]) -> Result<usize, String> {
+    let rfc = clean_rfc(supplier_rfc)?;
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let tx = conn.transaction().map_err(sql)?;
+    let mut saved = 0;
+    for m in matches {
+        let code = clean_code(&m.supplier_code);
+        if code.is_empty() {
+            continue;
+        }
+        if !product_exists(&tx, m.product_id)? {
+            log::warn!("alias {} de {} no guardado: no existe el producto local {}", code, rfc, m.product_id);
+            continue;
+        }
+        // Si el código ya apuntaba a otro producto, la confirmación nueva manda y el conteo reinicia.
+        tx.execute(
+            "INSERT INTO supplier_aliases (supplier_rfc, supplier_code, product_id, description)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (supplier_rfc, supplier_code) DO UPDATE SET
+                 confirmations = CASE WHEN product_id = excluded.product_id THEN confirmations + 1 ELSE 1 END,
+                 product_id = excluded.product_id,
+                 description = COALESCE(excluded.description, description),
+                 updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')",
+            params![rfc, code, m.product_id, m.description.as_deref().map(str::trim).filter(|d| !d.is_empty())],
+        )
+        .map_err(sql)?;
+        saved += 1;
+    }
+    tx.commit().map_err(sql)?;
+    Ok(saved)
+}
+
+/// Código del proveedor → id de producto, para `matching`.
+pub fn for_supplier(conn: &Connection, supplier_rfc: &str) -> rusqlite::Result<HashMap<String, i64>> {
+    let mut stmt = conn.prepare("SELECT supplier_code, product_id FROM supplier_aliases WHERE supplier_rfc = ?1")?;
+    let rows = stmt.query_map([supplier_rfc.trim().to_uppercase()], |r| Ok((r.get(0)?, r.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn list(conn: &Connection, supplier_rfc: Option<&str>, search: Option<&str>) -> rusqlite::Result<Vec<SupplierAlias>> {
+    let pattern = format!("%{}%", search.unwrap_or_default().trim());
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE (?1 IS NULL OR a.supplier_rfc = ?1)
+           AND (a.supplier_code LIKE ?2 OR p.name LIKE ?2 OR p.code LIKE ?2 OR IFNULL(a.description, '') LIKE ?2)
+         ORDER BY a.supplier_rfc, a.supplier_code",
+        SELECT
+    ))?;
+    let rfc = supplier_rfc.map(|r| r.trim().to_uppercase()).filter(|r| !r.is_empty());
+    let rows = stmt.query_map(params![rfc, pattern], SupplierAlias::from_row)?;
+    rows.collect()
+}
+
+/// Cambia el código del proveedor o el producto al que apunta un alias.
+pub fn update(conn: &Connection, id: i64, supplier_code: &str, product_id: i64) -> Result<SupplierAlias, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let code = clean_code(supplier_code);
+    if code.is_empty() {
+        return Err("El alias necesita el código del proveedor.".to_string());
+    }
+    if !product_exists(conn, product_id)? {
+        return Err(format!("No existe el producto {}.", product_id));
+    }
+    let (rfc, current): (String, String) = conn
+        .query_row("SELECT supplier_rfc, supplier_code FROM supplier_aliases WHERE id = ?1", [id], |r| {
+            Ok((r.get(0)?, r.get(1)?))
+        })
+        .optional()
+        .map_err(sql)?
+        .ok_or_else(|| format!("No existe el alias {}.", id))?;
+    if code != current {
+        let taken: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM supplier_aliases WHERE supplier_rfc = ?1 AND supplier_code = ?2",
+                params![rfc, code],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(sql)?;
+        if taken.is_some() {
+            return Err(format!("El proveedor {} ya tiene un alias con el código {}.", rfc, code));
+        }
+    }
+    conn.execute(
+        "UPDATE supplier_aliases SET supplier_code = ?2, product_id = ?3,
+             updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+         WHERE id = ?1",
+        params![id, code, product_id],
+    )
+    .map_err(sql)?;
+    conn.query_row(&format!("{} WHERE a.id = ?1", SELECT), [id], SupplierAlias::from_row)
+        .map_err(sql)
+}
+
+/// Guarda como alias las partidas confirmadas al revisar una factura del proveedor.
+#[tauri::command]
+pub fn import_learn_aliases(db: State<'_, Db>, supplier_rfc: String, matches: Vec<ConfirmedMatch>) -> Result<usize, String> {
+    let mut conn = db.lock()?;
+    learn(&mut conn, &supplier_rfc, &matches)
+}
+
+#[tauri::command]
+pub fn import_list_aliases(
+    db: State<'_, Db>,
+    supplier_rfc: Option<String>,
+    search: Option<String>,
+) -> Result<Vec<SupplierAlias>, String> {
+    db.with_conn(|conn| list(conn, supplier_rfc.as_deref(), search.as_deref()))
+}
+
+#[tauri::command]
+pub fn import_update_alias(
+    db: State<'_, Db>,
+    id: i64,
+    supplier_code: String,
+    product_id: i64,
+) -> Result<SupplierAlias, String> {
+    let conn = db.lock()?;
+    update(&conn, id, &supplier_code, product_id)
+}
+
+#[tauri::command]
+pub fn import_delete_alias(db: State<'_, Db>, id: i64) -> Result<(), String> {
+    db.with_conn(|conn| conn.execute("DELETE FROM supplier_aliases WHERE id = ?1", [id]).map(|_| ()))
+}
@@ -0,0 +1,633 @@
+//! Carga masiva del catálogo de productos desde CSV o Excel.
+//!
+//! El CSV puede venir con cualquier separador (`,` `;` tabulador o `|`) y en UTF-8, UTF-16 o
+//! Latin-1 (lo que guarda Excel en Windows). Las columnas se asignan a los campos de `products`
+//! con un mapeo que se puede guardar con nombre; sin mapeo se adivina por el encabezado.
+//! Primero se arma una vista previa con los productos a crear, los cambios por producto y los
+//! renglones con error; la importación vuelve a armarla y solo escribe si no hay errores.
+//! Los productos se identifican por `code`; una celda vacía no borra el dato que ya existe.
+
+use super::pdf::parse_amount;
+use crate::db::{self, Db, Product};
+use crate::units::{self, Unit};
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tauri::State;
+
+/// Campo de `products` → encabezado de la columna en el archivo.
+pub type ColumnMapping = BTreeMap<String, String>;
+
+/// Campos que se pueden importar y los encabezados con que se reconocen sin mapeo.
+const FIELDS: &[(&str, &[&str])] = &[
+    ("code", &["codigo", "clave", "sku", "code", "codigo interno", "clave interna"]),
+    ("barcode", &["codigo de barras", "cod barras", "barcode", "ean", "upc", "codigo barras"]),
+    ("name", &["nombre", "producto", "articulo", "name", "nombre del producto"]),
+    ("description", &["descripcion", "description", "detalle"]),
+    ("price", &["precio", "precio venta", "precio de venta", "price", "pventa", "precio publico"]),
+    ("cost", &["costo", "cost", "precio compra", "precio de compra", "costo unitario"]),
+    ("stock", &["existencia", "existencias", "stock", "inventario"]),
+    ("unit", &["unidad", "unidad de medida", "um", "unit"]),
+    ("category", &["categoria", "departamento", "linea", "category"]),
+    ("supplier", &["proveedor", "supplier"]),
+    ("minimum_stock", &["minimo", "stock minimo", "existencia minima", "minimum stock"]),
+    ("sat_product_key", &["clave sat", "clave prod serv", "claveprodserv", "clave producto sat"]),
+    ("sat_unit_key", &["clave unidad", "claveunidad", "unidad sat", "clave unidad sat"]),
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CatalogImportOptions {
+    /// Mapeo a usar; si viene junto con `mapping_name`, al importar se guarda con ese nombre.
+    #[serde(default)]
+    pub mapping: Option<ColumnMapping>,
+    /// Mapeo guardado a usar cuando no viene `mapping`.
+    #[serde(default)]
+    pub mapping_name: Option<String>,
+    /// Hoja del libro de Excel; por omisión la primera.
+    #[serde(default)]
+    pub sheet: Option<String>,
+    /// Solo crear productos nuevos y dejar igual los que ya existen.
+    #[serde(default)]
+    pub only_new: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedMapping {
+    pub name: String,
+    pub mapping: ColumnMapping,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogCreate {
+    /// Renglón del archivo (1 = encabezado).
+    pub row: usize,
+    pub product: Product,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogUpdate {
+    pub row: usize,
+    pub product_id: i64,
+    pub code: String,
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+    #[serde(skip)]
+    product: Product,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowIssue {
+    pub row: usize,
+    pub message: String,
+}
+
+/// Resultado de la vista previa: qué se crearía, qué cambiaría y qué renglones no sirven.
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogPreview {
+    pub headers: Vec<String>,
+    /// Mapeo aplicado (el recibido, el guardado o el adivinado).
+    pub mapping: ColumnMapping,
+    pub rows: usize,
+    pub creates: Vec<CatalogCreate>,
+    pub updates: Vec<CatalogUpdate>,
+    pub unchanged: usize,
+    /// Productos existentes que no se tocan por `only_new`.
+    pub skipped: usize,
+    pub errors: Vec<RowIssue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogImportResult {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// Encabezados y renglones de texto; `first_row` es el número de renglón del encabezado.
+struct Sheet {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    first_row: usize,
+}
+
+/// Texto del CSV: respeta el BOM y, si no es UTF-8 válido, lo lee como Windows-1252 (Latin-1).
+fn decode(bytes: &[u8]) -> String {
+    if let Some((encoding, bom)) = encoding_rs::Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom..]).0.into_owned();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252.decode(bytes).0.into_owned(),
+    }
+}
+
+/// Separador que aparece el mismo número de veces en los primeros renglones.
+fn sniff_delimiter(text: &str) -> u8 {
+    let sample: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).take(20).collect();
+    let count = |line: &str, d: u8| {
+        let mut quoted = false;
+        line.bytes()
+            .filter(|&b| {
+                if b == b'"' {
+                    quoted = !quoted;
+                }
+                !quoted && b == d
+            })
+            .count()
+    };
+    let mut best = (b',', 0usize);
+    for d in [b',', b';', b'\t', b'|'] {
+        let counts: Vec<usize> = sample.iter().map(|l| count(l, d)).collect();
+        let min = counts.iter().copied().min().unwrap_or(0);
+        let consistent = counts.iter().all(|&c| c == min);
+        let score = if consistent { min * 2 } else { min };
+        if score > best.1 {
+            best = (d, score);
+        }
+    }
+    best.0
+}
+
+fn read_csv(bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let text = decode(bytes);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(sniff_delimiter(&text))
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    reader
+        .records()
+        .map(|r| {
+            r.map(|rec| rec.iter().map(|c| c.trim().to_string()).collect())
+                .map_err(|e| format!("CSV: {}", e))
+        })
+        .collect()
+}
+
+fn read_workbook(path: &Path, sheet: Option<&str>) -> Result<Vec<Vec<String>>, String> {
+    use calamine::{Data, Reader};
+    let mut book = calamine::open_workbook_auto(path).map_err(|e| format!("No se pudo abrir el libro: {}", e))?;
+    let name = match sheet {
+        Some(s) => s.to_string(),
+        None => book.sheet_names().first().cloned().ok_or("El libro no tiene hojas.")?,
+    };
+    let range = book
+        .worksheet_range(&name)
+        .map_err(|e| format!("No se pudo leer la hoja {}: {}", name, e))?;
+    let cell = |c: &Data| match c {
+        // Los códigos de barras llegan como número; sin ".0" ni notación científica.
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", *f as i64),
+        Data::Empty | Data::Error(_) => String::new(),
+        other => other.to_string().trim().to_string(),
+    };
+    Ok(range.rows().map(|r| r.iter().map(cell).collect()).collect())
+}
+
+fn read_sheet(path: &Path, sheet: Option<&str>) -> Result<Sheet, String> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    let raw = match ext.as_str() {
+        "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => read_workbook(path, sheet)?,
+        _ => read_csv(&std::fs::read(path).map_err(|e| format!("No se pudo leer {}: {}", path.display(), e))?)?,
+    };
+    let mut rows = raw.into_iter().enumerate().filter(|(_, r)| r.iter().any(|c| !c.is_empty()));
+    let (header_idx, headers) = rows.next().ok_or("El archivo está vacío.")?;
+    Ok(Sheet { headers, rows: rows.map(|(_, r)| r).collect(), first_row: header_idx + 1 })
+}
+
+/// Minúsculas, sin acentos ni signos: `Código de Barras` → `codigo de barras`.
+fn normalize_header(h: &str) -> String {
+    let plain: String = h
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' => 'a',
+            'é' | 'è' | 'ë' => 'e',
+            'í' | 'ì' | 'ï' => 'i',
+            'ó' | 'ò' | 'ö' => 'o',
+            'ú' | 'ù' | 'ü' => 'u',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+    plain.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Mapeo adivinado por el nombre de las columnas.
+pub fn guess_mapping(headers: &[String]) -> ColumnMapping {
+    let mut mapping = ColumnMapping::new();
+    for header in headers {
+        let norm = normalize_header(header);
+        if let Some((field, _)) = FIELDS.iter().find(|(_, names)| names.contains(&norm.as_str())) {
+            mapping.entry(field.to_string()).or_insert_with(|| header.clone());
+        }
+    }
+    // Catálogos que solo traen "Descripción" como nombre del artículo.
+    if !mapping.contains_key("name") {
+        if let Some(desc) = mapping.remove("description") {
+            mapping.insert("name".to_string(), desc);
+        }
+    }
+    mapping
+}
+
+/// Índice de columna por campo; falla si el mapeo nombra un campo o encabezado que no existe.
+fn resolve(mapping: &ColumnMapping, headers: &[String]) -> Result<HashMap<String, usize>, String> {
+    let mut columns = HashMap::new();
+    for (field, header) in mapping {
+        if header.trim().is_empty() {
+            continue;
+        }
+        if !FIELDS.iter().any(|(f, _)| f == field) {
+            return Err(format!("El campo {} no se puede importar.", field));
+        }
+        let idx = headers
+            .iter()
+            .position(|h| h.trim() == header.trim())
+            .ok_or_else(|| format!("El archivo no tiene la columna \"{}\" (para {}).", header, field))?;
+        columns.insert(field.clone(), idx);
+    }
+    if !columns.contains_key("code") {
+        return Err("Indica qué columna trae el código del producto.".to_string());
+    }
+    Ok(columns)
+}
+
+fn load_mapping(conn: &Connection, name: &str) -> Result<Option<ColumnMapping>, String> {
+    let json: Option<String> = conn
+        .query_row("SELECT mapping FROM import_mappings WHERE name = ?1", [name.trim()], |r| r.get(0))
+        .optional()
+        .map_err(|e| format!("base local: {}", e))?;
+    json.map(|j| serde_json::from_str(&j).map_err(|e| format!("Mapeo {} dañado: {}", name, e)))
+        .transpose()
+}
+
+pub fn save_mapping(conn: &Connection, name: &str, mapping: &ColumnMapping) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("El mapeo necesita un nombre.".to_string());
+    }
+    let json = serde_json::to_string(mapping).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO import_mappings (name, mapping) VALUES (?1, ?2)
+         ON CONFLICT(name) DO UPDATE SET mapping = excluded.mapping,
+             updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')",
+        params![name.trim(), json],
+    )
+    .map_err(|e| format!("base local: {}", e))?;
+    Ok(())
+}
+
+/// Valores de un renglón ya convertidos; `None` es celda vacía o columna sin mapear.
+#[derive(Default)]
+struct RowValues {
+    code: Option<String>,
+    barcode: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    price: Option<Decimal>,
+    cost: Option<Decimal>,
+    stock: Option<Decimal>,
+    unit: Option<Unit>,
+    category: Option<String>,
+    supplier: Option<String>,
+    minimum_stock: Option<i64>,
+    sat_product_key: Option<String>,
+    sat_unit_key: Option<String>,
+}
+
+fn parse_row(row: &[String], columns: &HashMap<String, usize>) -> Result<RowValues, String> {
+    let get = |field: &str| {
+        columns
+            .get(field)
+            .and_then(|&i| row.get(i))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let amount = |field: &str, label: &str| -> Result<Option<Decimal>, String> {
+        match get(field) {
+            Some(v) => match parse_amount(&v) {
+                Some(d) if d >= Decimal::ZERO => Ok(Some(d)),
+                _ => Err(format!("{} \"{}\" no es un importe válido.", label, v)),
+            },
+            None => Ok(None),
+        }
+    };
+    let whole = |field: &str, label: &str| -> Result<Option<i64>, String> {
+        match get(field) {
+            Some(v) => match parse_amount(&v) {
+                Some(d) if d.fract().is_zero() => {
+                    i64::try_from(d).map(Some).map_err(|_| format!("{} \"{}\" es demasiado grande.", label, v))
+                }
+                _ => Err(format!("{} \"{}\" debe ser un número entero.", label, v)),
+            },
+            None => Ok(None),
+        }
+    };
+    let quantity = |field: &str, label: &str| -> Result<Option<Decimal>, String> {
+        match get(field) {
+            Some(v) => parse_amount(&v)
+                .map(|d| Some(units::normalize(d)))
+                .ok_or_else(|| format!("{} \"{}\" no es una cantidad válida.", label, v)),
+            None => Ok(None),
+        }
+    };
+    let unit = match get("unit") {
+        Some(v) => Some(Unit::parse(&v).ok_or_else(|| format!("La unidad \"{}\" no se reconoce (pieza, kg, g, m, l).", v))?),
+        None => None,
+    };
+    let sat_product_key = get("sat_product_key");
+    if let Some(key) = &sat_product_key {
+        if key.len() != 8 || !key.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("La clave SAT \"{}\" debe tener 8 dígitos.", key));
+        }
+    }
+    Ok(RowValues {
+        code: get("code"),
+        barcode: get("barcode"),
+        name: get("name"),
+        description: get("description"),
+        price: amount("price", "El precio")?,
+        cost: amount("cost", "El costo")?,
+        stock: quantity("stock", "La existencia")?,
+        unit,
+        category: get("category"),
+        supplier: get("supplier"),
+        minimum_stock: whole("minimum_stock", "El mínimo")?,
+        sat_product_key,
+        sat_unit_key: get("sat_unit_key").map(|k| k.to_uppercase()),
+    })
+}
+
+/// Aplica los valores del renglón sobre el producto y anota qué cambió.
+fn merge(product: &mut Product, v: RowValues) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut text = |field: &str, slot: &mut Option<String>, value: Option<String>| {
+        if value.is_some() && *slot != value {
+            changes.push(FieldChange { field: field.to_string(), before: slot.clone(), after: value.clone() });
+            *slot = value;
+        }
+    };
+    text("barcode", &mut product.barcode, v.barcode);
+    text("description", &mut product.description, v.description);
+    text("category", &mut product.category, v.category);
+    text("supplier", &mut product.supplier, v.supplier);
+    text("sat_product_key", &mut product.sat_product_key, v.sat_product_key);
+    text("sat_unit_key", &mut product.sat_unit_key, v.sat_unit_key);
+    if let Some(minimum) = v.minimum_stock.filter(|m| product.minimum_stock != Some(*m)) {
+        changes.push(FieldChange {
+            field: "minimum_stock".to_string(),
+            before: product.minimum_stock.map(|m| m.to_string()),
+            after: Some(minimum.to_string()),
+        });
+        product.minimum_stock = Some(minimum);
+    }
+    let mut change = |field: &str, before: String, after: String| {
+        changes.push(FieldChange { field: field.to_string(), before: Some(before), after: Some(after) });
+    };
+    if let Some(name) = v.name.filter(|n| *n != product.name) {
+        change("name", product.name.clone(), name.clone());
+        product.name = name;
+    }
+    if let Some(price) = v.price.filter(|p| *p != product.price) {
+        change("price", product.price.to_string(), price.to_string());
+        product.price = price;
+    }
+    if let Some(cost) = v.cost.filter(|c| *c != product.cost) {
+        change("cost", product.cost.to_string(), cost.to_string());
+        product.cost = cost;
+    }
+    if let Some(unit) = v.unit.filter(|u| *u != product.unit) {
+        change("unit", product.unit.as_str().to_string(), unit.as_str().to_string());
+        product.unit = unit;
+    }
+    if let Some(stock) = v.stock.filter(|s| *s != product.stock) {
+        change("stock", product.stock.to_string(), stock.to_string());
+        product.stock = stock;
+    }
+    changes
+}
+
+fn new_product(code: String, name: String) -> Product {
+    Product {
+        id: None,
+        code,
+        barcode: None,
+        name,
+        description: None,
+        price: Decimal::ZERO,
+        cost: Decimal::ZERO,
+        average_cost: Decimal::ZERO,
+        stock: Decimal::ZERO,
+        unit: Unit::default(),
+        quantity_precision: None,
+        category: None,
+        supplier: None,
+        minimum_stock: None,
+        image_url: None,
+        tax_profile_id: None,
+        sat_product_key: None,
+        sat_unit_key: None,
+        last_sale_date: None,
+        created_at: None,
+        updated_at: None,
+    }
+}
+
+/// Arma la vista previa sin escribir nada.
+pub fn plan(conn: &Connection, path: &Path, opts: &CatalogImportOptions) -> Result<CatalogPreview, String> {
+    let sheet = read_sheet(path, opts.sheet.as_deref())?;
+    let mapping = match (&opts.mapping, &opts.mapping_name) {
+        (Some(m), _) => m.clone(),
+        (None, Some(name)) => load_mapping(conn, name)?.ok_or_else(|| format!("No hay un mapeo llamado {}.", name))?,
+        (None, None) => guess_mapping(&sheet.headers),
+    };
+    let columns = resolve(&mapping, &sheet.headers)?;
+
+    let existing: Vec<Product> = {
+        let mut stmt = conn.prepare("SELECT * FROM products").map_err(|e| format!("base local: {}", e))?;
+        let rows = stmt.query_map([], Product::from_row).map_err(|e| format!("base local: {}", e))?;
+        rows.collect::<rusqlite::Result<_>>().map_err(|e| format!("base local: {}", e))?
+    };
+    let by_code: HashMap<&str, &Product> = existing.iter().map(|p| (p.code.as_str(), p)).collect();
+    let by_barcode: HashMap<&str, &Product> =
+        existing.iter().filter_map(|p| p.barcode.as_deref().map(|b| (b, p))).collect();
+
+    let mut preview = CatalogPreview {
+        headers: sheet.headers.clone(),
+        mapping,
+        rows: sheet.rows.len(),
+        creates: Vec::new(),
+        updates: Vec::new(),
+        unchanged: 0,
+        skipped: 0,
+        errors: Vec::new(),
+    };
+    let mut seen_codes: HashMap<String, usize> = HashMap::new();
+    let mut seen_barcodes: HashMap<String, usize> = HashMap::new();
+    for (i, raw) in sheet.rows.iter().enumerate() {
+        let row = sheet.first_row + i + 1;
+        let mut issue = |message: String| preview.errors.push(RowIssue { row, message });
+        let values = match parse_row(raw, &columns) {
+            Ok(v) => v,
+            Err(e) => {
+                issue(e);
+                continue;
+            }
+        };
+        let Some(code) = values.code.clone() else {
+            issue("Falta el código.".to_string());
+            continue;
+        };
+        if let Some(first) = seen_codes.insert(code.clone(), row) {
+            issue(format!("El código {} se repite (renglón {}).", code, first));
+            continue;
+        }
+        if let Some(barcode) = &values.barcode {
+            if let Some(first) = seen_barcodes.insert(barcode.clone(), row) {
+                issue(format!("El código de barras {} se repite (renglón {}).", barcode, first));
+                continue;
+            }
+            if let Some(owner) = by_barcode.get(barcode.as_str()).filter(|p| p.code != code) {
+                issue(format!("El código de barras {} ya es del producto {} ({}).", barcode, owner.code, owner.name));
+                continue;
+            }
+        }
+        match by_code.get(code.as_str()) {
+            Some(current) => {
+                if opts.only_new {
+                    preview.skipped += 1;
+                    continue;
+                }
+                let mut product = (*current).clone();
+                let changes = merge(&mut product, values);
+                if let Err(e) = units::check_precision(product.stock, product.unit, product.precision(), &product.name) {
+                    issue(e);
+                    continue;
+                }
+                if changes.is_empty() {
+                    preview.unchanged += 1;
+                } else {
+                    preview.updates.push(CatalogUpdate {
+                        row,
+                        product_id: product.id.unwrap_or_default(),
+                        code,
+                        name: product.name.clone(),
+                        changes,
+                        product,
+                    });
+                }
+            }
+            None => {
+                let Some(name) = values.name.clone() else {
+                    issue(format!("El producto nuevo {} no trae nombre.", code));
+                    continue;
+                };
+                let mut product = new_product(code, name);
+                merge(&mut product, values);
+                if let Err(e) = units::check_precision(product.stock, product.unit, product.precision(), &product.name) {
+                    issue(e);
+                    continue;
+                }
+                preview.creates.push(CatalogCreate { row, product });
+            }
+        }
+    }
+    Ok(preview)
+}
+
+/// Escribe la vista previa en una sola transacción; con errores no se escribe nada.
+pub fn apply(conn: &mut Connection, path: &Path, opts: &CatalogImportOptions) -> Result<CatalogImportResult, String> {
+    let preview = plan(conn, path, opts)?;
+    if !preview.errors.is_empty() {
+        return Err(format!(
+            "El archivo tiene {} renglón(es) con errores; corrígelos antes de importar.",
+            preview.errors.len()
+        ));
+    }
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let tx = conn.transaction().map_err(sql)?;
+    for c in &preview.creates {
+        db::save_product(&tx, &c.product).map_err(sql)?;
+    }
+    for u in &preview.updates {
+        db::save_product(&tx, &u.product).map_err(sql)?;
+    }
+    if let (Some(mapping), Some(name)) = (&opts.mapping, &opts.mapping_name) {
+        save_mapping(&tx, name, mapping)?;
+    }
+    tx.commit().map_err(sql)?;
+    log::info!(
+        "catálogo importado de {}: {} nuevos, {} actualizados",
+        path.display(),
+        preview.creates.len(),
+        preview.updates.len()
+    );
+    Ok(CatalogImportResult {
+        created: preview.creates.len(),
+        updated: preview.updates.len(),
+        unchanged: preview.unchanged,
+    })
+}
+
+/// Vista previa (dry-run) de la importación de un catálogo CSV / Excel.
+#[tauri::command]
+pub fn import_catalog_preview(
+    db: State<'_, Db>,
+    path: String,
+    options: Option<CatalogImportOptions>,
+) -> Result<CatalogPreview, String> {
+    let conn = db.lock()?;
+    plan(&conn, Path::new(&path), &options.unwrap_or_default())
+}
+
+/// Importa el catálogo; si el archivo tiene errores no escribe nada.
+#[tauri::command]
+pub fn import_catalog_commit(
+    db: State<'_, Db>,
+    path: String,
+    options: Option<CatalogImportOptions>,
+) -> Result<CatalogImportResult, String> {
+    let mut conn = db.lock()?;
+    apply(&mut conn, Path::new(&path), &options.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn import_list_mappings(db: State<'_, Db>) -> Result<Vec<SavedMapping>, String> {
+    let conn = db.lock()?;
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let mut stmt = conn
+        .prepare("SELECT name, mapping, updated_at FROM import_mappings ORDER BY name")
+        .map_err(sql)?;
+    let rows = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?)))
+        .map_err(sql)?;
+    let mut list = Vec::new();
+    for row in rows {
+        let (name, json, updated_at) = row.map_err(sql)?;
+        let mapping = serde_json::from_str(&json).unwrap_or_default();
+        list.push(SavedMapping { name, mapping, updated_at });
+    }
+    Ok(list)
+}
+
+#[tauri::command]
+pub fn import_save_mapping(db: State<'_, Db>, name: String, mapping: ColumnMapping) -> Result<(), String> {
+    let conn = db.lock()?;
+    save_mapping(&conn, &name, &mapping)
+}
+
+#[tauri::command]
+pub fn import_delete_mapping(db: State<'_, Db>, name: String) -> Result<(), String> {
+    db.with_conn(|conn| conn.execute("DELETE FROM import_mappings WHERE name = ?1", [name.trim()]).map(|_| ()))
+}
@@ -0,0 +1,320 @@
+//! Relación de partidas de una factura de proveedor con productos del catálogo.
+//!
+//! El índice se arma una vez por llamada: nombres normalizados (sin acentos, `600ml` → `600 ml`),
+//! códigos y códigos de barras exactos, y un índice invertido de palabras y trigramas para no
+//! comparar cada partida contra todo el catálogo. Por partida se toman los productos que más
+//! trigramas comparten y solo a esos se les calcula la similitud por conjunto de palabras.
+//! Un código del proveedor ya conocido (alias, ver `aliases`), el código interno o el código de
+//! barras exactos ganan sobre cualquier parecido por nombre.
+
+use super::aliases;
+use crate::db::{Db, Product};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tauri::State;
+
+/// Productos a los que se les calcula la similitud completa por partida.
+const SHORTLIST: usize = 60;
+/// Debajo de esto no se propone el producto.
+const MIN_SCORE: f64 = 0.35;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MatchLine {
+    #[serde(default)]
+    pub name: String,
+    /// Código del proveedor (NoIdentificacion en el CFDI).
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub barcode: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MatchRequest {
+    pub lines: Vec<MatchLine>,
+    /// RFC del emisor; con él se cargan los alias guardados del proveedor.
+    #[serde(default)]
+    pub supplier_rfc: Option<String>,
+    /// Código del proveedor → id de producto ya confirmado (se suman a los guardados).
+    #[serde(default)]
+    pub aliases: HashMap<String, i64>,
+    /// Candidatos por partida (5 por omisión).
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Alias,
+    CodeExact,
+    BarcodeExact,
+    NameExact,
+    NameSimilar,
+    NamePartial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    fn from_score(score: f64) -> Self {
+        if score >= 0.9 {
+            Confidence::High
+        } else if score >= 0.7 {
+            Confidence::Medium
+        } else {
+            Confidence::Low
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchCandidate {
+    pub product: Product,
+    pub score: f64,
+    pub kind: MatchKind,
+    pub confidence: Confidence,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LineMatches {
+    /// Posición de la partida en la solicitud.
+    pub index: usize,
+    pub candidates: Vec<MatchCandidate>,
+}
+
+/// Minúsculas sin acentos ni signos, con números y unidades separados (`600ML` → `600 ml`).
+pub fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    for c in text.to_lowercase().chars() {
+        let c = match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        };
+        if let Some(p) = prev {
+            if c != ' ' && p != ' ' && p.is_ascii_digit() != c.is_ascii_digit() {
+                out.push(' ');
+            }
+        }
+        out.push(c);
+        prev = Some(c);
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Códigos comparados sin espacios, guiones ni mayúsculas.
+fn normalize_code(code: &str) -> String {
+    code.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_uppercase).collect()
+}
+
+fn trigrams(norm: &str) -> HashSet<String> {
+    let mut set = HashSet::new();
+    for word in norm.split(' ') {
+        let padded: Vec<char> = format!("  {} ", word).chars().collect();
+        for w in padded.windows(3) {
+            set.insert(w.iter().collect());
+        }
+    }
+    set
+}
+
+/// Parecido entre dos conjuntos de palabras (0 a 1). Las palabras largas cuentan aunque
+/// tengan una errata; el resultado mezcla Dice con qué tanto del nombre corto está en el largo.
+fn token_set_similarity(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let same = |x: &String, y: &String| {
+        x == y || (x.len() >= 4 && y.len() >= 4 && strsim::normalized_levenshtein(x, y) >= 0.8)
+    };
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let mut used = vec![false; long.len()];
+    let mut common = 0usize;
+    for x in short {
+        if let Some(i) = (0..long.len()).find(|&i| !used[i] && same(x, &long[i])) {
+            used[i] = true;
+            common += 1;
+        }
+    }
+    let dice = 2.0 * common as f64 / (a.len() + b.len()) as f64;
+    let containment = common as f64 / short.len() as f64;
+    0.6 * dice + 0.4 * containment
+}
+
+struct Entry {
+    product: Product,
+    tokens: Vec<String>,
+    grams: HashSet<String>,
+}
+
+/// Índice del catálogo para relacionar muchas partidas en una sola llamada.
+pub struct CatalogIndex {
+    entries: Vec<Entry>,
+    by_code: HashMap<String, usize>,
+    by_barcode: HashMap<String, usize>,
+    by_id: HashMap<i64, usize>,
+    by_gram: HashMap<String, Vec<usize>>,
+}
+
+impl CatalogIndex {
+    pub fn build(products: Vec<Product>) -> Self {
+        let mut index = CatalogIndex {
+            entries: Vec::with_capacity(products.len()),
+            by_code: HashMap::new(),
+            by_barcode: HashMap::new(),
+            by_id: HashMap::new(),
+            by_gram: HashMap::new(),
+        };
+        for product in products {
+            let i = index.entries.len();
+            let norm = normalize(&product.name);
+            let grams = trigrams(&norm);
+            for g in &grams {
+                index.by_gram.entry(g.clone()).or_default().push(i);
+            }
+            index.by_code.insert(normalize_code(&product.code), i);
+            if let Some(b) = product.barcode.as_deref().map(normalize_code).filter(|b| !b.is_empty()) {
+                index.by_barcode.entry(b).or_insert(i);
+            }
+            if let Some(id) = product.id {
+                index.by_id.insert(id, i);
+            }
+            index.entries.push(Entry {
+                product,
+                tokens: norm.split(' ').filter(|t| !t.is_empty()).map(str::to_string).collect(),
+                grams,
+            });
+        }
+        index
+    }
+
+    fn name_score(&self, i: usize, tokens: &[String], grams: &HashSet<String>) -> f64 {
+        let entry = &self.entries[i];
+        let shared = grams.intersection(&entry.grams).count();
+        let dice = 2.0 * shared as f64 / (grams.len() + entry.grams.len()).max(1) as f64;
+        0.7 * token_set_similarity(tokens, &entry.tokens) + 0.3 * dice
+    }
+
+    /// Candidatos ordenados de mejor a peor para una partida; `aliases` va con códigos normalizados.
+    fn candidates(&self, line: &MatchLine, aliases: &HashMap<String, i64>, limit: usize) -> Vec<MatchCandidate> {
+        let norm = normalize(&line.name);
+        let tokens: Vec<String> = norm.split(' ').filter(|t| !t.is_empty()).map(str::to_string).collect();
+        let grams = trigrams(&norm);
+
+        // Los trigramas que trae buena parte del catálogo ("pro", "ml ") no distinguen y son
+        // los que más cuestan; solo se usan si la partida no tiene otros.
+        let common = (self.entries.len() / 10).max(200);
+        let postings: Vec<&[usize]> = grams.iter().filter_map(|g| self.by_gram.get(g)).map(Vec::as_slice).collect();
+        let rare: Vec<&[usize]> = postings.iter().copied().filter(|p| p.len() <= common).collect();
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for posting in if rare.is_empty() { &postings } else { &rare } {
+            for &i in *posting {
+                *shared.entry(i).or_default() += 1;
+            }
+        }
+        let mut scored: HashMap<usize, (f64, MatchKind)> = HashMap::new();
+
+        // Identificadores exactos: alias del proveedor, código interno y código de barras.
+        let code = line.code.as_deref().map(normalize_code).filter(|c| !c.is_empty());
+        let barcode = line.barcode.as_deref().map(normalize_code).filter(|c| !c.is_empty());
+        let alias = code.as_ref().and_then(|c| aliases.get(c)).and_then(|id| self.by_id.get(id));
+        if let Some(&i) = alias {
+            scored.insert(i, (1.0, MatchKind::Alias));
+        }
+        for c in [&code, &barcode].into_iter().flatten() {
+            if let Some(&i) = self.by_barcode.get(c) {
+                scored.entry(i).or_insert((0.98, MatchKind::BarcodeExact));
+            }
+        }
+        if let Some(&i) = code.as_ref().and_then(|c| self.by_code.get(c)) {
+            // El código del proveedor puede coincidir por casualidad con uno nuestro:
+            // si el nombre no se parece, se propone pero no en confianza alta.
+            let name = self.name_score(i, &tokens, &grams);
+            let score = if tokens.is_empty() || name >= 0.3 { 0.95 } else { 0.75 };
+            scored.entry(i).or_insert((score, MatchKind::CodeExact));
+        }
+
+        let mut shortlist: Vec<(usize, usize)> = shared.into_iter().collect();
+        shortlist.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (i, _) in shortlist.into_iter().take(SHORTLIST) {
+            let score = self.name_score(i, &tokens, &grams);
+            let kind = if score >= 0.9 {
+                MatchKind::NameExact
+            } else if score >= 0.65 {
+                MatchKind::NameSimilar
+            } else {
+                MatchKind::NamePartial
+            };
+            // Los nombres nunca llegan al puntaje de un identificador exacto.
+            let score = score * 0.9;
+            let slot = scored.entry(i).or_insert((score, kind));
+            if slot.0 < score {
+                *slot = (score, kind);
+            }
+        }
+
+        let mut out: Vec<MatchCandidate> = scored
+            .into_iter()
+            .filter(|(_, (score, _))| *score >= MIN_SCORE)
+            .map(|(i, (score, kind))| MatchCandidate {
+                product: self.entries[i].product.clone(),
+                score: (score * 1000.0).round() / 1000.0,
+                kind,
+                confidence: Confidence::from_score(score),
+            })
+            .collect();
+        out.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.product.name.cmp(&b.product.name)));
+        out.truncate(limit);
+        out
+    }
+}
+
+/// Relaciona todas las partidas de una factura contra el catálogo.
+pub fn match_lines(index: &CatalogIndex, req: &MatchRequest) -> Vec<LineMatches> {
+    let limit = req.limit.unwrap_or(5).clamp(1, 50);
+    let aliases: HashMap<String, i64> = req.aliases.iter().map(|(code, id)| (normalize_code(code), *id)).collect();
+    req.lines
+        .iter()
+        .enumerate()
+        .map(|(index_in_request, line)| LineMatches {
+            index: index_in_request,
+            candidates: index.candidates(line, &aliases, limit),
+        })
+        .collect()
+}
+
+fn all_products(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Product>> {
+    let mut stmt = conn.prepare("SELECT * FROM products")?;
+    let rows = stmt.query_map([], Product::from_row)?;
+    rows.collect()
+}
+
+/// Candidatos con nivel de confianza para cada partida de una factura de proveedor.
+#[tauri::command]
+pub fn match_invoice_lines(db: State<'_, Db>, mut request: MatchRequest) -> Result<Vec<LineMatches>, String> {
+    let (products, saved) = db.with_conn(|conn| {
+        let saved = match request.supplier_rfc.as_deref().filter(|r| !r.trim().is_empty()) {
+            Some(rfc) => aliases::for_supplier(conn, rfc)?,
+            None => HashMap::new(),
+        };
+        Ok((all_products(conn)?, saved))
+    })?;
+    for (code, id) in saved {
+        request.aliases.entry(code).or_insert(id);
+    }
+    let index = CatalogIndex::build(products);
+    Ok(match_lines(&index, &request))
+}
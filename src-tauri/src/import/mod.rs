@@ -0,0 +1,13 @@
+//! Importación de compras y catálogos de proveedores.
+//!
+//! - `aliases`: códigos del proveedor confirmados como alias de productos propios.
+//! - `catalog`: carga masiva de productos desde CSV o Excel con mapeo de columnas y vista previa.
+//! - `matching`: relación de partidas de proveedor con productos del catálogo (índice y puntajes).
+//! - `pdf`: texto con posiciones de facturas en PDF y reconstrucción de la tabla de partidas.
+//!
+//! El XML de un CFDI se lee con `cfdi::reader`; aquí va lo que no trae datos estructurados.
+
+pub mod aliases;
+pub mod catalog;
+pub mod matching;
+pub mod pdf;
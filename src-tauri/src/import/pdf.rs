@@ -0,0 +1,534 @@
+//! Facturas de proveedor en PDF: texto con coordenadas y partidas candidatas.
+//!
+//! `pdf-extract` decodifica las fuentes y entrega cada carácter con su matriz de texto. Con las
+//! posiciones se arman renglones (misma línea base) y celdas (huecos anchos entre palabras).
+//! Si hay un encabezado de tabla (Cantidad, Descripción, Importe, ...) las celdas se asignan
+//! a sus columnas y los renglones sin números se pegan a la descripción de la partida anterior;
+//! si no, se toman los renglones que terminan en importes. Cada partida lleva una confianza
+//! de 0 a 1 según qué tan completa está y si cantidad × precio cuadra con el importe.
+
+use crate::cfdi::validate;
+use pdf_extract::{Document, MediaBox, OutputDev, OutputError, Transform};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::path::Path;
+
+/// Un carácter en coordenadas de página (origen arriba a la izquierda, en puntos).
+#[derive(Debug, Clone)]
+struct Glyph {
+    page: u32,
+    x: f64,
+    y: f64,
+    width: f64,
+    size: f64,
+    text: String,
+}
+
+#[derive(Default)]
+struct Collector {
+    page: u32,
+    top: f64,
+    glyphs: Vec<Glyph>,
+}
+
+impl OutputDev for Collector {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        self.page = page_num;
+        self.top = media_box.ury;
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn output_character(&mut self, trm: &Transform, width: f64, _: f64, font_size: f64, ch: &str) -> Result<(), OutputError> {
+        let scale = (trm.m11 * trm.m22 - trm.m12 * trm.m21).abs().sqrt();
+        let size = if scale > 0.0 { font_size * scale } else { font_size };
+        self.glyphs.push(Glyph {
+            page: self.page,
+            x: trm.m31,
+            y: self.top - trm.m32,
+            width: width * size,
+            size: size.max(1.0),
+            text: ch.to_string(),
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfWord {
+    pub x0: f64,
+    pub x1: f64,
+    pub text: String,
+}
+
+/// Palabras separadas por un hueco ancho (más de ~un carácter).
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfCell {
+    pub x0: f64,
+    pub x1: f64,
+    pub text: String,
+    #[serde(skip)]
+    pub words: Vec<PdfWord>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfRow {
+    pub page: u32,
+    /// Línea base desde arriba de la página.
+    pub y: f64,
+    pub size: f64,
+    pub cells: Vec<PdfCell>,
+}
+
+impl PdfRow {
+    pub fn text(&self) -> String {
+        self.cells.iter().map(|c| c.text.as_str()).collect::<Vec<_>>().join("  ")
+    }
+
+    fn words(&self) -> impl Iterator<Item = &PdfWord> {
+        self.cells.iter().flat_map(|c| c.words.iter())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateLine {
+    pub page: u32,
+    pub description: String,
+    pub code: Option<String>,
+    pub unit: Option<String>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub quantity: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub unit_price: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub amount: Option<Decimal>,
+    /// 0 a 1.
+    pub confidence: f64,
+    /// Texto de los renglones de los que salió la partida.
+    pub raw: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfExtraction {
+    pub pages: u32,
+    /// Se encontró el encabezado de la tabla de partidas.
+    pub header_found: bool,
+    /// UUID del CFDI si el PDF es su representación impresa.
+    pub uuid: Option<String>,
+    /// RFCs que aparecen en el documento (emisor primero, normalmente).
+    pub rfcs: Vec<String>,
+    pub rows: Vec<PdfRow>,
+    pub lines: Vec<CandidateLine>,
+}
+
+fn is_blank(text: &str) -> bool {
+    text.chars().all(char::is_whitespace)
+}
+
+/// Agrupa los caracteres en renglones, palabras y celdas.
+fn build_rows(mut glyphs: Vec<Glyph>) -> Vec<PdfRow> {
+    glyphs.sort_by(|a, b| a.page.cmp(&b.page).then(a.y.total_cmp(&b.y)).then(a.x.total_cmp(&b.x)));
+    let mut lines: Vec<Vec<Glyph>> = Vec::new();
+    for g in glyphs {
+        match lines.last_mut() {
+            Some(line) if line[0].page == g.page && (g.y - line[0].y).abs() <= 0.4 * line[0].size.max(g.size) => {
+                line.push(g)
+            }
+            _ => lines.push(vec![g]),
+        }
+    }
+
+    let mut rows = Vec::new();
+    for mut line in lines {
+        line.sort_by(|a, b| a.x.total_cmp(&b.x));
+        let size = line.iter().map(|g| g.size).fold(0.0, f64::max);
+        let mut words: Vec<PdfWord> = Vec::new();
+        let mut last_end: Option<f64> = None;
+        for g in line.iter().filter(|g| !is_blank(&g.text)) {
+            let gap = last_end.map(|end| g.x - end);
+            match (words.last_mut(), gap) {
+                (Some(word), Some(gap)) if gap < 0.2 * g.size => {
+                    word.text.push_str(&g.text);
+                    word.x1 = g.x + g.width;
+                }
+                _ => words.push(PdfWord { x0: g.x, x1: g.x + g.width, text: g.text.clone() }),
+            }
+            last_end = Some(g.x + g.width);
+        }
+        if words.is_empty() {
+            continue;
+        }
+        let mut cells: Vec<PdfCell> = Vec::new();
+        for word in words {
+            match cells.last_mut() {
+                Some(cell) if word.x0 - cell.x1 < 0.9 * size => {
+                    cell.text.push(' ');
+                    cell.text.push_str(&word.text);
+                    cell.x1 = word.x1;
+                    cell.words.push(word);
+                }
+                _ => cells.push(PdfCell { x0: word.x0, x1: word.x1, text: word.text.clone(), words: vec![word] }),
+            }
+        }
+        rows.push(PdfRow { page: line[0].page, y: line[0].y, size, cells });
+    }
+    rows
+}
+
+/// Importe como aparece impreso: `$1,234.50`, `1 234.50`, `12,50`.
+pub fn parse_amount(text: &str) -> Option<Decimal> {
+    let cleaned: String = text
+        .trim()
+        .trim_start_matches('$')
+        .trim_end_matches("MXN")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '$')
+        .collect();
+    if cleaned.is_empty() || !cleaned.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',' || c == '-') {
+        return None;
+    }
+    if !cleaned.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let normalized = match (cleaned.rfind('.'), cleaned.rfind(',')) {
+        // 1.234,50
+        (Some(dot), Some(comma)) if comma > dot => cleaned.replace('.', "").replace(',', "."),
+        // 12,50 (coma decimal) frente a 1,234 (miles)
+        (None, Some(comma)) if cleaned.len() - comma - 1 <= 2 && cleaned.matches(',').count() == 1 => {
+            cleaned.replace(',', ".")
+        }
+        _ => cleaned.replace(',', ""),
+    };
+    normalized.parse().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Column {
+    Quantity,
+    Unit,
+    Code,
+    SatKey,
+    Description,
+    UnitPrice,
+    Discount,
+    Tax,
+    Amount,
+}
+
+/// Minúsculas y sin acentos para comparar encabezados.
+fn fold(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' => 'a',
+            'é' => 'e',
+            'í' => 'i',
+            'ó' => 'o',
+            'ú' | 'ü' => 'u',
+            c => c,
+        })
+        .collect()
+}
+
+/// Columnas que reconoce el encabezado, con su extensión horizontal.
+fn header_columns(row: &PdfRow) -> Option<Vec<(Column, f64, f64)>> {
+    let words: Vec<&PdfWord> = row.words().collect();
+    let mut columns: Vec<(Column, f64, f64)> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let t = fold(&words[i].text);
+        let next = words.get(i + 1).map(|w| fold(&w.text)).unwrap_or_default();
+        let (kind, span) = if t.starts_with("clave") || t.starts_with("cve") {
+            if next.starts_with("unidad") || next.starts_with("unid") {
+                (Some(Column::Unit), 2)
+            } else if next.starts_with("prod") || next.starts_with("sat") {
+                (Some(Column::SatKey), 2)
+            } else {
+                (Some(Column::Code), 1)
+            }
+        } else if t.starts_with("cant") || t == "qty" {
+            (Some(Column::Quantity), 1)
+        } else if t.starts_with("unidad") || t.starts_with("unid") || t == "um" || t == "u.m." || t == "u/m" {
+            (Some(Column::Unit), 1)
+        } else if t.starts_with("codigo") || t == "sku" || t.starts_with("ident") || t == "no." && next.starts_with("ident") {
+            (Some(Column::Code), if t == "no." { 2 } else { 1 })
+        } else if t.starts_with("descuento") || t == "dto." || t == "desc." {
+            (Some(Column::Discount), 1)
+        } else if t.starts_with("descrip") || t.starts_with("concepto") || t.starts_with("producto") || t.starts_with("articulo") {
+            (Some(Column::Description), 1)
+        } else if t.starts_with("precio") || t.starts_with("valor") || t.starts_with("unitario") || t == "p.u." || t == "p.unit" {
+            (Some(Column::UnitPrice), 1)
+        } else if t.starts_with("importe") || t == "total" || t.starts_with("monto") || t == "subtotal" {
+            (Some(Column::Amount), 1)
+        } else if t == "iva" || t.starts_with("impuesto") {
+            (Some(Column::Tax), 1)
+        } else {
+            (None, 1)
+        };
+        if let Some(kind) = kind {
+            let x1 = words[i + span - 1].x1.max(words[i].x1);
+            match columns.last_mut() {
+                // "Precio Unitario", "Valor Unitario": una sola columna.
+                Some(last) if last.0 == kind => last.2 = x1,
+                _ => columns.push((kind, words[i].x0, x1)),
+            }
+        }
+        i += span.min(words.len() - i);
+    }
+    let has = |k: Column| columns.iter().any(|c| c.0 == k);
+    let numeric = has(Column::Amount) || has(Column::UnitPrice);
+    (columns.len() >= 3 && has(Column::Description) && numeric).then_some(columns)
+}
+
+/// Renglones que cierran la tabla.
+fn is_table_end(row: &PdfRow) -> bool {
+    let first = row.cells.first().map(|c| fold(&c.text)).unwrap_or_default();
+    ["subtotal", "sub-total", "total", "importe con letra", "son:", "cantidad con letra", "observaciones"]
+        .iter()
+        .any(|k| first.starts_with(k))
+}
+
+fn confidence(line: &CandidateLine, with_header: bool) -> f64 {
+    let mut score: f64 = if with_header { 0.4 } else { 0.15 };
+    if line.description.chars().filter(|c| c.is_alphabetic()).count() >= 3 {
+        score += 0.1;
+    }
+    for value in [line.quantity, line.unit_price, line.amount] {
+        if value.is_some_and(|v| v > Decimal::ZERO) {
+            score += 0.1;
+        }
+    }
+    if let (Some(q), Some(p), Some(a)) = (line.quantity, line.unit_price, line.amount) {
+        // Se acepta un centavo o el 1 % (precios con más decimales o descuentos chicos).
+        let diff = (q * p - a).abs();
+        if diff <= Decimal::new(2, 2) || diff <= a.abs() / Decimal::from(100) {
+            score += 0.2;
+        } else {
+            score -= 0.2;
+        }
+    }
+    (score.clamp(0.0, 1.0) * 100.0).round() / 100.0
+}
+
+/// Parte una celda donde empieza una columna del encabezado: columnas angostas quedan pegadas
+/// con un solo espacio (un código largo junto a la descripción).
+fn split_at_columns(cell: &PdfCell, columns: &[(Column, f64, f64)], size: f64) -> Vec<PdfCell> {
+    let mut parts: Vec<PdfCell> = Vec::new();
+    for (i, word) in cell.words.iter().enumerate() {
+        let starts_column = i > 0
+            && columns
+                .iter()
+                .skip(1)
+                .any(|c| c.1 > cell.words[i - 1].x1 - 1.0 && c.1 < word.x0 + size);
+        match parts.last_mut() {
+            Some(part) if !starts_column => {
+                part.text.push(' ');
+                part.text.push_str(&word.text);
+                part.x1 = word.x1;
+            }
+            _ => parts.push(PdfCell { x0: word.x0, x1: word.x1, text: word.text.clone(), words: Vec::new() }),
+        }
+    }
+    parts
+}
+
+/// Columna de cada celda: la de mayor traslape, con límites a la mitad entre encabezados.
+fn assign(row: &PdfRow, columns: &[(Column, f64, f64)]) -> Vec<(Column, String)> {
+    let centers: Vec<f64> = columns.iter().map(|c| (c.1 + c.2) / 2.0).collect();
+    let bounds: Vec<(f64, f64)> = (0..columns.len())
+        .map(|i| {
+            let left = if i == 0 { f64::MIN } else { (centers[i - 1] + centers[i]) / 2.0 };
+            let right = if i + 1 == columns.len() { f64::MAX } else { (centers[i] + centers[i + 1]) / 2.0 };
+            (left, right)
+        })
+        .collect();
+    let mut out: Vec<(Column, String)> = Vec::new();
+    for cell in row.cells.iter().flat_map(|c| split_at_columns(c, columns, row.size)) {
+        let best = bounds
+            .iter()
+            .enumerate()
+            .map(|(i, (l, r))| (i, cell.x1.min(*r) - cell.x0.max(*l)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| columns[i].0);
+        if let Some(kind) = best {
+            match out.iter_mut().find(|(k, _)| *k == kind) {
+                Some((_, text)) => {
+                    text.push(' ');
+                    text.push_str(&cell.text);
+                }
+                None => out.push((kind, cell.text.clone())),
+            }
+        }
+    }
+    out
+}
+
+fn lines_with_header(rows: &[PdfRow]) -> Option<Vec<CandidateLine>> {
+    let start = rows.iter().position(|r| header_columns(r).is_some())?;
+    let mut columns = header_columns(&rows[start])?;
+    let mut lines: Vec<CandidateLine> = Vec::new();
+    let mut in_table = true;
+    let mut last_y: Option<(u32, f64, f64)> = None;
+    for row in &rows[start + 1..] {
+        // El encabezado se repite en cada página.
+        if let Some(again) = header_columns(row) {
+            columns = again;
+            in_table = true;
+            last_y = None;
+            continue;
+        }
+        if !in_table {
+            continue;
+        }
+        if is_table_end(row) {
+            in_table = false;
+            continue;
+        }
+        let cells = assign(row, &columns);
+        let get = |k: Column| cells.iter().find(|(c, _)| *c == k).map(|(_, t)| t.trim().to_string());
+        let quantity = get(Column::Quantity).and_then(|t| parse_amount(&t));
+        let unit_price = get(Column::UnitPrice).and_then(|t| parse_amount(&t));
+        let amount = get(Column::Amount).and_then(|t| parse_amount(&t));
+        let description = get(Column::Description).unwrap_or_default();
+        let close = last_y.is_some_and(|(page, y, size)| page == row.page && row.y - y <= 2.2 * size);
+        if quantity.is_none() && unit_price.is_none() && amount.is_none() {
+            // Descripción en varios renglones.
+            if let (Some(prev), true, false) = (lines.last_mut(), close, description.is_empty()) {
+                prev.description.push(' ');
+                prev.description.push_str(&description);
+                prev.raw.push('\n');
+                prev.raw.push_str(&row.text());
+                last_y = Some((row.page, row.y, row.size));
+            }
+            continue;
+        }
+        lines.push(CandidateLine {
+            page: row.page,
+            description,
+            code: get(Column::Code).filter(|c| !c.is_empty()),
+            unit: get(Column::Unit).filter(|u| !u.is_empty()),
+            quantity,
+            unit_price,
+            amount,
+            confidence: 0.0,
+            raw: row.text(),
+        });
+        last_y = Some((row.page, row.y, row.size));
+    }
+    for line in lines.iter_mut() {
+        line.confidence = confidence(line, true);
+    }
+    Some(lines)
+}
+
+/// Sin encabezado: renglones con texto y al menos dos importes al final.
+fn lines_without_header(rows: &[PdfRow]) -> Vec<CandidateLine> {
+    let mut lines = Vec::new();
+    for row in rows {
+        if is_table_end(row) {
+            continue;
+        }
+        let numbers: Vec<Option<Decimal>> = row.cells.iter().map(|c| parse_amount(&c.text)).collect();
+        let trailing = numbers.iter().rev().take_while(|n| n.is_some()).count();
+        if trailing < 2 || trailing == row.cells.len() {
+            continue;
+        }
+        let text_cells = &row.cells[..row.cells.len() - trailing];
+        let values: Vec<Decimal> = numbers[numbers.len() - trailing..].iter().flatten().copied().collect();
+        // Cantidad al inicio ("2  Papas") o como primer número de tres.
+        let mut description = text_cells.iter().map(|c| c.text.as_str()).collect::<Vec<_>>().join(" ");
+        let mut quantity = (values.len() >= 3).then(|| values[0]);
+        if quantity.is_none() {
+            if let Some((first, rest)) = description.split_once(' ') {
+                if let Some(q) = parse_amount(first) {
+                    quantity = Some(q);
+                    description = rest.to_string();
+                }
+            }
+        }
+        let mut line = CandidateLine {
+            page: row.page,
+            description,
+            code: None,
+            unit: None,
+            quantity,
+            unit_price: values.get(values.len() - 2).copied(),
+            amount: values.last().copied(),
+            confidence: 0.0,
+            raw: row.text(),
+        };
+        line.confidence = confidence(&line, false);
+        lines.push(line);
+    }
+    lines
+}
+
+/// UUID y RFCs impresos en el documento.
+fn identifiers(rows: &[PdfRow]) -> (Option<String>, Vec<String>) {
+    let mut uuid = None;
+    let mut rfcs: Vec<String> = Vec::new();
+    for word in rows.iter().flat_map(|r| r.words()) {
+        let token = word.text.trim_matches(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '&' && c != 'Ñ');
+        let upper = token.to_uppercase();
+        // "Folio fiscal:" suele venir pegado a dos puntos.
+        let candidate = upper.rsplit(':').next().unwrap_or(&upper);
+        if uuid.is_none() && validate::is_uuid(candidate) {
+            uuid = Some(candidate.to_string());
+        } else if validate::is_rfc(candidate) && !rfcs.iter().any(|r| r == candidate) {
+            rfcs.push(candidate.to_string());
+        }
+    }
+    (uuid, rfcs)
+}
+
+/// Lee el PDF, arma los renglones y propone las partidas.
+pub fn extract(path: &Path) -> Result<PdfExtraction, String> {
+    let mut doc = Document::load(path).map_err(|e| format!("No se pudo abrir el PDF: {}", e))?;
+    if doc.is_encrypted() {
+        doc.decrypt("").map_err(|_| "El PDF está protegido con contraseña.".to_string())?;
+    }
+    let pages = doc.get_pages().len() as u32;
+    let mut collector = Collector::default();
+    // pdf-extract entra en pánico con algunas fuentes raras; se reporta como error.
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        pdf_extract::output_doc(&doc, &mut collector)
+    }));
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return Err(format!("No se pudo leer el texto del PDF: {:?}", e)),
+        Err(_) => return Err("No se pudo leer el texto del PDF (fuente no soportada).".to_string()),
+    }
+    if collector.glyphs.iter().all(|g| is_blank(&g.text)) {
+        return Err("El PDF no contiene texto extraíble (puede ser solo imagen). Use XML (CFDI) o CSV.".to_string());
+    }
+    let rows = build_rows(collector.glyphs);
+    let (uuid, rfcs) = identifiers(&rows);
+    let (header_found, lines) = match lines_with_header(&rows) {
+        Some(lines) if !lines.is_empty() => (true, lines),
+        _ => (false, lines_without_header(&rows)),
+    };
+    log::info!("pdf: {} página(s), {} renglones, {} partidas candidatas", pages, rows.len(), lines.len());
+    Ok(PdfExtraction { pages, header_found, uuid, rfcs, rows, lines })
+}
+
+/// Texto con posiciones y partidas candidatas de una factura en PDF. La ruta viene del diálogo
+/// de archivos (plugin dialog), igual que en la importación de catálogos.
+#[tauri::command]
+pub fn import_pdf_invoice(path: String) -> Result<PdfExtraction, String> {
+    extract(Path::new(&path))
+}
@@ -0,0 +1,337 @@
+//! Kardex: movimientos de inventario de solo escritura.
+//!
+//! Cada cambio de `products.stock` deja un renglón con el tipo de movimiento, la cantidad con
+//! signo, el documento de referencia, el usuario y la existencia que resultó. La tabla no se
+//! puede modificar ni borrar (triggers de la migración 14), y la suma de las cantidades de un
+//! producto debe dar su `stock`; `reconcile` lista los que no cuadran.
+//! Las ventas, cancelaciones y compras registran su movimiento en la misma transacción que
+//! mueve el stock; devoluciones, ajustes y traspasos se capturan con `inventory_record_movement`.
+//! Las cantidades pueden llevar decimales (productos a granel); la suma se hace en Rust con
+//! `Decimal` para no arrastrar errores de punto flotante.
+
+use crate::db::{self, Db};
+use crate::units;
+use rusqlite::{params, Connection, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementType {
+    Sale,
+    Return,
+    Purchase,
+    Adjustment,
+    Transfer,
+    /// Stock que regresa al cancelar una venta.
+    Cancellation,
+}
+
+impl MovementType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MovementType::Sale => "sale",
+            MovementType::Return => "return",
+            MovementType::Purchase => "purchase",
+            MovementType::Adjustment => "adjustment",
+            MovementType::Transfer => "transfer",
+            MovementType::Cancellation => "cancellation",
+        }
+    }
+}
+
+/// Movimiento por registrar; `quantity` lleva signo (negativo = salida).
+#[derive(Debug, Clone)]
+pub struct NewMovement<'a> {
+    pub product_id: i64,
+    pub kind: MovementType,
+    pub quantity: Decimal,
+    /// Tipo de documento (`sale`, `purchase`, `stocktake`, `sync`, ...).
+    pub reference_type: Option<&'a str>,
+    pub reference_id: Option<i64>,
+    /// Folio legible: número de venta, UUID de la factura, ...
+    pub reference: Option<&'a str>,
+    pub user_id: Option<&'a str>,
+    pub notes: Option<&'a str>,
+}
+
+impl<'a> NewMovement<'a> {
+    pub fn new(product_id: i64, kind: MovementType, quantity: Decimal) -> Self {
+        NewMovement {
+            product_id,
+            kind,
+            quantity,
+            reference_type: None,
+            reference_id: None,
+            reference: None,
+            user_id: None,
+            notes: None,
+        }
+    }
+
+    pub fn document(mut self, reference_type: &'a str, reference_id: i64, reference: Option<&'a str>) -> Self {
+        self.reference_type = Some(reference_type);
+        self.reference_id = Some(reference_id);
+        self.reference = reference;
+        self
+    }
+
+    pub fn by(mut self, user_id: Option<&'a str>) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    pub fn notes(mut self, notes: Option<&'a str>) -> Self {
+        self.notes = notes;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryMovement {
+    pub id: i64,
+    pub product_id: i64,
+    pub product_code: Option<String>,
+    pub product_name: Option<String>,
+    pub movement_type: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+    /// Existencia después del movimiento.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub balance: Decimal,
+    pub reference_type: Option<String>,
+    pub reference_id: Option<i64>,
+    pub reference: Option<String>,
+    pub user_id: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+}
+
+impl InventoryMovement {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(InventoryMovement {
+            id: row.get("id")?,
+            product_id: row.get("product_id")?,
+            product_code: row.get("product_code")?,
+            product_name: row.get("product_name")?,
+            movement_type: row.get("movement_type")?,
+            quantity: db::get_quantity(row, "quantity")?,
+            balance: db::get_quantity(row, "balance")?,
+            reference_type: row.get("reference_type")?,
+            reference_id: row.get("reference_id")?,
+            reference: row.get("reference")?,
+            user_id: row.get("user_id")?,
+            notes: row.get("notes")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+/// Anota un movimiento cuyo stock ya se escribió en `products`; el saldo es la existencia actual.
+pub(crate) fn log_movement(conn: &Connection, m: &NewMovement) -> rusqlite::Result<i64> {
+    if m.quantity.is_zero() {
+        return Ok(0);
+    }
+    let balance = current_stock(conn, m.product_id)?;
+    conn.execute(
+        "INSERT INTO inventory_movements (product_id, movement_type, quantity, balance, reference_type,
+             reference_id, reference, user_id, notes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            m.product_id, m.kind.as_str(), db::quantity_value(m.quantity), db::quantity_value(balance),
+            m.reference_type, m.reference_id, m.reference, m.user_id, m.notes
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn current_stock(conn: &Connection, product_id: i64) -> rusqlite::Result<Decimal> {
+    conn.query_row("SELECT stock FROM products WHERE id = ?1", [product_id], |r| db::get_quantity(r, 0))
+}
+
+/// Mueve el stock del producto y lo anota en el kardex.
+pub(crate) fn post(conn: &Connection, m: &NewMovement) -> rusqlite::Result<i64> {
+    if m.quantity.is_zero() {
+        return Ok(0);
+    }
+    let stock = current_stock(conn, m.product_id)? + m.quantity;
+    conn.execute(
+        "UPDATE products SET stock = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+        params![m.product_id, db::quantity_value(stock)],
+    )?;
+    log_movement(conn, m)
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MovementFilter {
+    #[serde(default)]
+    pub product_id: Option<i64>,
+    #[serde(default)]
+    pub movement_type: Option<MovementType>,
+    /// Fechas ISO (`AAAA-MM-DD` o con hora), inclusivas.
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+pub fn list_movements(conn: &Connection, f: &MovementFilter) -> rusqlite::Result<Vec<InventoryMovement>> {
+    // `to` sin hora incluye todo ese día.
+    let to = f.to.as_deref().map(|t| if t.len() == 10 { format!("{}T23:59:59.999Z", t) } else { t.to_string() });
+    let mut stmt = conn.prepare(
+        "SELECT m.*, p.code AS product_code, p.name AS product_name
+         FROM inventory_movements m LEFT JOIN products p ON p.id = m.product_id
+         WHERE (?1 IS NULL OR m.product_id = ?1)
+           AND (?2 IS NULL OR m.movement_type = ?2)
+           AND (?3 IS NULL OR m.created_at >= ?3)
+           AND (?4 IS NULL OR m.created_at <= ?4)
+         ORDER BY m.id DESC LIMIT ?5",
+    )?;
+    let rows = stmt.query_map(
+        params![f.product_id, f.movement_type.map(|t| t.as_str()), f.from, to, f.limit.unwrap_or(500)],
+        InventoryMovement::from_row,
+    )?;
+    rows.collect()
+}
+
+/// Producto cuyo `stock` no coincide con la suma de su kardex.
+#[derive(Debug, Clone, Serialize)]
+pub struct StockDiscrepancy {
+    pub product_id: i64,
+    pub code: String,
+    pub name: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub stock: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub ledger_stock: Decimal,
+    /// `stock - ledger_stock`.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub difference: Decimal,
+}
+
+pub fn reconcile(conn: &Connection) -> rusqlite::Result<Vec<StockDiscrepancy>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.code, p.name, p.stock, IFNULL(SUM(m.quantity), 0) AS ledger
+         FROM products p LEFT JOIN inventory_movements m ON m.product_id = p.id
+         GROUP BY p.id ORDER BY p.name",
+    )?;
+    let rows = stmt.query_map([], |r| {
+        let stock = db::get_quantity(r, 3)?;
+        let ledger_stock = db::get_quantity(r, 4)?;
+        Ok(StockDiscrepancy {
+            product_id: r.get(0)?,
+            code: r.get(1)?,
+            name: r.get(2)?,
+            stock,
+            ledger_stock,
+            difference: stock - ledger_stock,
+        })
+    })?;
+    // La suma de REAL puede traer ruido; se compara ya redondeada.
+    rows.filter(|d| !matches!(d, Ok(d) if d.difference.is_zero())).collect()
+}
+
+/// Movimientos capturados a mano: devolución de cliente, ajuste o traspaso.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MovementInput {
+    pub product_id: i64,
+    pub movement_type: MovementType,
+    /// Con signo: positivo entra, negativo sale.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+pub fn record_manual(conn: &mut Connection, input: &MovementInput) -> Result<InventoryMovement, String> {
+    match input.movement_type {
+        MovementType::Return if input.quantity <= Decimal::ZERO => {
+            return Err("Una devolución regresa mercancía: la cantidad debe ser positiva.".to_string())
+        }
+        MovementType::Return | MovementType::Adjustment | MovementType::Transfer => {}
+        other => {
+            return Err(format!(
+                "Los movimientos de tipo {} los registra su documento (venta, compra o cancelación).",
+                other.as_str()
+            ))
+        }
+    }
+    if input.quantity.is_zero() {
+        return Err("La cantidad del movimiento no puede ser cero.".to_string());
+    }
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let tx = conn.transaction().map_err(sql)?;
+    let product = db::get_product(&tx, input.product_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró el producto {}.", input.product_id))?;
+    units::check_precision(input.quantity, product.unit, product.precision(), &product.name)?;
+    let reference = input.reference.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    let movement = NewMovement {
+        reference_type: Some("manual"),
+        reference,
+        ..NewMovement::new(input.product_id, input.movement_type, input.quantity)
+    }
+    .by(input.user_id.as_deref())
+    .notes(input.notes.as_deref());
+    let id = post(&tx, &movement).map_err(sql)?;
+    crate::sync::enqueue_stock_delta(&tx, input.product_id, input.quantity).map_err(sql)?;
+    let saved = tx
+        .query_row(
+            "SELECT m.*, p.code AS product_code, p.name AS product_name
+             FROM inventory_movements m LEFT JOIN products p ON p.id = m.product_id WHERE m.id = ?1",
+            [id],
+            InventoryMovement::from_row,
+        )
+        .map_err(sql)?;
+    tx.commit().map_err(sql)?;
+    log::info!(
+        "kardex: {} {} del producto {} (saldo {})",
+        saved.movement_type,
+        saved.quantity,
+        saved.product_id,
+        saved.balance
+    );
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn inventory_list_movements(db: State<'_, Db>, filter: Option<MovementFilter>) -> Result<Vec<InventoryMovement>, String> {
+    db.with_conn(|conn| list_movements(conn, &filter.unwrap_or_default()))
+}
+
+#[tauri::command]
+pub fn inventory_record_movement(db: State<'_, Db>, movement: MovementInput) -> Result<InventoryMovement, String> {
+    let mut conn = db.lock()?;
+    record_manual(&mut conn, &movement)
+}
+
+/// Productos cuyo stock no cuadra con el kardex. Con `fix`, se anota un ajuste por la
+/// diferencia para que el kardex llegue a la existencia actual (el stock no cambia).
+#[tauri::command]
+pub fn inventory_reconcile(db: State<'_, Db>, fix: Option<bool>, user_id: Option<String>) -> Result<Vec<StockDiscrepancy>, String> {
+    db.with_conn(|conn| {
+        let found = reconcile(conn)?;
+        if fix.unwrap_or(false) && !found.is_empty() {
+            let tx = conn.transaction()?;
+            for d in &found {
+                let movement = NewMovement {
+                    reference_type: Some("reconcile"),
+                    ..NewMovement::new(d.product_id, MovementType::Adjustment, d.difference)
+                }
+                .by(user_id.as_deref())
+                .notes(Some("Conciliación del kardex con la existencia"));
+                log_movement(&tx, &movement)?;
+            }
+            tx.commit()?;
+            log::warn!("kardex: {} productos conciliados", found.len());
+        }
+        Ok(found)
+    })
+}
@@ -1,30 +1,24 @@
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::Write;
+use tauri::Manager;
+
+pub mod barcode;
+pub mod cfdi;
+pub mod db;
+pub mod devices;
+pub mod import;
+pub mod inventory;
+pub mod money;
+pub mod purchases;
+pub mod sales;
+pub mod stocktake;
+pub mod sync;
+pub mod tax;
+pub mod ticket;
+pub mod units;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
-/// En Windows: devuelve el nombre de impresora a usar (el dado o la predeterminada). Para enviar bytes crudos sin que PowerShell corrompa el contenido.
-#[cfg(target_os = "windows")]
-fn windows_printer_name(printer_name: &str) -> Result<String, String> {
-    let name = printer_name.trim();
-    if !name.is_empty() {
-        return Ok(name.to_string());
-    }
-    let output = std::process::Command::new("powershell")
-        .creation_flags(0x08000000)
-        .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_Printer -Filter \"Default=$true\").Name"])
-        .output()
-        .map_err(|e| format!("No se pudo obtener impresora: {}", e))?;
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let default = stdout.trim();
-    if default.is_empty() {
-        return Err("Selecciona una impresora en Configuración o define una predeterminada en Windows.".to_string());
-    }
-    Ok(default.to_string())
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 struct Printer {
     name: String,
@@ -156,10 +150,33 @@ fn escpos_code128(data: &str) -> Vec<u8> {
     out
 }
 
+/// Capacidad de un QR modelo 2 (versión 40) en modo byte con corrección M.
+const QR_MAX_BYTES: usize = 2331;
+
+/// QR nativo ESC/POS (GS ( k, modelo 2, corrección M) centrado. Para el bloque de autofactura.
+/// Si el contenido no cabe en un QR devuelve error en lugar de cortarlo.
+fn escpos_qr(data: &str) -> Result<Vec<u8>, String> {
+    let bytes = data.as_bytes();
+    if bytes.len() > QR_MAX_BYTES {
+        return Err(format!("el QR admite hasta {} bytes y el contenido tiene {}", QR_MAX_BYTES, bytes.len()));
+    }
+    let n = bytes.len() + 3;
+    let mut out = Vec::with_capacity(n + 32);
+    out.extend_from_slice(&[0x1B, 0x61, 0x01]); // ESC a 1 (centrar)
+    out.extend_from_slice(&[0x1D, 0x28, 0x6B, 0x04, 0x00, 0x31, 0x41, 0x32, 0x00]); // modelo 2
+    out.extend_from_slice(&[0x1D, 0x28, 0x6B, 0x03, 0x00, 0x31, 0x43, 0x05]); // módulo de 5 dots
+    out.extend_from_slice(&[0x1D, 0x28, 0x6B, 0x03, 0x00, 0x31, 0x45, 0x31]); // corrección M
+    out.extend_from_slice(&[0x1D, 0x28, 0x6B, (n & 0xFF) as u8, (n >> 8) as u8, 0x31, 0x50, 0x30]);
+    out.extend_from_slice(bytes);
+    out.extend_from_slice(&[0x1D, 0x28, 0x6B, 0x03, 0x00, 0x31, 0x51, 0x30]); // imprimir
+    out.push(b'\n');
+    out.extend_from_slice(&[0x1B, 0x61, 0x00]); // ESC a 0
+    Ok(out)
+}
+
 /// Print barcode labels to the same thermal printer as tickets.
 #[tauri::command]
 fn print_barcode_labels(printer_name: String, labels: Vec<BarcodeLabel>) -> Result<(), String> {
-    use std::io::Write;
     log::info!("print_barcode_labels: {} labels, printer: {:?}", labels.len(), printer_name.trim());
 
     let name_lower = printer_name.to_lowercase();
@@ -221,77 +238,20 @@ fn print_barcode_labels(printer_name: String, labels: Vec<BarcodeLabel>) -> Resu
     }
     log::warn!("print_barcode_labels: USB direct failed, fallback to lp/spooler");
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        let temp_dir = std::env::temp_dir();
-        let file_name = format!(
-            "pos_barcodes_{}.bin",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis()
-        );
-        let path = temp_dir.join(&file_name);
-        let mut file = File::create(&path).map_err(|e| format!("Failed to create temp file: {}", e))?;
-        file.write_all(&to_send).map_err(|e| format!("Failed to write barcodes: {}", e))?;
-        file.sync_all().map_err(|e| format!("Failed to sync file: {}", e))?;
-        drop(file);
-        let path_str = path.to_string_lossy();
-        let mut cmd = Command::new("lp");
-        if !printer_name.trim().is_empty() {
-            cmd.arg("-d").arg(printer_name.trim());
-        }
-        let status = cmd
-            .args(["-o", "raw", "-o", "document-format=application/octet-stream", path_str.as_ref()])
-            .status();
-        std::fs::remove_file(&path).ok();
-        let exit_status = status.map_err(|e| format!("lp failed: {}", e))?;
-        if !exit_status.success() {
-            return Err("No se pudo imprimir. En macOS agregue la impresora como Raw en http://localhost:631.".to_string());
-        }
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        use std::io::Write;
-        use std::process::{Command, Stdio};
-        let mut cmd = Command::new("lp");
-        if !printer_name.trim().is_empty() {
-            cmd.arg("-d").arg(printer_name.trim());
-        }
-        let mut child = cmd
-            .args(["-o", "raw"])
-            .stdin(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("lp spawn: {}", e))?;
-        if let Some(ref mut stdin) = child.stdin {
-            stdin.write_all(&to_send).map_err(|e| format!("lp stdin: {}", e))?;
-        }
-        drop(child.stdin.take());
-        let exit_status = child.wait().map_err(|e| format!("lp wait: {}", e))?;
-        if !exit_status.success() {
-            return Err("No se pudo imprimir. Revisa la impresora en Configuración.".to_string());
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        let name = windows_printer_name(&printer_name)?;
-        raw_printer::write_to_device(&name, &to_send, Some("Etiquetas"))
-            .map_err(|e| format!("Error al imprimir: {}", e))?;
-    }
+    devices::printer::print_raw(&printer_name, "Etiquetas", &to_send)?;
 
     log::info!("print_barcode_labels completed");
     Ok(())
 }
 
 /// Print ticket to the given printer (or default if name is empty). Optional logo as base64 data URL.
+/// `ticket_qr` (p. ej. el `qr` de `cfdi_ticket_autofactura`) se imprime como QR al final del texto.
 #[tauri::command]
 fn print_ticket(
     printer_name: String,
     ticket_text: String,
     ticket_logo_base64: Option<String>,
+    ticket_qr: Option<String>,
 ) -> Result<(), String> {
     log::info!(
         "print_ticket called, printer: {:?}, text length: {}, logo: {}",
@@ -319,6 +279,14 @@ fn print_ticket(
     // Quitar espacios al inicio y normalizar saltos: solo LF (\n). \r en Windows suele sacar caracteres raros (EAOI).
     let ticket_trimmed = ticket_text.trim_start().replace("\r\n", "\n").replace('\r', "\n");
     to_send.extend_from_slice(&to_ascii_thermal(&ticket_trimmed));
+    if let Some(qr) = ticket_qr.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        to_send.push(b'\n');
+        match escpos_qr(qr) {
+            Ok(escpos) => to_send.extend(escpos),
+            // Sin QR el cliente todavía puede facturar con la liga y el código impresos arriba.
+            Err(e) => log::warn!("print_ticket: se omite el QR, {}", e),
+        }
+    }
     to_send.extend_from_slice(&[0x1B, 0x64, 0x05]); // ESC d 5 (feed)
     to_send.extend_from_slice(&[0x1D, 0x56, 0x00]); // GS V 0 (corte parcial)
 
@@ -329,75 +297,15 @@ fn print_ticket(
     }
     log::warn!("print_ticket: USB direct failed, fallback to lp/spooler");
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        let temp_dir = std::env::temp_dir();
-        let file_name = format!(
-            "pos_ticket_{}.bin",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis()
-        );
-        let path = temp_dir.join(&file_name);
-        let mut file = File::create(&path).map_err(|e| format!("Failed to create temp file: {}", e))?;
-        file.write_all(&to_send).map_err(|e| format!("Failed to write ticket: {}", e))?;
-        file.sync_all().map_err(|e| format!("Failed to sync file: {}", e))?;
-        drop(file);
-        let path_str = path.to_string_lossy();
-        let mut cmd = Command::new("lp");
-        if !printer_name.trim().is_empty() {
-            cmd.arg("-d").arg(printer_name.trim());
-        }
-        let status = cmd
-            .args(["-o", "raw", "-o", "document-format=application/octet-stream", path_str.as_ref()])
-            .status();
-        std::fs::remove_file(&path).ok();
-        let exit_status = status.map_err(|e| format!("lp failed: {}", e))?;
-        if !exit_status.success() {
-            return Err("No se pudo imprimir. En macOS agregue la impresora como Raw en http://localhost:631 (Administration > Add Printer > USB > Make: Raw).".to_string());
-        }
-        log::info!("print_ticket: lp -o raw (file .bin) completed");
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        use std::io::Write;
-        use std::process::{Command, Stdio};
-        let mut cmd = Command::new("lp");
-        if !printer_name.trim().is_empty() {
-            cmd.arg("-d").arg(printer_name.trim());
-        }
-        let mut child = cmd
-            .args(["-o", "raw"])
-            .stdin(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("lp spawn: {}", e))?;
-        if let Some(ref mut stdin) = child.stdin {
-            stdin.write_all(&to_send).map_err(|e| format!("lp stdin: {}", e))?;
-        }
-        drop(child.stdin.take());
-        let exit_status = child.wait().map_err(|e| format!("lp wait: {}", e))?;
-        if !exit_status.success() {
-            return Err("No se pudo imprimir. En Ajustes del sistema → Impresoras, revisa que la impresora esté “Aceptando trabajos” y que uses controlador Genérico o Raw si está disponible.".to_string());
-        }
-        log::info!("print_ticket: lp -o raw (stdin) completed");
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        let name = windows_printer_name(&printer_name)?;
-        raw_printer::write_to_device(&name, &to_send, Some("Ticket"))
-            .map_err(|e| format!("Error al imprimir: {}", e))?;
-    }
+    devices::printer::print_raw(&printer_name, "Ticket", &to_send)?;
 
     log::info!("print_ticket completed");
     Ok(())
 }
 
 /// Imprime un ticket de prueba de 2 líneas (poco rollo) para probar la impresora.
-/// Devuelve mensaje con la impresora usada y bytes enviados (para depurar).
+/// En Windows devuelve la impresora usada (la predeterminada ya resuelta si no se eligió) y los
+/// bytes enviados, para depurar; en macOS y Linux, solo si se envió.
 #[tauri::command]
 fn print_test_ticket(printer_name: String) -> Result<String, String> {
     let ticket_text = "PRUEBA\n---\n";
@@ -413,66 +321,24 @@ fn print_test_ticket(printer_name: String) -> Result<String, String> {
         return Ok("Enviado por USB directo".to_string());
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        let temp_dir = std::env::temp_dir();
-        let file_name = format!("pos_test_{}.bin", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis());
-        let path = temp_dir.join(&file_name);
-        let mut file = File::create(&path).map_err(|e| format!("{}", e))?;
-        file.write_all(&to_send).map_err(|e| format!("{}", e))?;
-        file.sync_all().ok();
-        drop(file);
-        let path_str = path.to_string_lossy();
-        let mut cmd = Command::new("lp");
-        if !printer_name.trim().is_empty() {
-            cmd.arg("-d").arg(printer_name.trim());
-        }
-        let status = cmd.args(["-o", "raw", "-o", "document-format=application/octet-stream", path_str.as_ref()]).status();
-        std::fs::remove_file(&path).ok();
-        let exit_status = status.map_err(|e| format!("{}", e))?;
-        if !exit_status.success() {
-            return Err("No se pudo imprimir prueba.".to_string());
-        }
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        use std::io::Write;
-        use std::process::{Command, Stdio};
-        let mut cmd = Command::new("lp");
-        if !printer_name.trim().is_empty() {
-            cmd.arg("-d").arg(printer_name.trim());
-        }
-        let mut child = cmd.args(["-o", "raw"]).stdin(Stdio::piped()).spawn().map_err(|e| format!("{}", e))?;
-        if let Some(ref mut stdin) = child.stdin {
-            stdin.write_all(&to_send).map_err(|e| format!("{}", e))?;
-        }
-        drop(child.stdin.take());
-        let exit_status = child.wait().map_err(|e| format!("{}", e))?;
-        if !exit_status.success() {
-            return Err("No se pudo imprimir prueba.".to_string());
-        }
-    }
-
+    use devices::transport::Transport;
+    // En Windows el nombre vacío se resuelve aquí para reportar qué impresora se usó.
     #[cfg(target_os = "windows")]
-    {
-        let name = windows_printer_name(&printer_name)?;
-        log::info!("print_test_ticket: enviando {} bytes a impresora '{}'", to_send.len(), name);
-        match raw_printer::write_to_device(&name, &to_send, Some("Prueba")) {
-            Ok(n) => {
-                log::info!("print_test_ticket: write_to_device OK, {} bytes escritos", n);
-                return Ok(format!("Enviado a '{}' ({} bytes). Revisa la cola de impresión si no sale nada.", name, n));
-            }
-            Err(e) => {
-                log::error!("print_test_ticket: write_to_device error: {}", e);
-                return Err(format!("Error al imprimir en '{}': {}", name, e));
-            }
-        }
-    }
-
+    let printer_name = devices::printer::windows_printer_name(&printer_name)?;
+    let mut printer = devices::printer::SpoolerTransport::new(&printer_name, "Prueba");
+    #[cfg(target_os = "windows")]
+    printer.write_all(&to_send).map_err(|e| e.to_string())?;
+    #[cfg(not(target_os = "windows"))]
+    printer.write_all(&to_send).map_err(|e| {
+        log::error!("print_test_ticket: {}", e);
+        "No se pudo imprimir prueba.".to_string()
+    })?;
     log::info!("print_test_ticket completed");
-    Ok("Impresión enviada".to_string())
+    if cfg!(target_os = "windows") {
+        Ok(format!("Enviado a '{}' ({} bytes). Revisa la cola de impresión si no sale nada.", printer_name, to_send.len()))
+    } else {
+        Ok("Impresión enviada".to_string())
+    }
 }
 
 #[tauri::command]
@@ -663,10 +529,122 @@ pub fn run() {
             .build(),
         )?;
       }
+      let db_path = app.path().app_data_dir()?.join("moneymachine.db");
+      app.manage(db::Db::open(&db_path)?);
       Ok(())
     })
     .plugin(tauri_plugin_shell::init())
-    .invoke_handler(tauri::generate_handler![get_printers, print_ticket, print_barcode_labels, print_test_ticket])
+    .plugin(tauri_plugin_dialog::init())
+    .manage(sync::SyncEngine::default())
+    .manage(devices::scale::ScaleService::default())
+    .manage(devices::display::DisplayService::default())
+    .manage(devices::scanner::ScannerService::default())
+    .invoke_handler(tauri::generate_handler![
+      get_printers,
+      print_ticket,
+      print_barcode_labels,
+      print_test_ticket,
+      db::db_list_products,
+      db::db_get_product,
+      db::db_find_product_by_code,
+      db::db_save_product,
+      db::db_delete_product,
+      db::db_list_customers,
+      db::db_get_customer,
+      db::db_save_customer,
+      db::db_delete_customer,
+      db::db_list_sales,
+      db::db_list_pending_sales,
+      db::db_get_sale,
+      db::db_save_sale,
+      db::db_update_sale_status,
+      db::db_delete_sale,
+      db::db_list_quotations,
+      db::db_get_quotation_by_code,
+      db::db_save_quotation,
+      db::db_update_quotation_status,
+      sales::process_sale,
+      purchases::purchase_receive,
+      purchases::purchase_list,
+      purchases::purchase_get,
+      purchases::purchase_find_by_uuid,
+      inventory::inventory_list_movements,
+      inventory::inventory_record_movement,
+      inventory::inventory_reconcile,
+      stocktake::stocktake_open,
+      stocktake::stocktake_list,
+      stocktake::stocktake_add_counts,
+      stocktake::stocktake_report,
+      stocktake::stocktake_close,
+      stocktake::stocktake_cancel,
+      tax::tax_list_profiles,
+      tax::tax_save_profile,
+      tax::tax_delete_profile,
+      tax::tax_assign_profile,
+      tax::tax_sale_breakdown,
+      cfdi::cfdi_get_settings,
+      cfdi::cfdi_save_settings,
+      cfdi::cfdi_build_invoice,
+      cfdi::cfdi_import_csd,
+      cfdi::cfdi_csd_info,
+      cfdi::cfdi_remove_csd,
+      cfdi::cfdi_seal_invoice,
+      cfdi::cfdi_stamp_invoice,
+      cfdi::cfdi_list_invoices,
+      cfdi::cfdi_get_invoice_xml,
+      cfdi::cfdi_get_pac_settings,
+      cfdi::cfdi_save_pac_settings,
+      cfdi::cfdi_cancel_invoice,
+      cfdi::cfdi_refresh_invoice_status,
+      cfdi::cfdi_get_cancel_acuse,
+      cfdi::cfdi_global_pending_sales,
+      cfdi::cfdi_stamp_global_invoice,
+      cfdi::cfdi_register_payment,
+      cfdi::cfdi_list_payments,
+      cfdi::cfdi_stamp_payment,
+      cfdi::cfdi_delete_payment,
+      cfdi::cfdi_ticket_autofactura,
+      cfdi::cfdi_verify_autofactura,
+      cfdi::cfdi_stamp_autofactura,
+      cfdi::cfdi_parse_xml,
+      import::pdf::import_pdf_invoice,
+      import::catalog::import_catalog_preview,
+      import::catalog::import_catalog_commit,
+      import::catalog::import_list_mappings,
+      import::catalog::import_save_mapping,
+      import::catalog::import_delete_mapping,
+      import::matching::match_invoice_lines,
+      import::aliases::import_learn_aliases,
+      import::aliases::import_list_aliases,
+      import::aliases::import_update_alias,
+      import::aliases::import_delete_alias,
+      ticket::render_sale_ticket,
+      sync::sync_configure,
+      sync::sync_status,
+      sync::sync_now,
+      sync::sync_start,
+      sync::sync_stop,
+      devices::scale::scale_list_ports,
+      devices::scale::scale_connect,
+      devices::scale::scale_disconnect,
+      devices::scale::scale_status,
+      devices::scale::scale_read,
+      devices::scale::scale_simulate,
+      devices::display::display_connect,
+      devices::display::display_disconnect,
+      devices::display::display_status,
+      devices::display::display_item,
+      devices::display::display_total,
+      devices::display::display_change,
+      devices::display::display_idle,
+      devices::scanner::scanner_list_devices,
+      devices::scanner::scanner_connect,
+      devices::scanner::scanner_disconnect,
+      devices::scanner::scanner_status,
+      devices::scanner::scanner_simulate,
+      barcode::barcode_parse,
+      barcode::barcode_lookup,
+    ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
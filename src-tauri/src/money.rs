@@ -0,0 +1,351 @@
+//! Dinero en punto fijo: un entero de centavos más la moneda.
+//!
+//! Reglas de redondeo (las mismas para ventas, impuestos y tickets):
+//! - Toda conversión de un `Decimal` a `Money` redondea a la unidad mínima de la moneda
+//!   con mitad hacia arriba (`0.005 -> 0.01`, `-0.005 -> -0.01`).
+//! - Impuestos por renglón (`TaxRounding::PerLine`): se redondea el impuesto de cada renglón
+//!   y el total es la suma. Por documento (`PerDocument`): se calcula sobre la suma y se
+//!   redondea una sola vez. El ticket y los reportes deben usar la misma regla que la venta.
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Currency {
+    #[default]
+    MXN,
+    USD,
+}
+
+impl Currency {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::MXN => "MXN",
+            Currency::USD => "USD",
+        }
+    }
+
+    /// Decimales de la unidad mínima (centavos).
+    pub fn decimals(&self) -> u32 {
+        2
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        "$"
+    }
+
+    pub fn from_code(code: &str) -> Option<Currency> {
+        match code.trim().to_uppercase().as_str() {
+            "MXN" => Some(Currency::MXN),
+            "USD" => Some(Currency::USD),
+            _ => None,
+        }
+    }
+}
+
+/// Cómo se redondean los impuestos de un documento.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxRounding {
+    PerLine,
+    #[default]
+    PerDocument,
+}
+
+/// Redondeo mitad hacia arriba a `dp` decimales.
+pub fn round_half_up(d: Decimal, dp: u32) -> Decimal {
+    d.round_dp_with_strategy(dp, RoundingStrategy::MidpointAwayFromZero)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn zero(currency: Currency) -> Money {
+        Money { minor: 0, currency }
+    }
+
+    pub fn from_minor(minor: i64, currency: Currency) -> Money {
+        Money { minor, currency }
+    }
+
+    /// Convierte redondeando mitad hacia arriba a centavos.
+    pub fn from_decimal(amount: Decimal, currency: Currency) -> Money {
+        let scale = Decimal::from(10i64.pow(currency.decimals()));
+        // Importes fuera del rango de i64 (o de `Decimal` al escalar) se saturan.
+        let saturated = if amount.is_sign_negative() { i64::MIN } else { i64::MAX };
+        let minor = match amount.checked_mul(scale) {
+            Some(scaled) => i64::try_from(round_half_up(scaled, 0)).unwrap_or(saturated),
+            None => saturated,
+        };
+        Money { minor, currency }
+    }
+
+    pub fn mxn(amount: Decimal) -> Money {
+        Money::from_decimal(amount, Currency::MXN)
+    }
+
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn to_decimal(&self) -> Decimal {
+        Decimal::new(self.minor, self.currency.decimals())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+
+    pub fn abs(&self) -> Money {
+        Money { minor: self.minor.abs(), currency: self.currency }
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, String> {
+        self.same_currency(&other)?;
+        let minor = self.minor.checked_add(other.minor).ok_or("Importe fuera de rango.")?;
+        Ok(Money { minor, currency: self.currency })
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, String> {
+        self.checked_add(-other)
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), String> {
+        if self.currency != other.currency {
+            return Err(format!(
+                "No se pueden combinar importes en {} y {}.",
+                self.currency.code(),
+                other.currency.code()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Precio × cantidad, redondeado a centavos.
+    pub fn times(&self, quantity: Decimal) -> Money {
+        Money::from_decimal(self.to_decimal() * quantity, self.currency)
+    }
+
+    /// `rate` por ciento de este importe (p. ej. `percent(16)` para IVA sobre base).
+    pub fn percent(&self, rate: Decimal) -> Money {
+        Money::from_decimal(self.to_decimal() * rate / Decimal::ONE_HUNDRED, self.currency)
+    }
+
+    /// Parte de impuesto contenida en un importe que ya lo incluye.
+    pub fn included_tax(&self, rate: Decimal) -> Money {
+        if rate <= Decimal::ZERO {
+            return Money::zero(self.currency);
+        }
+        Money::from_decimal(
+            self.to_decimal() * rate / (Decimal::ONE_HUNDRED + rate),
+            self.currency,
+        )
+    }
+
+    /// Reparte este importe en proporción a `weights` sin perder ni sobrar centavos
+    /// (método del residuo mayor). Se usa para prorratear descuentos entre renglones.
+    pub fn allocate(&self, weights: &[Money]) -> Vec<Money> {
+        let total: i128 = weights.iter().map(|w| w.minor as i128).sum();
+        if weights.is_empty() {
+            return Vec::new();
+        }
+        if total == 0 {
+            let mut out = vec![Money::zero(self.currency); weights.len()];
+            out[0] = *self;
+            return out;
+        }
+        let mut parts: Vec<(usize, i64, i128)> = weights
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let exact = self.minor as i128 * w.minor as i128;
+                (i, (exact / total) as i64, (exact % total).abs())
+            })
+            .collect();
+        let mut remainder = self.minor - parts.iter().map(|p| p.1).sum::<i64>();
+        parts.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        let step = if remainder < 0 { -1 } else { 1 };
+        for part in parts.iter_mut() {
+            if remainder == 0 {
+                break;
+            }
+            part.1 += step;
+            remainder -= step;
+        }
+        parts.sort_by_key(|p| p.0);
+        parts.into_iter().map(|p| Money { minor: p.1, currency: self.currency }).collect()
+    }
+
+    /// Suma en una moneda dada (la suma vacía es cero).
+    pub fn sum<I: IntoIterator<Item = Money>>(items: I, currency: Currency) -> Money {
+        items.into_iter().fold(Money::zero(currency), |acc, m| acc + m)
+    }
+
+    /// Importe sin símbolo con los decimales de la moneda, p. ej. `1234.50`.
+    pub fn amount_str(&self) -> String {
+        format!("{:.*}", self.currency.decimals() as usize, self.to_decimal())
+    }
+}
+
+impl fmt::Display for Money {
+    /// Formato de ticket: `$1234.50` (`-$3.00` para negativos).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        write!(f, "{}{}{}", sign, self.currency.symbol(), self.abs().amount_str())
+    }
+}
+
+impl PartialOrd for Money {
+    /// Solo se comparan importes de la misma moneda.
+    fn partial_cmp(&self, other: &Money) -> Option<std::cmp::Ordering> {
+        if self.currency != other.currency {
+            return None;
+        }
+        Some(self.minor.cmp(&other.minor))
+    }
+}
+
+/// Suma/resta con operadores: mezclar monedas es un error de programación y hace panic.
+/// Para datos que vienen de fuera usar `checked_add`/`checked_sub`.
+impl Add for Money {
+    type Output = Money;
+    fn add(self, other: Money) -> Money {
+        self.checked_add(other).expect("suma de importes")
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, other: Money) -> Money {
+        self.checked_sub(other).expect("resta de importes")
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money { minor: -self.minor, currency: self.currency }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoneyRepr {
+    #[serde(with = "rust_decimal::serde::float")]
+    amount: Decimal,
+    #[serde(default)]
+    currency: Currency,
+}
+
+impl Serialize for Money {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        MoneyRepr { amount: self.to_decimal(), currency: self.currency }.serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Money, D::Error> {
+        let repr = MoneyRepr::deserialize(d)?;
+        Ok(Money::from_decimal(repr.amount, repr.currency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    fn minors(parts: &[Money]) -> Vec<i64> {
+        parts.iter().map(Money::minor).collect()
+    }
+
+    fn cents(values: &[i64]) -> Vec<Money> {
+        values.iter().map(|&v| Money::from_minor(v, Currency::MXN)).collect()
+    }
+
+    #[test]
+    fn from_decimal_rounds_half_away_from_zero() {
+        assert_eq!(Money::mxn(d("0.005")).minor(), 1);
+        assert_eq!(Money::mxn(d("-0.005")).minor(), -1);
+        assert_eq!(Money::mxn(d("0.0049")).minor(), 0);
+        assert_eq!(Money::mxn(d("-0.0049")).minor(), 0);
+        assert_eq!(Money::mxn(d("1.115")).minor(), 112);
+        assert_eq!(Money::mxn(d("-1.115")).minor(), -112);
+        assert_eq!(Money::mxn(d("2.5")).to_decimal(), d("2.50"));
+        assert_eq!(Money::mxn(d("100000000000000000000")).minor(), i64::MAX);
+        assert_eq!(Money::mxn(Decimal::MAX).minor(), i64::MAX);
+        assert_eq!(Money::mxn(Decimal::MIN).minor(), i64::MIN);
+    }
+
+    #[test]
+    fn included_tax_is_the_part_inside_the_price() {
+        assert_eq!(Money::mxn(d("116")).included_tax(d("16")), Money::mxn(d("16")));
+        assert_eq!(Money::mxn(d("100")).included_tax(d("16")), Money::mxn(d("13.79")));
+        assert_eq!(Money::mxn(d("-116")).included_tax(d("16")), Money::mxn(d("-16")));
+        assert_eq!(Money::mxn(d("108")).included_tax(d("8")), Money::mxn(d("8")));
+        assert!(Money::mxn(d("50")).included_tax(Decimal::ZERO).is_zero());
+        assert!(Money::mxn(d("50")).included_tax(d("-16")).is_zero());
+    }
+
+    #[test]
+    fn allocate_keeps_every_cent() {
+        let cases: &[(i64, &[i64])] = &[
+            (100, &[1, 1, 1]),
+            (-100, &[1, 1, 1]),
+            (5, &[100, 100, 100]),
+            (1000, &[150, 250, 600]),
+            (-999, &[333, 1, 7000, 12]),
+            (1, &[50, 50]),
+            (100, &[0, 0, 0]),
+            (-7, &[0, 0]),
+            (250, &[0, 10, 0]),
+        ];
+        for &(amount, weights) in cases {
+            let parts = Money::from_minor(amount, Currency::MXN).allocate(&cents(weights));
+            assert_eq!(parts.len(), weights.len());
+            assert_eq!(Money::sum(parts, Currency::MXN).minor(), amount, "{} entre {:?}", amount, weights);
+        }
+    }
+
+    #[test]
+    fn allocate_breaks_ties_by_position_and_follows_the_weights() {
+        let mxn = |minor| Money::from_minor(minor, Currency::MXN);
+        assert_eq!(minors(&mxn(100).allocate(&cents(&[1, 1, 1]))), vec![34, 33, 33]);
+        assert_eq!(minors(&mxn(-100).allocate(&cents(&[1, 1, 1]))), vec![-34, -33, -33]);
+        assert_eq!(minors(&mxn(5).allocate(&cents(&[1, 1, 1]))), vec![2, 2, 1]);
+        assert_eq!(minors(&mxn(1000).allocate(&cents(&[150, 250, 600]))), vec![150, 250, 600]);
+        // El centavo que sobra va al renglón con el residuo mayor, no al primero.
+        assert_eq!(minors(&mxn(10).allocate(&cents(&[1, 2]))), vec![3, 7]);
+        // Sin pesos todo va al primer renglón.
+        assert_eq!(minors(&mxn(100).allocate(&cents(&[0, 0, 0]))), vec![100, 0, 0]);
+        assert!(mxn(100).allocate(&[]).is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+//! Recepción de mercancía (compras a proveedor) contra la base local.
+//! Todo ocurre en una sola transacción: entra el stock de todas las partidas o de ninguna.
+//!
+//! Por producto se guarda el último costo (`cost`) y el costo promedio ponderado
+//! (`average_cost`). El promedio toma la existencia anterior al costo promedio anterior; si la
+//! existencia es negativa (se vendió sin stock) se cuenta como cero.
+//! Un CFDI solo se puede recibir una vez: el UUID es único en `purchases`. Cada partida deja
+//! su movimiento `purchase` en el kardex.
+
+use crate::db::{self, Db};
+use crate::inventory::{self, MovementType, NewMovement};
+use crate::units;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PurchaseLineInput {
+    pub product_id: i64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unit_cost: Decimal,
+    /// Código del proveedor (NoIdentificacion).
+    #[serde(default)]
+    pub supplier_code: Option<String>,
+    /// Descripción como viene en la factura.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PurchaseInput {
+    #[serde(default)]
+    pub supplier_rfc: Option<String>,
+    #[serde(default)]
+    pub supplier_name: Option<String>,
+    /// UUID del CFDI del proveedor; sin UUID (remisión, PDF) no se revisa duplicado.
+    #[serde(default)]
+    pub invoice_uuid: Option<String>,
+    #[serde(default)]
+    pub folio: Option<String>,
+    #[serde(default)]
+    pub invoice_date: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    pub lines: Vec<PurchaseLineInput>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurchaseItem {
+    pub id: i64,
+    pub product_id: i64,
+    pub supplier_code: Option<String>,
+    pub description: Option<String>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unit_cost: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub subtotal: Decimal,
+    /// Costo promedio del producto después de esta entrada.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub average_cost: Decimal,
+}
+
+impl PurchaseItem {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(PurchaseItem {
+            id: row.get("id")?,
+            product_id: row.get("product_id")?,
+            supplier_code: row.get("supplier_code")?,
+            description: row.get("description")?,
+            quantity: db::get_quantity(row, "quantity")?,
+            unit_cost: db::get_decimal(row, "unit_cost")?,
+            subtotal: db::get_decimal(row, "subtotal")?,
+            average_cost: db::get_decimal(row, "average_cost")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Purchase {
+    pub id: i64,
+    pub supplier_rfc: Option<String>,
+    pub supplier_name: Option<String>,
+    pub invoice_uuid: Option<String>,
+    pub folio: Option<String>,
+    pub invoice_date: Option<String>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total: Decimal,
+    pub user_id: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+    pub items: Vec<PurchaseItem>,
+}
+
+impl Purchase {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Purchase {
+            id: row.get("id")?,
+            supplier_rfc: row.get("supplier_rfc")?,
+            supplier_name: row.get("supplier_name")?,
+            invoice_uuid: row.get("invoice_uuid")?,
+            folio: row.get("folio")?,
+            invoice_date: row.get("invoice_date")?,
+            total: db::get_decimal(row, "total")?,
+            user_id: row.get("user_id")?,
+            notes: row.get("notes")?,
+            created_at: row.get("created_at")?,
+            items: Vec::new(),
+        })
+    }
+}
+
+fn with_items(conn: &Connection, mut purchase: Purchase) -> rusqlite::Result<Purchase> {
+    let mut stmt = conn.prepare("SELECT * FROM purchase_items WHERE purchase_id = ?1 ORDER BY id")?;
+    let rows = stmt.query_map([purchase.id], PurchaseItem::from_row)?;
+    purchase.items = rows.collect::<rusqlite::Result<_>>()?;
+    Ok(purchase)
+}
+
+pub fn get_purchase(conn: &Connection, id: i64) -> rusqlite::Result<Option<Purchase>> {
+    let purchase = conn
+        .query_row("SELECT * FROM purchases WHERE id = ?1", [id], Purchase::from_row)
+        .optional()?;
+    purchase.map(|p| with_items(conn, p)).transpose()
+}
+
+pub fn find_by_uuid(conn: &Connection, uuid: &str) -> rusqlite::Result<Option<Purchase>> {
+    let id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM purchases WHERE invoice_uuid = ?1",
+            [uuid.trim().to_uppercase()],
+            |r| r.get(0),
+        )
+        .optional()?;
+    id.map(|id| get_purchase(conn, id)).transpose().map(Option::flatten)
+}
+
+/// Texto opcional sin espacios a los lados; vacío cuenta como ausente.
+fn clean(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// Promedio ponderado del costo después de recibir `quantity` a `unit_cost`.
+fn weighted_average(stock: Decimal, average: Decimal, quantity: Decimal, unit_cost: Decimal) -> Decimal {
+    let on_hand = stock.max(Decimal::ZERO);
+    let total = on_hand + quantity;
+    if total.is_zero() {
+        return unit_cost;
+    }
+    ((on_hand * average + quantity * unit_cost) / total).round_dp(6)
+}
+
+fn receive_tx(tx: &Transaction, input: &PurchaseInput) -> Result<i64, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let uuid = clean(&input.invoice_uuid).map(|u| u.to_uppercase());
+    if let Some(uuid) = &uuid {
+        if let Some(previous) = find_by_uuid(tx, uuid).map_err(sql)? {
+            return Err(format!(
+                "La factura {} ya se recibió el {} (compra #{}).",
+                uuid,
+                previous.created_at.chars().take(10).collect::<String>(),
+                previous.id
+            ));
+        }
+    }
+    for line in &input.lines {
+        if line.quantity <= Decimal::ZERO {
+            return Err(format!("La cantidad recibida del producto {} debe ser mayor a cero.", line.product_id));
+        }
+        if line.unit_cost < Decimal::ZERO {
+            return Err(format!("El costo del producto {} no puede ser negativo.", line.product_id));
+        }
+    }
+    let total: Decimal = input
+        .lines
+        .iter()
+        .map(|l| (l.quantity * l.unit_cost).round_dp(2))
+        .sum();
+    tx.execute(
+        "INSERT INTO purchases (supplier_rfc, supplier_name, invoice_uuid, folio, invoice_date, total, user_id, notes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            clean(&input.supplier_rfc).map(|r| r.to_uppercase()),
+            clean(&input.supplier_name),
+            uuid.as_deref(),
+            clean(&input.folio),
+            clean(&input.invoice_date),
+            total.to_string(),
+            clean(&input.user_id),
+            clean(&input.notes)
+        ],
+    )
+    .map_err(sql)?;
+    let purchase_id = tx.last_insert_rowid();
+    // En el kardex la compra se identifica por el UUID del CFDI o, sin él, por el folio.
+    let reference = uuid.clone().or_else(|| clean(&input.folio));
+
+    for line in &input.lines {
+        let product = db::get_product(tx, line.product_id)
+            .map_err(sql)?
+            .ok_or_else(|| format!("No se encontró el producto {}.", line.product_id))?;
+        units::check_precision(line.quantity, product.unit, product.precision(), &product.name)?;
+        let average = weighted_average(product.stock, product.average_cost, line.quantity, line.unit_cost);
+        tx.execute(
+            "UPDATE products SET cost = ?2, average_cost = ?3 WHERE id = ?1",
+            params![line.product_id, line.unit_cost.to_string(), average.to_string()],
+        )
+        .map_err(sql)?;
+        let movement = NewMovement::new(line.product_id, MovementType::Purchase, line.quantity)
+            .document("purchase", purchase_id, reference.as_deref())
+            .by(input.user_id.as_deref());
+        inventory::post(tx, &movement).map_err(sql)?;
+        crate::sync::enqueue_stock_delta(tx, line.product_id, line.quantity).map_err(sql)?;
+        tx.execute(
+            "INSERT INTO purchase_items (purchase_id, product_id, supplier_code, description, quantity,
+                 unit_cost, subtotal, average_cost)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                purchase_id,
+                line.product_id,
+                clean(&line.supplier_code),
+                clean(&line.description),
+                db::quantity_value(line.quantity),
+                line.unit_cost.to_string(),
+                (line.quantity * line.unit_cost).round_dp(2).to_string(),
+                average.to_string()
+            ],
+        )
+        .map_err(sql)?;
+    }
+    Ok(purchase_id)
+}
+
+/// Registra la compra y da entrada al stock de todas sus partidas.
+pub fn receive_purchase(conn: &mut Connection, input: &PurchaseInput) -> Result<Purchase, String> {
+    if input.lines.is_empty() {
+        return Err("La compra no tiene partidas.".to_string());
+    }
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let tx = conn.transaction().map_err(sql)?;
+    // Si algo falla, `tx` se descarta y SQLite hace rollback.
+    let id = receive_tx(&tx, input)?;
+    tx.commit().map_err(sql)?;
+    let purchase = get_purchase(conn, id)
+        .map_err(sql)?
+        .ok_or_else(|| "No se encontró la compra guardada.".to_string())?;
+    log::info!(
+        "compra #{} recibida: {} partidas, total {} ({})",
+        purchase.id,
+        purchase.items.len(),
+        purchase.total,
+        purchase.invoice_uuid.as_deref().unwrap_or("sin UUID")
+    );
+    Ok(purchase)
+}
+
+#[tauri::command]
+pub fn purchase_receive(db: State<'_, Db>, purchase: PurchaseInput) -> Result<Purchase, String> {
+    let mut conn = db.lock()?;
+    receive_purchase(&mut conn, &purchase)
+}
+
+/// Compras recientes, opcionalmente de un proveedor.
+#[tauri::command]
+pub fn purchase_list(db: State<'_, Db>, supplier_rfc: Option<String>, limit: Option<i64>) -> Result<Vec<Purchase>, String> {
+    db.with_conn(|conn| {
+        let rfc = supplier_rfc.map(|r| r.trim().to_uppercase()).filter(|r| !r.is_empty());
+        let mut stmt = conn.prepare(
+            "SELECT * FROM purchases WHERE (?1 IS NULL OR supplier_rfc = ?1)
+             ORDER BY created_at DESC, id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![rfc, limit.unwrap_or(100)], Purchase::from_row)?;
+        let purchases = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        purchases.into_iter().map(|p| with_items(conn, p)).collect()
+    })
+}
+
+#[tauri::command]
+pub fn purchase_get(db: State<'_, Db>, id: i64) -> Result<Option<Purchase>, String> {
+    db.with_conn(|conn| get_purchase(conn, id))
+}
+
+/// Compra ya recibida con ese UUID, para avisar antes de revisar la factura otra vez.
+#[tauri::command]
+pub fn purchase_find_by_uuid(db: State<'_, Db>, uuid: String) -> Result<Option<Purchase>, String> {
+    db.with_conn(|conn| find_by_uuid(conn, &uuid))
+}
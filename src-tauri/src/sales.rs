@@ -0,0 +1,484 @@
+//! Cobro de ventas contra la base local.
+//! Todo ocurre en una sola transacción: si algo falla no queda venta a medias ni stock descontado.
+
+use crate::db::{self, Db, Sale, SaleItem};
+use crate::inventory::{self, MovementType, NewMovement};
+use crate::money::{Currency, Money, TaxRounding};
+use crate::tax::{self, TaxDocument, TaxProfile};
+use crate::units;
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use tauri::State;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CartItem {
+    pub product_id: i64,
+    /// Piezas, o kg / m / l con decimales según el producto.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+    /// Precio unitario con el que se vendió (si el cajero lo cambió). Si no viene se usa el del catálogo.
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub unit_price: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SaleRequest {
+    pub items: Vec<CartItem>,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub discount: Decimal,
+    /// `percentage` (default, como en saleStore) o `amount`.
+    #[serde(default)]
+    pub discount_type: Option<String>,
+    /// Porcentaje de impuesto incluido en los precios (Configuración → Impuestos). Solo se usa
+    /// para productos sin perfil de impuestos.
+    #[serde(default, with = "rust_decimal::serde::float")]
+    pub tax_rate: Decimal,
+    pub payment_method: Option<String>,
+    #[serde(default)]
+    pub receipt_type: Option<String>,
+    pub customer_id: Option<i64>,
+    pub user_id: Option<String>,
+    pub notes: Option<String>,
+    /// `completed` (default) o `pending`. Las pendientes no descuentan stock.
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Código de moneda de Configuración (default MXN).
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub tax_rounding: TaxRounding,
+}
+
+/// Totales de la venta. El impuesto sale del perfil de cada producto; con precios que ya lo
+/// incluyen se desglosa, con precios más impuestos se suma al total.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Totals {
+    pub subtotal: Money,
+    pub discount: Money,
+    pub tax: Money,
+    pub total: Money,
+    /// Impuestos por renglón (ya con su parte del descuento) y desglose por tasa.
+    pub taxes: TaxDocument,
+}
+
+pub fn compute_totals(
+    lines: &[(Money, &TaxProfile)],
+    discount: Decimal,
+    percentage: bool,
+    rounding: TaxRounding,
+    currency: Currency,
+) -> Result<Totals, String> {
+    let amounts: Vec<Money> = lines.iter().map(|(amount, _)| *amount).collect();
+    let subtotal = Money::sum(amounts.iter().copied(), currency);
+    if discount < Decimal::ZERO {
+        return Err("El descuento no puede ser negativo.".to_string());
+    }
+    let discount = if percentage {
+        if discount > Decimal::ONE_HUNDRED {
+            return Err("El descuento no puede ser mayor a 100%.".to_string());
+        }
+        subtotal.percent(discount)
+    } else {
+        Money::from_decimal(discount, currency)
+    };
+    if discount > subtotal {
+        return Err("El descuento es mayor que el subtotal.".to_string());
+    }
+    // El descuento se prorratea entre renglones antes de calcular impuestos.
+    let shares = discount.allocate(&amounts);
+    let discounted: Vec<(Money, &TaxProfile)> = lines
+        .iter()
+        .zip(shares)
+        .map(|((amount, profile), share)| (*amount - share, *profile))
+        .collect();
+    let taxes = tax::compute_document(&discounted, rounding, currency);
+    Ok(Totals { subtotal, discount, tax: taxes.taxes, total: taxes.total, taxes })
+}
+
+/// Código de esta caja para que dos cajas sin internet no generen el mismo folio. Se genera
+/// una sola vez con 32 bits aleatorios y se guarda en `sync_state`.
+fn device_code(conn: &Connection) -> Result<String, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let existing: Option<String> = conn
+        .query_row("SELECT value FROM sync_state WHERE key = 'device_code'", [], |r| r.get(0))
+        .optional()
+        .map_err(sql)?;
+    if let Some(code) = existing {
+        return Ok(code);
+    }
+    let mut bytes = [0u8; 4];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("No se pudo generar el código de la caja: {}", e))?;
+    let code: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    conn.execute("INSERT INTO sync_state (key, value) VALUES ('device_code', ?1)", [&code])
+        .map_err(sql)?;
+    Ok(code)
+}
+
+/// Folio `SALE-AAAAMMDD-CAJA-NNNN`, consecutivo por día y caja. El consecutivo se compara como
+/// número: pasando de 9999 sigue en 10000.
+fn next_sale_number(conn: &Connection) -> Result<String, String> {
+    let prefix = format!("SALE-{}-{}-", Local::now().format("%Y%m%d"), device_code(conn)?);
+    let last: Option<i64> = conn
+        .query_row(
+            "SELECT MAX(CAST(substr(sale_number, ?2) AS INTEGER)) FROM sales WHERE sale_number LIKE ?1",
+            params![format!("{}%", prefix), prefix.len() + 1],
+            |r| r.get(0),
+        )
+        .map_err(|e| format!("base local: {}", e))?;
+    Ok(format!("{}{:04}", prefix, last.unwrap_or(0) + 1))
+}
+
+fn process_sale_tx(tx: &Transaction, req: &SaleRequest) -> Result<i64, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let status = req.status.as_deref().unwrap_or("completed");
+    if !matches!(status, "completed" | "pending") {
+        return Err(format!("Estado de venta inválido: {}", status));
+    }
+    let completing = status == "completed";
+    if completing && req.payment_method.as_deref().unwrap_or("").trim().is_empty() {
+        return Err("Selecciona la forma de pago.".to_string());
+    }
+
+    let currency = match req.currency.as_deref() {
+        Some(code) => Currency::from_code(code).ok_or_else(|| format!("Moneda no soportada: {}", code))?,
+        None => Currency::MXN,
+    };
+
+    // Validar carrito contra el stock actual (dentro de la transacción).
+    let mut lines = Vec::with_capacity(req.items.len());
+    let mut profiles: Vec<TaxProfile> = Vec::with_capacity(req.items.len());
+    let mut items: Vec<SaleItem> = Vec::with_capacity(req.items.len());
+    for item in &req.items {
+        if item.quantity <= Decimal::ZERO {
+            return Err("Las cantidades deben ser mayores a cero.".to_string());
+        }
+        let product = db::get_product(tx, item.product_id)
+            .map_err(sql)?
+            .ok_or_else(|| format!("El producto {} ya no existe.", item.product_id))?;
+        // La cantidad se valida como llegó; redondearla antes escondería decimales de más.
+        units::check_precision(item.quantity, product.unit, product.precision(), &product.name)?;
+        let quantity = units::normalize(item.quantity);
+        // El mismo producto puede venir en dos renglones: validar contra lo ya apartado.
+        let reserved: Decimal = items
+            .iter()
+            .filter(|i| i.product_id == item.product_id)
+            .map(|i| i.quantity)
+            .sum();
+        if completing && product.stock < reserved + quantity {
+            return Err(format!(
+                "Stock insuficiente para {}. Disponible: {}",
+                product.name,
+                units::format(product.stock - reserved, product.unit, product.precision())
+            ));
+        }
+        let unit_price = Money::from_decimal(item.unit_price.unwrap_or(product.price), currency);
+        if unit_price.is_negative() {
+            return Err(format!("Precio inválido para {}.", product.name));
+        }
+        let line = unit_price.times(quantity);
+        lines.push(line);
+        profiles.push(tax::profile_for_product(tx, item.product_id, req.tax_rate)?);
+        items.push(SaleItem {
+            id: None,
+            sale_id: None,
+            product_id: item.product_id,
+            quantity,
+            unit: product.unit,
+            unit_price: unit_price.to_decimal(),
+            subtotal: line.to_decimal(),
+            tax_profile: None,
+            tax_base: None,
+            tax_ieps: None,
+            tax_iva: None,
+        });
+    }
+
+    let percentage = req.discount_type.as_deref() != Some("amount");
+    let taxed: Vec<(Money, &TaxProfile)> = lines.iter().copied().zip(profiles.iter()).collect();
+    let totals = compute_totals(&taxed, req.discount, percentage, req.tax_rounding, currency)?;
+    for ((item, profile), line_tax) in items.iter_mut().zip(&profiles).zip(&totals.taxes.lines) {
+        item.tax_profile = Some(profile.clone());
+        item.tax_base = Some(line_tax.base.to_decimal());
+        item.tax_ieps = Some(line_tax.ieps.to_decimal());
+        item.tax_iva = Some(line_tax.iva.to_decimal());
+    }
+
+    let sale = Sale {
+        id: None,
+        sale_number: next_sale_number(tx)?,
+        subtotal: totals.subtotal.to_decimal(),
+        discount: totals.discount.to_decimal(),
+        tax: totals.tax.to_decimal(),
+        total: totals.total.to_decimal(),
+        payment_method: req.payment_method.clone(),
+        receipt_type: req.receipt_type.clone().unwrap_or_else(|| "ticket".to_string()),
+        customer_id: req.customer_id,
+        user_id: req.user_id.clone(),
+        notes: req.notes.clone(),
+        status: status.to_string(),
+        tax_rounding: req.tax_rounding,
+        created_at: None,
+        sale_items: items,
+    };
+    let sale_id = db::insert_sale(tx, &sale).map_err(sql)?;
+
+    if completing {
+        take_stock(tx, sale_id, &sale).map_err(sql)?;
+    }
+    crate::sync::enqueue(tx, "sale", sale_id).map_err(sql)?;
+    Ok(sale_id)
+}
+
+/// Descuenta del inventario las partidas de una venta que se cobra y lo anota en el kardex.
+fn take_stock(tx: &Transaction, sale_id: i64, sale: &Sale) -> rusqlite::Result<()> {
+    for item in &sale.sale_items {
+        let movement = NewMovement::new(item.product_id, MovementType::Sale, -item.quantity)
+            .document("sale", sale_id, Some(&sale.sale_number))
+            .by(sale.user_id.as_deref());
+        inventory::post(tx, &movement)?;
+        tx.execute(
+            "UPDATE products SET last_sale_date = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+            [item.product_id],
+        )?;
+        crate::sync::enqueue_stock_delta(tx, item.product_id, -item.quantity)?;
+    }
+    Ok(())
+}
+
+/// Cobra una venta pendiente: valida el stock de ese momento y lo descuenta.
+pub(crate) fn complete_sale_tx(tx: &Transaction, sale_id: i64) -> Result<(), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let sale = db::get_sale(tx, sale_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la venta {}.", sale_id))?;
+    match sale.status.as_str() {
+        "completed" => return Ok(()),
+        "cancelled" => return Err(format!("La venta {} está cancelada.", sale.sale_number)),
+        _ => {}
+    }
+    let mut needed: Vec<(i64, Decimal)> = Vec::new();
+    for item in &sale.sale_items {
+        match needed.iter_mut().find(|(id, _)| *id == item.product_id) {
+            Some((_, q)) => *q += item.quantity,
+            None => needed.push((item.product_id, item.quantity)),
+        }
+    }
+    for (product_id, quantity) in needed {
+        let product = db::get_product(tx, product_id)
+            .map_err(sql)?
+            .ok_or_else(|| format!("El producto {} ya no existe.", product_id))?;
+        if product.stock < quantity {
+            return Err(format!(
+                "Stock insuficiente para {}. Disponible: {}",
+                product.name,
+                units::format(product.stock, product.unit, product.precision())
+            ));
+        }
+    }
+    take_stock(tx, sale_id, &sale).map_err(sql)?;
+    tx.execute(
+        "UPDATE sales SET status = 'completed', updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+        [sale_id],
+    )
+    .map_err(sql)?;
+    crate::sync::enqueue(tx, "sale", sale_id).map_err(sql)?;
+    log::info!("venta {} cobrada; stock descontado", sale.sale_number);
+    Ok(())
+}
+
+/// Cambia el estado de una venta moviendo el stock según la transición:
+/// pendiente → cobrada lo descuenta, cobrada → cancelada lo regresa. Una venta cobrada
+/// no vuelve a pendiente y una cancelada ya no cambia.
+pub fn update_sale_status(db: &Db, sale_id: i64, status: &str) -> Result<(), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let mut conn = db.lock()?;
+    let tx = conn.transaction().map_err(sql)?;
+    let current: String = tx
+        .query_row("SELECT status FROM sales WHERE id = ?1", [sale_id], |r| r.get(0))
+        .optional()
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la venta {}.", sale_id))?;
+    match (current.as_str(), status) {
+        (a, b) if a == b => return Ok(()),
+        (_, "cancelled") => cancel_sale_tx(&tx, sale_id)?,
+        ("pending", "completed") => complete_sale_tx(&tx, sale_id)?,
+        ("completed", "pending") => return Err("Una venta cobrada no puede volver a pendiente; cancélala.".to_string()),
+        ("cancelled", _) => return Err("La venta está cancelada y ya no puede cambiar de estado.".to_string()),
+        _ => return Err(format!("Estado de venta inválido: {}", status)),
+    }
+    tx.commit().map_err(sql)
+}
+
+/// Cancela la venta y regresa al inventario lo que se descontó al cobrarla. Las ventas
+/// pendientes nunca descontaron stock.
+pub(crate) fn cancel_sale_tx(tx: &Transaction, sale_id: i64) -> Result<(), String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let sale = db::get_sale(tx, sale_id)
+        .map_err(sql)?
+        .ok_or_else(|| format!("No se encontró la venta {}.", sale_id))?;
+    if sale.status == "cancelled" {
+        return Ok(());
+    }
+    if sale.status == "completed" {
+        for item in &sale.sale_items {
+            let movement = NewMovement::new(item.product_id, MovementType::Cancellation, item.quantity)
+                .document("sale", sale_id, Some(&sale.sale_number))
+                .by(sale.user_id.as_deref());
+            inventory::post(tx, &movement).map_err(sql)?;
+            crate::sync::enqueue_stock_delta(tx, item.product_id, item.quantity).map_err(sql)?;
+        }
+    }
+    tx.execute(
+        "UPDATE sales SET status = 'cancelled', updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+        [sale_id],
+    )
+    .map_err(sql)?;
+    crate::sync::enqueue(tx, "sale", sale_id).map_err(sql)?;
+    log::info!("venta {} cancelada; stock restituido", sale.sale_number);
+    Ok(())
+}
+
+/// Valida, cobra y guarda la venta. Reemplaza a `processSale` de saleService.js.
+pub fn process_sale_local(db: &Db, req: &SaleRequest) -> Result<Sale, String> {
+    if req.items.is_empty() {
+        return Err("La venta no tiene productos.".to_string());
+    }
+    let mut conn = db.lock()?;
+    let tx = conn.transaction().map_err(|e| format!("base local: {}", e))?;
+    // Si algo falla, `tx` se descarta y SQLite hace rollback.
+    let sale_id = process_sale_tx(&tx, req)?;
+    tx.commit().map_err(|e| format!("base local: {}", e))?;
+    db::get_sale(&conn, sale_id)
+        .map_err(|e| format!("base local: {}", e))?
+        .ok_or_else(|| "No se encontró la venta guardada.".to_string())
+}
+
+#[tauri::command]
+pub fn process_sale(db: State<'_, Db>, sale: SaleRequest) -> Result<Sale, String> {
+    let saved = process_sale_local(&db, &sale)?;
+    log::info!("process_sale: {} total {} ({})", saved.sale_number, saved.total, saved.status);
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(conn: &Connection, code: &str, unit: &str, stock: i64) -> i64 {
+        conn.execute(
+            "INSERT INTO products (code, name, price, stock, unit) VALUES (?1, ?1, '10', ?2, ?3)",
+            params![code, stock, unit],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn request(items: &[(i64, &str)]) -> SaleRequest {
+        SaleRequest {
+            items: items
+                .iter()
+                .map(|(product_id, quantity)| CartItem {
+                    product_id: *product_id,
+                    quantity: quantity.parse().unwrap(),
+                    unit_price: None,
+                })
+                .collect(),
+            discount: Decimal::ZERO,
+            discount_type: None,
+            tax_rate: Decimal::ZERO,
+            payment_method: Some("cash".to_string()),
+            receipt_type: None,
+            customer_id: None,
+            user_id: None,
+            notes: None,
+            status: None,
+            currency: None,
+            tax_rounding: TaxRounding::default(),
+        }
+    }
+
+    /// (ventas, partidas, existencia de `product_id`, movimientos del kardex, filas en cola)
+    fn footprint(db: &Db, product_id: i64) -> (i64, i64, Decimal, i64, i64) {
+        let conn = db.lock().unwrap();
+        let count = |table: &str| conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0)).unwrap();
+        let stock = conn
+            .query_row("SELECT stock FROM products WHERE id = ?1", [product_id], |r| db::get_quantity(r, 0))
+            .unwrap();
+        (count("sales"), count("sale_items"), stock, count("inventory_movements"), count("sync_outbox"))
+    }
+
+    #[test]
+    fn process_sale_writes_everything_or_nothing() {
+        let db = Db::open_in_memory().unwrap();
+        let (nail, cheese) = {
+            let conn = db.lock().unwrap();
+            (product(&conn, "CLAVO", "pieza", 10), product(&conn, "QUESO", "kg", 4))
+        };
+        assert_eq!(footprint(&db, nail), (0, 0, Decimal::from(10), 0, 0));
+
+        let sale = process_sale_local(&db, &request(&[(nail, "3"), (cheese, "0.5")])).unwrap();
+        assert_eq!(sale.status, "completed");
+        assert_eq!(sale.sale_items.len(), 2);
+        // Venta, 2 partidas, 2 salidas en el kardex; en cola la venta y los 2 deltas de stock.
+        assert_eq!(footprint(&db, nail), (1, 2, Decimal::from(7), 2, 3));
+        {
+            let conn = db.lock().unwrap();
+            let (kind, quantity): (String, i64) = conn
+                .query_row(
+                    "SELECT movement_type, quantity FROM inventory_movements WHERE product_id = ?1",
+                    [nail],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .unwrap();
+            assert_eq!((kind.as_str(), quantity), ("sale", -3));
+            let queued: i64 = conn
+                .query_row("SELECT COUNT(*) FROM sync_outbox WHERE entity = 'sale' AND local_id = ?1", [sale.id], |r| r.get(0))
+                .unwrap();
+            assert_eq!(queued, 1);
+        }
+
+        // Un renglón malo al final deshace los anteriores: producto inexistente, cantidad con
+        // decimales de más, cantidad en cero o sin stock suficiente.
+        for bad in [(9999, "1"), (nail, "1.5"), (cheese, "0"), (nail, "8")] {
+            let err = process_sale_local(&db, &request(&[(nail, "2"), (cheese, "0.25"), bad]));
+            assert!(err.is_err(), "{:?}", bad);
+            assert_eq!(footprint(&db, nail), (1, 2, Decimal::from(7), 2, 3), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn quantities_are_checked_before_rounding() {
+        let db = Db::open_in_memory().unwrap();
+        let (bulk, piece) = {
+            let conn = db.lock().unwrap();
+            (product(&conn, "QUESO", "kg", 5), product(&conn, "CLAVO", "pieza", 5))
+        };
+        let err = process_sale_local(&db, &request(&[(bulk, "1.0004")])).unwrap_err();
+        assert!(err.contains("3 decimales"), "{}", err);
+        let err = process_sale_local(&db, &request(&[(piece, "2.0000001")])).unwrap_err();
+        assert!(err.contains("no lleva decimales"), "{}", err);
+        let sale = process_sale_local(&db, &request(&[(bulk, "1.250")])).unwrap();
+        assert_eq!(sale.sale_items[0].quantity, "1.25".parse::<Decimal>().unwrap());
+    }
+
+    #[test]
+    fn sale_numbers_keep_counting_past_9999() {
+        let db = Db::open_in_memory().unwrap();
+        let conn = db.lock().unwrap();
+        let code = device_code(&conn).unwrap();
+        assert_eq!(code.len(), 8);
+        assert_eq!(device_code(&conn).unwrap(), code);
+
+        let prefix = format!("SALE-{}-{}-", Local::now().format("%Y%m%d"), code);
+        for n in [9998, 9999, 10000] {
+            conn.execute(
+                "INSERT INTO sales (sale_number, subtotal, total) VALUES (?1, '0', '0')",
+                [format!("{}{:04}", prefix, n)],
+            )
+            .unwrap();
+        }
+        assert_eq!(next_sale_number(&conn).unwrap(), format!("{}10001", prefix));
+    }
+}
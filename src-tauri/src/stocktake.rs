@@ -0,0 +1,414 @@
+//! Conteo físico de inventario (toma de inventario) por sesiones.
+//!
+//! Al abrir la sesión se fija qué productos entran (todos o una categoría). Los conteos se
+//! suman por producto aunque vengan de varias personas o de varias lecturas del mismo código;
+//! una cantidad negativa corrige un conteo de más. Cada lectura guarda su hora y su lugar en el
+//! kardex: lo contado vale a la hora de la última lectura, y lo que se vende o recibe después
+//! se le suma. Así la existencia final es conteo + movimientos posteriores, y lo vendido
+//! mientras se cuenta no se descuenta dos veces. Al cerrar se registra un ajuste en el kardex
+//! por cada diferencia; los productos sin contar se quedan igual salvo que se pida tomarlos
+//! como cero al cerrar (conteo completo).
+
+use crate::db::{self, Db};
+use crate::inventory::{self, MovementType, NewMovement};
+use crate::units::{self, Unit};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stocktake {
+    pub id: i64,
+    pub name: String,
+    /// Categoría contada; `None` es todo el catálogo.
+    pub category: Option<String>,
+    /// `open`, `closed` o `cancelled`.
+    pub status: String,
+    pub opened_by: Option<String>,
+    pub closed_by: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+    pub closed_at: Option<String>,
+}
+
+impl Stocktake {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Stocktake {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            category: row.get("category")?,
+            status: row.get("status")?,
+            opened_by: row.get("opened_by")?,
+            closed_by: row.get("closed_by")?,
+            notes: row.get("notes")?,
+            created_at: row.get("created_at")?,
+            closed_at: row.get("closed_at")?,
+        })
+    }
+}
+
+/// Lectura del escáner o captura manual; basta el código o el id del producto.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CountInput {
+    #[serde(default)]
+    pub product_id: Option<i64>,
+    /// Código interno o de barras.
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Cantidad contada en esta lectura (1 por omisión); kg, m o l con decimales.
+    #[serde(default = "one", with = "rust_decimal::serde::float")]
+    pub quantity: Decimal,
+    /// Quién contó (para juntar los conteos de varias personas).
+    #[serde(default)]
+    pub counter: Option<String>,
+}
+
+fn one() -> Decimal {
+    Decimal::ONE
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VarianceLine {
+    pub product_id: i64,
+    pub code: String,
+    pub name: String,
+    pub unit: Unit,
+    /// Existencia del sistema a la hora de la última lectura (la actual si nadie lo ha contado).
+    #[serde(with = "rust_decimal::serde::float")]
+    pub expected: Decimal,
+    /// `None` si nadie lo ha contado.
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub counted: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub variance: Decimal,
+    /// Costo promedio (o último costo si no hay promedio).
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unit_cost: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub variance_value: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StocktakeReport {
+    pub stocktake: Stocktake,
+    pub lines: Vec<VarianceLine>,
+    pub counted_products: usize,
+    pub uncounted_products: usize,
+    /// Personas que han registrado conteos.
+    pub counters: Vec<String>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub shortage_value: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub surplus_value: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub net_value: Decimal,
+}
+
+fn get(conn: &Connection, id: i64) -> Result<Stocktake, String> {
+    conn.query_row("SELECT * FROM stocktakes WHERE id = ?1", [id], Stocktake::from_row)
+        .optional()
+        .map_err(|e| format!("base local: {}", e))?
+        .ok_or_else(|| format!("No se encontró el conteo {}.", id))
+}
+
+fn get_open(conn: &Connection, id: i64) -> Result<Stocktake, String> {
+    let session = get(conn, id)?;
+    if session.status != "open" {
+        return Err(format!("El conteo \"{}\" ya está cerrado.", session.name));
+    }
+    Ok(session)
+}
+
+/// Abre una sesión y congela la existencia de los productos que entran en ella.
+pub fn open(conn: &mut Connection, name: &str, category: Option<&str>, user_id: Option<&str>) -> Result<Stocktake, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let category = category.map(str::trim).filter(|c| !c.is_empty());
+    let open_now: Option<String> = conn
+        .query_row(
+            "SELECT name FROM stocktakes WHERE status = 'open' AND (category IS NULL OR ?1 IS NULL OR category = ?1)",
+            [category],
+            |r| r.get(0),
+        )
+        .optional()
+        .map_err(sql)?;
+    if let Some(other) = open_now {
+        return Err(format!("Ya hay un conteo abierto que cubre esos productos: \"{}\".", other));
+    }
+    let name = match name.trim() {
+        "" => format!("Conteo {}", chrono::Local::now().format("%Y-%m-%d")),
+        n => n.to_string(),
+    };
+    let tx = conn.transaction().map_err(sql)?;
+    tx.execute(
+        "INSERT INTO stocktakes (name, category, opened_by) VALUES (?1, ?2, ?3)",
+        params![name, category, user_id],
+    )
+    .map_err(sql)?;
+    let id = tx.last_insert_rowid();
+    tx.execute(
+        "INSERT INTO stocktake_items (stocktake_id, product_id, expected)
+         SELECT ?1, id, stock FROM products WHERE ?2 IS NULL OR category = ?2",
+        params![id, category],
+    )
+    .map_err(sql)?;
+    tx.commit().map_err(sql)?;
+    log::info!("conteo #{} abierto: {}", id, name);
+    get(conn, id)
+}
+
+/// Suma lecturas al conteo. Un producto fuera de la categoría entra con su existencia actual.
+pub fn add_counts(conn: &mut Connection, id: i64, counts: &[CountInput]) -> Result<usize, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    get_open(conn, id)?;
+    let tx = conn.transaction().map_err(sql)?;
+    for count in counts {
+        let product = match (count.product_id, count.code.as_deref().map(str::trim)) {
+            (Some(pid), _) => db::get_product(&tx, pid)
+                .map_err(sql)?
+                .ok_or_else(|| format!("No se encontró el producto {}.", pid))?,
+            (None, Some(code)) if !code.is_empty() => db::find_product_by_code(&tx, code)
+                .map_err(sql)?
+                .ok_or_else(|| format!("No hay producto con el código {}.", code))?,
+            _ => return Err("Cada conteo necesita el código o el producto.".to_string()),
+        };
+        let product_id = product.id.unwrap_or_default();
+        units::check_precision(count.quantity, product.unit, product.precision(), &product.name)?;
+        let inserted = tx
+            .execute(
+                "INSERT OR IGNORE INTO stocktake_items (stocktake_id, product_id, expected)
+                 SELECT ?1, id, stock FROM products WHERE id = ?2",
+                params![id, product_id],
+            )
+            .map_err(sql)?;
+        if inserted > 0 {
+            log::info!("conteo #{}: producto {} fuera de la categoría agregado", id, product_id);
+        }
+        tx.execute(
+            "INSERT INTO stocktake_counts (stocktake_id, product_id, quantity, counter, ledger_id)
+             VALUES (?1, ?2, ?3, ?4, (SELECT IFNULL(MAX(id), 0) FROM inventory_movements))",
+            params![id, product_id, db::quantity_value(count.quantity), count.counter.as_deref().map(str::trim).filter(|c| !c.is_empty())],
+        )
+        .map_err(sql)?;
+    }
+    tx.commit().map_err(sql)?;
+    Ok(counts.len())
+}
+
+/// Diferencias contra la existencia del sistema a la hora de la última lectura de cada producto;
+/// los productos sin contar van al final.
+///
+/// `variance` es lo que hay que ajustar a la existencia actual: conteo + movimientos posteriores
+/// a la última lectura − existencia actual.
+pub fn report(conn: &Connection, id: i64) -> Result<StocktakeReport, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let stocktake = get(conn, id)?;
+    let mut stmt = conn
+        .prepare(
+            "WITH counted AS (
+                 SELECT product_id, SUM(quantity) AS counted, MAX(id) AS last_count
+                 FROM stocktake_counts WHERE stocktake_id = ?1 GROUP BY product_id
+             )
+             SELECT i.product_id, p.code, p.name, p.unit, p.stock, p.average_cost, p.cost, c.counted,
+                    (SELECT SUM(m.quantity) FROM inventory_movements m JOIN stocktake_counts l ON l.id = c.last_count
+                      WHERE m.product_id = i.product_id
+                        AND (m.id > l.ledger_id OR (l.ledger_id IS NULL AND m.created_at > l.created_at))) AS moved
+             FROM stocktake_items i JOIN products p ON p.id = i.product_id
+             LEFT JOIN counted c ON c.product_id = i.product_id
+             WHERE i.stocktake_id = ?1
+             ORDER BY c.counted IS NULL, p.name",
+        )
+        .map_err(sql)?;
+    let rows = stmt
+        .query_map([id], |r| {
+            let average = db::get_decimal(r, "average_cost")?;
+            let unit_cost = if average.is_zero() { db::get_decimal(r, "cost")? } else { average };
+            let expected = db::get_quantity(r, "stock")? - db::get_quantity(r, "moved")?;
+            let counted = db::get_quantity_opt(r, "counted")?;
+            let variance = counted.map(|c| c - expected).unwrap_or_default();
+            Ok(VarianceLine {
+                product_id: r.get("product_id")?,
+                code: r.get("code")?,
+                name: r.get("name")?,
+                unit: Unit::from_db(r.get::<_, Option<String>>("unit")?.as_deref()),
+                expected,
+                counted,
+                variance,
+                unit_cost,
+                variance_value: (variance * unit_cost).round_dp(2),
+            })
+        })
+        .map_err(sql)?;
+    let lines: Vec<VarianceLine> = rows.collect::<rusqlite::Result<_>>().map_err(sql)?;
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT counter FROM stocktake_counts WHERE stocktake_id = ?1 AND counter IS NOT NULL ORDER BY counter")
+        .map_err(sql)?;
+    let counters = stmt
+        .query_map([id], |r| r.get(0))
+        .map_err(sql)?
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .map_err(sql)?;
+    let shortage_value: Decimal = lines.iter().map(|l| l.variance_value).filter(|v| v.is_sign_negative()).sum();
+    let surplus_value: Decimal = lines.iter().map(|l| l.variance_value).filter(|v| v.is_sign_positive()).sum();
+    let counted_products = lines.iter().filter(|l| l.counted.is_some()).count();
+    Ok(StocktakeReport {
+        stocktake,
+        counted_products,
+        uncounted_products: lines.len() - counted_products,
+        counters,
+        shortage_value,
+        surplus_value,
+        net_value: shortage_value + surplus_value,
+        lines,
+    })
+}
+
+/// Cierra la sesión y registra los ajustes. Con `zero_uncounted` lo no contado se toma como cero
+/// a la hora del cierre, es decir, su existencia actual queda en cero.
+pub fn close(conn: &mut Connection, id: i64, user_id: Option<&str>, zero_uncounted: bool) -> Result<StocktakeReport, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let session = get_open(conn, id)?;
+    let mut summary = report(conn, id)?;
+    let tx = conn.transaction().map_err(sql)?;
+    let mut adjusted = 0;
+    for line in &mut summary.lines {
+        if line.counted.is_none() && zero_uncounted {
+            line.counted = Some(Decimal::ZERO);
+            line.variance = -line.expected;
+            line.variance_value = (line.variance * line.unit_cost).round_dp(2);
+        }
+        if line.variance.is_zero() {
+            continue;
+        }
+        let movement = NewMovement::new(line.product_id, MovementType::Adjustment, line.variance)
+            .document("stocktake", id, Some(&session.name))
+            .by(user_id)
+            .notes(Some("Conteo físico"));
+        inventory::post(&tx, &movement).map_err(sql)?;
+        crate::sync::enqueue_stock_delta(&tx, line.product_id, line.variance).map_err(sql)?;
+        adjusted += 1;
+    }
+    tx.execute(
+        "UPDATE stocktakes SET status = 'closed', closed_by = ?2,
+             closed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+         WHERE id = ?1",
+        params![id, user_id],
+    )
+    .map_err(sql)?;
+    tx.commit().map_err(sql)?;
+    log::info!("conteo #{} cerrado: {} productos ajustados", id, adjusted);
+    let mut closed = report(conn, id)?;
+    // El reporte final conserva los ceros asumidos al cerrar.
+    closed.lines = summary.lines;
+    closed.shortage_value = closed.lines.iter().map(|l| l.variance_value).filter(|v| v.is_sign_negative()).sum();
+    closed.surplus_value = closed.lines.iter().map(|l| l.variance_value).filter(|v| v.is_sign_positive()).sum();
+    closed.net_value = closed.shortage_value + closed.surplus_value;
+    Ok(closed)
+}
+
+#[tauri::command]
+pub fn stocktake_open(
+    db: State<'_, Db>,
+    name: Option<String>,
+    category: Option<String>,
+    user_id: Option<String>,
+) -> Result<Stocktake, String> {
+    let mut conn = db.lock()?;
+    open(&mut conn, name.as_deref().unwrap_or_default(), category.as_deref(), user_id.as_deref())
+}
+
+#[tauri::command]
+pub fn stocktake_list(db: State<'_, Db>, status: Option<String>) -> Result<Vec<Stocktake>, String> {
+    db.with_conn(|conn| {
+        let mut stmt =
+            conn.prepare("SELECT * FROM stocktakes WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC LIMIT 200")?;
+        let rows = stmt.query_map([status], Stocktake::from_row)?;
+        rows.collect()
+    })
+}
+
+/// Agrega lecturas (una o un lote del lector) a un conteo abierto.
+#[tauri::command]
+pub fn stocktake_add_counts(db: State<'_, Db>, stocktake_id: i64, counts: Vec<CountInput>) -> Result<usize, String> {
+    let mut conn = db.lock()?;
+    add_counts(&mut conn, stocktake_id, &counts)
+}
+
+#[tauri::command]
+pub fn stocktake_report(db: State<'_, Db>, stocktake_id: i64) -> Result<StocktakeReport, String> {
+    let conn = db.lock()?;
+    report(&conn, stocktake_id)
+}
+
+#[tauri::command]
+pub fn stocktake_close(
+    db: State<'_, Db>,
+    stocktake_id: i64,
+    user_id: Option<String>,
+    zero_uncounted: Option<bool>,
+) -> Result<StocktakeReport, String> {
+    let mut conn = db.lock()?;
+    close(&mut conn, stocktake_id, user_id.as_deref(), zero_uncounted.unwrap_or(false))
+}
+
+/// Descarta la sesión sin tocar el inventario.
+#[tauri::command]
+pub fn stocktake_cancel(db: State<'_, Db>, stocktake_id: i64) -> Result<(), String> {
+    let conn = db.lock()?;
+    get_open(&conn, stocktake_id)?;
+    conn.execute(
+        "UPDATE stocktakes SET status = 'cancelled', closed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+        [stocktake_id],
+    )
+    .map_err(|e| format!("base local: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(code: &str, quantity: i64) -> CountInput {
+        CountInput { product_id: None, code: Some(code.to_string()), quantity: Decimal::from(quantity), counter: None }
+    }
+
+    fn sell(conn: &Connection, product_id: i64, quantity: i64) {
+        inventory::post(conn, &NewMovement::new(product_id, MovementType::Sale, Decimal::from(-quantity))).unwrap();
+    }
+
+    fn stock(conn: &Connection, product_id: i64) -> Decimal {
+        conn.query_row("SELECT stock FROM products WHERE id = ?1", [product_id], |r| db::get_quantity(r, 0)).unwrap()
+    }
+
+    #[test]
+    fn sales_during_the_count_are_not_subtracted_twice() {
+        let db = Db::open_in_memory().unwrap();
+        let mut conn = db.lock().unwrap();
+        let mut ids = Vec::new();
+        for code in ["A", "B", "C"] {
+            conn.execute("INSERT INTO products (code, name, price, stock) VALUES (?1, ?1, '1', 12)", [code]).unwrap();
+            ids.push(conn.last_insert_rowid());
+        }
+        let (a, b, c) = (ids[0], ids[1], ids[2]);
+        open(&mut conn, "Semanal", None, None).unwrap();
+
+        // A: se venden 3 antes de contarlo y en el anaquel quedan 9, como dice el sistema.
+        sell(&conn, a, 3);
+        add_counts(&mut conn, 1, &[count("A", 9)]).unwrap();
+        // B: se cuentan 10 (faltan 2) y después se venden 4.
+        add_counts(&mut conn, 1, &[count("B", 10)]).unwrap();
+        sell(&conn, b, 4);
+        // C: nadie lo cuenta y se vende 1.
+        sell(&conn, c, 1);
+
+        let report = report(&conn, 1).unwrap();
+        let line = |id: i64| report.lines.iter().find(|l| l.product_id == id).unwrap();
+        assert_eq!((line(a).expected, line(a).variance), (Decimal::from(9), Decimal::ZERO));
+        assert_eq!((line(b).expected, line(b).variance), (Decimal::from(12), Decimal::from(-2)));
+
+        close(&mut conn, 1, None, true).unwrap();
+        assert_eq!(stock(&conn, a), Decimal::from(9));
+        assert_eq!(stock(&conn, b), Decimal::from(6));
+        assert_eq!(stock(&conn, c), Decimal::ZERO);
+    }
+}
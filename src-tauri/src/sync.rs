@@ -0,0 +1,1091 @@
+//! Sincronización entre la base local y Supabase (PostgREST).
+//!
+//! - Subida: ventas, cotizaciones y movimientos de stock quedan en `sync_outbox` y se envían
+//!   en orden. Si algo falla la fila se queda con el error y se reintenta en el siguiente ciclo.
+//! - Bajada: productos posteriores a la última marca, paginados por `(updated_at, id)`
+//!   (`products_watermark` + `products_watermark_id`) para no saltar filas con la misma hora.
+//! - Conflictos: en campos de catálogo gana la última escritura (`updated_at`); el stock se
+//!   fusiona sumando al stock remoto los deltas locales que aún no se suben.
+
+use crate::db::{self, Db};
+use crate::inventory::{self, MovementType, NewMovement};
+use crate::units::Unit;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Evento que recibe el frontend cada vez que cambia el estado de sincronización.
+pub const SYNC_STATUS_EVENT: &str = "sync-status";
+
+const PAGE_SIZE: usize = 500;
+
+/// Conexión al backend remoto. `base_url` apunta al prefijo REST (p. ej. `https://xyz.supabase.co/rest/v1`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub base_url: String,
+    pub api_key: String,
+    pub access_token: Option<String>,
+    pub tenant_id: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncState {
+    #[default]
+    Idle,
+    Running,
+    Offline,
+    Error,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncStatus {
+    pub state: SyncState,
+    pub last_sync_at: Option<String>,
+    pub pending: i64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub pushed: usize,
+    pub failed: usize,
+    pub pulled_products: usize,
+}
+
+/// Estado de Tauri del motor de sincronización.
+#[derive(Default)]
+pub struct SyncEngine {
+    config: Mutex<Option<SyncConfig>>,
+    status: Mutex<SyncStatus>,
+    /// Evita dos ciclos simultáneos (botón manual + ciclo automático).
+    cycle: Mutex<()>,
+    auto: AtomicBool,
+}
+
+impl SyncEngine {
+    /// Guarda la conexión remota. El frontend la vuelve a mandar cuando se renueva el token.
+    pub fn configure(&self, config: SyncConfig) -> Result<(), String> {
+        if config.base_url.trim().is_empty() || config.tenant_id.trim().is_empty() {
+            return Err("Falta la URL del servidor o el negocio (tenant).".to_string());
+        }
+        *self.config.lock().map_err(|_| "sincronización bloqueada".to_string())? = Some(config);
+        Ok(())
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    fn set_status(&self, f: impl FnOnce(&mut SyncStatus)) -> SyncStatus {
+        match self.status.lock() {
+            Ok(mut s) => {
+                f(&mut s);
+                s.clone()
+            }
+            Err(_) => SyncStatus::default(),
+        }
+    }
+}
+
+// ---------- Cola de salida ----------
+
+/// Encola una entidad (`product`, `sale`, `quotation`) para subirla. Si ya estaba en cola no se duplica.
+pub(crate) fn enqueue(conn: &Connection, entity: &str, local_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO sync_outbox (entity, local_id) VALUES (?1, ?2)",
+        params![entity, local_id],
+    )?;
+    Ok(())
+}
+
+/// Encola un cambio de stock relativo. Los deltas se suman en el servidor, no se sobrescriben.
+pub(crate) fn enqueue_stock_delta(conn: &Connection, product_id: i64, delta: Decimal) -> rusqlite::Result<()> {
+    if delta.is_zero() {
+        return Ok(());
+    }
+    // También para productos sin `remote_id`: el delta espera en cola hasta que se liguen, y
+    // mientras tanto el pull los suma a la existencia remota.
+    conn.execute(
+        "INSERT INTO sync_outbox (entity, local_id, delta) VALUES ('stock', ?1, ?2)",
+        params![product_id, db::quantity_value(delta)],
+    )?;
+    Ok(())
+}
+
+fn pending_count(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM sync_outbox", [], |r| r.get(0))
+}
+
+fn pending_stock_delta(conn: &Connection, product_id: i64) -> rusqlite::Result<Decimal> {
+    conn.query_row(
+        "SELECT IFNULL(SUM(delta), 0) FROM sync_outbox WHERE entity = 'stock' AND local_id = ?1",
+        [product_id],
+        |r| db::get_quantity(r, 0),
+    )
+}
+
+/// Cantidad como número JSON; entera si no lleva decimales, como la espera un esquema sin granel.
+fn quantity_json(quantity: Decimal) -> Value {
+    match db::quantity_value(quantity) {
+        rusqlite::types::Value::Integer(whole) => json!(whole),
+        rusqlite::types::Value::Real(real) => json!(real),
+        _ => Value::Null,
+    }
+}
+
+fn get_state(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT value FROM sync_state WHERE key = ?1", [key], |r| r.get(0))
+        .optional()
+}
+
+fn set_state(conn: &Connection, key: &str, value: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sync_state (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+// ---------- Cliente PostgREST ----------
+
+#[derive(Debug)]
+enum RemoteError {
+    /// Sin conexión o el servidor no respondió.
+    Offline(String),
+    /// El servidor respondió con error (permisos, datos inválidos...).
+    Rejected(String),
+    /// Depende de otra fila de la cola que aún no sube; se queda pendiente sin contar intento.
+    Waiting(String),
+}
+
+impl std::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteError::Offline(e) => write!(f, "sin conexión: {}", e),
+            RemoteError::Rejected(e) => write!(f, "rechazado por el servidor: {}", e),
+            RemoteError::Waiting(e) => write!(f, "en espera: {}", e),
+        }
+    }
+}
+
+impl From<ureq::Error> for RemoteError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(code, resp) => {
+                let body = resp.into_string().unwrap_or_default();
+                RemoteError::Rejected(format!("HTTP {}: {}", code, body))
+            }
+            ureq::Error::Transport(t) => RemoteError::Offline(t.to_string()),
+        }
+    }
+}
+
+struct Remote<'a> {
+    config: &'a SyncConfig,
+    agent: ureq::Agent,
+}
+
+impl<'a> Remote<'a> {
+    fn new(config: &'a SyncConfig) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(5))
+            .timeout(Duration::from_secs(20))
+            .build();
+        Remote { config, agent }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let url = format!("{}/{}", self.config.base_url.trim_end_matches('/'), path);
+        let token = self.config.access_token.as_deref().unwrap_or(&self.config.api_key);
+        self.agent
+            .request(method, &url)
+            .set("apikey", &self.config.api_key)
+            .set("Authorization", &format!("Bearer {}", token))
+    }
+
+    fn get(&self, path: &str) -> Result<Value, RemoteError> {
+        let resp = self.request("GET", path).call()?;
+        resp.into_json().map_err(|e| RemoteError::Rejected(format!("JSON inválido: {}", e)))
+    }
+
+    fn post(&self, path: &str, body: &Value, prefer: &str) -> Result<Value, RemoteError> {
+        let resp = self
+            .request("POST", path)
+            .set("Prefer", prefer)
+            .send_json(body.clone())?;
+        let text = resp.into_string().map_err(|e| RemoteError::Offline(e.to_string()))?;
+        if text.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&text).map_err(|e| RemoteError::Rejected(format!("JSON inválido: {}", e)))
+    }
+
+    fn delete(&self, path: &str) -> Result<(), RemoteError> {
+        self.request("DELETE", path).call()?;
+        Ok(())
+    }
+}
+
+fn first_id(v: &Value) -> Option<i64> {
+    match v {
+        Value::Array(rows) => rows.first().and_then(|r| r.get("id")).and_then(Value::as_i64),
+        Value::Object(_) => v.get("id").and_then(Value::as_i64),
+        _ => None,
+    }
+}
+
+fn remote_id(conn: &Connection, table: &str, local_id: i64) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        &format!("SELECT remote_id FROM {} WHERE id = ?1", table),
+        [local_id],
+        |r| r.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+}
+
+// ---------- Subida ----------
+
+struct OutboxEntry {
+    id: i64,
+    entity: String,
+    local_id: i64,
+    delta: Option<Decimal>,
+}
+
+/// Da de alta en el servidor un producto creado en esta caja y guarda su `remote_id`.
+/// Si otra caja ya subió el mismo código, solo se liga; el pull trae su existencia.
+fn push_product(db: &Db, remote: &Remote, product_id: i64) -> Result<(), RemoteError> {
+    let loaded = db
+        .with_conn(|conn| {
+            let product = crate::db::get_product(conn, product_id)?;
+            let linked = remote_id(conn, "products", product_id)?.is_some();
+            // La existencia que sube es la de antes de los deltas en cola; esos se suben después.
+            let pending = pending_stock_delta(conn, product_id)?;
+            Ok(product.map(|p| (p, linked, pending)))
+        })
+        .map_err(RemoteError::Rejected)?;
+    let Some((p, linked, pending)) = loaded else {
+        return Ok(());
+    };
+    // El pull pudo ligarlo por código mientras esperaba en la cola.
+    if linked {
+        return Ok(());
+    }
+
+    let tenant = urlencode(&remote.config.tenant_id);
+    let existing = remote.get(&format!(
+        "products?select=id&tenant_id=eq.{}&code=eq.{}",
+        tenant,
+        urlencode(&p.code)
+    ))?;
+    let remote_product_id = match first_id(&existing) {
+        Some(rid) => rid,
+        None => {
+            let mut body = json!({
+                "tenant_id": remote.config.tenant_id,
+                "code": p.code,
+                "barcode": p.barcode,
+                "name": p.name,
+                "description": p.description,
+                "price": p.price,
+                "cost": p.cost,
+                "stock": quantity_json(p.stock - pending),
+                "category": p.category,
+                "supplier": p.supplier,
+                "minimum_stock": p.minimum_stock,
+                "image_url": p.image_url,
+            });
+            // Las columnas de granel solo existen con supabase_decimal_quantities.sql.
+            if p.unit != Unit::Pieza || p.quantity_precision.is_some() {
+                body["unit"] = json!(p.unit.as_str());
+                body["quantity_precision"] = json!(p.quantity_precision);
+            }
+            let created = remote.post("products", &body, "return=representation")?;
+            first_id(&created)
+                .ok_or_else(|| RemoteError::Rejected("el servidor no devolvió el id del producto".to_string()))?
+        }
+    };
+
+    db.with_conn(|conn| {
+        conn.execute("UPDATE products SET remote_id = ?2 WHERE id = ?1", params![product_id, remote_product_id])
+            .map(|_| ())
+    })
+    .map_err(RemoteError::Rejected)
+}
+
+fn push_sale(db: &Db, remote: &Remote, sale_id: i64) -> Result<(), RemoteError> {
+    let loaded = db
+        .with_conn(|conn| {
+            let sale = crate::db::get_sale(conn, sale_id)?;
+            let customer = match sale.as_ref().and_then(|s| s.customer_id) {
+                Some(cid) => remote_id(conn, "customers", cid)?,
+                None => None,
+            };
+            let mut products = Vec::new();
+            for item in sale.iter().flat_map(|s| s.sale_items.iter()) {
+                products.push(remote_id(conn, "products", item.product_id)?);
+            }
+            Ok((sale, customer, products))
+        })
+        .map_err(RemoteError::Rejected)?;
+    let (sale, remote_customer, remote_products) = loaded;
+    let Some(sale) = sale else {
+        // La venta se borró localmente; no hay nada que subir.
+        return Ok(());
+    };
+    // Sin todos los productos en el servidor no se sube ni el encabezado: la venta espera a que
+    // las filas `product` de la cola (anteriores a ella) los den de alta.
+    let mut product_ids = Vec::new();
+    for (item, remote_product) in sale.sale_items.iter().zip(remote_products) {
+        let product_id = remote_product.ok_or_else(|| {
+            RemoteError::Waiting(format!("el producto local {} aún no existe en el servidor", item.product_id))
+        })?;
+        product_ids.push(product_id);
+    }
+
+    let tenant = &remote.config.tenant_id;
+    let body = json!({
+        "tenant_id": tenant,
+        "sale_number": sale.sale_number,
+        "subtotal": sale.subtotal,
+        "discount": sale.discount,
+        "tax": sale.tax,
+        "total": sale.total,
+        "payment_method": sale.payment_method,
+        "receipt_type": sale.receipt_type,
+        "customer_id": remote_customer,
+        "user_id": sale.user_id,
+        "notes": sale.notes,
+        "status": sale.status,
+        "created_at": sale.created_at,
+    });
+    // Upsert por (tenant_id, sale_number): reintentar no duplica la venta.
+    let created = remote.post(
+        "sales?on_conflict=tenant_id,sale_number",
+        &body,
+        "return=representation,resolution=merge-duplicates",
+    )?;
+    let remote_sale_id = first_id(&created)
+        .ok_or_else(|| RemoteError::Rejected("el servidor no devolvió el id de la venta".to_string()))?;
+
+    let mut items = Vec::new();
+    for (item, product_id) in sale.sale_items.iter().zip(product_ids) {
+        items.push(json!({
+            "sale_id": remote_sale_id,
+            "product_id": product_id,
+            "quantity": quantity_json(item.quantity),
+            "unit_price": item.unit_price,
+            "subtotal": item.subtotal,
+        }));
+    }
+    // Las partidas se reemplazan completas para que un reintento no las duplique.
+    remote.delete(&format!("sale_items?sale_id=eq.{}", remote_sale_id))?;
+    if !items.is_empty() {
+        remote.post("sale_items", &Value::Array(items), "return=minimal")?;
+    }
+
+    db.with_conn(|conn| {
+        conn.execute("UPDATE sales SET remote_id = ?2 WHERE id = ?1", params![sale_id, remote_sale_id])
+            .map(|_| ())
+    })
+    .map_err(RemoteError::Rejected)
+}
+
+fn push_quotation(db: &Db, remote: &Remote, quotation_id: i64) -> Result<(), RemoteError> {
+    let quotation = db
+        .with_conn(|conn| {
+            conn.query_row(
+                "SELECT * FROM quotations WHERE id = ?1",
+                [quotation_id],
+                crate::db::Quotation::from_row,
+            )
+            .optional()
+        })
+        .map_err(RemoteError::Rejected)?;
+    let Some(q) = quotation else {
+        return Ok(());
+    };
+    let body = json!({
+        "tenant_id": remote.config.tenant_id,
+        "quotation_code": q.quotation_code,
+        "items": q.items,
+        "subtotal": q.subtotal,
+        "discount": q.discount,
+        "subtotal_after_discount": q.subtotal_after_discount,
+        "tax_rate": q.tax_rate,
+        "tax_amount": q.tax_amount,
+        "total": q.total,
+        "user_id": q.user_id,
+        "status": q.status,
+        "created_at": q.created_at,
+        "expires_at": q.expires_at,
+    });
+    let saved = remote.post(
+        "quotations?on_conflict=tenant_id,quotation_code",
+        &body,
+        "return=representation,resolution=merge-duplicates",
+    )?;
+    if let Some(rid) = first_id(&saved) {
+        db.with_conn(|conn| {
+            conn.execute("UPDATE quotations SET remote_id = ?2 WHERE id = ?1", params![quotation_id, rid])
+                .map(|_| ())
+        })
+        .map_err(RemoteError::Rejected)?;
+    }
+    Ok(())
+}
+
+fn push_stock_delta(db: &Db, remote: &Remote, product_id: i64, delta: Decimal) -> Result<(), RemoteError> {
+    let rid = db
+        .with_conn(|conn| remote_id(conn, "products", product_id))
+        .map_err(RemoteError::Rejected)?
+        .ok_or_else(|| {
+            RemoteError::Waiting(format!("el producto local {} aún no existe en el servidor", product_id))
+        })?;
+    // Ver supabase_sync_rpc.sql: suma el delta en el servidor (no sobrescribe el stock).
+    remote.post(
+        "rpc/adjust_stock",
+        &json!({ "p_product_id": rid, "p_delta": quantity_json(delta) }),
+        "return=minimal",
+    )?;
+    Ok(())
+}
+
+fn push(db: &Db, remote: &Remote, report: &mut SyncReport) -> Result<(), RemoteError> {
+    let entries = db
+        .with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT id, entity, local_id, delta FROM sync_outbox ORDER BY id")?;
+            let rows = stmt.query_map([], |r| {
+                Ok(OutboxEntry { id: r.get(0)?, entity: r.get(1)?, local_id: r.get(2)?, delta: db::get_quantity_opt(r, 3)? })
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(RemoteError::Rejected)?;
+
+    for entry in entries {
+        let result = match entry.entity.as_str() {
+            "product" => push_product(db, remote, entry.local_id),
+            "sale" => push_sale(db, remote, entry.local_id),
+            "quotation" => push_quotation(db, remote, entry.local_id),
+            "stock" => push_stock_delta(db, remote, entry.local_id, entry.delta.unwrap_or_default()),
+            other => Err(RemoteError::Rejected(format!("entidad desconocida en cola: {}", other))),
+        };
+        match result {
+            Ok(()) => {
+                db.with_conn(|conn| conn.execute("DELETE FROM sync_outbox WHERE id = ?1", [entry.id]).map(|_| ()))
+                    .map_err(RemoteError::Rejected)?;
+                report.pushed += 1;
+            }
+            // Sin red no tiene caso seguir con el resto de la cola.
+            Err(RemoteError::Offline(e)) => return Err(RemoteError::Offline(e)),
+            Err(RemoteError::Waiting(e)) => {
+                log::info!("sync: {} {} en espera: {}", entry.entity, entry.local_id, e);
+            }
+            Err(RemoteError::Rejected(e)) => {
+                log::warn!("sync: {} {} rechazado: {}", entry.entity, entry.local_id, e);
+                db.with_conn(|conn| {
+                    conn.execute(
+                        "UPDATE sync_outbox SET attempts = attempts + 1, last_error = ?2 WHERE id = ?1",
+                        params![entry.id, e],
+                    )
+                    .map(|_| ())
+                })
+                .map_err(RemoteError::Rejected)?;
+                report.failed += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+// ---------- Bajada ----------
+
+#[derive(Debug, Deserialize)]
+struct RemoteProduct {
+    id: i64,
+    code: String,
+    barcode: Option<String>,
+    name: String,
+    description: Option<String>,
+    #[serde(with = "rust_decimal::serde::float")]
+    price: Decimal,
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    cost: Option<Decimal>,
+    #[serde(default, with = "rust_decimal::serde::float")]
+    stock: Decimal,
+    /// Solo si el servidor ya tiene las columnas de supabase_decimal_quantities.sql.
+    #[serde(default)]
+    unit: Option<String>,
+    #[serde(default)]
+    quantity_precision: Option<u32>,
+    category: Option<String>,
+    supplier: Option<String>,
+    minimum_stock: Option<i64>,
+    image_url: Option<String>,
+    updated_at: String,
+}
+
+fn parse_ts(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s).ok().map(|d| d.with_timezone(&Utc))
+}
+
+/// Aplica un producto remoto sobre la base local con las reglas de conflicto.
+fn apply_remote_product(conn: &Connection, p: &RemoteProduct) -> rusqlite::Result<()> {
+    let local: Option<(i64, String, Decimal)> = conn
+        .query_row(
+            "SELECT id, updated_at, stock FROM products WHERE remote_id = ?1
+             UNION ALL
+             SELECT id, updated_at, stock FROM products WHERE remote_id IS NULL AND code = ?2
+             LIMIT 1",
+            params![p.id, p.code],
+            |r| Ok((r.get(0)?, r.get(1)?, db::get_quantity(r, 2)?)),
+        )
+        .optional()?;
+    let cost = p.cost.unwrap_or_default().to_string();
+    let unit = p.unit.as_deref().and_then(Unit::parse).map(|u| u.as_str());
+
+    match local {
+        None => {
+            conn.execute(
+                "INSERT INTO products (remote_id, code, barcode, name, description, price, cost, average_cost,
+                     stock, category, supplier, minimum_stock, image_url, updated_at, unit, quantity_precision)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7, ?8, ?9, ?10, ?11, ?12, ?13, IFNULL(?14, 'pieza'), ?15)",
+                params![
+                    p.id, p.code, p.barcode, p.name, p.description, p.price.to_string(), cost,
+                    db::quantity_value(p.stock), p.category, p.supplier, p.minimum_stock, p.image_url,
+                    p.updated_at, unit, p.quantity_precision
+                ],
+            )?;
+            let opening = NewMovement {
+                reference_type: Some("sync"),
+                ..NewMovement::new(conn.last_insert_rowid(), MovementType::Adjustment, p.stock)
+            };
+            inventory::log_movement(conn, &opening.notes(Some("Existencia inicial del servidor")))?;
+        }
+        Some((local_id, local_updated, local_stock)) => {
+            // Stock: base remota + lo vendido/ajustado aquí que todavía no se sube.
+            let stock = p.stock + pending_stock_delta(conn, local_id)?;
+            let remote_wins = match (parse_ts(&p.updated_at), parse_ts(&local_updated)) {
+                (Some(r), Some(l)) => r >= l,
+                _ => true,
+            };
+            if remote_wins {
+                conn.execute(
+                    "UPDATE products SET remote_id = ?2, code = ?3, barcode = ?4, name = ?5, description = ?6,
+                         price = ?7, cost = ?8, stock = ?9, category = ?10, supplier = ?11,
+                         minimum_stock = ?12, image_url = ?13, updated_at = ?14, unit = IFNULL(?15, unit),
+                         quantity_precision = IFNULL(?16, quantity_precision)
+                     WHERE id = ?1",
+                    params![
+                        local_id, p.id, p.code, p.barcode, p.name, p.description, p.price.to_string(), cost,
+                        db::quantity_value(stock), p.category, p.supplier, p.minimum_stock, p.image_url,
+                        p.updated_at, unit, p.quantity_precision
+                    ],
+                )?;
+            } else {
+                conn.execute(
+                    "UPDATE products SET remote_id = ?2, stock = ?3 WHERE id = ?1",
+                    params![local_id, p.id, db::quantity_value(stock)],
+                )?;
+            }
+            // Lo que movieron otras cajas llega como ajuste para que el kardex siga cuadrando.
+            let synced = NewMovement {
+                reference_type: Some("sync"),
+                ..NewMovement::new(local_id, MovementType::Adjustment, stock - local_stock)
+            };
+            inventory::log_movement(conn, &synced.notes(Some("Existencia del servidor")))?;
+        }
+    }
+    Ok(())
+}
+
+fn pull_products(db: &Db, remote: &Remote, report: &mut SyncReport) -> Result<(), RemoteError> {
+    loop {
+        let (watermark, watermark_id) = db
+            .with_conn(|conn| Ok((get_state(conn, "products_watermark")?, get_state(conn, "products_watermark_id")?)))
+            .map_err(RemoteError::Rejected)?;
+        let watermark = watermark.unwrap_or_else(|| "1970-01-01T00:00:00Z".to_string());
+        let watermark_id: i64 = watermark_id.and_then(|id| id.parse().ok()).unwrap_or(0);
+        // Después de un UPDATE masivo muchas filas comparten `updated_at`; el id desempata.
+        let after = format!(
+            "(updated_at.gt.\"{ts}\",and(updated_at.eq.\"{ts}\",id.gt.{id}))",
+            ts = watermark,
+            id = watermark_id
+        );
+        let path = format!(
+            "products?select=*&tenant_id=eq.{}&or={}&order=updated_at.asc,id.asc&limit={}",
+            urlencode(&remote.config.tenant_id),
+            urlencode(&after),
+            PAGE_SIZE
+        );
+        let rows: Vec<RemoteProduct> = serde_json::from_value(remote.get(&path)?)
+            .map_err(|e| RemoteError::Rejected(format!("producto remoto inválido: {}", e)))?;
+        let count = rows.len();
+        if count == 0 {
+            return Ok(());
+        }
+        db.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            for p in &rows {
+                apply_remote_product(&tx, p)?;
+            }
+            if let Some(last) = rows.last() {
+                set_state(&tx, "products_watermark", &last.updated_at)?;
+                set_state(&tx, "products_watermark_id", &last.id.to_string())?;
+            }
+            tx.commit()
+        })
+        .map_err(RemoteError::Rejected)?;
+        report.pulled_products += count;
+        if count < PAGE_SIZE {
+            return Ok(());
+        }
+    }
+}
+
+fn urlencode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// ---------- Ciclo ----------
+
+/// Ejecuta un ciclo completo: primero sube la cola y después baja el catálogo.
+pub fn run_cycle(db: &Db, engine: &SyncEngine) -> Result<SyncReport, String> {
+    let _guard = engine.cycle.lock().map_err(|_| "sincronización bloqueada".to_string())?;
+    let config = engine
+        .config
+        .lock()
+        .map_err(|_| "sincronización bloqueada".to_string())?
+        .clone()
+        .ok_or("Sincronización sin configurar.")?;
+    engine.set_status(|s| s.state = SyncState::Running);
+
+    let remote = Remote::new(&config);
+    let mut report = SyncReport::default();
+    let result = push(db, &remote, &mut report).and_then(|_| pull_products(db, &remote, &mut report));
+    let pending = db.with_conn(|conn| pending_count(conn)).unwrap_or(0);
+
+    match result {
+        Ok(()) => {
+            let now = Utc::now().to_rfc3339();
+            engine.set_status(|s| {
+                s.state = if report.failed > 0 { SyncState::Error } else { SyncState::Idle };
+                s.last_sync_at = Some(now);
+                s.pending = pending;
+                s.last_error = if report.failed > 0 {
+                    Some(format!("{} registros no se pudieron subir", report.failed))
+                } else {
+                    None
+                };
+            });
+            Ok(report)
+        }
+        Err(e) => {
+            let state = match e {
+                RemoteError::Offline(_) => SyncState::Offline,
+                RemoteError::Rejected(_) | RemoteError::Waiting(_) => SyncState::Error,
+            };
+            let msg = e.to_string();
+            engine.set_status(|s| {
+                s.state = state;
+                s.pending = pending;
+                s.last_error = Some(msg.clone());
+            });
+            Err(msg)
+        }
+    }
+}
+
+fn emit_status(app: &AppHandle, status: &SyncStatus) {
+    if let Err(e) = app.emit(SYNC_STATUS_EVENT, status.clone()) {
+        log::warn!("sync: no se pudo emitir estado: {}", e);
+    }
+}
+
+// ---------- Comandos Tauri ----------
+
+#[tauri::command]
+pub fn sync_configure(engine: State<'_, SyncEngine>, config: SyncConfig) -> Result<(), String> {
+    engine.configure(config)
+}
+
+#[tauri::command]
+pub fn sync_status(db: State<'_, Db>, engine: State<'_, SyncEngine>) -> Result<SyncStatus, String> {
+    let pending = db.with_conn(|conn| pending_count(conn))?;
+    Ok(engine.set_status(|s| s.pending = pending))
+}
+
+/// Ejecuta un ciclo de sincronización ahora (botón "Sincronizar").
+#[tauri::command]
+pub fn sync_now(app: AppHandle, db: State<'_, Db>, engine: State<'_, SyncEngine>) -> Result<SyncReport, String> {
+    emit_status(&app, &engine.set_status(|s| s.state = SyncState::Running));
+    let result = run_cycle(&db, &engine);
+    emit_status(&app, &engine.status());
+    result
+}
+
+/// Arranca la sincronización automática cada `interval_secs` segundos (mínimo 15).
+#[tauri::command]
+pub fn sync_start(app: AppHandle, engine: State<'_, SyncEngine>, interval_secs: Option<u64>) -> Result<(), String> {
+    if engine.auto.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let interval = Duration::from_secs(interval_secs.unwrap_or(60).max(15));
+    std::thread::spawn(move || {
+        let engine = app.state::<SyncEngine>();
+        let db = app.state::<Db>();
+        while engine.auto.load(Ordering::SeqCst) {
+            let has_config = engine.config.lock().map(|c| c.is_some()).unwrap_or(false);
+            if has_config {
+                if let Err(e) = run_cycle(&db, &engine) {
+                    log::warn!("sync: ciclo automático falló: {}", e);
+                }
+                emit_status(&app, &engine.status());
+            }
+            std::thread::sleep(interval);
+        }
+        log::info!("sync: ciclo automático detenido");
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn sync_stop(engine: State<'_, SyncEngine>) {
+    engine.auto.store(false, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    type Handler = dyn Fn(&str, &str, &str) -> (u16, String) + Send + Sync;
+
+    /// Servidor HTTP mínimo que hace de PostgREST: una respuesta por conexión.
+    struct StandIn {
+        url: String,
+        requests: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl StandIn {
+        fn start(handler: Arc<Handler>) -> StandIn {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/rest/v1", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let log = requests.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else { break };
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let mut parts = line.split_whitespace();
+                    let method = parts.next().unwrap_or_default().to_string();
+                    let path = parts.next().unwrap_or_default().trim_start_matches("/rest/v1/").to_string();
+                    let mut length = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = header.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    let body = String::from_utf8(body).unwrap();
+                    let path = percent_decode(&path);
+                    let (code, reply) = handler(&method, &path, &body);
+                    log.lock().unwrap().push((method, path));
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        code,
+                        reply.len(),
+                        reply
+                    );
+                }
+            });
+            StandIn { url, requests }
+        }
+
+        fn paths(&self, prefix: &str) -> Vec<String> {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().filter(|(_, p)| p.starts_with(prefix)).map(|(_, p)| p.clone()).collect()
+        }
+    }
+
+    fn percent_decode(s: &str) -> String {
+        let bytes = s.as_bytes();
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' && i + 2 < bytes.len() {
+                out.push(u8::from_str_radix(&s[i + 1..i + 3], 16).unwrap());
+                i += 3;
+            } else {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn engine(url: &str) -> SyncEngine {
+        let engine = SyncEngine::default();
+        engine
+            .configure(SyncConfig {
+                base_url: url.to_string(),
+                api_key: "anon".to_string(),
+                access_token: None,
+                tenant_id: "t1".to_string(),
+            })
+            .unwrap();
+        engine
+    }
+
+    /// Catálogo remoto: 1200 productos y los primeros 700 con la misma hora (UPDATE masivo).
+    fn catalog() -> Vec<Value> {
+        (1..=1200)
+            .map(|id| {
+                let ts = if id <= 700 { "2024-05-01T10:00:00.000000+00:00" } else { "2024-05-02T08:30:00.000000+00:00" };
+                json!({
+                    "id": id, "code": format!("P{:04}", id), "barcode": null, "name": format!("Producto {}", id),
+                    "description": null, "price": 10.5, "cost": 7, "stock": 3, "category": null, "supplier": null,
+                    "minimum_stock": null, "image_url": null, "updated_at": ts,
+                })
+            })
+            .collect()
+    }
+
+    /// Aplica `or=(updated_at.gt."X",and(updated_at.eq."X",id.gt.Y))` y `limit` como PostgREST.
+    fn page(rows: &[Value], path: &str) -> Vec<Value> {
+        let query = path.split_once('?').map(|(_, q)| q).unwrap_or_default();
+        let param = |name: &str| query.split('&').find_map(|kv| kv.strip_prefix(name)).unwrap_or_default().to_string();
+        let cursor = param("or=");
+        let ts = cursor.split('"').nth(1).unwrap_or_default().to_string();
+        let id: i64 = cursor.rsplit("id.gt.").next().unwrap().trim_end_matches("))").parse().unwrap();
+        let limit: usize = param("limit=").parse().unwrap();
+        let ts = parse_ts(&ts).unwrap();
+        rows.iter()
+            .filter(|r| {
+                let rts = parse_ts(r["updated_at"].as_str().unwrap()).unwrap();
+                rts > ts || (rts == ts && r["id"].as_i64().unwrap() > id)
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn pull_pages_by_keyset_without_skipping_shared_timestamps() {
+        let rows = catalog();
+        let server = StandIn::start(Arc::new(move |method, path, _| match (method, path) {
+            ("GET", p) if p.starts_with("products?") => (200, Value::Array(page(&rows, p)).to_string()),
+            _ => (404, "{}".to_string()),
+        }));
+        let db = Db::open_in_memory().unwrap();
+        let engine = engine(&server.url);
+
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!(report.pulled_products, 1200);
+        let (count, watermark, watermark_id) = db
+            .with_conn(|conn| {
+                let count: i64 = conn.query_row("SELECT COUNT(*) FROM products WHERE remote_id IS NOT NULL", [], |r| r.get(0))?;
+                Ok((count, get_state(conn, "products_watermark")?, get_state(conn, "products_watermark_id")?))
+            })
+            .unwrap();
+        assert_eq!(count, 1200);
+        assert_eq!(watermark.as_deref(), Some("2024-05-02T08:30:00.000000+00:00"));
+        assert_eq!(watermark_id.as_deref(), Some("1200"));
+        // 500 + 500 + 200: la segunda página arranca a media hora compartida (id 500).
+        let pages = server.paths("products?");
+        assert_eq!(pages.len(), 3);
+        assert!(pages[1].contains("id.gt.500"), "{}", pages[1]);
+
+        // Sin cambios remotos, el siguiente ciclo no baja nada.
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!(report.pulled_products, 0);
+    }
+
+    #[test]
+    fn rejected_outbox_rows_stay_queued_and_retry() {
+        let accept = Arc::new(AtomicBool::new(false));
+        let deltas = Arc::new(Mutex::new(Vec::new()));
+        let (accept_h, deltas_h) = (accept.clone(), deltas.clone());
+        let server = StandIn::start(Arc::new(move |method, path, body| match (method, path) {
+            ("POST", "rpc/adjust_stock") if accept_h.load(Ordering::SeqCst) => {
+                deltas_h.lock().unwrap().push(serde_json::from_str::<Value>(body).unwrap());
+                (204, String::new())
+            }
+            ("POST", "rpc/adjust_stock") => (500, r#"{"message":"boom"}"#.to_string()),
+            ("GET", p) if p.starts_with("products?") => (200, "[]".to_string()),
+            _ => (404, "{}".to_string()),
+        }));
+        let db = Db::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            conn.execute("INSERT INTO products (remote_id, code, name, price, stock) VALUES (77, 'A1', 'Clavo', '5', 10)", [])?;
+            enqueue_stock_delta(conn, conn.last_insert_rowid(), Decimal::from(-2))
+        })
+        .unwrap();
+        let engine = engine(&server.url);
+
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!((report.pushed, report.failed), (0, 1));
+        let (pending, attempts, error): (i64, i64, Option<String>) = db
+            .with_conn(|conn| {
+                conn.query_row("SELECT COUNT(*), MAX(attempts), MAX(last_error) FROM sync_outbox", [], |r| {
+                    Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+                })
+            })
+            .unwrap();
+        assert_eq!((pending, attempts), (1, 1));
+        assert!(error.unwrap().contains("HTTP 500"));
+        assert_eq!(engine.status().state, SyncState::Error);
+
+        accept.store(true, Ordering::SeqCst);
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!((report.pushed, report.failed), (1, 0));
+        assert_eq!(db.with_conn(|conn| pending_count(conn)).unwrap(), 0);
+        assert_eq!(*deltas.lock().unwrap(), vec![json!({ "p_product_id": 77, "p_delta": -2 })]);
+        assert_eq!(engine.status().state, SyncState::Idle);
+    }
+
+    #[test]
+    fn sales_wait_for_their_local_products_to_be_pushed() {
+        let accept = Arc::new(AtomicBool::new(false));
+        let items = Arc::new(Mutex::new(Vec::new()));
+        let (accept_h, items_h) = (accept.clone(), items.clone());
+        let server = StandIn::start(Arc::new(move |method, path, body| match (method, path) {
+            ("POST", "products") if accept_h.load(Ordering::SeqCst) => (201, r#"[{"id":31}]"#.to_string()),
+            ("POST", "products") => (500, r#"{"message":"boom"}"#.to_string()),
+            ("POST", p) if p.starts_with("sales?") => (201, r#"[{"id":8}]"#.to_string()),
+            ("DELETE", p) if p.starts_with("sale_items?") => (204, String::new()),
+            ("POST", "sale_items") => {
+                items_h.lock().unwrap().push(serde_json::from_str::<Value>(body).unwrap());
+                (201, String::new())
+            }
+            ("GET", p) if p.starts_with("products?") => (200, "[]".to_string()),
+            _ => (404, "{}".to_string()),
+        }));
+        let db = Db::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            conn.execute("INSERT INTO products (code, name, price, stock) VALUES ('N1', 'Nuevo', '3', 5)", [])?;
+            let product_id = conn.last_insert_rowid();
+            enqueue(conn, "product", product_id)?;
+            conn.execute("INSERT INTO sales (sale_number, subtotal, total) VALUES ('SALE-1', '3', '3')", [])?;
+            let sale_id = conn.last_insert_rowid();
+            conn.execute(
+                "INSERT INTO sale_items (sale_id, product_id, quantity, unit_price, subtotal) VALUES (?1, ?2, 1, '3', '3')",
+                params![sale_id, product_id],
+            )?;
+            enqueue(conn, "sale", sale_id)
+        })
+        .unwrap();
+        let engine = engine(&server.url);
+
+        // El alta falla: la venta ni siquiera intenta subir su encabezado y no cuenta como rechazo.
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!((report.pushed, report.failed), (0, 1));
+        assert!(server.paths("sales").is_empty());
+        let attempts: Vec<i64> = db
+            .with_conn(|conn| {
+                let mut stmt = conn.prepare("SELECT attempts FROM sync_outbox ORDER BY id")?;
+                let rows = stmt.query_map([], |r| r.get(0))?;
+                rows.collect()
+            })
+            .unwrap();
+        assert_eq!(attempts, vec![1, 0]);
+
+        accept.store(true, Ordering::SeqCst);
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!((report.pushed, report.failed), (2, 0));
+        assert_eq!(db.with_conn(|conn| remote_id(conn, "products", 1)).unwrap(), Some(31));
+        assert_eq!(items.lock().unwrap()[0][0]["product_id"], json!(31));
+    }
+
+    #[test]
+    fn stock_deltas_wait_for_the_product_to_exist_remotely() {
+        let rows = vec![json!({
+            "id": 12, "code": "L1", "barcode": null, "name": "Solo local", "description": null, "price": 1,
+            "cost": null, "stock": 10, "category": null, "supplier": null, "minimum_stock": null,
+            "image_url": null, "updated_at": "2024-05-01T10:00:00.000000+00:00",
+        })];
+        let deltas = Arc::new(Mutex::new(Vec::new()));
+        let deltas_h = deltas.clone();
+        let server = StandIn::start(Arc::new(move |method, path, body| match (method, path) {
+            ("POST", "rpc/adjust_stock") => {
+                deltas_h.lock().unwrap().push(serde_json::from_str::<Value>(body).unwrap());
+                (204, String::new())
+            }
+            ("GET", p) if p.starts_with("products?") => (200, Value::Array(page(&rows, p)).to_string()),
+            _ => (404, "{}".to_string()),
+        }));
+        let db = Db::open_in_memory().unwrap();
+        let local_id = db
+            .with_conn(|conn| {
+                conn.execute("INSERT INTO products (code, name, price, stock) VALUES ('L1', 'Solo local', '1', 3)", [])?;
+                let local_id = conn.last_insert_rowid();
+                enqueue_stock_delta(conn, local_id, Decimal::from(-1))?;
+                conn.execute("INSERT INTO products (remote_id, code, name, price, stock) VALUES (9, 'R1', 'Remoto', '1', 4)", [])?;
+                enqueue_stock_delta(conn, conn.last_insert_rowid(), Decimal::from(-1))?;
+                Ok(local_id)
+            })
+            .unwrap();
+        let engine = engine(&server.url);
+
+        // El delta del producto sin ligar se queda en cola sin contar como rechazo; el pull lo
+        // liga por código y conserva la venta local sobre la existencia remota.
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!((report.pushed, report.failed), (1, 0));
+        let stock = db
+            .with_conn(|conn| conn.query_row("SELECT stock FROM products WHERE id = ?1", [local_id], |r| db::get_quantity(r, 0)))
+            .unwrap();
+        assert_eq!(stock, Decimal::from(9));
+        assert_eq!(db.with_conn(|conn| pending_count(conn)).unwrap(), 1);
+
+        let report = run_cycle(&db, &engine).unwrap();
+        assert_eq!((report.pushed, report.failed), (1, 0));
+        assert_eq!(
+            *deltas.lock().unwrap(),
+            vec![json!({ "p_product_id": 9, "p_delta": -1 }), json!({ "p_product_id": 12, "p_delta": -1 })]
+        );
+    }
+
+    #[test]
+    fn unreachable_server_leaves_outbox_and_reports_offline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rest/v1", listener.local_addr().unwrap());
+        drop(listener);
+        let db = Db::open_in_memory().unwrap();
+        db.with_conn(|conn| {
+            conn.execute("INSERT INTO products (remote_id, code, name, price, stock) VALUES (5, 'B1', 'Pija', '1', 4)", [])?;
+            enqueue_stock_delta(conn, conn.last_insert_rowid(), Decimal::ONE)
+        })
+        .unwrap();
+        let engine = engine(&url);
+        assert!(run_cycle(&db, &engine).is_err());
+        assert_eq!(engine.status().state, SyncState::Offline);
+        assert_eq!(db.with_conn(|conn| pending_count(conn)).unwrap(), 1);
+    }
+}
@@ -0,0 +1,424 @@
+//! Cálculo de impuestos (IVA e IEPS) con perfiles por producto.
+//!
+//! - IVA: 16% general, 8% región fronteriza, 0% o exento.
+//! - IEPS: tasa opcional; el IVA se calcula sobre base + IEPS, como lo pide el SAT.
+//! - Precios con impuesto incluido (lo normal en mostrador) o más impuestos.
+//!
+//! El desglose por tasa (`TaxBreakdown`) es el mismo para ticket, reportes y CFDI.
+
+use crate::db::{self, Db, SaleItem};
+use crate::money::{Currency, Money, TaxRounding};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::State;
+
+/// Claves de impuesto del catálogo c_Impuesto del SAT.
+pub const SAT_IVA: &str = "002";
+pub const SAT_IEPS: &str = "003";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IvaRate {
+    /// 16%
+    General,
+    /// 8% región fronteriza
+    Frontera,
+    /// Tasa 0%
+    Cero,
+    /// Exento (no se traslada IVA; en CFDI va como TipoFactor Exento)
+    Exento,
+}
+
+impl IvaRate {
+    /// Porcentaje; `None` si es exento.
+    pub fn percent(&self) -> Option<Decimal> {
+        match self {
+            IvaRate::General => Some(Decimal::from(16)),
+            IvaRate::Frontera => Some(Decimal::from(8)),
+            IvaRate::Cero => Some(Decimal::ZERO),
+            IvaRate::Exento => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IvaRate::General => "general",
+            IvaRate::Frontera => "frontera",
+            IvaRate::Cero => "cero",
+            IvaRate::Exento => "exento",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<IvaRate> {
+        match s {
+            "general" => Some(IvaRate::General),
+            "frontera" => Some(IvaRate::Frontera),
+            "cero" => Some(IvaRate::Cero),
+            "exento" => Some(IvaRate::Exento),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaxProfile {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub name: String,
+    pub iva: IvaRate,
+    /// Porcentaje de IEPS (p. ej. 8 para botanas). `None` si no aplica.
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub ieps_rate: Option<Decimal>,
+    #[serde(default = "default_true")]
+    pub price_includes_tax: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl TaxProfile {
+    /// Perfil equivalente a la tasa global de Configuración → Impuestos (precios con impuesto).
+    /// Solo acepta las tasas de IVA vigentes; cualquier otra es un error de configuración.
+    pub fn from_global_rate(rate: Decimal) -> Result<TaxProfile, String> {
+        let iva = if rate == Decimal::from(16) {
+            IvaRate::General
+        } else if rate == Decimal::from(8) {
+            IvaRate::Frontera
+        } else if rate.is_zero() {
+            IvaRate::Cero
+        } else {
+            return Err(format!("Tasa de IVA no soportada: {}%. Use 16, 8 o 0 en Configuración → Impuestos.", rate.normalize()));
+        };
+        Ok(TaxProfile { id: None, name: format!("IVA {}%", rate.normalize()), iva, ieps_rate: None, price_includes_tax: true })
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let iva: String = row.get("iva")?;
+        let ieps: Option<String> = row.get("ieps_rate")?;
+        Ok(TaxProfile {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            iva: IvaRate::parse(&iva).unwrap_or(IvaRate::General),
+            ieps_rate: ieps.and_then(|s| s.parse().ok()),
+            price_includes_tax: row.get("price_includes_tax")?,
+        })
+    }
+}
+
+/// Impuestos de un renglón ya con descuento aplicado.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LineTax {
+    pub base: Money,
+    pub ieps: Money,
+    pub iva: Money,
+    pub total: Money,
+}
+
+/// Calcula base, IEPS e IVA de un importe. Si el precio incluye impuestos, `amount` es el
+/// total y la base se despeja; los centavos de redondeo se quedan en la base para que
+/// base + IEPS + IVA sea exactamente lo cobrado.
+pub fn compute_line(amount: Money, profile: &TaxProfile) -> LineTax {
+    let currency = amount.currency();
+    let ieps_pct = profile.ieps_rate.unwrap_or_default();
+    let iva_pct = profile.iva.percent().unwrap_or_default();
+    if profile.price_includes_tax {
+        let factor = (Decimal::ONE + ieps_pct / Decimal::ONE_HUNDRED) * (Decimal::ONE + iva_pct / Decimal::ONE_HUNDRED);
+        let base = Money::from_decimal(amount.to_decimal() / factor, currency);
+        let ieps = base.percent(ieps_pct);
+        let iva = (base + ieps).percent(iva_pct);
+        let base = amount - ieps - iva;
+        LineTax { base, ieps, iva, total: amount }
+    } else {
+        let ieps = amount.percent(ieps_pct);
+        let iva = (amount + ieps).percent(iva_pct);
+        LineTax { base: amount, ieps, iva, total: amount + ieps + iva }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum TaxKind {
+    #[serde(rename = "IVA")]
+    Iva,
+    #[serde(rename = "IEPS")]
+    Ieps,
+}
+
+impl TaxKind {
+    pub fn sat_code(&self) -> &'static str {
+        match self {
+            TaxKind::Iva => SAT_IVA,
+            TaxKind::Ieps => SAT_IEPS,
+        }
+    }
+}
+
+/// Un renglón del desglose: impuesto + tasa (o exento) con su base e importe.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaxBreakdown {
+    pub kind: TaxKind,
+    /// Porcentaje; `None` = exento.
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub rate: Option<Decimal>,
+    pub base: Money,
+    pub amount: Money,
+}
+
+impl TaxBreakdown {
+    pub fn is_exempt(&self) -> bool {
+        self.rate.is_none()
+    }
+
+    /// `TipoFactor` del CFDI.
+    pub fn tipo_factor(&self) -> &'static str {
+        if self.is_exempt() {
+            "Exento"
+        } else {
+            "Tasa"
+        }
+    }
+
+    /// `TasaOCuota` del CFDI con 6 decimales (16% -> `0.160000`).
+    pub fn tasa_o_cuota(&self) -> Option<String> {
+        self.rate.map(|r| format!("{:.6}", r / Decimal::ONE_HUNDRED))
+    }
+
+    /// Texto corto para ticket: `IVA 16%`, `IEPS 8%`, `IVA exento`.
+    pub fn label(&self) -> String {
+        let name = match self.kind {
+            TaxKind::Iva => "IVA",
+            TaxKind::Ieps => "IEPS",
+        };
+        match self.rate {
+            Some(r) => format!("{} {}%", name, r.normalize()),
+            None => format!("{} exento", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaxDocument {
+    pub lines: Vec<LineTax>,
+    pub breakdown: Vec<TaxBreakdown>,
+    pub subtotal: Money,
+    pub taxes: Money,
+    pub total: Money,
+}
+
+type Groups = BTreeMap<(TaxKind, Option<Decimal>), (Money, Money)>;
+
+/// Acumula base e impuesto por (impuesto, tasa). La base del IVA incluye el IEPS.
+fn add_to_groups(groups: &mut Groups, profile: &TaxProfile, line: &LineTax) {
+    let zero = (Money::zero(line.base.currency()), Money::zero(line.base.currency()));
+    if let Some(rate) = profile.ieps_rate.filter(|r| !r.is_zero()) {
+        let entry = groups.entry((TaxKind::Ieps, Some(rate))).or_insert(zero);
+        entry.0 += line.base;
+        entry.1 += line.ieps;
+    }
+    let entry = groups.entry((TaxKind::Iva, profile.iva.percent())).or_insert(zero);
+    entry.0 += line.base + line.ieps;
+    entry.1 += line.iva;
+}
+
+fn finish_groups(groups: Groups, rounding: TaxRounding, currency: Currency) -> Vec<TaxBreakdown> {
+    groups
+        .into_iter()
+        .map(|((kind, rate), (base, amount))| {
+            let amount = match (rounding, rate) {
+                (TaxRounding::PerDocument, Some(r)) => base.percent(r),
+                (_, None) => Money::zero(currency),
+                (TaxRounding::PerLine, Some(_)) => amount,
+            };
+            TaxBreakdown { kind, rate, base, amount }
+        })
+        .collect()
+}
+
+/// Impuestos de todo un documento. Con `PerLine` el desglose suma los renglones ya redondeados;
+/// con `PerDocument` el impuesto de cada tasa se calcula una sola vez sobre la suma de bases.
+///
+/// Los renglones con precio con impuesto cobran un importe fijo y su base se despeja; los de
+/// precio más impuesto suman su base y el total se arma con los impuestos del documento, así
+/// que con `PerDocument` se cumple subtotal + impuestos = total aunque difiera de la suma de
+/// los totales por renglón.
+pub fn compute_document(lines: &[(Money, &TaxProfile)], rounding: TaxRounding, currency: Currency) -> TaxDocument {
+    let computed: Vec<LineTax> = lines.iter().map(|(amount, profile)| compute_line(*amount, profile)).collect();
+    let mut groups = Groups::new();
+    for ((_, profile), line) in lines.iter().zip(&computed) {
+        add_to_groups(&mut groups, profile, line);
+    }
+
+    let breakdown = finish_groups(groups, rounding, currency);
+    let taxes = Money::sum(breakdown.iter().map(|b| b.amount), currency);
+    let mut charged = Money::zero(currency);
+    let mut open_bases = Money::zero(currency);
+    let mut included_taxes = Money::zero(currency);
+    for ((_, profile), line) in lines.iter().zip(&computed) {
+        if profile.price_includes_tax {
+            charged += line.total;
+            included_taxes += line.ieps + line.iva;
+        } else {
+            open_bases += line.base;
+        }
+    }
+    let total = if lines.iter().all(|(_, profile)| profile.price_includes_tax) {
+        charged
+    } else {
+        charged + open_bases + (taxes - included_taxes)
+    };
+    TaxDocument { lines: computed, breakdown, subtotal: total - taxes, taxes, total }
+}
+
+/// Desglose de una venta guardada a partir de los impuestos de cada partida, con la misma
+/// regla de redondeo de la venta. Las partidas anteriores a los perfiles de impuesto no traen
+/// desglose y se omiten.
+pub fn sale_breakdown(items: &[SaleItem], rounding: TaxRounding, currency: Currency) -> Vec<TaxBreakdown> {
+    let mut groups = Groups::new();
+    for item in items {
+        let (Some(profile), Some(base)) = (&item.tax_profile, item.tax_base) else {
+            continue;
+        };
+        let line = LineTax {
+            base: Money::from_decimal(base, currency),
+            ieps: Money::from_decimal(item.tax_ieps.unwrap_or_default(), currency),
+            iva: Money::from_decimal(item.tax_iva.unwrap_or_default(), currency),
+            total: Money::from_decimal(item.subtotal, currency),
+        };
+        add_to_groups(&mut groups, profile, &line);
+    }
+    finish_groups(groups, rounding, currency)
+}
+
+// ---------- Perfiles en la base local ----------
+
+pub(crate) fn get_profile(conn: &Connection, id: i64) -> rusqlite::Result<Option<TaxProfile>> {
+    conn.query_row("SELECT * FROM tax_profiles WHERE id = ?1", [id], TaxProfile::from_row)
+        .optional()
+}
+
+/// Perfil del producto; si no tiene, se usa la tasa global que manda el frontend.
+pub(crate) fn profile_for_product(conn: &Connection, product_id: i64, global_rate: Decimal) -> Result<TaxProfile, String> {
+    let sql = |e: rusqlite::Error| format!("base local: {}", e);
+    let profile_id: Option<i64> = conn
+        .query_row("SELECT tax_profile_id FROM products WHERE id = ?1", [product_id], |r| r.get(0))
+        .optional()
+        .map_err(sql)?
+        .flatten();
+    match profile_id.map(|id| get_profile(conn, id)).transpose().map_err(sql)?.flatten() {
+        Some(profile) => Ok(profile),
+        None => TaxProfile::from_global_rate(global_rate),
+    }
+}
+
+// ---------- Comandos Tauri ----------
+
+#[tauri::command]
+pub fn tax_list_profiles(db: State<'_, Db>) -> Result<Vec<TaxProfile>, String> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT * FROM tax_profiles ORDER BY name")?;
+        let rows = stmt.query_map([], TaxProfile::from_row)?;
+        rows.collect()
+    })
+}
+
+#[tauri::command]
+pub fn tax_save_profile(db: State<'_, Db>, profile: TaxProfile) -> Result<TaxProfile, String> {
+    if profile.name.trim().is_empty() {
+        return Err("El perfil de impuestos necesita nombre.".to_string());
+    }
+    if profile.ieps_rate.is_some_and(|r| r < Decimal::ZERO) {
+        return Err("La tasa de IEPS no puede ser negativa.".to_string());
+    }
+    let ieps = profile.ieps_rate.map(|r| r.normalize().to_string());
+    db.with_conn(|conn| {
+        let id = match profile.id {
+            Some(id) => {
+                conn.execute(
+                    "UPDATE tax_profiles SET name = ?2, iva = ?3, ieps_rate = ?4, price_includes_tax = ?5 WHERE id = ?1",
+                    params![id, profile.name.trim(), profile.iva.as_str(), ieps, profile.price_includes_tax],
+                )?;
+                id
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO tax_profiles (name, iva, ieps_rate, price_includes_tax) VALUES (?1, ?2, ?3, ?4)",
+                    params![profile.name.trim(), profile.iva.as_str(), ieps, profile.price_includes_tax],
+                )?;
+                conn.last_insert_rowid()
+            }
+        };
+        get_profile(conn, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+    })
+}
+
+#[tauri::command]
+pub fn tax_delete_profile(db: State<'_, Db>, id: i64) -> Result<(), String> {
+    db.with_conn(|conn| conn.execute("DELETE FROM tax_profiles WHERE id = ?1", [id]).map(|_| ()))
+}
+
+/// Asigna (o quita, con `None`) el perfil de impuestos de un producto.
+#[tauri::command]
+pub fn tax_assign_profile(db: State<'_, Db>, product_id: i64, profile_id: Option<i64>) -> Result<(), String> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE products SET tax_profile_id = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1",
+            params![product_id, profile_id],
+        )
+        .map(|_| ())
+    })
+}
+
+/// Desglose por tasa de una venta local (ticket, reportes, CFDI).
+#[tauri::command]
+pub fn tax_sale_breakdown(db: State<'_, Db>, sale_id: i64) -> Result<Vec<TaxBreakdown>, String> {
+    let sale = db
+        .with_conn(|conn| db::get_sale(conn, sale_id))?
+        .ok_or_else(|| "No se encontró la venta.".to_string())?;
+    Ok(sale_breakdown(&sale.sale_items, sale.tax_rounding, Currency::MXN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plus_tax() -> TaxProfile {
+        TaxProfile { id: None, name: "IVA 16% + impuesto".into(), iva: IvaRate::General, ieps_rate: None, price_includes_tax: false }
+    }
+
+    fn mxn(cents: i64) -> Money {
+        Money::from_decimal(Decimal::new(cents, 2), Currency::MXN)
+    }
+
+    #[test]
+    fn per_document_total_is_subtotal_plus_document_taxes() {
+        let profile = plus_tax();
+        let lines = vec![(mxn(3), &profile); 3];
+        let doc = compute_document(&lines, TaxRounding::PerDocument, Currency::MXN);
+        assert_eq!(doc.subtotal, mxn(9));
+        assert_eq!(doc.taxes, mxn(1));
+        assert_eq!(doc.total, mxn(10));
+
+        let per_line = compute_document(&lines, TaxRounding::PerLine, Currency::MXN);
+        assert_eq!(per_line.subtotal, mxn(9));
+        assert_eq!(per_line.taxes, mxn(0));
+        assert_eq!(per_line.total, mxn(9));
+    }
+
+    #[test]
+    fn tax_included_prices_keep_the_charged_total() {
+        let profile = TaxProfile::from_global_rate(Decimal::from(16)).unwrap();
+        let lines = vec![(mxn(3), &profile); 3];
+        let doc = compute_document(&lines, TaxRounding::PerDocument, Currency::MXN);
+        assert_eq!(doc.total, mxn(9));
+        assert_eq!(doc.subtotal + doc.taxes, doc.total);
+    }
+
+    #[test]
+    fn unsupported_global_rate_is_an_error() {
+        assert_eq!(TaxProfile::from_global_rate(Decimal::from(8)).unwrap().iva, IvaRate::Frontera);
+        assert_eq!(TaxProfile::from_global_rate(Decimal::ZERO).unwrap().iva, IvaRate::Cero);
+        assert!(TaxProfile::from_global_rate(Decimal::from(15)).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Texto del ticket para ventas cobradas en la base local.
+//! Mismo formato que `getTicketText` de printerService.js (32 columnas, plantillas
+//! simple / minimal / full) pero con importes `Money` para que el ticket cuadre con la venta.
+
+use crate::cfdi::autofactura::{self, TicketInvoiceData};
+use crate::db::{self, Db};
+use crate::money::{Currency, Money};
+use crate::tax::{self, TaxBreakdown};
+use crate::units::{self, Unit};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use tauri::State;
+
+pub const TICKET_WIDTH: usize = 32;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TicketOptions {
+    #[serde(default)]
+    pub business_name: String,
+    /// `simple` (default), `minimal` o `full`.
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub footer_lines: String,
+    /// Columnas de la impresora (32 para 58 mm, 48 para 80 mm).
+    #[serde(default)]
+    pub width: Option<usize>,
+    /// Imprimir el bloque de autofactura (folio, código de verificación y QR).
+    #[serde(default)]
+    pub autofactura: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TicketLine {
+    pub name: String,
+    pub quantity: Decimal,
+    pub unit: Unit,
+    /// Decimales con que se imprime la cantidad.
+    pub precision: u32,
+    pub unit_price: Money,
+    pub subtotal: Money,
+}
+
+#[derive(Debug, Clone)]
+pub struct TicketData {
+    pub sale_number: String,
+    pub date: String,
+    pub payment_method: String,
+    pub lines: Vec<TicketLine>,
+    pub subtotal: Money,
+    pub discount: Money,
+    pub tax: Money,
+    /// Desglose por tasa; si viene vacío se imprime solo `Impuesto:`.
+    pub taxes: Vec<TaxBreakdown>,
+    pub total: Money,
+    /// Bloque de autofactura; el QR lo imprime `print_ticket` con `ticket_qr`.
+    pub autofactura: Option<TicketInvoiceData>,
+}
+
+/// Renglón con etiqueta a la izquierda e importe a la derecha.
+fn amount_line(label: &str, amount: &Money, width: usize) -> String {
+    let value = amount.to_string();
+    let pad = width.saturating_sub(label.chars().count() + value.len()).max(1);
+    format!("{}{}{}", label, " ".repeat(pad), value)
+}
+
+fn fit(s: &str, width: usize) -> String {
+    let cut: String = s.chars().take(width).collect();
+    format!("{:<width$}", cut, width = width)
+}
+
+pub fn render_ticket(data: &TicketData, opts: &TicketOptions) -> String {
+    let width = opts.width.unwrap_or(TICKET_WIDTH).clamp(24, 64);
+    let template = opts.template.as_deref().unwrap_or("simple");
+    let minimal = template == "minimal";
+    let sep = "-".repeat(width);
+    let title = if opts.business_name.trim().is_empty() { "TICKET" } else { opts.business_name.trim() };
+    let mut lines: Vec<String> = Vec::new();
+
+    if minimal {
+        lines.push(fit(title, width));
+        lines.push(format!("#{}  {}", data.sale_number, data.date));
+        lines.push(format!("Pago: {}", data.payment_method));
+        lines.push(String::new());
+    } else {
+        lines.push(sep.clone());
+        lines.push(fit(title, width));
+        lines.push(sep.clone());
+        lines.push(format!("Ticket #{}", data.sale_number));
+        lines.push(format!("Fecha: {}", data.date));
+        lines.push(format!("Pago: {}", data.payment_method));
+        lines.push(sep.clone());
+        if template == "full" {
+            lines.push("Conserve este ticket".to_string());
+            lines.push(sep.clone());
+        }
+    }
+
+    // Cant(4) + espacio + nombre + precios alineados a la derecha.
+    const CANT_W: usize = 4;
+    const PRICE_W: usize = 9;
+    let name_w = width.saturating_sub(CANT_W + 1 + 2 * PRICE_W).max(8);
+    lines.push(fit("Cant Producto", width - 2 * PRICE_W) + &format!("{:>w$}{:>w$}", "P.Unit", "Total", w = PRICE_W));
+    if !minimal {
+        lines.push(sep.clone());
+    }
+    for item in &data.lines {
+        let prices = format!("{:>w$}{:>w$}", item.unit_price.to_string(), item.subtotal.to_string(), w = PRICE_W);
+        let quantity = units::format(item.quantity, item.unit, item.precision);
+        // Piezas: la cantidad va en su columna. Granel: `0.750 kg` no cabe ahí y va en su propio
+        // renglón junto a los importes, y el nombre usa todo el ancho.
+        let by_piece = item.unit == Unit::Pieza && quantity.chars().count() <= CANT_W;
+        let chunk_w = if by_piece { name_w } else { width - CANT_W - 1 };
+        let name: Vec<char> = item.name.trim().chars().collect();
+        let chunks: Vec<String> = if name.is_empty() {
+            vec!["N/A".to_string()]
+        } else {
+            name.chunks(chunk_w).take(3).map(|c| c.iter().collect()).collect()
+        };
+        for (i, chunk) in chunks.iter().enumerate() {
+            let left = if i == 0 && by_piece { format!("{:>CANT_W$} ", quantity) } else { " ".repeat(CANT_W + 1) };
+            let mut line = left + &fit(chunk, chunk_w);
+            if by_piece && i == chunks.len() - 1 {
+                line.push_str(&prices);
+            }
+            lines.push(line);
+        }
+        if !by_piece {
+            lines.push(fit(&format!("{} {}", " ".repeat(CANT_W), quantity), width - 2 * PRICE_W) + &prices);
+        }
+    }
+
+    if !minimal {
+        lines.push(sep.clone());
+    }
+    lines.push(amount_line("Subtotal:", &data.subtotal, width));
+    if !data.discount.is_zero() {
+        lines.push(amount_line("Descuento:", &-data.discount, width));
+    }
+    let rated: Vec<&TaxBreakdown> = data.taxes.iter().filter(|t| !t.is_exempt() && !t.amount.is_zero()).collect();
+    if rated.is_empty() {
+        if !data.tax.is_zero() {
+            lines.push(amount_line("Impuesto:", &data.tax, width));
+        }
+    } else {
+        for t in rated {
+            lines.push(amount_line(&format!("{}:", t.label()), &t.amount, width));
+        }
+    }
+    if !minimal {
+        lines.push(sep.clone());
+    }
+    lines.push(amount_line("TOTAL:", &data.total, width));
+    lines.push(sep.clone());
+
+    for footer in opts.footer_lines.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        lines.push(footer.to_string());
+    }
+    lines.push("   Gracias por su compra".to_string());
+    lines.push(sep.clone());
+    if let Some(af) = &data.autofactura {
+        lines.push("FACTURA ELECTRONICA".to_string());
+        lines.push(format!("Folio: {}", af.folio));
+        lines.push(format!("Fecha: {}", af.fecha));
+        lines.push(format!("Codigo: {}", af.codigo));
+        if let Some(vence) = &af.vence {
+            lines.push(format!("Facture antes del {}", vence));
+        }
+        if af.qr.starts_with("http") {
+            let url: Vec<char> = af.qr.split('?').next().unwrap_or_default().chars().collect();
+            lines.extend(url.chunks(width).map(|c| c.iter().collect::<String>()));
+        }
+        lines.push(sep);
+    }
+    // Dos renglones al final para que no se corte el ticket
+    lines.push(String::new());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Fecha guardada en UTC (ISO 8601) a hora local para el ticket.
+fn local_date(iso: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(iso)
+        .map(|d| d.with_timezone(&chrono::Local).format("%d/%m/%Y %H:%M").to_string())
+        .unwrap_or_else(|_| iso.to_string())
+}
+
+/// Arma los datos del ticket de una venta guardada en la base local.
+pub fn ticket_data(db: &Db, sale_id: i64) -> Result<TicketData, String> {
+    db.with_conn(|conn| {
+        let sale = db::get_sale(conn, sale_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let mut lines = Vec::with_capacity(sale.sale_items.len());
+        for item in &sale.sale_items {
+            let product = db::get_product(conn, item.product_id)?;
+            let precision = match &product {
+                Some(p) if p.unit == item.unit => p.precision(),
+                _ => item.unit.default_precision(),
+            };
+            lines.push(TicketLine {
+                name: product.map(|p| p.name).unwrap_or_else(|| "N/A".to_string()),
+                quantity: item.quantity,
+                unit: item.unit,
+                precision,
+                unit_price: Money::mxn(item.unit_price),
+                subtotal: Money::mxn(item.subtotal),
+            });
+        }
+        Ok(TicketData {
+            sale_number: sale.sale_number,
+            date: local_date(sale.created_at.as_deref().unwrap_or_default()),
+            payment_method: sale.payment_method.unwrap_or_else(|| "N/A".to_string()),
+            lines,
+            subtotal: Money::mxn(sale.subtotal),
+            discount: Money::mxn(sale.discount),
+            tax: Money::mxn(sale.tax),
+            taxes: tax::sale_breakdown(&sale.sale_items, sale.tax_rounding, Currency::MXN),
+            total: Money::mxn(sale.total),
+            autofactura: None,
+        })
+    })
+}
+
+/// Devuelve el texto del ticket de una venta local, listo para `print_ticket`.
+#[tauri::command]
+pub fn render_sale_ticket(db: State<'_, Db>, sale_id: i64, options: Option<TicketOptions>) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let mut data = ticket_data(&db, sale_id)?;
+    if options.autofactura {
+        let conn = db.lock()?;
+        data.autofactura = Some(autofactura::for_sale(&conn, sale_id)?);
+    }
+    Ok(render_ticket(&data, &options))
+}
@@ -0,0 +1,109 @@
+//! Unidades de medida y cantidades con decimales (granel: kg de clavo, metros de cable).
+//!
+//! Las cantidades son `Decimal` con hasta `QUANTITY_DP` decimales. Cada producto tiene su
+//! unidad y cuántos decimales admite (`quantity_precision`; sin valor se usa el de la unidad:
+//! pieza 0, kg 3, g 0, m 2, l 3). En SQLite van en las mismas columnas enteras de siempre:
+//! enteras si no llevan decimales y REAL si los llevan (ver `db::get_quantity`).
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Decimales con que se guardan existencias y partidas.
+pub const QUANTITY_DP: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Pieza,
+    Kg,
+    G,
+    M,
+    L,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 5] = [Unit::Pieza, Unit::Kg, Unit::G, Unit::M, Unit::L];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Pieza => "pieza",
+            Unit::Kg => "kg",
+            Unit::G => "g",
+            Unit::M => "m",
+            Unit::L => "l",
+        }
+    }
+
+    /// Abreviatura para el ticket.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Pieza => "pza",
+            other => other.as_str(),
+        }
+    }
+
+    /// Clave de unidad del SAT (c_ClaveUnidad) que le corresponde.
+    pub fn sat_key(&self) -> &'static str {
+        match self {
+            Unit::Pieza => "H87",
+            Unit::Kg => "KGM",
+            Unit::G => "GRM",
+            Unit::M => "MTR",
+            Unit::L => "LTR",
+        }
+    }
+
+    /// Decimales que admite la unidad si el producto no dice otra cosa.
+    pub fn default_precision(&self) -> u32 {
+        match self {
+            Unit::Pieza | Unit::G => 0,
+            Unit::M => 2,
+            Unit::Kg | Unit::L => 3,
+        }
+    }
+
+    /// Acepta el nombre y las abreviaturas comunes (`pza`, `kilo`, `mts`, `lt`, ...).
+    pub fn parse(value: &str) -> Option<Unit> {
+        let v = value.trim().trim_end_matches('.').to_lowercase();
+        let unit = match v.as_str() {
+            "pieza" | "piezas" | "pza" | "pzas" | "pz" | "pieza(s)" | "h87" => Unit::Pieza,
+            "kg" | "kgs" | "kilo" | "kilos" | "kilogramo" | "kilogramos" | "kgm" => Unit::Kg,
+            "g" | "gr" | "grs" | "gramo" | "gramos" | "grm" => Unit::G,
+            "m" | "mt" | "mts" | "metro" | "metros" | "mtr" => Unit::M,
+            "l" | "lt" | "lts" | "litro" | "litros" | "ltr" => Unit::L,
+            _ => return None,
+        };
+        Some(unit)
+    }
+
+    /// Lee la columna `unit`; un valor desconocido se toma como pieza.
+    pub fn from_db(value: Option<&str>) -> Unit {
+        value.and_then(Unit::parse).unwrap_or_default()
+    }
+}
+
+/// Redondea a `QUANTITY_DP` y quita ceros de sobra (`2.500` → `2.5`).
+pub fn normalize(quantity: Decimal) -> Decimal {
+    quantity.round_dp(QUANTITY_DP).normalize()
+}
+
+/// Revisa que la cantidad no traiga más decimales de los que admite el producto.
+pub fn check_precision(quantity: Decimal, unit: Unit, precision: u32, name: &str) -> Result<(), String> {
+    if quantity.round_dp(precision) == quantity {
+        return Ok(());
+    }
+    Err(match precision {
+        0 => format!("{} se vende por {}: la cantidad no lleva decimales.", name, unit.as_str()),
+        n => format!("{} admite hasta {} decimales en la cantidad.", name, n),
+    })
+}
+
+/// Cantidad con la precisión del producto y su unidad (`0.750 kg`); las piezas van sin unidad.
+pub fn format(quantity: Decimal, unit: Unit, precision: u32) -> String {
+    let shown = format!("{:.*}", precision.min(QUANTITY_DP) as usize, quantity.round_dp(precision));
+    match unit {
+        Unit::Pieza => shown,
+        _ => format!("{} {}", shown, unit.symbol()),
+    }
+}
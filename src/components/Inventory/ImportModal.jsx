@@ -1,13 +1,15 @@
 import React, { useState, useRef } from 'react'
-import { FaTimes, FaFileUpload, FaExclamationTriangle } from 'react-icons/fa'
+import { FaTimes, FaFileUpload, FaFilePdf, FaExclamationTriangle } from 'react-icons/fa'
 import { useSettingsStore } from '../../store/settingsStore'
-import { importProducts } from '../../services/importService'
+import { importProducts, importPdfInvoiceFromDialog } from '../../services/importService'
+import { isTauri } from '../../services/printerService'
 import InvoiceReviewModal from './InvoiceReviewModal'
 import './ImportModal.css'
 
 /**
  * Modal para importar productos desde XML (CFDI), CSV o PDF.
  * Al parsear, abre InvoiceReviewModal para revisar y confirmar partidas.
+ * En la app de escritorio el PDF se elige con el diálogo nativo para que el backend lo lea por ruta.
  */
 export default function ImportModal({ onClose, onImportComplete }) {
   const t = useSettingsStore((s) => s.t)
@@ -17,7 +19,22 @@ export default function ImportModal({ onClose, onImportComplete }) {
   const [invoiceData, setInvoiceData] = useState(null)
   const inputRef = useRef(null)
 
-  const accept = '.xml,.csv,.pdf'
+  const desktop = isTauri()
+  const accept = desktop ? '.xml,.csv' : '.xml,.csv,.pdf'
+
+  const showResult = (result) => {
+    if (result._parsingError) {
+      setError(result._parsingError)
+      setInvoiceData(null)
+    } else {
+      setError(null)
+      setInvoiceData({
+        items: result.items,
+        supplier: result.supplier || '',
+        folio: result.folio || '',
+      })
+    }
+  }
 
   const handleFileChange = async (e) => {
     const selected = e.target.files?.[0]
@@ -32,18 +49,7 @@ export default function ImportModal({ onClose, onImportComplete }) {
     }
     setLoading(true)
     try {
-      const result = await importProducts(selected)
-      if (result._parsingError) {
-        setError(result._parsingError)
-        setInvoiceData(null)
-      } else {
-        setError(null)
-        setInvoiceData({
-          items: result.items,
-          supplier: result.supplier || '',
-          folio: result.folio || '',
-        })
-      }
+      showResult(await importProducts(selected))
     } catch (err) {
       setError(err?.message || 'Error al procesar')
       setInvoiceData(null)
@@ -53,6 +59,23 @@ export default function ImportModal({ onClose, onImportComplete }) {
     e.target.value = ''
   }
 
+  const handlePickPdf = async () => {
+    setError(null)
+    setInvoiceData(null)
+    setLoading(true)
+    try {
+      const result = await importPdfInvoiceFromDialog()
+      if (!result) return
+      setFile({ name: result.fileName, size: null })
+      showResult(result)
+    } catch (err) {
+      setError(err?.message || 'Error al procesar')
+      setInvoiceData(null)
+    } finally {
+      setLoading(false)
+    }
+  }
+
   const handleRemoveFile = () => {
     setFile(null)
     setError(null)
@@ -107,6 +130,17 @@ export default function ImportModal({ onClose, onImportComplete }) {
               <FaFileUpload />
               {loading ? 'Procesando…' : 'Seleccionar archivo'}
             </label>
+            {desktop && (
+              <button
+                type="button"
+                className="import-modal__file-label"
+                onClick={handlePickPdf}
+                disabled={loading}
+              >
+                <FaFilePdf />
+                Factura en PDF
+              </button>
+            )}
           </div>
 
           {file && (
@@ -114,9 +148,11 @@ export default function ImportModal({ onClose, onImportComplete }) {
               <span className="import-modal__preview-icon"><FaFileUpload /></span>
               <div className="import-modal__preview-info">
                 <div className="import-modal__preview-name">{file.name}</div>
-                <div className="import-modal__preview-size">
-                  {(file.size / 1024).toFixed(1)} KB
-                </div>
+                {file.size != null && (
+                  <div className="import-modal__preview-size">
+                    {(file.size / 1024).toFixed(1)} KB
+                  </div>
+                )}
               </div>
               <button
                 type="button"
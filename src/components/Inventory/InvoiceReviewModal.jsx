@@ -4,6 +4,8 @@ import { useSettingsStore } from '../../store/settingsStore'
 import { matchingService } from '../../services/matchingService'
 import { productService } from '../../services/productService'
 import { useInventoryStore } from '../../store/inventoryStore'
+import { localStoreService } from '../../services/localStoreService'
+import { isTauri } from '../../services/printerService'
 import './InvoiceReviewModal.css'
 
 /**
@@ -82,7 +84,7 @@ const InvoiceReviewModal = ({ invoiceData, onClose, onComplete }) => {
     
     setIsMatching(true)
     try {
-      const matchedItems = await matchingService.matchInvoiceItems(invoiceData.items)
+      const matchedItems = await matchingService.matchInvoiceItems(invoiceData.items, invoiceData.supplierRfc)
       
       // Calculate suggested sale price (33% markup) for each item
       const itemsWithPricing = matchedItems.map(item => {
@@ -182,6 +184,38 @@ const InvoiceReviewModal = ({ invoiceData, onClose, onComplete }) => {
     ))
   }
 
+  // Under Tauri the matched products are local SQLite rows, so they are created and updated
+  // in the local store; going through inventoryStore would hit Supabase with a local id
+  const saveNewProduct = (productData) => (
+    isTauri() ? localStoreService.saveProduct(productData) : createProduct(productData)
+  )
+
+  const saveExistingProduct = async (product, updates) => {
+    if (isTauri()) {
+      const current = await localStoreService.getProduct(product.id)
+      if (!current) throw new Error(`El producto ${product.name} ya no existe en la base local`)
+      return localStoreService.saveProduct({ ...current, ...updates })
+    }
+    await updateProduct(product.id, updates)
+    return { ...product, ...updates }
+  }
+
+  // Save "supplier code -> product" so the next invoice from this supplier matches it exactly
+  // Aliases only point at local product ids; anything not in the local store is not learned
+  const rememberSupplierCode = async (item, product) => {
+    if (!isTauri() || !product?.id) return
+    try {
+      const local = await localStoreService.getProduct(product.id)
+      if (!local) {
+        console.warn('Alias del proveedor no guardado: el producto no existe en la base local', product.id)
+        return
+      }
+      await matchingService.learnConfirmedMatches(invoiceData.supplierRfc, [{ ...item, selectedProduct: local }])
+    } catch (error) {
+      console.warn('No se pudo guardar el alias del proveedor:', error)
+    }
+  }
+
   const handleConfirmItem = async (itemIndex) => {
     const item = items[itemIndex]
     if (!item || item.isConfirmed) return
@@ -203,7 +237,8 @@ const InvoiceReviewModal = ({ invoiceData, onClose, onComplete }) => {
           description: item.description || item.name
         }
         
-        const newProduct = await createProduct(productData)
+        const newProduct = await saveNewProduct(productData)
+        rememberSupplierCode(item, newProduct)
         
         setItems(prev => prev.map((it, idx) => 
           idx === itemIndex 
@@ -215,15 +250,16 @@ const InvoiceReviewModal = ({ invoiceData, onClose, onComplete }) => {
       } else if (item.selectedProduct) {
         // Update existing product: stock, cost, and price
         const newStock = (item.selectedProduct.stock || 0) + (item.stock || 0)
-        await updateProduct(item.selectedProduct.id, { 
+        const savedProduct = await saveExistingProduct(item.selectedProduct, {
           stock: newStock,
           cost: cost, // Update purchase price
           price: salePrice // Update sale price
         })
+        rememberSupplierCode(item, savedProduct)
         
         setItems(prev => prev.map((it, idx) => 
           idx === itemIndex 
-            ? { ...it, isConfirmed: true }
+            ? { ...it, selectedProduct: savedProduct, isConfirmed: true }
             : it
         ))
         
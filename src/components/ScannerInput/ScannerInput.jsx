@@ -1,6 +1,8 @@
 import React, { useRef, useEffect, useState } from 'react'
 import { useSaleStore } from '../../store/saleStore'
 import { productService } from '../../services/productService'
+import { isTauri } from '../../services/printerService'
+import { localStoreService } from '../../services/localStoreService'
 import './ScannerInput.css'
 
 /**
@@ -8,6 +10,8 @@ import './ScannerInput.css'
  * Listens to keyboard and barcode scanner input
  * - F2: Focus/refocus input
  * - Enter: Add product or trigger not found callback
+ * - In Tauri, also takes 'barcode-scanned' events from the backend scanner listener,
+ *   so scans are not lost when the input is not focused (e.g. a modal is open)
  */
 const ScannerInput = ({ onProductNotFound }) => {
   const inputRef = useRef(null)
@@ -44,53 +48,102 @@ const ScannerInput = ({ onProductNotFound }) => {
     setValue(e.target.value)
   }
 
-  // Handle Enter key
-  const handleKeyDown = async (e) => {
-    if (e.key === 'Enter' && value.trim()) {
-      e.preventDefault()
-      const barcode = value.trim()
-      console.log('📥 Scanner input received:', barcode)
-      setValue('')
+  const processBarcode = async (barcode) => {
+    console.log('📥 Scanner input received:', barcode)
 
-      try {
-        // Try to find product
-        const product = await productService.findByCode(barcode)
-
-        if (product) {
-          console.log('✅ Product found, adding to sale:', product.name, product.code)
-          // Product found - add to sale
-          const success = addItem(product, 1)
-          if (success) {
-            console.log('✅ Product added successfully')
-          } else {
-            console.warn('⚠️ Product found but could not be added (stock issue?)')
-            alert(`Producto encontrado pero no se pudo agregar. Verifica el stock disponible.`)
-          }
-          // Keep focus for next scan
-          setTimeout(() => {
-            if (inputRef.current) {
-              inputRef.current.focus()
-            }
-          }, 100)
+    try {
+      // GS1 and in-store weight/price labels carry the product key plus a quantity or amount
+      let scan = null
+      if (isTauri()) {
+        try {
+          scan = await localStoreService.parseBarcode(barcode)
+        } catch (error) {
+          console.warn('⚠️ Could not parse barcode, searching as typed:', error)
+        }
+      }
+
+      // Try to find product
+      let product = null
+      for (const key of scan ? [scan.lookup, ...scan.alternates] : [barcode]) {
+        product = await productService.findByCode(key)
+        if (product) break
+      }
+
+      if (product) {
+        console.log('✅ Product found, adding to sale:', product.name, product.code)
+        let quantity = 1
+        if (scan?.weight) {
+          quantity = scan.weight
+        } else if (scan?.price && product.price > 0) {
+          quantity = Math.round((scan.price / product.price) * 1000) / 1000
+        }
+        // Product found - add to sale
+        const success = addItem(product, quantity)
+        if (success) {
+          console.log('✅ Product added successfully')
         } else {
-          console.warn('❌ Product not found:', barcode)
-          // Product not found - show quick add modal
-          if (onProductNotFound) {
-            onProductNotFound(barcode)
-          } else {
-            alert(`Producto no encontrado: ${barcode}`)
-          }
+          console.warn('⚠️ Product found but could not be added (stock issue?)')
+          alert(`Producto encontrado pero no se pudo agregar. Verifica el stock disponible.`)
         }
-      } catch (error) {
-        console.error('❌ Error searching for product:', error)
-        alert(`Error al buscar producto: ${error.message}`)
-        // Refocus input
+        // Keep focus for next scan
         setTimeout(() => {
           if (inputRef.current) {
             inputRef.current.focus()
           }
         }, 100)
+      } else {
+        console.warn('❌ Product not found:', barcode)
+        // Product not found - show quick add modal
+        if (onProductNotFound) {
+          onProductNotFound(barcode)
+        } else {
+          alert(`Producto no encontrado: ${barcode}`)
+        }
       }
+    } catch (error) {
+      console.error('❌ Error searching for product:', error)
+      alert(`Error al buscar producto: ${error.message}`)
+      // Refocus input
+      setTimeout(() => {
+        if (inputRef.current) {
+          inputRef.current.focus()
+        }
+      }, 100)
+    }
+  }
+
+  // Latest handler for the backend listener, which is registered once
+  const processBarcodeRef = useRef(processBarcode)
+  processBarcodeRef.current = processBarcode
+
+  // Scans from the backend scanner listener arrive regardless of focus
+  useEffect(() => {
+    if (!isTauri()) return
+    let unlisten = null
+    let cancelled = false
+    import('@tauri-apps/api/event')
+      .then(({ listen }) => listen('barcode-scanned', (event) => {
+        const code = event.payload?.code?.trim()
+        if (code) processBarcodeRef.current(code)
+      }))
+      .then((fn) => {
+        if (cancelled) fn()
+        else unlisten = fn
+      })
+      .catch((error) => console.warn('⚠️ Backend scanner listener not available:', error))
+    return () => {
+      cancelled = true
+      if (unlisten) unlisten()
+    }
+  }, [])
+
+  // Handle Enter key
+  const handleKeyDown = async (e) => {
+    if (e.key === 'Enter' && value.trim()) {
+      e.preventDefault()
+      const barcode = value.trim()
+      setValue('')
+      await processBarcode(barcode)
     }
   }
 
@@ -1,5 +1,7 @@
-import React, { useState } from 'react'
+import React, { useEffect, useState } from 'react'
 import { LiquidButton } from '../inventory/LiquidButton'
+import { isTauri } from '../../services/printerService'
+import { localStoreService } from '../../services/localStoreService'
 
 export function FacturacionMXSettings() {
   const [regimen, setRegimen] = useState('')
@@ -14,9 +16,48 @@ export function FacturacionMXSettings() {
   const [preguntarCliente, setPreguntarCliente] = useState(true)
 
   const [saved, setSaved] = useState(false)
+  const [error, setError] = useState('')
 
-  const handleSave = () => {
-    // Mock save
+  useEffect(() => {
+    if (!isTauri()) return
+    localStoreService
+      .getCfdiSettings()
+      .then((s) => {
+        setRegimen(s.regimen_fiscal)
+        setRfc(s.rfc)
+        setRazon(s.razon_social)
+        setCp(s.codigo_postal)
+        setSerie(s.serie)
+        setFolio(String(s.next_folio))
+        setMetodo(s.metodo_pago === 'PPD' ? 'PPD' : 'PUE')
+        setUso(s.uso_cfdi)
+        setActivar(s.enabled)
+        setPreguntarCliente(s.ask_customer)
+      })
+      .catch((e) => setError(String(e)))
+  }, [])
+
+  const handleSave = async () => {
+    setError('')
+    if (isTauri()) {
+      try {
+        await localStoreService.saveCfdiSettings({
+          rfc,
+          razon_social: razon,
+          regimen_fiscal: regimen,
+          codigo_postal: cp,
+          serie,
+          next_folio: parseInt(folio, 10) || 1,
+          metodo_pago: metodo,
+          uso_cfdi: uso,
+          enabled: activar,
+          ask_customer: preguntarCliente,
+        })
+      } catch (e) {
+        setError(String(e))
+        return
+      }
+    }
     setSaved(true)
     setTimeout(() => setSaved(false), 900)
   }
@@ -148,6 +189,12 @@ export function FacturacionMXSettings() {
         </label>
       </div>
 
+      {error && (
+        <div className="rounded-2xl border border-red-500/40 bg-red-500/10 px-3 py-2 text-xs text-red-300 whitespace-pre-line">
+          {error}
+        </div>
+      )}
+
       <div className="pt-3 flex justify-end">
         <LiquidButton size="sm" onClick={handleSave}>
           {saved ? 'Guardado' : 'Guardar configuración'}
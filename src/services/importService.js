@@ -2,6 +2,8 @@
  * Import Service
  * Parsea archivos de factura (XML CFDI, CSV, PDF) y devuelve datos para InvoiceReviewModal
  */
+import { isTauri } from './printerService'
+import { localStoreService } from './localStoreService'
 
 /** Extrae texto de un PDF usando pdfjs-dist (todas las páginas). */
 async function extractTextFromPdf(arrayBuffer) {
@@ -180,6 +182,64 @@ function parseXML(text) {
   return { items, supplier, folio }
 }
 
+/**
+ * Convierte el CFDI leído en el backend (cfdi_parse_xml) al formato de InvoiceReviewModal.
+ * El costo unitario va neto de descuento; conserva uuid, RFC del proveedor y avisos de cuadre.
+ */
+function fromCfdiDocument(cfdi) {
+  const items = cfdi.conceptos
+    .filter((c) => c.descripcion)
+    .map((c) => {
+      const cost = c.cantidad ? (c.importe - (c.descuento || 0)) / c.cantidad : c.valor_unitario
+      return {
+        name: c.descripcion,
+        description: c.descripcion,
+        code: c.no_identificacion || c.clave_prod_serv || '',
+        barcode: c.no_identificacion || '',
+        supplierCode: c.no_identificacion || null,
+        price: c.valor_unitario,
+        cost: Math.round(cost * 1e6) / 1e6,
+        stock: c.cantidad,
+        satProductKey: c.clave_prod_serv,
+        satUnitKey: c.clave_unidad || null,
+      }
+    })
+  return {
+    items,
+    supplier: cfdi.emisor.nombre || cfdi.emisor.rfc,
+    supplierRfc: cfdi.emisor.rfc,
+    folio: [cfdi.serie, cfdi.folio].filter(Boolean).join('-'),
+    uuid: cfdi.uuid,
+    total: cfdi.total,
+    warnings: cfdi.warnings,
+    cfdi,
+  }
+}
+
+/**
+ * Convierte las partidas candidatas del PDF (import_pdf_invoice) al formato de InvoiceReviewModal.
+ */
+function fromPdfExtraction(pdf) {
+  const items = pdf.lines
+    .filter((l) => l.description)
+    .map((l) => {
+      const stock = l.quantity || 1
+      const cost = l.unit_price ?? (l.amount != null ? l.amount / stock : 0)
+      return {
+        name: l.description,
+        description: l.description,
+        code: l.code || '',
+        barcode: l.code || '',
+        supplierCode: l.code || null,
+        price: cost,
+        cost,
+        stock,
+        confidence: l.confidence,
+      }
+    })
+  return { items, supplier: '', supplierRfc: pdf.rfcs[0] || '', folio: '', uuid: pdf.uuid }
+}
+
 /**
  * Parsea CSV con headers (nombre/name/producto, codigo/code/sku, precio/price, costo/cost, stock/cantidad)
  */
@@ -216,6 +276,23 @@ function parseCSV(text) {
   return { items, supplier: '', folio: '' }
 }
 
+/**
+ * Factura PDF en la app de escritorio: se elige con el diálogo nativo y el backend lee las
+ * posiciones del texto desde la ruta. Devuelve null si se cancela el diálogo.
+ */
+export async function importPdfInvoiceFromDialog() {
+  const path = await localStoreService.pickFile([{ name: 'Factura PDF', extensions: ['pdf'] }])
+  if (!path) return null
+  const fileName = path.split(/[\\/]/).pop()
+  try {
+    const data = fromPdfExtraction(await localStoreService.importPdfInvoice(path))
+    if (data.items.length === 0) return { fileName, _parsingError: 'No se detectaron partidas en el PDF. Pruebe con XML (CFDI) o CSV.' }
+    return { ...data, fileName }
+  } catch (err) {
+    return { fileName, _parsingError: err?.message || 'Error al procesar el PDF' }
+  }
+}
+
 /**
  * Parsea un archivo (File) y devuelve objeto para InvoiceReviewModal: { items, supplier, folio } o { _parsingError }
  */
@@ -241,8 +318,10 @@ export async function importProducts(file) {
     if (!text) return { _parsingError: 'No se pudo leer el archivo' }
 
     if (isXml) {
-      const data = parseXML(text)
+      const data = isTauri() ? fromCfdiDocument(await localStoreService.parseCfdiXml(text)) : parseXML(text)
       if (!data.items || data.items.length === 0) return { _parsingError: 'No se encontraron conceptos en el XML' }
+      const received = isTauri() && data.uuid ? await localStoreService.findPurchaseByUuid(data.uuid) : null
+      if (received) return { _parsingError: `Esta factura ya se recibió el ${received.created_at.slice(0, 10)} (compra #${received.id}).` }
       return data
     }
     if (isCsv) {
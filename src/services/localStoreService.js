@@ -0,0 +1,174 @@
+/**
+ * Local store service
+ * Reads and writes the embedded SQLite database (Tauri backend) so the POS keeps working offline
+ */
+import { isTauri } from './printerService'
+
+const getInvoke = async () => {
+  let invoke
+  try {
+    const api = await import('@tauri-apps/api/core')
+    invoke = api.invoke
+  } catch {
+    invoke = window.__TAURI__?.core?.invoke
+  }
+  if (typeof invoke !== 'function') {
+    throw new Error('Tauri invoke no disponible')
+  }
+  return invoke
+}
+
+const call = async (command, args = {}) => {
+  const invoke = await getInvoke()
+  return invoke(command, args)
+}
+
+export const localStoreService = {
+  isAvailable: isTauri,
+
+  // Products
+  listProducts: (search = '', limit = 500) => call('db_list_products', { search, limit }),
+  getProduct: (id) => call('db_get_product', { id }),
+  findProductByCode: (code) => call('db_find_product_by_code', { code }),
+  saveProduct: (product) => call('db_save_product', { product }),
+  deleteProduct: (id) => call('db_delete_product', { id }),
+
+  // Customers
+  listCustomers: (search = '') => call('db_list_customers', { search }),
+  getCustomer: (id) => call('db_get_customer', { id }),
+  saveCustomer: (customer) => call('db_save_customer', { customer }),
+  deleteCustomer: (id) => call('db_delete_customer', { id }),
+
+  // Sales
+  listSales: (status = null, limit = 50) => call('db_list_sales', { status, limit }),
+  listPendingSales: () => call('db_list_pending_sales'),
+  getSale: (id) => call('db_get_sale', { id }),
+  saveSale: (sale) => call('db_save_sale', { sale }),
+  updateSaleStatus: (id, status) => call('db_update_sale_status', { id, status }),
+  deleteSale: (id) => call('db_delete_sale', { id }),
+  // Validates stock, computes totals and decrements stock in one transaction;
+  // quantities may carry decimals for products sold by kg, g, m or l
+  processSale: (sale) => call('process_sale', { sale }),
+  // Ticket text for a local sale, ready for print_ticket
+  renderSaleTicket: (saleId, options = null) => call('render_sale_ticket', { saleId, options }),
+
+  // Tax profiles (IVA / IEPS per product)
+  listTaxProfiles: () => call('tax_list_profiles'),
+  saveTaxProfile: (profile) => call('tax_save_profile', { profile }),
+  deleteTaxProfile: (id) => call('tax_delete_profile', { id }),
+  assignTaxProfile: (productId, profileId = null) => call('tax_assign_profile', { productId, profileId }),
+  getSaleTaxBreakdown: (saleId) => call('tax_sale_breakdown', { saleId }),
+
+  // CFDI 4.0
+  getCfdiSettings: () => call('cfdi_get_settings'),
+  saveCfdiSettings: (settings) => call('cfdi_save_settings', { settings }),
+  buildInvoice: (saleId, options = null) => call('cfdi_build_invoice', { saleId, options }),
+  sealInvoice: (saleId, options = null) => call('cfdi_seal_invoice', { saleId, options }),
+  importCsd: (cerPath, keyPath, password) => call('cfdi_import_csd', { cerPath, keyPath, password }),
+  getCsdInfo: () => call('cfdi_csd_info'),
+  removeCsd: () => call('cfdi_remove_csd'),
+  stampInvoice: (saleId, options = null) => call('cfdi_stamp_invoice', { saleId, options }),
+  listInvoices: (saleId = null, limit = 50) => call('cfdi_list_invoices', { saleId, limit }),
+  getInvoiceXml: (id) => call('cfdi_get_invoice_xml', { id }),
+  getPacSettings: () => call('cfdi_get_pac_settings'),
+  savePacSettings: (settings, password = null) => call('cfdi_save_pac_settings', { settings, password }),
+  cancelInvoice: (invoiceId, motivo, folioSustitucion = null) =>
+    call('cfdi_cancel_invoice', { invoiceId, motivo, folioSustitucion }),
+  refreshInvoiceStatus: (invoiceId = null) => call('cfdi_refresh_invoice_status', { invoiceId }),
+  getCancelAcuse: (id) => call('cfdi_get_cancel_acuse', { id }),
+  getGlobalPendingSales: (desde, hasta) => call('cfdi_global_pending_sales', { desde, hasta }),
+  stampGlobalInvoice: (request) => call('cfdi_stamp_global_invoice', { request }),
+  registerInvoicePayment: (invoiceId, payment) => call('cfdi_register_payment', { invoiceId, payment }),
+  listInvoicePayments: (invoiceId) => call('cfdi_list_payments', { invoiceId }),
+  stampInvoicePayment: (paymentId) => call('cfdi_stamp_payment', { paymentId }),
+  deleteInvoicePayment: (paymentId) => call('cfdi_delete_payment', { paymentId }),
+  // Autofactura: folio, verification code and QR payload for a ticket
+  getTicketAutofactura: (saleId) => call('cfdi_ticket_autofactura', { saleId }),
+  verifyAutofactura: (request) => call('cfdi_verify_autofactura', { request }),
+  stampAutofactura: (request, options) => call('cfdi_stamp_autofactura', { request, options }),
+  // Supplier CFDI 3.3/4.0 XML -> emisor, receptor, UUID, conceptos, taxes and totals
+  parseCfdiXml: (xml) => call('cfdi_parse_xml', { xml }),
+  // Supplier PDF invoice -> positioned rows and candidate lines with confidence (0-1)
+  importPdfInvoice: (path) => call('import_pdf_invoice', { path }),
+  // Native open-file dialog (dialog plugin) -> chosen path, or null if cancelled
+  pickFile: (filters = []) => call('plugin:dialog|open', { options: { multiple: false, directory: false, filters } }),
+  // Product catalog from CSV/XLSX: preview returns creates, updates (field diffs) and row errors
+  previewCatalogImport: (path, options = null) => call('import_catalog_preview', { path, options }),
+  commitCatalogImport: (path, options = null) => call('import_catalog_commit', { path, options }),
+  listImportMappings: () => call('import_list_mappings'),
+  saveImportMapping: (name, mapping) => call('import_save_mapping', { name, mapping }),
+  deleteImportMapping: (name) => call('import_delete_mapping', { name }),
+  // Ranked catalog candidates (high/medium/low) for every invoice line in one call
+  matchInvoiceLines: (request) => call('match_invoice_lines', { request }),
+  // Supplier code aliases (RFC + NoIdentificacion -> product), learned from confirmed lines
+  learnSupplierAliases: (supplierRfc, matches) => call('import_learn_aliases', { supplierRfc, matches }),
+  listSupplierAliases: (supplierRfc = null, search = null) => call('import_list_aliases', { supplierRfc, search }),
+  updateSupplierAlias: (id, supplierCode, productId) => call('import_update_alias', { id, supplierCode, productId }),
+  deleteSupplierAlias: (id) => call('import_delete_alias', { id }),
+
+  // Purchase receipts: stock in, last cost and weighted average cost; a CFDI UUID is received once
+  receivePurchase: (purchase) => call('purchase_receive', { purchase }),
+  listPurchases: (supplierRfc = null, limit = null) => call('purchase_list', { supplierRfc, limit }),
+  getPurchase: (id) => call('purchase_get', { id }),
+  findPurchaseByUuid: (uuid) => call('purchase_find_by_uuid', { uuid }),
+
+  // Inventory ledger (kardex): movements are append-only and carry the resulting balance
+  listInventoryMovements: (filter = null) => call('inventory_list_movements', { filter }),
+  recordInventoryMovement: (movement) => call('inventory_record_movement', { movement }),
+  reconcileInventory: (fix = false, userId = null) => call('inventory_reconcile', { fix, userId }),
+
+  // Stocktake sessions (counts: [{ code | product_id, quantity, counter }])
+  openStocktake: (name = null, category = null, userId = null) => call('stocktake_open', { name, category, userId }),
+  listStocktakes: (status = null) => call('stocktake_list', { status }),
+  addStocktakeCounts: (stocktakeId, counts) => call('stocktake_add_counts', { stocktakeId, counts }),
+  getStocktakeReport: (stocktakeId) => call('stocktake_report', { stocktakeId }),
+  closeStocktake: (stocktakeId, userId = null, zeroUncounted = false) =>
+    call('stocktake_close', { stocktakeId, userId, zeroUncounted }),
+  cancelStocktake: (stocktakeId) => call('stocktake_cancel', { stocktakeId }),
+
+  // Quotations
+  listQuotations: (status = null) => call('db_list_quotations', { status }),
+  getQuotationByCode: (code) => call('db_get_quotation_by_code', { code }),
+  saveQuotation: (quotation) => call('db_save_quotation', { quotation }),
+  updateQuotationStatus: (code, status) => call('db_update_quotation_status', { code, status }),
+
+  // Sync with Supabase (listen to the 'sync-status' event for live updates)
+  configureSync: (config) => call('sync_configure', { config }),
+  syncNow: () => call('sync_now'),
+  getSyncStatus: () => call('sync_status'),
+  startSync: (intervalSecs = 60) => call('sync_start', { intervalSecs }),
+  stopSync: () => call('sync_stop'),
+
+  // Serial scale (listen to 'scale-weight' for readings in kg and 'scale-status' for connection changes)
+  // config: { protocol: 'toledo' | 'cas', serial: { port, baud_rate, data_bits, parity, stop_bits }, simulated }
+  listScalePorts: () => call('scale_list_ports'),
+  connectScale: (config) => call('scale_connect', { config }),
+  disconnectScale: () => call('scale_disconnect'),
+  getScaleStatus: () => call('scale_status'),
+  readScaleWeight: (timeoutMs = 3000) => call('scale_read', { timeoutMs }),
+  setSimulatedWeight: (weight, status = 'stable') => call('scale_simulate', { weight, status }),
+
+  // Customer pole display (2x20 VFD, Epson DM-D); ports come from listScalePorts
+  // config: { serial: { port, baud_rate, ... }, simulated, columns, idle_message } ('|' splits the two lines)
+  connectDisplay: (config) => call('display_connect', { config }),
+  disconnectDisplay: () => call('display_disconnect'),
+  getDisplayStatus: () => call('display_status'),
+  // item: { name, quantity, unit, precision, unit_price }
+  showDisplayItem: (item) => call('display_item', { item }),
+  showDisplayTotal: (total, items = null) => call('display_total', { total, items }),
+  showDisplayChange: (paid, change) => call('display_change', { paid, change }),
+  showDisplayIdle: (message = null) => call('display_idle', { message }),
+
+  // Barcode scanner outside the webview (listen to 'barcode-scanned' for { code, source } and 'scanner-status')
+  // config: { serial: { port, baud_rate, ... } | device: '/dev/input/by-id/...-event-kbd' | '/dev/hidraw0', simulated, prefix, suffix, grab }
+  listScannerDevices: () => call('scanner_list_devices'),
+  connectScanner: (config) => call('scanner_connect', { config }),
+  disconnectScanner: () => call('scanner_disconnect'),
+  getScannerStatus: () => call('scanner_status'),
+  simulateScan: (code) => call('scanner_simulate', { code }),
+
+  // Scanned code parsing: GS1-128 / DataBar (GTIN, lot, expiry, weight, price) and in-store 20–29 EAN-13 labels
+  // rules: { item_digits, value: 'price' | 'weight', weight_prefixes, price_decimals, weight_decimals }
+  parseBarcode: (code, rules = null) => call('barcode_parse', { code, rules }),
+  lookupBarcode: (code, rules = null) => call('barcode_lookup', { code, rules })
+}
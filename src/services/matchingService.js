@@ -3,6 +3,8 @@
  * Provides fuzzy matching between invoice items and existing products
  */
 import { productService } from './productService'
+import { localStoreService } from './localStoreService'
+import { isTauri } from './printerService'
 
 /**
  * Calculate Levenshtein distance between two strings
@@ -196,14 +198,15 @@ export const getConfidenceLabel = (confidence) => {
 /**
  * Match invoice items with existing products
  */
-export const matchInvoiceItems = async (invoiceItems) => {
-  // Get all existing products
-  const existingProducts = await productService.getAll()
-  
+export const matchInvoiceItems = async (invoiceItems, supplierRfc = null) => {
+  const matchesPerItem = isTauri()
+    ? await matchInBackend(invoiceItems, supplierRfc)
+    : await matchInWebview(invoiceItems)
+
   const matchedItems = []
-  
-  for (const item of invoiceItems) {
-    const matches = await findMatches(item, existingProducts)
+
+  invoiceItems.forEach((item, i) => {
+    const matches = matchesPerItem[i] || []
     const bestMatch = matches.length > 0 ? matches[0] : null
     
     // For LOW confidence matches, suggest creating new product instead
@@ -218,14 +221,68 @@ export const matchInvoiceItems = async (invoiceItems) => {
       isConfirmed: false,
       _lowConfidenceWarning: shouldSuggestNew // Flag to show warning in UI
     })
-  }
-  
+  })
+
   return matchedItems
 }
 
+/**
+ * Match all items against the local catalog index in the Rust backend (one call per invoice)
+ */
+const matchInBackend = async (invoiceItems, supplierRfc) => {
+  const results = await localStoreService.matchInvoiceLines({
+    supplier_rfc: supplierRfc || null,
+    lines: invoiceItems.map((item) => ({
+      name: item.name || '',
+      code: item.supplierCode || item.code || null,
+      barcode: item.barcode || null
+    }))
+  })
+  const perItem = []
+  for (const result of results) {
+    perItem[result.index] = result.candidates.map((c) => ({
+      product: c.product,
+      score: c.score,
+      matchType: c.kind,
+      confidence: c.confidence,
+      similarity: c.score
+    }))
+  }
+  return perItem
+}
+
+/**
+ * Remember confirmed "supplier code -> product" lines so the next invoice matches them exactly
+ */
+export const learnConfirmedMatches = async (supplierRfc, confirmedItems) => {
+  if (!isTauri() || !supplierRfc) return 0
+  const matches = confirmedItems
+    .filter((item) => item.supplierCode && item.selectedProduct?.id)
+    .map((item) => ({
+      supplier_code: item.supplierCode,
+      product_id: item.selectedProduct.id,
+      description: item.name || null
+    }))
+  if (matches.length === 0) return 0
+  return localStoreService.learnSupplierAliases(supplierRfc, matches)
+}
+
+/**
+ * Browser fallback: Levenshtein against every product
+ */
+const matchInWebview = async (invoiceItems) => {
+  const existingProducts = await productService.getAll()
+  const perItem = []
+  for (const item of invoiceItems) {
+    perItem.push(await findMatches(item, existingProducts))
+  }
+  return perItem
+}
+
 export const matchingService = {
   findMatches,
   matchInvoiceItems,
+  learnConfirmedMatches,
   getConfidenceColor,
   getConfidenceLabel,
   calculateSimilarity
@@ -164,15 +164,16 @@ export function getTicketText(sale, overrides = {}) {
 /**
  * Print ticket
  * @param {Object} sale - Sale data to print
- * @param {{ printerName?: string, printerWidth?: string }} options - Optional. In Tauri, printerName is sent to the selected printer.
+ * @param {{ printerName?: string, printerWidth?: string, ticketText?: string, ticketQr?: string }} options - Optional. In Tauri, printerName is sent to the selected printer.
+ *   ticketText overrides the generated text (e.g. renderSaleTicket); ticketQr is printed as a QR below it.
  * @returns {Promise<boolean>}
  */
 export const printTicket = async (sale, options = {}) => {
-  const { printerName = '', printerWidth } = options
+  const { printerName = '', printerWidth, ticketQr = null } = options
 
   try {
     if (isTauri()) {
-      const ticketText = getTicketText(sale)
+      const ticketText = options.ticketText || getTicketText(sale)
       const settings = getTicketSettings()
       const ticketLogoBase64 =
         settings.ticketPrintLogo && settings.businessLogo && String(settings.businessLogo).trim()
@@ -191,7 +192,8 @@ export const printTicket = async (sale, options = {}) => {
       await invoke('print_ticket', {
         printerName: printerName || '',
         ticketText,
-        ticketLogoBase64
+        ticketLogoBase64,
+        ticketQr
       })
       return true
     }
@@ -0,0 +1,50 @@
+-- ============================================
+-- Fractional quantities for bulk goods (kg, g, m, l)
+-- ============================================
+-- Run this SQL in your Supabase SQL Editor after supabase_sync_rpc.sql
+--
+-- Stock and sale quantities become NUMERIC(12,3) so the desktop app can sell
+-- 0.750 kg of nails or 2.5 m of cable. Products get a unit of measure and an
+-- optional per-product precision (decimals allowed in the quantity).
+--
+-- Postgres can't change the type of a column that a view reads, so the views
+-- below are dropped first. Re-run supabase_schema.sql (section 9),
+-- supabase_reports_views.sql and supabase_pending_sales_schema.sql afterwards
+-- to recreate them.
+-- ============================================
+
+DROP VIEW IF EXISTS low_stock_products;
+DROP VIEW IF EXISTS products_out_of_stock;
+DROP VIEW IF EXISTS top_products_by_quantity;
+DROP VIEW IF EXISTS product_sales_performance;
+DROP VIEW IF EXISTS profit_analysis;
+DROP VIEW IF EXISTS pending_sales;
+
+ALTER TABLE products ALTER COLUMN stock TYPE NUMERIC(12, 3);
+ALTER TABLE sale_items ALTER COLUMN quantity TYPE NUMERIC(12, 3);
+
+ALTER TABLE products
+ADD COLUMN IF NOT EXISTS unit TEXT NOT NULL DEFAULT 'pieza'
+  CHECK (unit IN ('pieza', 'kg', 'g', 'm', 'l'));
+ALTER TABLE products
+ADD COLUMN IF NOT EXISTS quantity_precision SMALLINT
+  CHECK (quantity_precision BETWEEN 0 AND 3);
+
+COMMENT ON COLUMN products.unit IS 'Unit of measure: pieza, kg, g, m or l';
+COMMENT ON COLUMN products.quantity_precision IS 'Decimals allowed in quantities; NULL uses the unit default (pieza 0, kg 3, g 0, m 2, l 3)';
+
+-- Stock deltas from the desktop app can now carry decimals.
+DROP FUNCTION IF EXISTS adjust_stock(BIGINT, INTEGER);
+
+CREATE OR REPLACE FUNCTION adjust_stock(p_product_id BIGINT, p_delta NUMERIC)
+RETURNS NUMERIC
+LANGUAGE sql
+SECURITY INVOKER
+AS $$
+  UPDATE products
+  SET stock = stock + p_delta
+  WHERE id = p_product_id
+  RETURNING stock;
+$$;
+
+GRANT EXECUTE ON FUNCTION adjust_stock(BIGINT, NUMERIC) TO authenticated;
@@ -0,0 +1,24 @@
+-- ============================================
+-- Offline sync support (Tauri local store -> Supabase)
+-- ============================================
+-- Run this SQL in your Supabase SQL Editor after supabase_multi_tenant_migration.sql
+--
+-- The desktop app pushes stock changes as relative deltas so that two
+-- registers selling the same product offline don't overwrite each other.
+-- ============================================
+
+CREATE OR REPLACE FUNCTION adjust_stock(p_product_id BIGINT, p_delta INTEGER)
+RETURNS INTEGER
+LANGUAGE sql
+SECURITY INVOKER
+AS $$
+  UPDATE products
+  SET stock = stock + p_delta
+  WHERE id = p_product_id
+  RETURNING stock;
+$$;
+
+GRANT EXECUTE ON FUNCTION adjust_stock(BIGINT, INTEGER) TO authenticated;
+
+-- Pull uses updated_at as watermark; make sure it's indexed per tenant
+CREATE INDEX IF NOT EXISTS idx_products_tenant_updated_at ON products(tenant_id, updated_at);